@@ -0,0 +1,61 @@
+// Decoding a SET request through the codec, for values from a few bytes to a megabyte. The value
+// is a slice of the read buffer, it's not copied when large.
+use bytes::BytesMut;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use simple_redis_server::RespCodec;
+use tokio_util::codec::Decoder;
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for size in [64, 64 * 1024, 1024 * 1024] {
+        let request = format!(
+            "*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n${}\r\n{}\r\n",
+            size,
+            "x".repeat(size)
+        );
+        group.throughput(Throughput::Bytes(request.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &request, |b, request| {
+            b.iter_batched(
+                || BytesMut::from(request.as_bytes()),
+                |mut buf| RespCodec::default().decode(&mut buf).expect("a request"),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+// An MSET of many small values arriving 4KB at a time, the way a large request is read from a
+// socket. Each read only scans the bytes it brought.
+fn decode_in_reads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_in_reads");
+    for pairs in [1000, 10000] {
+        let mut request = format!("*{}\r\n$4\r\nMSET\r\n", pairs * 2 + 1);
+        for i in 0..pairs {
+            let key = format!("key:{}", i);
+            request.push_str(&format!("${}\r\n{}\r\n$5\r\nvalue\r\n", key.len(), key));
+        }
+        group.throughput(Throughput::Bytes(request.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(pairs),
+            &request,
+            |b, request| {
+                b.iter(|| {
+                    let mut codec = RespCodec::default();
+                    let mut buf = BytesMut::new();
+                    for read in request.as_bytes().chunks(4096) {
+                        buf.extend_from_slice(read);
+                        if let Some(frame) = codec.decode(&mut buf).expect("a request") {
+                            return frame;
+                        }
+                    }
+                    unreachable!("the request was all read")
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode, decode_in_reads);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+// Parsing requests into commands, the names in the cases clients send them. The command is found
+// in the registry without allocating, whatever the case of its name.
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use simple_redis_server::{cmd::Command, BulkString, RespArray, RespFrame};
+
+fn request(args: &[&str]) -> RespArray {
+    RespArray::new(
+        args.iter()
+            .map(|arg| BulkString::from(*arg).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+}
+
+fn dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispatch");
+    group.throughput(Throughput::Elements(1));
+    for name in ["get", "GET", "HGetAll", "restore-asking"] {
+        let request = match name {
+            "restore-asking" => request(&[name, "key", "0", "payload"]),
+            _ => request(&[name, "key"]),
+        };
+        group.bench_with_input(BenchmarkId::from_parameter(name), &request, |b, request| {
+            b.iter_batched(
+                || request.clone(),
+                |request| Command::try_from(request).expect("a command"),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
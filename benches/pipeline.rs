@@ -0,0 +1,54 @@
+// Throughput of SET over one connection, one request at a time or pipelined like
+// redis-benchmark -P 16. The replies of a pipeline are written back in one go. The server runs on
+// the runtime of the build, `--features current-thread` measures the single reactor.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use simple_redis_server::{handle_stream, runtime, Backend};
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+};
+use tokio::net::TcpListener;
+
+const REQUEST: &[u8] = b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n";
+const REPLY: &[u8] = b"+OK\r\n";
+
+// A server on a port of its own, running until the process exits.
+fn start_server() -> SocketAddr {
+    let runtime = runtime().expect("tokio runtime");
+    let listener = runtime
+        .block_on(TcpListener::bind("127.0.0.1:0"))
+        .expect("bind a port");
+    let addr = listener.local_addr().expect("local address");
+    thread::spawn(move || {
+        runtime.block_on(async move {
+            let backend = Backend::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_stream(stream, backend.clone()));
+            }
+        })
+    });
+    addr
+}
+
+fn pipeline(c: &mut Criterion) {
+    let addr = start_server();
+    let mut group = c.benchmark_group("pipeline");
+    for depth in [1, 16] {
+        let mut stream = TcpStream::connect(addr).expect("connect");
+        stream.set_nodelay(true).expect("nodelay");
+        let requests = REQUEST.repeat(depth);
+        let mut replies = vec![0; REPLY.len() * depth];
+        group.throughput(Throughput::Elements(depth as u64));
+        group.bench_function(BenchmarkId::new("set", depth), |b| {
+            b.iter(|| {
+                stream.write_all(&requests).expect("send the requests");
+                stream.read_exact(&mut replies).expect("read the replies");
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
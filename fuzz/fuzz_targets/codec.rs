@@ -0,0 +1,29 @@
+#![no_main]
+
+// The requests of a connection as the server reads them, inline commands and the limits on the
+// headers included, the bytes arriving in two reads.
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use simple_redis_server::{DecodeLimits, RespCodec};
+use tokio_util::codec::Decoder;
+
+fuzz_target!(|data: &[u8]| {
+    let limits = DecodeLimits {
+        max_bulk_len: 1024,
+        max_aggregate_len: 64,
+        max_depth: 8,
+    };
+    let mut codec = RespCodec::default().with_limits(limits);
+    let (first, second) = data.split_at(data.len() / 2);
+    let mut buf = BytesMut::from(first);
+    for more in [second, &[]] {
+        loop {
+            match codec.decode(&mut buf) {
+                Ok(Some(_)) => continue,
+                Ok(None) => break,
+                Err(_) => return,
+            }
+        }
+        buf.extend_from_slice(more);
+    }
+});
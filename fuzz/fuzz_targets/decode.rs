@@ -0,0 +1,23 @@
+#![no_main]
+
+// A frame decoded from any bytes takes as many of them as expect_length says, and is written back
+// as bytes which decode again. The inputs found to break this belong in corpus/decode, which the
+// tests of the decoder go through.
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use simple_redis_server::{RespDecoder, RespEncoder, RespError, RespFrame};
+
+fuzz_target!(|data: &[u8]| {
+    let expected = RespFrame::expect_length(data);
+    let mut buf = BytesMut::from(data);
+    match RespFrame::decode(&mut buf) {
+        Ok(frame) => {
+            assert_eq!(expected, Ok(data.len() - buf.len()));
+            let encoded = frame.encode_to_vec();
+            assert_eq!(frame.encoded_len(), encoded.len());
+            assert!(RespFrame::decode(&mut BytesMut::from(&encoded[..])).is_ok());
+        }
+        Err(RespError::NotComplete) => assert!(expected.is_err()),
+        Err(_) => {}
+    }
+});
@@ -0,0 +1,508 @@
+use crate::{
+    cmd::{lookup, COMMANDS},
+    glob::glob_match,
+};
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::Path,
+    sync::RwLock,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AclError {
+    #[error("Error in ACL SETUSER modifier '{0}': Syntax error")]
+    Syntax(String),
+    #[error("Error in ACL SETUSER modifier '{0}': Unknown command or category name in ACL")]
+    UnknownCommand(String),
+    #[error("The 'default' user cannot be removed")]
+    DefaultUser,
+    #[error("This Redis instance is not configured to use an ACL file. You may want to specify users via the ACL SETUSER command and then issue a CONFIG REWRITE (assuming you have a Redis configuration file set) in order to store users in the Redis configuration.")]
+    NoAclFile,
+    #[error("{0} at line {1} of the ACL file")]
+    BadLine(String, usize),
+    #[error("{0}")]
+    Io(String),
+}
+
+impl From<io::Error> for AclError {
+    fn from(e: io::Error) -> Self {
+        AclError::Io(e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct User {
+    pub name: String,
+    pub enabled: bool,
+    // any password is accepted
+    pub nopass: bool,
+    // SHA256 of the passwords, hex encoded
+    pub passwords: Vec<String>,
+    // allowed commands, either `name` or `name|subcommand`
+    commands: BTreeSet<String>,
+    // denied subcommands of allowed containers, `name|subcommand`, they win over the container
+    denied: BTreeSet<String>,
+    // the custom commands are allowed, the @custom category
+    custom: bool,
+    // the command rules as given, e.g. "+@read -keys", used to describe the user
+    command_rules: Vec<String>,
+    // glob patterns of the keys the user can access
+    pub keys: Vec<String>,
+}
+
+// The users known by the server, checked before every command.
+#[derive(Debug)]
+pub struct Acl {
+    users: RwLock<BTreeMap<String, User>>,
+}
+
+impl Default for Acl {
+    fn default() -> Self {
+        let mut users = BTreeMap::new();
+        users.insert("default".to_string(), User::default_user());
+        Self {
+            users: RwLock::new(users),
+        }
+    }
+}
+
+impl User {
+    // a new user can do nothing until rules are added
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            enabled: false,
+            nopass: false,
+            passwords: vec![],
+            commands: BTreeSet::new(),
+            denied: BTreeSet::new(),
+            custom: false,
+            command_rules: vec![],
+            keys: vec![],
+        }
+    }
+
+    fn default_user() -> Self {
+        let mut user = Self::new("default");
+        for rule in ["on", "nopass", "~*", "+@all"] {
+            // the rules are known to be valid
+            let _ = user.apply(rule);
+        }
+        user
+    }
+
+    // Applies a rule like `on`, `>password`, `~pattern`, `+@category` or `-command`.
+    pub fn apply(&mut self, rule: &str) -> Result<(), AclError> {
+        match rule.to_ascii_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => self.keys = vec!["*".to_string()],
+            "resetkeys" => self.keys.clear(),
+            "allcommands" => return self.apply("+@all"),
+            "nocommands" => return self.apply("-@all"),
+            "reset" => *self = Self::new(self.name.clone()),
+            _ => match rule.split_at(rule.chars().next().map_or(0, |c| c.len_utf8())) {
+                (">", password) => {
+                    let hash = sha256_hex(password);
+                    if !self.passwords.contains(&hash) {
+                        self.passwords.push(hash);
+                    }
+                    self.nopass = false;
+                }
+                ("<", password) => {
+                    let hash = sha256_hex(password);
+                    self.passwords.retain(|p| *p != hash);
+                }
+                ("#", hash) if hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                    let hash = hash.to_ascii_lowercase();
+                    if !self.passwords.contains(&hash) {
+                        self.passwords.push(hash);
+                    }
+                    self.nopass = false;
+                }
+                ("~", pattern) => {
+                    if !self.keys.iter().any(|p| p == pattern) {
+                        self.keys.push(pattern.to_string());
+                    }
+                }
+                (sign @ ("+" | "-"), name) => {
+                    let commands = command_names(name)
+                        .ok_or_else(|| AclError::UnknownCommand(rule.to_string()))?;
+                    for command in commands {
+                        match sign {
+                            "+" => self.allow(command),
+                            _ => self.deny(command),
+                        }
+                    }
+                    // the custom commands are not known up front, @all includes them
+                    if name.eq_ignore_ascii_case("@all") || name.eq_ignore_ascii_case("@custom") {
+                        self.custom = sign == "+";
+                    }
+                    // +@all and -@all make all the previous rules irrelevant
+                    if name.eq_ignore_ascii_case("@all") {
+                        self.command_rules.clear();
+                    }
+                    self.command_rules.push(rule.to_ascii_lowercase());
+                }
+                _ => return Err(AclError::Syntax(rule.to_string())),
+            },
+        }
+        Ok(())
+    }
+
+    // A container allows its subcommands again, a subcommand is allowed on its own.
+    fn allow(&mut self, command: String) {
+        if command.contains('|') {
+            self.denied.remove(&command);
+        } else {
+            let prefix = format!("{}|", command);
+            self.denied.retain(|denied| !denied.starts_with(&prefix));
+        }
+        self.commands.insert(command);
+    }
+
+    // A container denies its subcommands too, a subcommand is denied even if its container is
+    // allowed.
+    fn deny(&mut self, command: String) {
+        self.commands.remove(&command);
+        if command.contains('|') {
+            self.denied.insert(command);
+        } else {
+            let prefix = format!("{}|", command);
+            self.commands
+                .retain(|allowed| !allowed.starts_with(&prefix));
+            self.denied.retain(|denied| !denied.starts_with(&prefix));
+        }
+    }
+
+    // `name` is the command name, with the subcommand for containers, e.g. `client|list`. A
+    // command the server doesn't have is taken for a custom one.
+    pub fn can_run(&self, name: &str) -> bool {
+        let top = name.split('|').next().unwrap_or_default();
+        if lookup(top).is_none() {
+            return self.custom;
+        }
+        if self.denied.contains(name) {
+            return false;
+        }
+        self.commands.contains(top) || self.commands.contains(name)
+    }
+
+    pub fn can_access(&self, key: &[u8]) -> bool {
+        self.keys
+            .iter()
+            .any(|pattern| glob_match(pattern.as_bytes(), key, false))
+    }
+
+    pub fn command_rules(&self) -> String {
+        match self.command_rules.is_empty() {
+            true => "-@all".to_string(),
+            false => self.command_rules.join(" "),
+        }
+    }
+
+    // The user as a line of ACL LIST and of the ACL file, e.g. `user default on nopass ~* +@all`.
+    pub fn describe(&self) -> String {
+        let mut rules = vec![
+            format!("user {}", self.name),
+            (if self.enabled { "on" } else { "off" }).to_string(),
+        ];
+        if self.nopass {
+            rules.push("nopass".to_string());
+        }
+        rules.extend(self.passwords.iter().map(|hash| format!("#{}", hash)));
+        rules.extend(self.keys.iter().map(|pattern| format!("~{}", pattern)));
+        rules.push(self.command_rules());
+        rules.join(" ")
+    }
+}
+
+impl Acl {
+    // Creates or modifies a user, either all the rules apply or none.
+    pub fn set_user(&self, name: &str, rules: &[String]) -> Result<(), AclError> {
+        let mut users = self.users.write().unwrap_or_else(|e| e.into_inner());
+        let mut user = users.get(name).cloned().unwrap_or_else(|| User::new(name));
+        for rule in rules {
+            user.apply(rule)?;
+        }
+        users.insert(name.to_string(), user);
+        Ok(())
+    }
+
+    pub fn get_user(&self, name: &str) -> Option<User> {
+        let users = self.users.read().unwrap_or_else(|e| e.into_inner());
+        users.get(name).cloned()
+    }
+
+    // Returns the number of deleted users.
+    pub fn del_users(&self, names: &[String]) -> Result<usize, AclError> {
+        if names.iter().any(|name| name == "default") {
+            return Err(AclError::DefaultUser);
+        }
+        let mut users = self.users.write().unwrap_or_else(|e| e.into_inner());
+        Ok(names
+            .iter()
+            .filter(|name| users.remove(name.as_str()).is_some())
+            .count())
+    }
+
+    pub fn users(&self) -> Vec<User> {
+        let users = self.users.read().unwrap_or_else(|e| e.into_inner());
+        users.values().cloned().collect()
+    }
+
+    // `requirepass` is an extra password of the default user.
+    pub fn authenticate(&self, name: &str, password: &str, requirepass: &str) -> bool {
+        let Some(user) = self.get_user(name) else {
+            return false;
+        };
+        if !user.enabled {
+            return false;
+        }
+        if name == "default" && !requirepass.is_empty() {
+            return password == requirepass || user.passwords.contains(&sha256_hex(password));
+        }
+        user.nopass || user.passwords.contains(&sha256_hex(password))
+    }
+
+    // Whether the default user can be used without AUTH.
+    pub fn default_nopass(&self, requirepass: &str) -> bool {
+        requirepass.is_empty()
+            && self
+                .get_user("default")
+                .is_some_and(|user| user.enabled && user.nopass)
+    }
+
+    // Checks that a user can run a command, `keys` are only computed when the user can't
+    // access all the keys. `custom` tells whether it's a custom command, the other commands
+    // unknown to the server are left to fail on their own.
+    pub fn check(
+        &self,
+        username: &str,
+        name: &str,
+        custom: bool,
+        keys: impl FnOnce() -> Vec<Bytes>,
+    ) -> Result<(), String> {
+        if !custom && lookup(name.split('|').next().unwrap_or_default()).is_none() {
+            return Ok(());
+        }
+        let users = self.users.read().unwrap_or_else(|e| e.into_inner());
+        let user = users
+            .get(username)
+            .filter(|user| user.can_run(name))
+            .ok_or_else(|| {
+                format!(
+                    "NOPERM User {} has no permissions to run the '{}' command",
+                    username, name
+                )
+            })?;
+        if user.keys.iter().any(|pattern| pattern == "*") {
+            return Ok(());
+        }
+        match keys().iter().all(|key| user.can_access(key)) {
+            true => Ok(()),
+            false => Err("NOPERM No permissions to access a key".to_string()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), AclError> {
+        let content = self
+            .users()
+            .iter()
+            .map(|user| user.describe() + "\n")
+            .collect::<String>();
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    // Replaces all the users by the ones of the ACL file, nothing changes on error.
+    pub fn load(&self, path: &Path) -> Result<(), AclError> {
+        let content = fs::read_to_string(path)?;
+        let mut loaded = BTreeMap::new();
+        for (i, line) in content.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                None => continue,
+                Some(word) if word.starts_with('#') => continue,
+                Some("user") => {}
+                Some(_) => {
+                    return Err(AclError::BadLine(
+                        "Line should start with user keyword".to_string(),
+                        i + 1,
+                    ))
+                }
+            }
+            let name = words
+                .next()
+                .ok_or_else(|| AclError::BadLine("Missing user name".to_string(), i + 1))?;
+            let mut user = User::new(name);
+            for rule in words {
+                user.apply(rule)
+                    .map_err(|e| AclError::BadLine(e.to_string(), i + 1))?;
+            }
+            loaded.insert(name.to_string(), user);
+        }
+        loaded
+            .entry("default".to_string())
+            .or_insert_with(User::default_user);
+        *self.users.write().unwrap_or_else(|e| e.into_inner()) = loaded;
+        Ok(())
+    }
+}
+
+// All the categories of the known commands, without the leading @.
+pub fn categories() -> BTreeSet<String> {
+    let mut categories = BTreeSet::from(["all".to_string(), "custom".to_string()]);
+    for spec in COMMANDS {
+        for category in spec.categories() {
+            categories.insert(category.trim_start_matches('@').to_string());
+        }
+    }
+    categories
+}
+
+// The commands of a category, None if the category is unknown. The custom commands belong to the
+// backend, they are not listed.
+pub fn category_commands(category: &str) -> Option<Vec<&'static str>> {
+    let category = category.to_ascii_lowercase();
+    if !categories().contains(&category) {
+        return None;
+    }
+    let tag = format!("@{}", category);
+    Some(
+        COMMANDS
+            .iter()
+            .filter(|spec| category == "all" || spec.categories().contains(&tag))
+            .map(|spec| spec.name)
+            .collect(),
+    )
+}
+
+// The commands a `+`/`-` rule refers to: `@category`, `command` or `command|subcommand`.
+fn command_names(name: &str) -> Option<Vec<String>> {
+    if let Some(category) = name.strip_prefix('@') {
+        let commands = category_commands(category)?;
+        return Some(commands.into_iter().map(String::from).collect());
+    }
+    let name = name.to_ascii_lowercase();
+    let top = name.split('|').next().unwrap_or_default();
+    lookup(top).map(|_| vec![name])
+}
+
+fn sha256_hex(password: &str) -> String {
+    Sha256::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_rules() -> Result<(), AclError> {
+        let acl = Acl::default();
+        let rules = ["on", ">secret", "~user:*", "+@read", "-hgetall"].map(String::from);
+        acl.set_user("alice", &rules)?;
+        let user = acl.get_user("alice").unwrap();
+        assert!(user.can_run("get"));
+        assert!(!user.can_run("hgetall"));
+        assert!(!user.can_run("set"));
+        assert!(user.can_access(b"user:1"));
+        assert!(!user.can_access(b"order:1"));
+        assert!(acl.authenticate("alice", "secret", ""));
+        assert!(!acl.authenticate("alice", "wrong", ""));
+        assert_eq!(
+            acl.check("alice", "set", false, Vec::new),
+            Err("NOPERM User alice has no permissions to run the 'set' command".to_string())
+        );
+        assert_eq!(
+            acl.check("alice", "get", false, || vec!["order:1".into()]),
+            Err("NOPERM No permissions to access a key".to_string())
+        );
+        assert!(user.describe().ends_with("~user:* +@read -hgetall"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_denied_subcommands_win_over_their_container() -> Result<(), AclError> {
+        let acl = Acl::default();
+        acl.set_user("ops", &["on", "+client", "-client|kill"].map(String::from))?;
+        let user = acl.get_user("ops").unwrap();
+        assert!(user.can_run("client|list"));
+        assert!(!user.can_run("client|kill"));
+
+        // the container allows it again, and denies the subcommands allowed on their own
+        acl.set_user("ops", &["+client"].map(String::from))?;
+        assert!(acl.get_user("ops").unwrap().can_run("client|kill"));
+        acl.set_user(
+            "ops",
+            &["-client", "+client|list", "-client"].map(String::from),
+        )?;
+        assert!(!acl.get_user("ops").unwrap().can_run("client|list"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_commands_follow_the_custom_category() -> Result<(), AclError> {
+        let acl = Acl::default();
+        acl.set_user("app", &["on", "+@read"].map(String::from))?;
+        assert!(acl.check("app", "myapp.ratelimit", true, Vec::new).is_err());
+        // an unknown command is not one to check
+        assert!(acl.check("app", "nosuchcommand", false, Vec::new).is_ok());
+
+        acl.set_user("app", &["+@custom"].map(String::from))?;
+        assert!(acl.check("app", "myapp.ratelimit", true, Vec::new).is_ok());
+        acl.set_user("app", &["-@all", "+get"].map(String::from))?;
+        assert!(acl.check("app", "myapp.ratelimit", true, Vec::new).is_err());
+        // the default user can run everything
+        assert!(acl
+            .check("default", "myapp.ratelimit", true, Vec::new)
+            .is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_rules_are_not_applied() {
+        let acl = Acl::default();
+        let rules = ["on", "+nosuchcommand"].map(String::from);
+        assert_eq!(
+            acl.set_user("bob", &rules),
+            Err(AclError::UnknownCommand("+nosuchcommand".to_string()))
+        );
+        assert!(acl.get_user("bob").is_none());
+        assert_eq!(
+            acl.del_users(&["default".to_string()]),
+            Err(AclError::DefaultUser)
+        );
+    }
+
+    #[test]
+    fn test_acl_file_round_trip() -> Result<(), AclError> {
+        let path = std::env::temp_dir().join(format!("simple-redis-{}.acl", std::process::id()));
+        let acl = Acl::default();
+        acl.set_user("alice", &["on", ">pw", "allkeys", "+get"].map(String::from))?;
+        acl.save(&path)?;
+
+        let loaded = Acl::default();
+        loaded.load(&path)?;
+        assert_eq!(loaded.get_user("alice"), acl.get_user("alice"));
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
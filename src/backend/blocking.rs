@@ -0,0 +1,282 @@
+use bytes::Bytes;
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, Weak,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{oneshot, Notify},
+    time::{self, Instant},
+};
+
+// the reply of a command unblocked by CLIENT UNBLOCK ... ERROR
+pub(crate) const UNBLOCKED: &str = "UNBLOCKED client unblocked via CLIENT UNBLOCK";
+
+// Why a blocked client resumes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Wakeup {
+    // one of the keys was written, the command checks it again
+    Ready(Bytes),
+    // the timeout expired, or CLIENT UNBLOCK
+    TimedOut,
+    // CLIENT UNBLOCK ... ERROR
+    Unblocked,
+}
+
+// The clients blocked by a command until one of its keys is written or its timeout expires, like
+// BLPOP or WAIT. A write wakes up the client which blocked first on the key, the timeouts of all
+// the clients are expired by a single timer task.
+#[derive(Debug, Default)]
+pub struct BlockedClients {
+    shared: Arc<Shared>,
+    // the timer task is spawned by the first client blocking with a timeout
+    timer: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    waiters: Mutex<Waiters>,
+    // the number of blocked clients, a write takes no lock while there is none
+    len: AtomicUsize,
+    // tells the timer task about an earlier deadline, or that the registry is gone
+    rescheduled: Arc<Notify>,
+}
+
+#[derive(Debug, Default)]
+struct Waiters {
+    next_ticket: u64,
+    by_ticket: HashMap<u64, Waiter>,
+    // the clients blocked on a key, oldest first
+    by_key: HashMap<Bytes, VecDeque<u64>>,
+    deadlines: BTreeSet<(Instant, u64)>,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    client: u64,
+    keys: Vec<Bytes>,
+    deadline: Option<Instant>,
+    sender: oneshot::Sender<Wakeup>,
+}
+
+// A client blocked by `BlockedClients::block`, it's no longer blocked once dropped.
+#[derive(Debug)]
+pub struct Blocked<'a> {
+    clients: &'a BlockedClients,
+    ticket: u64,
+    receiver: oneshot::Receiver<Wakeup>,
+}
+
+impl BlockedClients {
+    // Blocks a client until one of the keys is written, the timeout expires (never if None) or
+    // CLIENT UNBLOCK. A command without keys, like WAIT, only waits for the timeout.
+    pub fn block(&self, client: u64, keys: Vec<Bytes>, timeout: Option<Duration>) -> Blocked<'_> {
+        let (sender, receiver) = oneshot::channel();
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut waiters = self.shared.lock();
+        let ticket = waiters.next_ticket;
+        waiters.next_ticket += 1;
+        for key in &keys {
+            waiters
+                .by_key
+                .entry(key.clone())
+                .or_default()
+                .push_back(ticket);
+        }
+        if let Some(deadline) = deadline {
+            let earliest = waiters
+                .deadlines
+                .first()
+                .is_none_or(|(first, _)| deadline < *first);
+            waiters.deadlines.insert((deadline, ticket));
+            if earliest {
+                self.shared.rescheduled.notify_one();
+            }
+            if !self.timer.swap(true, Ordering::Relaxed) {
+                let rescheduled = self.shared.rescheduled.clone();
+                tokio::spawn(expire_timeouts(Arc::downgrade(&self.shared), rescheduled));
+            }
+        }
+        waiters.by_ticket.insert(
+            ticket,
+            Waiter {
+                client,
+                keys,
+                deadline,
+                sender,
+            },
+        );
+        self.shared.len.fetch_add(1, Ordering::Relaxed);
+        Blocked {
+            clients: self,
+            ticket,
+            receiver,
+        }
+    }
+
+    // Wakes up the client which blocked first on a key that was written.
+    pub fn signal(&self, key: &[u8]) {
+        if self.is_empty() {
+            return;
+        }
+        let mut waiters = self.shared.lock();
+        while let Some(&ticket) = waiters.by_key.get(key).and_then(|queue| queue.front()) {
+            let waiter = self.shared.take(&mut waiters, ticket);
+            // a client gone in between doesn't count
+            if waiter.is_some_and(|waiter| {
+                waiter
+                    .sender
+                    .send(Wakeup::Ready(Bytes::copy_from_slice(key)))
+                    .is_ok()
+            }) {
+                return;
+            }
+        }
+    }
+
+    // Wakes up a blocked client, for CLIENT UNBLOCK. Returns false if it wasn't blocked.
+    pub fn unblock(&self, client: u64, wakeup: Wakeup) -> bool {
+        let mut waiters = self.shared.lock();
+        let ticket = waiters
+            .by_ticket
+            .iter()
+            .find(|(_, waiter)| waiter.client == client)
+            .map(|(ticket, _)| *ticket);
+        match ticket.and_then(|ticket| self.shared.take(&mut waiters, ticket)) {
+            Some(waiter) => waiter.sender.send(wakeup).is_ok(),
+            None => false,
+        }
+    }
+
+    // the number of blocked clients
+    pub fn len(&self) -> usize {
+        self.shared.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for BlockedClients {
+    fn drop(&mut self) {
+        // the timer task ends
+        self.shared.rescheduled.notify_one();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Waiters> {
+        self.waiters.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Removes a client from the keys it waits on and from the deadlines.
+    fn take(&self, waiters: &mut Waiters, ticket: u64) -> Option<Waiter> {
+        let waiter = waiters.by_ticket.remove(&ticket)?;
+        for key in &waiter.keys {
+            if let Some(queue) = waiters.by_key.get_mut(key) {
+                queue.retain(|t| *t != ticket);
+                if queue.is_empty() {
+                    waiters.by_key.remove(key);
+                }
+            }
+        }
+        if let Some(deadline) = waiter.deadline {
+            waiters.deadlines.remove(&(deadline, ticket));
+        }
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(waiter)
+    }
+}
+
+impl Blocked<'_> {
+    // Resolves once the client is woken up, it's no longer blocked then.
+    pub async fn wakeup(&mut self) -> Wakeup {
+        (&mut self.receiver).await.unwrap_or(Wakeup::TimedOut)
+    }
+}
+
+impl Drop for Blocked<'_> {
+    fn drop(&mut self) {
+        let shared = &self.clients.shared;
+        let mut waiters = shared.lock();
+        shared.take(&mut waiters, self.ticket);
+    }
+}
+
+// The timer task, it sleeps until the earliest deadline and wakes up the clients whose timeout
+// expired.
+async fn expire_timeouts(shared: Weak<Shared>, rescheduled: Arc<Notify>) {
+    loop {
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        let next = {
+            let mut waiters = shared.lock();
+            let now = Instant::now();
+            while let Some(&(deadline, ticket)) = waiters.deadlines.first() {
+                if deadline > now {
+                    break;
+                }
+                if let Some(waiter) = shared.take(&mut waiters, ticket) {
+                    let _ = waiter.sender.send(Wakeup::TimedOut);
+                }
+            }
+            waiters.deadlines.first().map(|(deadline, _)| *deadline)
+        };
+        drop(shared);
+        match next {
+            Some(next) => tokio::select! {
+                _ = time::sleep_until(next) => {}
+                _ = rescheduled.notified() => {}
+            },
+            None => rescheduled.notified().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fifo_wakeup() {
+        let clients = BlockedClients::default();
+        let mut first = clients.block(1, vec!["a".into(), "b".into()], None);
+        let mut second = clients.block(2, vec!["b".into()], None);
+        assert_eq!(clients.len(), 2);
+
+        clients.signal(b"b");
+        assert_eq!(first.wakeup().await, Wakeup::Ready("b".into()));
+        // the first client no longer waits on "a"
+        clients.signal(b"a");
+        clients.signal(b"b");
+        assert_eq!(second.wakeup().await, Wakeup::Ready("b".into()));
+        assert!(clients.is_empty());
+
+        let mut third = clients.block(3, vec!["c".into()], None);
+        drop(clients.block(4, vec!["c".into()], None));
+        assert!(clients.unblock(3, Wakeup::Unblocked));
+        assert!(!clients.unblock(4, Wakeup::Unblocked));
+        assert_eq!(third.wakeup().await, Wakeup::Unblocked);
+        assert!(clients.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_timeouts() {
+        let clients = BlockedClients::default();
+        let start = Instant::now();
+        let mut late = clients.block(1, vec!["k".into()], Some(Duration::from_millis(200)));
+        // an earlier deadline reschedules the timer task
+        let mut early = clients.block(2, vec![], Some(Duration::from_millis(50)));
+        assert_eq!(early.wakeup().await, Wakeup::TimedOut);
+        assert!(start.elapsed() < Duration::from_millis(200));
+        assert_eq!(clients.len(), 1);
+        assert_eq!(late.wakeup().await, Wakeup::TimedOut);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(clients.is_empty());
+    }
+}
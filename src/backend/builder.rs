@@ -0,0 +1,114 @@
+use super::{keyspace::SHARDS, Backend, BackendInner, Clock, Keyspace};
+use crate::{cmd::CommandRegistry, ServerConfig};
+use dashmap::DashMap;
+use std::{path::Path, sync::Arc};
+use tracing::warn;
+
+// The options of a backend, see `Backend::builder`.
+#[derive(Debug, Default)]
+pub struct BackendBuilder {
+    config: ServerConfig,
+    capacity: usize,
+    shards: Option<usize>,
+    clock: Option<Arc<dyn Clock>>,
+    commands: CommandRegistry,
+}
+
+impl BackendBuilder {
+    pub fn config(mut self, config: ServerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    // The number of keys expected, the maps are allocated for them up front.
+    pub fn capacity(mut self, keys: usize) -> Self {
+        self.capacity = keys;
+        self
+    }
+
+    // The number of locks the keyspace is split into, 16 by default.
+    pub fn shards(mut self, shards: usize) -> Self {
+        self.shards = Some(shards);
+        self
+    }
+
+    // The time of the expiration times and the eviction policies, the system clock by default.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    // The custom commands the clients can run besides the built-in ones, none by default.
+    pub fn commands(mut self, commands: CommandRegistry) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    pub fn build(self) -> Backend {
+        let defaults = BackendInner::default();
+        let backend = Backend(Arc::new(BackendInner {
+            keyspace: Keyspace::with_capacity(self.shards.unwrap_or(SHARDS), self.capacity),
+            access: DashMap::with_capacity(self.capacity),
+            clock: self.clock.unwrap_or(defaults.clock.clone()),
+            config: self.config,
+            commands: self.commands,
+            ..defaults
+        }));
+        let aclfile = backend.config.snapshot().aclfile;
+        if !aclfile.is_empty() {
+            if let Err(e) = backend.acl.load(Path::new(&aclfile)) {
+                warn!("Failed to load the ACL file {}: {}", aclfile, e);
+            }
+        }
+        backend
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::now_ms, Config, ManualClock, RespFrame};
+    use std::time::Duration;
+
+    #[test]
+    fn test_manual_clock_expires_keys() {
+        let clock = Arc::new(ManualClock::new(now_ms()));
+        let backend = Backend::builder()
+            .shards(4)
+            .capacity(100)
+            .clock(clock.clone())
+            .build();
+        backend.set("k".into(), RespFrame::Integer(1));
+        backend.expire_at(b"k", backend.now_ms() + 10_000);
+        clock.advance(Duration::from_secs(9));
+        assert!(backend.exists(b"k"));
+        clock.advance(Duration::from_secs(1));
+        assert!(!backend.exists(b"k"));
+    }
+
+    #[test]
+    fn test_manual_clock_lru_eviction() {
+        let clock = Arc::new(ManualClock::new(0));
+        let backend = Backend::builder()
+            .config(ServerConfig::new(Config {
+                maxmemory: 1000,
+                maxmemory_policy: "allkeys-lru".to_string(),
+                ..Default::default()
+            }))
+            .clock(clock.clone())
+            .build();
+        for i in 0..5 {
+            backend.set(format!("key:{}", i).into(), RespFrame::Integer(i));
+            clock.advance(Duration::from_secs(1));
+        }
+        backend.get(b"key:0");
+        backend
+            .config
+            .set(&[("maxmemory".into(), (backend.used_memory() - 1).to_string())])
+            .unwrap();
+        assert!(backend.free_memory());
+        // the oldest access is evicted, key:0 was just read
+        assert!(backend.exists(b"key:0"));
+        assert!(!backend.exists(b"key:1"));
+    }
+}
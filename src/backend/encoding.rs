@@ -0,0 +1,257 @@
+use super::evict::frame_size;
+use crate::{EncodingLimits, RespFrame};
+use bytes::Bytes;
+use std::collections::{HashMap, HashSet};
+
+// Like the listpacks and intsets of redis, small hashes and sets are stored in flat vectors and
+// converted to hash tables once they grow past the limits of the config. They are never
+// converted back.
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Hash {
+    // the fields in insertion order, looked up by a linear scan
+    Listpack(Vec<(Bytes, RespFrame)>),
+    Table(HashMap<Bytes, RespFrame>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Set {
+    // an intset: sorted, looked up by a binary search
+    Ints(Vec<i64>),
+    Listpack(Vec<Bytes>),
+    Table(HashSet<Bytes>),
+}
+
+impl Default for Hash {
+    fn default() -> Self {
+        Hash::Listpack(vec![])
+    }
+}
+
+impl Default for Set {
+    fn default() -> Self {
+        Set::Ints(vec![])
+    }
+}
+
+impl Hash {
+    pub fn from_fields(fields: Vec<(Bytes, RespFrame)>, limits: &EncodingLimits) -> Self {
+        let mut hash = Hash::default();
+        for (field, value) in fields {
+            hash.insert(field, value, limits);
+        }
+        hash
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Hash::Listpack(_) => "listpack",
+            Hash::Table(_) => "hashtable",
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Hash::Listpack(fields) => fields.len(),
+            Hash::Table(fields) => fields.len(),
+        }
+    }
+
+    pub fn get(&self, field: &[u8]) -> Option<&RespFrame> {
+        match self {
+            Hash::Listpack(fields) => fields
+                .iter()
+                .find(|(f, _)| f.as_ref() == field)
+                .map(|(_, value)| value),
+            Hash::Table(fields) => fields.get(field),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (&Bytes, &RespFrame)> + '_> {
+        match self {
+            Hash::Listpack(fields) => Box::new(fields.iter().map(|(field, value)| (field, value))),
+            Hash::Table(fields) => Box::new(fields.iter()),
+        }
+    }
+
+    // Returns the previous value of the field.
+    pub fn insert(
+        &mut self,
+        field: Bytes,
+        value: RespFrame,
+        limits: &EncodingLimits,
+    ) -> Option<RespFrame> {
+        if let Hash::Listpack(fields) = self {
+            let fits = field.len() as u64 <= limits.hash_max_listpack_value
+                && frame_size(&value) <= limits.hash_max_listpack_value;
+            if let Some((_, old)) = fields.iter_mut().find(|(f, _)| *f == field) {
+                if fits {
+                    return Some(std::mem::replace(old, value));
+                }
+            } else if fits && (fields.len() as u64) < limits.hash_max_listpack_entries {
+                fields.push((field, value));
+                return None;
+            }
+            *self = Hash::Table(fields.drain(..).collect());
+        }
+        match self {
+            Hash::Table(fields) => fields.insert(field, value),
+            Hash::Listpack(_) => unreachable!("converted above"),
+        }
+    }
+}
+
+impl Set {
+    pub fn from_members(members: Vec<Bytes>, limits: &EncodingLimits) -> Self {
+        let mut set = Set::default();
+        for member in members {
+            set.insert(member, limits);
+        }
+        set
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Set::Ints(_) => "intset",
+            Set::Listpack(_) => "listpack",
+            Set::Table(_) => "hashtable",
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Set::Ints(members) => members.len(),
+            Set::Listpack(members) => members.len(),
+            Set::Table(members) => members.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, member: &[u8]) -> bool {
+        match self {
+            Set::Ints(members) => as_int(member).is_some_and(|n| members.binary_search(&n).is_ok()),
+            Set::Listpack(members) => members.iter().any(|m| m.as_ref() == member),
+            Set::Table(members) => members.contains(member),
+        }
+    }
+
+    // The members, the integers of an intset are formatted on the fly.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = Bytes> + '_> {
+        match self {
+            Set::Ints(members) => Box::new(members.iter().map(|n| Bytes::from(n.to_string()))),
+            Set::Listpack(members) => Box::new(members.iter().cloned()),
+            Set::Table(members) => Box::new(members.iter().cloned()),
+        }
+    }
+
+    // Returns true if the member was not already in the set.
+    pub fn insert(&mut self, member: Bytes, limits: &EncodingLimits) -> bool {
+        if self.contains(&member) {
+            return false;
+        }
+        let len = self.len() as u64 + 1;
+        if let Set::Ints(members) = self {
+            match as_int(&member) {
+                Some(n) if len <= limits.set_max_intset_entries => {
+                    let at = members.binary_search(&n).unwrap_or_else(|at| at);
+                    members.insert(at, n);
+                    return true;
+                }
+                _ => *self = Set::Listpack(self.iter().collect()),
+            }
+        }
+        if let Set::Listpack(members) = self {
+            if len > limits.set_max_listpack_entries
+                || member.len() as u64 > limits.set_max_listpack_value
+            {
+                *self = Set::Table(members.drain(..).collect());
+            }
+        }
+        match self {
+            Set::Listpack(members) => members.push(member),
+            Set::Table(members) => {
+                members.insert(member);
+            }
+            Set::Ints(_) => unreachable!("converted above"),
+        }
+        true
+    }
+
+    // Returns true if the member was in the set.
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self {
+            Set::Ints(members) => match as_int(member).map(|n| members.binary_search(&n)) {
+                Some(Ok(at)) => {
+                    members.remove(at);
+                    true
+                }
+                _ => false,
+            },
+            Set::Listpack(members) => match members.iter().position(|m| m.as_ref() == member) {
+                Some(at) => {
+                    members.remove(at);
+                    true
+                }
+                None => false,
+            },
+            Set::Table(members) => members.remove(member),
+        }
+    }
+}
+
+// The member as an integer if it's the canonical representation of one, "01" is not.
+fn as_int(member: &[u8]) -> Option<i64> {
+    let n: i64 = std::str::from_utf8(member).ok()?.parse().ok()?;
+    (n.to_string().as_bytes() == member).then_some(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_conversions() {
+        let limits = EncodingLimits {
+            set_max_intset_entries: 4,
+            set_max_listpack_entries: 6,
+            ..Default::default()
+        };
+        let mut set = Set::from_members(vec!["3".into(), "-1".into(), "2".into()], &limits);
+        assert_eq!(set, Set::Ints(vec![-1, 2, 3]));
+        assert!(set.insert("a".into(), &limits));
+        assert_eq!(set.encoding(), "listpack");
+        assert!(set.contains(b"-1") && set.contains(b"a"));
+        for member in ["b", "c"] {
+            set.insert(member.into(), &limits);
+        }
+        assert_eq!(set.encoding(), "listpack");
+        set.insert("d".into(), &limits);
+        assert_eq!(set.encoding(), "hashtable");
+        assert_eq!(set.len(), 7);
+        assert!(set.remove(b"2") && !set.contains(b"2"));
+    }
+
+    #[test]
+    fn test_hash_conversion() {
+        let limits = EncodingLimits {
+            hash_max_listpack_value: 4,
+            ..Default::default()
+        };
+        let mut hash = Hash::default();
+        assert_eq!(
+            hash.insert("f".into(), RespFrame::Integer(1), &limits),
+            None
+        );
+        assert_eq!(hash.encoding(), "listpack");
+        let long = RespFrame::BulkString(b"too long".into());
+        assert_eq!(
+            hash.insert("f".into(), long.clone(), &limits),
+            Some(RespFrame::Integer(1))
+        );
+        assert_eq!(hash.encoding(), "hashtable");
+        assert_eq!(hash.get(b"f"), Some(&long));
+    }
+}
@@ -0,0 +1,237 @@
+use super::Backend;
+use crate::{BulkString, RespFrame};
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+// What a write did to a key, published to the listeners registered with `Backend::on_write`.
+// The names are the ones of the redis keyspace notifications.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyEvent<'a> {
+    Set(&'a RespFrame),
+    HSet(&'a [u8], &'a RespFrame),
+    SAdd(&'a [u8]),
+    Del,
+    // deleted once its expiration time passed
+    Expired,
+    // the expiration time in unix milliseconds
+    Expire(u64),
+    Persist,
+    // renamed to the given key, which gets `RenameTo`
+    RenameFrom(&'a [u8]),
+    RenameTo,
+    // a member moved to the given set, which gets `SMoveTo`
+    SMoveFrom(&'a [u8], &'a [u8]),
+    SMoveTo(&'a [u8]),
+    // the DUMP payload stored by RESTORE, and the expiration time in unix milliseconds
+    Restore(&'a [u8], Option<u64>),
+    // replaced or deleted by loading a snapshot
+    Loaded,
+}
+
+type Listener = Arc<dyn Fn(&Backend, &[u8], &KeyEvent) + Send + Sync>;
+
+// The listeners of the writes. They run in the order they were registered while the key is
+// locked, each sees the events of a key in the order of the writes. A listener must not call
+// back into the backend.
+pub(crate) struct EventBus {
+    listeners: RwLock<Vec<Listener>>,
+}
+
+impl KeyEvent<'_> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyEvent::Set(_) => "set",
+            KeyEvent::HSet(..) => "hset",
+            KeyEvent::SAdd(_) | KeyEvent::SMoveTo(_) => "sadd",
+            KeyEvent::Del => "del",
+            KeyEvent::Expired => "expired",
+            KeyEvent::Expire(_) => "expire",
+            KeyEvent::Persist => "persist",
+            KeyEvent::RenameFrom(_) => "rename_from",
+            KeyEvent::RenameTo => "rename_to",
+            KeyEvent::SMoveFrom(..) => "srem",
+            KeyEvent::Restore(..) => "restore",
+            KeyEvent::Loaded => "loaded",
+        }
+    }
+
+    // Whether the key may have new content, the clients blocked on it are woken up.
+    fn adds(&self) -> bool {
+        !matches!(
+            self,
+            KeyEvent::Del
+                | KeyEvent::Expired
+                | KeyEvent::Expire(_)
+                | KeyEvent::Persist
+                | KeyEvent::RenameFrom(_)
+                | KeyEvent::SMoveFrom(..)
+        )
+    }
+
+    // Whether the write is sent to the AOF and the replicas. The other side of a move comes with
+    // the command of its source, a snapshot is loaded the same on every server.
+    fn propagated(&self) -> bool {
+        !matches!(
+            self,
+            KeyEvent::RenameTo | KeyEvent::SMoveTo(_) | KeyEvent::Loaded
+        )
+    }
+
+    // The command that makes the same write on a replica.
+    fn command(&self, key: &[u8]) -> Vec<RespFrame> {
+        let bulk = |arg: &[u8]| -> RespFrame { BulkString::from(arg).into() };
+        match self {
+            KeyEvent::Set(value) => vec![bulk(b"set"), bulk(key), (*value).clone()],
+            KeyEvent::HSet(field, value) => {
+                vec![bulk(b"hset"), bulk(key), bulk(field), (*value).clone()]
+            }
+            KeyEvent::SAdd(member) => vec![bulk(b"sadd"), bulk(key), bulk(member)],
+            KeyEvent::Del | KeyEvent::Expired => vec![bulk(b"del"), bulk(key)],
+            // an absolute time, replicas would drift with a relative one
+            KeyEvent::Expire(when) => {
+                vec![
+                    bulk(b"pexpireat"),
+                    bulk(key),
+                    bulk(when.to_string().as_bytes()),
+                ]
+            }
+            KeyEvent::Persist => vec![bulk(b"persist"), bulk(key)],
+            KeyEvent::RenameFrom(to) => vec![bulk(b"rename"), bulk(key), bulk(to)],
+            KeyEvent::SMoveFrom(destination, member) => {
+                vec![bulk(b"smove"), bulk(key), bulk(destination), bulk(member)]
+            }
+            KeyEvent::Restore(payload, when) => {
+                let ttl = when.unwrap_or(0).to_string();
+                let mut command = vec![
+                    bulk(b"restore"),
+                    bulk(key),
+                    bulk(ttl.as_bytes()),
+                    bulk(payload),
+                    bulk(b"replace"),
+                ];
+                // an absolute time, like the PEXPIREAT above
+                if when.is_some() {
+                    command.push(bulk(b"absttl"));
+                }
+                command
+            }
+            KeyEvent::RenameTo | KeyEvent::SMoveTo(_) | KeyEvent::Loaded => {
+                unreachable!("{} is not propagated", self.name())
+            }
+        }
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let bus = Self {
+            listeners: RwLock::default(),
+        };
+        // the writes reach the AOF and the replicas in the order of the key locks
+        bus.subscribe(Arc::new(|backend, key, event| {
+            if event.propagated() {
+                backend.feed(|| event.command(key));
+            }
+        }));
+        bus.subscribe(Arc::new(|backend, key, _| backend.touch(key)));
+        bus.subscribe(Arc::new(|backend, key, _| backend.tracking.invalidate(key)));
+        bus.subscribe(Arc::new(|backend, key, event| {
+            if event.adds() {
+                backend.blocked.signal(key);
+            }
+        }));
+        bus
+    }
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let listeners = self.listeners.read().unwrap_or_else(|e| e.into_inner());
+        f.debug_struct("EventBus")
+            .field("listeners", &listeners.len())
+            .finish()
+    }
+}
+
+impl EventBus {
+    pub fn subscribe(&self, listener: Listener) {
+        self.listeners
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(listener);
+    }
+
+    pub fn publish(&self, backend: &Backend, key: &[u8], event: KeyEvent) {
+        let listeners = self.listeners.read().unwrap_or_else(|e| e.into_inner());
+        for listener in listeners.iter() {
+            listener(backend, key, &event);
+        }
+    }
+}
+
+impl Backend {
+    // Registers a listener of the writes, called with each key modified and what happened to it.
+    // It runs while the key is locked and must not call back into the backend.
+    pub fn on_write(&self, listener: impl Fn(&[u8], &KeyEvent) + Send + Sync + 'static) {
+        self.events
+            .subscribe(Arc::new(move |_, key, event| listener(key, event)));
+    }
+
+    pub(crate) fn publish(&self, key: &[u8], event: KeyEvent) {
+        self.events.publish(self, key, event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_on_write() {
+        let backend = Backend::new();
+        let events = Arc::new(Mutex::new(vec![]));
+        let seen = events.clone();
+        backend.on_write(move |key, event| {
+            seen.lock()
+                .unwrap()
+                .push(format!("{} {}", event.name(), String::from_utf8_lossy(key)));
+        });
+        backend.set("a".into(), RespFrame::Integer(1));
+        backend.expire_at(b"a", u64::MAX);
+        backend.rename(b"a", Bytes::from("b"));
+        backend.sadd("s", "m").unwrap();
+        backend.sadd("s", "m").unwrap();
+        backend.del(b"b");
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "set a",
+                "expire a",
+                "rename_from a",
+                "rename_to b",
+                "sadd s",
+                "del b"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_event_command() {
+        let value = RespFrame::Integer(1);
+        let command = KeyEvent::HSet(b"f", &value).command(b"h");
+        assert_eq!(command.len(), 4);
+        assert_eq!(command[3], value);
+        assert_eq!(
+            KeyEvent::Expired.command(b"k"),
+            [BulkString::from("del").into(), BulkString::from("k").into()] as [RespFrame; 2]
+        );
+        let command = KeyEvent::Restore(b"payload", Some(42)).command(b"k");
+        assert_eq!(command[2], BulkString::from("42").into());
+        assert_eq!(command.last(), Some(&BulkString::from("absttl").into()));
+        assert_eq!(KeyEvent::Restore(b"payload", None).command(b"k").len(), 5);
+    }
+}
@@ -0,0 +1,398 @@
+use super::{random, sample, Backend};
+use crate::{RespEncoder, RespFrame};
+use bytes::Bytes;
+use std::{
+    ops::Add,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+// rough cost of a key in the maps besides its name and value
+pub(crate) const KEY_OVERHEAD: u64 = 64;
+// keys looked at to pick the one to evict, maxmemory-samples in redis
+const MAXMEMORY_SAMPLES: usize = 5;
+
+// LFU counter of a new key, high enough for it not to be evicted right away
+const LFU_INIT_VAL: u8 = 5;
+// one access in this many is counted in the hits of a key, see `Backend::hotkeys`
+const HITS_SAMPLE_RATE: u64 = 8;
+
+pub(crate) const OOM: &str = "OOM command not allowed when used memory > 'maxmemory'.";
+
+// How recently and how often a key is accessed, for the eviction policies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct KeyAccess {
+    // unix milliseconds
+    pub last: u64,
+    // logarithmic access frequency, see `hit`
+    pub counter: u8,
+    // the accesses sampled since the key was created, see `HITS_SAMPLE_RATE`
+    pub hits: u64,
+}
+
+impl KeyAccess {
+    fn new(now: u64) -> Self {
+        Self {
+            last: now,
+            counter: LFU_INIT_VAL,
+            hits: 0,
+        }
+    }
+
+    // The counter loses one for every lfu-decay-time minutes the key wasn't accessed.
+    pub fn frequency(&self, now: u64, decay_time: u64) -> u8 {
+        if decay_time == 0 {
+            return self.counter;
+        }
+        let periods = now.saturating_sub(self.last) / 60_000 / decay_time;
+        self.counter.saturating_sub(periods.min(255) as u8)
+    }
+
+    // Like redis, the counter is incremented with a probability that shrinks as it grows, it
+    // takes about a million hits to saturate it with lfu-log-factor 10.
+    fn hit(&mut self, now: u64, (log_factor, decay_time): (u64, u64)) {
+        let counter = self.frequency(now, decay_time);
+        let base = counter.saturating_sub(LFU_INIT_VAL) as f64;
+        let p = 1.0 / (base * log_factor as f64 + 1.0);
+        let random = random();
+        if random.is_multiple_of(HITS_SAMPLE_RATE) {
+            self.hits += 1;
+        }
+        let r = (random >> 11) as f64 / (1u64 << 53) as f64;
+        self.counter = match counter < u8::MAX && r < p {
+            true => counter + 1,
+            false => counter,
+        };
+        self.last = now;
+    }
+}
+
+// The memory used by the keys of each type, their names and values with the overhead of the maps.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub strings: u64,
+    pub sets: u64,
+    pub hashes: u64,
+}
+
+// The memory usage of the dataset, kept up to date by every write.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryCounters {
+    strings: AtomicU64,
+    sets: AtomicU64,
+    hashes: AtomicU64,
+    // the highest total reached
+    peak: AtomicU64,
+}
+
+impl MemoryUsage {
+    pub(crate) fn strings(bytes: u64) -> Self {
+        Self {
+            strings: bytes,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn sets(bytes: u64) -> Self {
+        Self {
+            sets: bytes,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn hashes(bytes: u64) -> Self {
+        Self {
+            hashes: bytes,
+            ..Default::default()
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.strings + self.sets + self.hashes
+    }
+}
+
+impl Add for MemoryUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            strings: self.strings + other.strings,
+            sets: self.sets + other.sets,
+            hashes: self.hashes + other.hashes,
+        }
+    }
+}
+
+impl MemoryCounters {
+    fn counters(&self) -> [&AtomicU64; 3] {
+        [&self.strings, &self.sets, &self.hashes]
+    }
+}
+
+// The memory used by a value, approximated by its length.
+pub(crate) fn frame_size(frame: &RespFrame) -> u64 {
+    match frame {
+        RespFrame::BulkString(s) => s.len() as u64,
+        frame => frame.encoded_len() as u64,
+    }
+}
+
+pub(crate) fn entry_size(key: &[u8]) -> u64 {
+    KEY_OVERHEAD + key.len() as u64
+}
+
+impl Backend {
+    // Approximate memory used by the dataset, compared to maxmemory.
+    pub fn used_memory(&self) -> u64 {
+        self.memory_usage().total()
+    }
+
+    // The memory used by the dataset, by type.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let [strings, sets, hashes] = self
+            .memory
+            .counters()
+            .map(|counter| counter.load(Ordering::Relaxed));
+        MemoryUsage {
+            strings,
+            sets,
+            hashes,
+        }
+    }
+
+    // The highest memory used by the dataset since the start.
+    pub fn peak_memory(&self) -> u64 {
+        self.memory.peak.load(Ordering::Relaxed)
+    }
+
+    // The memory used by a key of any type, None if it doesn't exist.
+    pub fn key_memory(&self, key: &[u8]) -> Option<u64> {
+        self.expire_if_needed(key);
+        let shard = self.keyspace.read(key);
+        shard.contains(key).then(|| shard.memory(key).total())
+    }
+
+    pub(crate) fn resize(&self, before: MemoryUsage, after: MemoryUsage) {
+        let before = [before.strings, before.sets, before.hashes];
+        let after = [after.strings, after.sets, after.hashes];
+        for (i, counter) in self.memory.counters().into_iter().enumerate() {
+            match after[i] >= before[i] {
+                true => counter.fetch_add(after[i] - before[i], Ordering::Relaxed),
+                false => counter.fetch_sub(before[i] - after[i], Ordering::Relaxed),
+            };
+        }
+        self.memory
+            .peak
+            .fetch_max(self.used_memory(), Ordering::Relaxed);
+    }
+
+    // Counts the memory of all the keys again, after the dataset was replaced.
+    pub(crate) fn recount_memory(&self) {
+        let keys = self.keyspace.keys();
+        let mut usage = MemoryUsage::default();
+        self.keyspace.for_each(|shard| {
+            for key in shard.keys() {
+                usage = usage + shard.memory(key);
+            }
+        });
+        let counted = [usage.strings, usage.sets, usage.hashes];
+        for (counter, bytes) in self.memory.counters().into_iter().zip(counted) {
+            counter.store(bytes, Ordering::Relaxed);
+        }
+        self.memory.peak.fetch_max(usage.total(), Ordering::Relaxed);
+        self.access.clear();
+        for key in keys {
+            self.accessed(&key);
+        }
+    }
+
+    // Records an access to a key, for the eviction policies and DEBUG HOTKEYS. Nothing is
+    // recorded when neither needs it.
+    pub(crate) fn accessed(&self, key: &[u8]) {
+        if !self.hotkeys.load(Ordering::Relaxed) && !self.config.evicts_by_access() {
+            return;
+        }
+        let now = self.now_ms();
+        let lfu = self.config.lfu();
+        // the key is copied only the first time
+        if let Some(mut access) = self.access.get_mut(key) {
+            access.hit(now, lfu);
+            return;
+        }
+        self.access
+            .entry(Bytes::copy_from_slice(key))
+            .and_modify(|access| access.hit(now, lfu))
+            .or_insert_with(|| KeyAccess::new(now));
+    }
+
+    // The most accessed keys with an estimate of their number of accesses, the most accessed
+    // first. The accesses are sampled, a key accessed a few times may not show up.
+    pub fn hotkeys(&self, count: usize) -> Vec<(Bytes, u64)> {
+        let mut hot: Vec<(Bytes, u64)> = self
+            .access
+            .iter()
+            .filter(|access| access.hits > 0)
+            .map(|access| (access.key().clone(), access.hits * HITS_SAMPLE_RATE))
+            .collect();
+        hot.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hot.truncate(count);
+        hot
+    }
+
+    // Evicts keys until the memory used is back under maxmemory. Returns false if that's not
+    // possible, the command that needs memory is then refused with OOM. Replicas leave it to
+    // their master.
+    pub(crate) fn free_memory(&self) -> bool {
+        let (maxmemory, policy) = self.config.maxmemory();
+        if maxmemory == 0 || self.replication.master().is_some() {
+            return true;
+        }
+        let _guard = self.exec_lock.read().unwrap_or_else(|e| e.into_inner());
+        while self.used_memory() > maxmemory {
+            let Some(key) = self.eviction_candidate(&policy) else {
+                return false;
+            };
+            if self.del(&key) {
+                self.stats.key_evicted();
+            } else {
+                // a key without value, nothing to reclaim
+                self.access.remove(&key);
+            }
+        }
+        true
+    }
+
+    // The best key to evict among a few sampled ones, from all the keys or only those with a TTL.
+    fn eviction_candidate(&self, policy: &str) -> Option<Bytes> {
+        let (pool, criteria) = policy.split_once('-')?;
+        let keys: Vec<Bytes> = match pool {
+            // the accesses are not recorded before the policy needs them, the keys are sampled
+            "allkeys" => self.keyspace.sample(MAXMEMORY_SAMPLES),
+            "volatile" => sample(&self.expires, MAXMEMORY_SAMPLES)
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect(),
+            _ => return None,
+        };
+        let access = |key: &Bytes| self.access.get(key).map(|access| *access);
+        match criteria {
+            "random" => keys.into_iter().next(),
+            "ttl" => keys.into_iter().min_by_key(|key| self.expire_time(key)),
+            // the least frequently used, the least recently used among them
+            "lfu" => {
+                let (now, (_, decay_time)) = (self.now_ms(), self.config.lfu());
+                keys.into_iter().min_by_key(|key| {
+                    access(key).map_or((0, 0), |a| (a.frequency(now, decay_time), a.last))
+                })
+            }
+            _ => keys
+                .into_iter()
+                .min_by_key(|key| access(key).map_or(0, |a| a.last)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, ServerConfig};
+
+    #[test]
+    fn test_lru_eviction() {
+        let backend = Backend::with_config(ServerConfig::new(Config {
+            maxmemory: 1000,
+            maxmemory_policy: "allkeys-lru".to_string(),
+            ..Default::default()
+        }));
+        for i in 0..10 {
+            backend.set(format!("key:{}", i).into(), RespFrame::Integer(i));
+            backend.access.insert(
+                format!("key:{}", i).into(),
+                KeyAccess {
+                    last: i as u64,
+                    counter: LFU_INIT_VAL,
+                    hits: 0,
+                },
+            );
+        }
+        assert!(backend.used_memory() > 700);
+        assert!(backend.free_memory());
+
+        backend
+            .config
+            .set(&[("maxmemory".into(), "500".into())])
+            .unwrap();
+        assert!(backend.free_memory());
+        assert!(backend.used_memory() <= 500);
+        // the most recently used key is never the one evicted
+        assert!(backend.exists(b"key:9"));
+        assert!(backend.stats.evicted_keys() > 0);
+
+        backend
+            .config
+            .set(&[("maxmemory-policy".into(), "noeviction".into())])
+            .unwrap();
+        backend
+            .config
+            .set(&[("maxmemory".into(), "1".into())])
+            .unwrap();
+        assert!(!backend.free_memory());
+    }
+
+    #[test]
+    fn test_lfu_counter() {
+        let mut access = KeyAccess::new(0);
+        for _ in 0..1000 {
+            access.hit(0, (10, 1));
+        }
+        // the first hits count more than the next ones
+        assert!(access.counter > LFU_INIT_VAL + 1 && access.counter < 30);
+        assert_eq!(access.frequency(3 * 60_000, 1), access.counter - 3);
+        assert_eq!(access.frequency(3 * 60_000, 0), access.counter);
+
+        let backend = Backend::with_config(ServerConfig::new(Config {
+            maxmemory: 1,
+            maxmemory_policy: "allkeys-lfu".to_string(),
+            ..Default::default()
+        }));
+        backend.set("rare".into(), RespFrame::Integer(0));
+        backend.set("often".into(), RespFrame::Integer(0));
+        backend
+            .access
+            .alter(b"often".as_slice(), |_, access| KeyAccess {
+                counter: 100,
+                ..access
+            });
+        backend
+            .config
+            .set(&[("maxmemory".into(), "100".into())])
+            .unwrap();
+        assert!(backend.free_memory());
+        assert!(backend.exists(b"often") && !backend.exists(b"rare"));
+    }
+
+    #[test]
+    fn test_hotkeys() {
+        let backend = Backend::new();
+        // neither eviction nor DEBUG HOTKEYS needs the accesses, they are not recorded
+        backend.set("hot".into(), RespFrame::Integer(0));
+        backend.get(b"hot");
+        assert!(backend.access.is_empty());
+
+        backend.hotkeys.store(true, Ordering::Relaxed);
+        backend.set("warm".into(), RespFrame::Integer(0));
+        backend.set("cold".into(), RespFrame::Integer(0));
+        for i in 0..10_000 {
+            backend.get(b"hot");
+            if i % 10 == 0 {
+                backend.get(b"warm");
+            }
+        }
+        let hot = backend.hotkeys(1);
+        assert_eq!(hot.len(), 1);
+        assert_eq!(hot[0].0, "hot");
+        // about one access in HITS_SAMPLE_RATE is counted
+        assert!(hot[0].1 > 5_000 && hot[0].1 < 15_000);
+        assert_eq!(backend.hotkeys(16)[1].0, "warm");
+    }
+}
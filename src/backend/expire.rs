@@ -0,0 +1,134 @@
+use super::{sample, Backend, KeyEvent};
+use bytes::Bytes;
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::time;
+
+// like redis with hz 10, each cycle samples the keys with a TTL and spends at most a quarter of
+// the period deleting the expired ones
+const ACTIVE_EXPIRE_PERIOD: Duration = Duration::from_millis(100);
+const ACTIVE_EXPIRE_BUDGET: Duration = Duration::from_millis(25);
+const ACTIVE_EXPIRE_KEYS_PER_LOOP: usize = 20;
+// another sample is taken while more than this percentage of the last one was expired
+const ACTIVE_EXPIRE_ACCEPTABLE_STALE: usize = 25;
+
+// Milliseconds since the unix epoch, the unit of the expiration times.
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+impl Backend {
+    // Sets the expiration time of a key in unix milliseconds. Returns false if the key doesn't
+    // exist.
+    pub fn expire_at(&self, key: &[u8], when: u64) -> bool {
+        self.expire_if_needed(key);
+        // the key is locked until published, like set and del: it can't be deleted meanwhile
+        let shard = self.keyspace.write(key);
+        if !shard.contains(key) {
+            return false;
+        }
+        self.expires.insert(Bytes::copy_from_slice(key), when);
+        self.publish(key, KeyEvent::Expire(when));
+        true
+    }
+
+    // Removes the expiration time of a key. Returns true if it had one.
+    pub fn persist(&self, key: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        if self.expires.remove(key).is_none() {
+            return false;
+        }
+        self.publish(key, KeyEvent::Persist);
+        true
+    }
+
+    // The expiration time of a key in unix milliseconds, None if it has none.
+    pub fn expire_time(&self, key: &[u8]) -> Option<u64> {
+        self.expires.get(key).map(|when| *when)
+    }
+
+    // Deletes the key if its time has come, returns true if it did. The replicas wait for the DEL
+    // of their master, which keeps them consistent with it.
+    pub(crate) fn expire_if_needed(&self, key: &[u8]) -> bool {
+        let expired = self
+            .expire_time(key)
+            .is_some_and(|when| when <= self.now_ms());
+        if !expired || self.replication.master().is_some() {
+            return false;
+        }
+        self.remove(key, KeyEvent::Expired);
+        self.stats.key_expired();
+        true
+    }
+
+    // Reclaims the expired keys that are never read, until the server shuts down.
+    pub async fn active_expire(&self) {
+        let mut interval = time::interval(ACTIVE_EXPIRE_PERIOD);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = self.shutdown_requested() => break,
+            }
+            if self.active_expire.load(Ordering::Relaxed) {
+                self.active_expire_cycle();
+            }
+        }
+    }
+
+    // Returns the number of keys deleted.
+    fn active_expire_cycle(&self) -> usize {
+        let start = Instant::now();
+        let mut deleted = 0;
+        loop {
+            let _guard = self.exec_lock.read().unwrap_or_else(|e| e.into_inner());
+            let sample = sample(&self.expires, ACTIVE_EXPIRE_KEYS_PER_LOOP);
+            if sample.is_empty() {
+                break;
+            }
+            let sampled = sample.len();
+            let now = self.now_ms();
+            let expired: Vec<Bytes> = sample
+                .into_iter()
+                .filter_map(|(key, when)| (when <= now).then_some(key))
+                .collect();
+            deleted += expired
+                .iter()
+                .filter(|key| self.expire_if_needed(key))
+                .count();
+            if expired.len() * 100 <= sampled * ACTIVE_EXPIRE_ACCEPTABLE_STALE
+                || start.elapsed() > ACTIVE_EXPIRE_BUDGET
+            {
+                break;
+            }
+        }
+        deleted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+
+    #[test]
+    fn test_active_expire_cycle() {
+        let backend = Backend::new();
+        for i in 0..100 {
+            let key = Bytes::from(format!("key:{}", i));
+            backend.set(key.clone(), RespFrame::Integer(i));
+            if i % 2 == 0 {
+                backend.expire_at(&key, 1);
+            }
+        }
+        backend.expire_at(b"key:1", now_ms() + 60_000);
+        // all the keys with a TTL are sampled since they are mostly expired
+        assert_eq!(backend.active_expire_cycle(), 50);
+        assert_eq!(backend.dbsize(), 50);
+        assert_eq!(backend.expires.len(), 1);
+        assert_eq!(backend.stats.expired_keys(), 50);
+    }
+}
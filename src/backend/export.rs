@@ -0,0 +1,156 @@
+use super::{keyspace::Shard, Backend};
+use crate::{glob_match, RespFrame, Snapshot};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// The value of a key, owned, see `Backend::iter`. Serialized as in json.rs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(RespFrame),
+    Set(Vec<Bytes>),
+    Hash(Vec<(Bytes, RespFrame)>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(with = "super::json::key")]
+    pub key: Bytes,
+    #[serde(flatten)]
+    pub value: Value,
+    // the expiration time in unix milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expire_at: Option<u64>,
+}
+
+impl Backend {
+    // The keys with their values at one point in time, see `snapshot`. The values are copied one
+    // shard at a time while iterating, the expired keys are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = Entry> {
+        let view = self.snapshot();
+        let expires: HashMap<Bytes, u64> = view.expires.into_iter().collect();
+        let now = self.now_ms();
+        view.shards
+            .into_iter()
+            .flat_map(move |shard| shard_entries(&shard, &expires))
+            .filter(move |entry| entry.expire_at.is_none_or(|when| when > now))
+    }
+
+    // The keys matching a glob-style pattern, like KEYS.
+    pub fn keys(&self, pattern: &[u8]) -> Vec<Bytes> {
+        let mut keys = vec![];
+        self.keyspace.for_each(|shard| {
+            keys.extend(
+                shard
+                    .keys()
+                    .filter(|key| glob_match(pattern, key, false))
+                    .cloned(),
+            )
+        });
+        let now = self.now_ms();
+        keys.retain(|key| self.expire_time(key).is_none_or(|when| when > now));
+        keys
+    }
+
+    // A copy of the whole dataset, which can be saved or imported into another backend.
+    pub fn export(&self) -> Snapshot {
+        Snapshot::capture(self)
+    }
+
+    // Writes the keys like the commands creating them would, they replace the keys of the same
+    // name and reach the AOF and the replicas. The expired keys are skipped. Returns the number of
+    // keys written.
+    pub fn import(&self, entries: impl IntoIterator<Item = Entry>) -> usize {
+        let now = self.now_ms();
+        let mut count = 0;
+        for Entry {
+            key,
+            value,
+            expire_at,
+        } in entries
+        {
+            if expire_at.is_some_and(|when| when <= now) {
+                continue;
+            }
+            match value {
+                Value::String(value) => self.set(key.clone(), value),
+                // the key was just deleted, it can't be of another type
+                Value::Set(members) => {
+                    self.del(&key);
+                    for member in members {
+                        let _ = self.sadd(key.clone(), member);
+                    }
+                }
+                Value::Hash(fields) => {
+                    self.del(&key);
+                    for (field, value) in fields {
+                        let _ = self.hset(key.clone(), field, value);
+                    }
+                }
+            }
+            if let Some(when) = expire_at {
+                self.expire_at(&key, when);
+            }
+            count += 1;
+        }
+        count
+    }
+}
+
+fn shard_entries(shard: &Shard, expires: &HashMap<Bytes, u64>) -> Vec<Entry> {
+    let entry = |key: &Bytes, value| Entry {
+        key: key.clone(),
+        value,
+        expire_at: expires.get(key).copied(),
+    };
+    let strings = shard
+        .map
+        .iter()
+        .map(|(key, value)| entry(key, Value::String(value.clone())));
+    let sets = shard
+        .hset
+        .iter()
+        .map(|(key, members)| entry(key, Value::Set(members.iter().collect())));
+    let hashes = shard.hmap.iter().map(|(key, fields)| {
+        let fields = fields
+            .iter()
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect();
+        entry(key, Value::Hash(fields))
+    });
+    strings.chain(sets).chain(hashes).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_import() {
+        let backend = Backend::new();
+        backend.set("user:1".into(), RespFrame::Integer(1));
+        backend.sadd("user:tags", "a").unwrap();
+        backend
+            .hset("item".into(), "f".into(), RespFrame::Integer(2))
+            .unwrap();
+        backend.set("gone".into(), RespFrame::Integer(3));
+        backend.expire_at(b"gone", 1);
+        backend.expire_at(b"item", u64::MAX);
+
+        let mut keys = backend.keys(b"user:*");
+        keys.sort();
+        assert_eq!(keys, ["user:1", "user:tags"]);
+        assert_eq!(backend.iter().count(), 3);
+
+        let copy = Backend::new();
+        assert_eq!(copy.import(backend.export()), 3);
+        assert_eq!(copy.dbsize(), 3);
+        assert!(copy.sismember(b"user:tags", b"a"));
+        assert_eq!(copy.expire_time(b"item"), Some(u64::MAX));
+        let item = copy.iter().find(|entry| entry.key == "item").unwrap();
+        assert_eq!(
+            item.value,
+            Value::Hash(vec![("f".into(), RespFrame::Integer(2))])
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use super::{Backend, Entry, Value};
+use crate::{BulkString, RespFrame};
+use bytes::Bytes;
+use serde::{de::Deserializer, ser::Error as _, Deserialize, Serialize, Serializer};
+use std::io::{self, Read, Write};
+
+// The JSON form of the dataset is an array of entries, one per line:
+// {"key":"user:1","type":"hash","value":[["name","ann"]],"expire_at":1700000000000}
+// Keys, members and values are strings when they are valid UTF-8, arrays of bytes otherwise.
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Text {
+    Utf8(String),
+    Binary(Vec<u8>),
+}
+
+// the values of strings and hashes, integers are kept as JSON numbers
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Frame {
+    Integer(i64),
+    Text(Text),
+}
+
+impl From<&[u8]> for Text {
+    fn from(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Text::Utf8(s.to_string()),
+            Err(_) => Text::Binary(bytes.to_vec()),
+        }
+    }
+}
+
+impl From<Text> for Bytes {
+    fn from(text: Text) -> Self {
+        match text {
+            Text::Utf8(s) => s.into(),
+            Text::Binary(bytes) => bytes.into(),
+        }
+    }
+}
+
+impl TryFrom<&RespFrame> for Frame {
+    type Error = String;
+
+    fn try_from(frame: &RespFrame) -> Result<Self, Self::Error> {
+        match frame {
+            RespFrame::BulkString(s) => Ok(Frame::Text(Text::from(s.as_ref()))),
+            RespFrame::Integer(n) => Ok(Frame::Integer(*n)),
+            frame => Err(format!("{:?} can't be stored as JSON", frame)),
+        }
+    }
+}
+
+impl From<Frame> for RespFrame {
+    fn from(frame: Frame) -> Self {
+        match frame {
+            Frame::Integer(n) => RespFrame::Integer(n),
+            Frame::Text(text) => RespFrame::BulkString(BulkString::new(Bytes::from(text).to_vec())),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+enum JsonValue {
+    String(Frame),
+    Set(Vec<Text>),
+    Hash(Vec<(Text, Frame)>),
+}
+
+impl TryFrom<&Value> for JsonValue {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        Ok(match value {
+            Value::String(value) => JsonValue::String(value.try_into()?),
+            Value::Set(members) => {
+                JsonValue::Set(members.iter().map(|m| m.as_ref().into()).collect())
+            }
+            Value::Hash(fields) => JsonValue::Hash(
+                fields
+                    .iter()
+                    .map(|(field, value)| Ok((field.as_ref().into(), value.try_into()?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+        })
+    }
+}
+
+impl From<JsonValue> for Value {
+    fn from(value: JsonValue) -> Self {
+        match value {
+            JsonValue::String(value) => Value::String(value.into()),
+            JsonValue::Set(members) => Value::Set(members.into_iter().map(Bytes::from).collect()),
+            JsonValue::Hash(fields) => Value::Hash(
+                fields
+                    .into_iter()
+                    .map(|(field, value)| (field.into(), value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        JsonValue::try_from(self)
+            .map_err(S::Error::custom)?
+            .serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        JsonValue::deserialize(d).map(Value::from)
+    }
+}
+
+// the key of an entry, see export.rs
+pub(crate) mod key {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(key: &Bytes, s: S) -> Result<S::Ok, S::Error> {
+        Text::from(key.as_ref()).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Bytes, D::Error> {
+        Text::deserialize(d).map(Bytes::from)
+    }
+}
+
+impl Backend {
+    // Writes the dataset as JSON, see the format above. The keys are read from a snapshot, the
+    // commands keep running meanwhile.
+    pub fn dump_json(&self, mut w: impl Write) -> io::Result<()> {
+        w.write_all(b"[")?;
+        for (i, entry) in self.iter().enumerate() {
+            w.write_all(if i == 0 { b"\n" } else { b",\n" })?;
+            serde_json::to_writer(&mut w, &entry)?;
+        }
+        w.write_all(b"\n]\n")?;
+        w.flush()
+    }
+
+    // Imports the entries of a JSON dump, see `import`. Returns the number of keys written.
+    pub fn load_json(&self, r: impl Read) -> io::Result<usize> {
+        let entries: Vec<Entry> = serde_json::from_reader(r)?;
+        Ok(self.import(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() -> io::Result<()> {
+        let backend = Backend::new();
+        backend.set("n".into(), RespFrame::Integer(42));
+        backend.expire_at(b"n", u64::MAX);
+        backend.set(
+            Bytes::from_static(b"bin\xff"),
+            RespFrame::BulkString(b"\x00\x01".into()),
+        );
+        backend
+            .hset("h".into(), "f".into(), RespFrame::BulkString(b"v".into()))
+            .unwrap();
+        backend.sadd("s", "m").unwrap();
+
+        let mut json = vec![];
+        backend.dump_json(&mut json)?;
+        let text = String::from_utf8(json.clone()).unwrap();
+        assert!(text.contains(r#"{"key":"h","type":"hash","value":[["f","v"]]}"#));
+        assert!(text.contains(r#"{"key":"n","type":"string","value":42,"expire_at":"#));
+
+        let copy = Backend::new();
+        assert_eq!(copy.load_json(json.as_slice())?, 4);
+        let mut entries: Vec<Entry> = copy.iter().collect();
+        let mut expected: Vec<Entry> = backend.iter().collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        expected.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(entries, expected);
+        Ok(())
+    }
+}
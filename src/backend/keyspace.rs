@@ -0,0 +1,362 @@
+use super::{
+    encoding::{Hash, Set},
+    evict::{entry_size, frame_size, MemoryUsage},
+    random,
+};
+use crate::{RespFrame, SimpleError};
+use bytes::Bytes;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash as _, Hasher},
+    ops::{Deref, DerefMut},
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+// number of shards of the keyspace
+pub(crate) const SHARDS: usize = 16;
+
+pub(crate) const WRONGTYPE: &str =
+    "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+// A command met a key of another type than the one it works on, replied with WRONGTYPE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrongType;
+
+impl From<WrongType> for RespFrame {
+    fn from(_: WrongType) -> Self {
+        SimpleError::new(WRONGTYPE).into()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeyType {
+    String,
+    Set,
+    Hash,
+}
+
+// The keys of all types, split into shards each behind its own lock. Single key operations lock
+// the shard of the key, multi key commands lock all their shards at once with `lock`. A key is in
+// one of the maps of its shard only, the one of its type.
+// A shard is shared with the snapshots taken since it was last modified, the next write copies
+// it, see `snapshot`.
+#[derive(Debug)]
+pub(crate) struct Keyspace {
+    shards: Vec<RwLock<Arc<Shard>>>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Shard {
+    pub map: HashMap<Bytes, RespFrame>,
+    pub hset: HashMap<Bytes, Set>,
+    pub hmap: HashMap<Bytes, Hash>,
+}
+
+// A point-in-time view of the dataset, from `Backend::snapshot`. It shares the shards with the
+// keyspace, taking one costs a reference per shard and a copy of the expiration times.
+#[derive(Debug, Clone)]
+pub struct DatasetView {
+    pub(crate) shards: Vec<Arc<Shard>>,
+    pub(crate) expires: Vec<(Bytes, u64)>,
+}
+
+// A shard locked for writing, copied on the first modification if a snapshot shares it.
+pub(crate) struct ShardWriteGuard<'a>(RwLockWriteGuard<'a, Arc<Shard>>);
+
+// The shards locked for writing by `Keyspace::lock`, released when dropped.
+pub(crate) struct ShardGuards<'a> {
+    keyspace: &'a Keyspace,
+    guards: Vec<(usize, ShardWriteGuard<'a>)>,
+}
+
+impl Default for Keyspace {
+    fn default() -> Self {
+        Self::new(SHARDS)
+    }
+}
+
+impl Keyspace {
+    pub fn new(shards: usize) -> Self {
+        Self::with_capacity(shards, 0)
+    }
+
+    // The string maps have room for `capacity` keys spread over the shards.
+    pub fn with_capacity(shards: usize, capacity: usize) -> Self {
+        let shards = shards.max(1);
+        let shard = || Shard {
+            map: HashMap::with_capacity(capacity.div_ceil(shards)),
+            ..Default::default()
+        };
+        Self {
+            shards: (0..shards)
+                .map(|_| RwLock::new(Arc::new(shard())))
+                .collect(),
+        }
+    }
+
+    fn index(&self, key: &[u8]) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish() as usize % self.shards.len()
+    }
+
+    pub fn read(&self, key: &[u8]) -> RwLockReadGuard<'_, Arc<Shard>> {
+        read(&self.shards[self.index(key)])
+    }
+
+    pub fn write(&self, key: &[u8]) -> ShardWriteGuard<'_> {
+        write(&self.shards[self.index(key)])
+    }
+
+    // Locks the shards of all the keys for writing. The shards are always locked in the same
+    // order, two commands locking overlapping shards can't deadlock.
+    pub fn lock(&self, keys: &[&[u8]]) -> ShardGuards<'_> {
+        let mut indexes: Vec<usize> = keys.iter().map(|key| self.index(key)).collect();
+        indexes.sort_unstable();
+        indexes.dedup();
+        ShardGuards {
+            keyspace: self,
+            guards: indexes
+                .into_iter()
+                .map(|i| (i, write(&self.shards[i])))
+                .collect(),
+        }
+    }
+
+    // Visits the shards one after the other, each is read locked only while it's visited.
+    pub fn for_each(&self, mut f: impl FnMut(&Shard)) {
+        for shard in &self.shards {
+            f(&read(shard));
+        }
+    }
+
+    // Up to n keys of any type, from a random position of a random shard, then from the next
+    // shards if it has fewer.
+    pub fn sample(&self, n: usize) -> Vec<Bytes> {
+        let first = random() as usize % self.shards.len();
+        let mut keys = Vec::with_capacity(n);
+        for i in 0..self.shards.len() {
+            if keys.len() == n {
+                break;
+            }
+            let shard = read(&self.shards[(first + i) % self.shards.len()]);
+            let len = shard.len();
+            if len == 0 {
+                continue;
+            }
+            let start = random() as usize % len;
+            let missing = (n - keys.len()).min(len);
+            keys.extend(
+                shard
+                    .keys()
+                    .skip(start)
+                    .chain(shard.keys())
+                    .take(missing)
+                    .cloned(),
+            );
+        }
+        keys
+    }
+
+    pub fn keys(&self) -> Vec<Bytes> {
+        let mut keys = vec![];
+        self.for_each(|shard| keys.extend(shard.keys().cloned()));
+        keys
+    }
+
+    pub fn len(&self) -> usize {
+        let mut len = 0;
+        self.for_each(|shard| len += shard.len());
+        len
+    }
+
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            *write(shard) = Shard::default();
+        }
+    }
+
+    // All the shards at one point in time, `f` runs while they are read locked. The shards are
+    // locked in the order of `lock`, a multi key command is either entirely in the snapshot or
+    // not at all.
+    pub fn snapshot(&self, f: impl FnOnce()) -> Vec<Arc<Shard>> {
+        let guards: Vec<_> = self.shards.iter().map(read).collect();
+        f();
+        guards.iter().map(|shard| Arc::clone(shard)).collect()
+    }
+}
+
+impl DatasetView {
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Deref for ShardWriteGuard<'_> {
+    type Target = Shard;
+
+    fn deref(&self) -> &Shard {
+        &self.0
+    }
+}
+
+impl DerefMut for ShardWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Shard {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+impl Shard {
+    pub fn key_type(&self, key: &[u8]) -> Option<KeyType> {
+        if self.map.contains_key(key) {
+            Some(KeyType::String)
+        } else if self.hset.contains_key(key) {
+            Some(KeyType::Set)
+        } else if self.hmap.contains_key(key) {
+            Some(KeyType::Hash)
+        } else {
+            None
+        }
+    }
+
+    // Whether a command working on keys of a type can use the key: it's of that type or missing.
+    pub fn check(&self, key: &[u8], expected: KeyType) -> Result<(), WrongType> {
+        match self.key_type(key) {
+            Some(found) if found != expected => Err(WrongType),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn contains(&self, key: &[u8]) -> bool {
+        self.map.contains_key(key) || self.hmap.contains_key(key) || self.hset.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len() + self.hmap.len() + self.hset.len()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Bytes> {
+        self.map
+            .keys()
+            .chain(self.hset.keys())
+            .chain(self.hmap.keys())
+    }
+
+    // Removes a key of any type. Returns true if it existed.
+    pub fn remove(&mut self, key: &[u8]) -> bool {
+        self.map.remove(key).is_some()
+            | self.hset.remove(key).is_some()
+            | self.hmap.remove(key).is_some()
+    }
+
+    // The memory used by a key of any type.
+    pub fn memory(&self, key: &[u8]) -> MemoryUsage {
+        let string = self
+            .map
+            .get(key)
+            .map(|value| entry_size(key) + frame_size(value));
+        let set = self
+            .hset
+            .get(key)
+            .map(|set| entry_size(key) + set.iter().map(|m| m.len() as u64).sum::<u64>());
+        let hash = self.hmap.get(key).map(|hash| {
+            let fields = hash
+                .iter()
+                .map(|(field, value)| field.len() as u64 + frame_size(value));
+            entry_size(key) + fields.sum::<u64>()
+        });
+        MemoryUsage {
+            strings: string.unwrap_or(0),
+            sets: set.unwrap_or(0),
+            hashes: hash.unwrap_or(0),
+        }
+    }
+}
+
+impl ShardGuards<'_> {
+    // The shard of a key, which must be one of the locked keys.
+    pub fn shard(&mut self, key: &[u8]) -> &mut Shard {
+        let index = self.keyspace.index(key);
+        let (_, guard) = self
+            .guards
+            .iter_mut()
+            .find(|(i, _)| *i == index)
+            .expect("the shard of the key is locked");
+        guard
+    }
+}
+
+fn read(shard: &RwLock<Arc<Shard>>) -> RwLockReadGuard<'_, Arc<Shard>> {
+    shard.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write(shard: &RwLock<Arc<Shard>>) -> ShardWriteGuard<'_> {
+    ShardWriteGuard(shard.write().unwrap_or_else(|e| e.into_inner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_shards_once() {
+        let keyspace = Keyspace::new(4);
+        let keys: Vec<Bytes> = (0..32).map(|i| format!("key:{}", i).into()).collect();
+        let refs: Vec<&[u8]> = keys.iter().map(|key| key.as_ref()).collect();
+        let mut guards = keyspace.lock(&refs);
+        assert_eq!(guards.guards.len(), 4);
+        for key in &keys {
+            guards
+                .shard(key)
+                .map
+                .insert(key.clone(), RespFrame::Integer(1));
+        }
+        drop(guards);
+        assert_eq!(keyspace.len(), 32);
+        assert!(keyspace.read(b"key:7").contains(b"key:7"));
+    }
+
+    #[test]
+    fn test_snapshot_copy_on_write() {
+        let keyspace = Keyspace::new(2);
+        let value = RespFrame::Integer(1);
+        keyspace.write(b"a").map.insert("a".into(), value.clone());
+        let shards = keyspace.snapshot(|| {});
+        // the snapshot shares the shards until they are modified
+        assert!(Arc::ptr_eq(&shards[0], &keyspace.shards[0].read().unwrap()));
+        keyspace.write(b"b").map.insert("b".into(), value.clone());
+        keyspace.write(b"a").remove(b"a");
+        assert_eq!(shards.iter().map(|shard| shard.len()).sum::<usize>(), 1);
+        assert!(shards.iter().any(|shard| shard.contains(b"a")));
+        assert!(!keyspace.read(b"a").contains(b"a"));
+        assert!(keyspace.read(b"b").contains(b"b"));
+    }
+
+    #[test]
+    fn test_sample_keys() {
+        let keyspace = Keyspace::new(4);
+        assert!(keyspace.sample(5).is_empty());
+        for i in 0..100 {
+            let key = Bytes::from(format!("key:{}", i));
+            keyspace.write(&key).map.insert(key, RespFrame::Integer(i));
+        }
+        let keys: std::collections::HashSet<Bytes> = keyspace.sample(20).into_iter().collect();
+        assert_eq!(keys.len(), 20);
+        // fewer keys than asked for
+        assert_eq!(keyspace.sample(200).len(), 100);
+    }
+
+    #[test]
+    fn test_check_key_type() {
+        let mut shard = Shard::default();
+        assert_eq!(shard.check(b"k", KeyType::Set), Ok(()));
+        shard.map.insert("k".into(), RespFrame::Integer(1));
+        assert_eq!(shard.key_type(b"k"), Some(KeyType::String));
+        assert_eq!(shard.check(b"k", KeyType::String), Ok(()));
+        assert_eq!(shard.check(b"k", KeyType::Hash), Err(WrongType));
+    }
+}
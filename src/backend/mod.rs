@@ -1,16 +1,94 @@
-use crate::RespFrame;
-use dashmap::{DashMap, DashSet};
-use std::ops::Deref;
-use std::sync::Arc;
+mod blocking;
+mod builder;
+mod clock;
+mod encoding;
+mod events;
+mod evict;
+mod expire;
+mod export;
+mod json;
+mod keyspace;
+mod pause;
+mod tracking;
+
+use crate::{
+    backend::evict::{entry_size, frame_size},
+    cmd::CommandRegistry,
+    script::{FunctionRegistry, ScriptCache},
+    Acl, Aof, ClientRegistry, Cluster, Monitors, Persistence, Replication, RespArray, RespFrame,
+    ServerConfig, ServerStats, SlowLog,
+};
+use bytes::Bytes;
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::{atomic::AtomicBool, Arc, RwLock};
+use std::{cell::Cell, ops::Deref};
+use tokio_util::sync::CancellationToken;
+
+pub(crate) use blocking::UNBLOCKED;
+pub use blocking::{Blocked, BlockedClients, Wakeup};
+pub use builder::BackendBuilder;
+pub use clock::{Clock, ManualClock, SystemClock};
+pub(crate) use encoding::{Hash, Set};
+use events::EventBus;
+pub use events::KeyEvent;
+use evict::MemoryCounters;
+pub use evict::MemoryUsage;
+pub(crate) use evict::{KeyAccess, KEY_OVERHEAD, OOM};
+pub(crate) use expire::now_ms;
+pub use export::{Entry, Value};
+pub use keyspace::{DatasetView, WrongType};
+pub(crate) use keyspace::{KeyType, Keyspace};
+pub use pause::{PauseGate, PauseMode};
+pub use tracking::{Tracking, TrackingMode};
 
 #[derive(Debug, Clone)]
 pub struct Backend(Arc<BackendInner>);
 
 #[derive(Debug)]
 pub struct BackendInner {
-    pub(crate) map: DashMap<String, RespFrame>,
-    pub(crate) hset: DashMap<String, DashSet<String>>,
-    pub(crate) hmap: DashMap<String, DashMap<String, RespFrame>>,
+    // strings, sets and hashes, see keyspace.rs
+    pub(crate) keyspace: Keyspace,
+    // expiration times of the keys in unix milliseconds, see expire.rs
+    pub(crate) expires: DashMap<Bytes, u64>,
+    // recency and frequency of access to the keys and approximate memory they use, see evict.rs
+    pub(crate) access: DashMap<Bytes, KeyAccess>,
+    pub(crate) memory: MemoryCounters,
+    pub(crate) tracking: Tracking,
+    // the listeners of the writes, see events.rs
+    pub(crate) events: EventBus,
+    // the keys watched by WATCH: bumped on every modification of the key, and the number of
+    // connections watching it. A key nobody watches has no entry.
+    pub(crate) versions: DashMap<Bytes, (u64, usize)>,
+    pub(crate) scripts: ScriptCache,
+    pub(crate) functions: FunctionRegistry,
+    // the commands of the application embedding the server, see custom.rs
+    pub(crate) commands: CommandRegistry,
+    pub(crate) stats: ServerStats,
+    pub(crate) slowlog: SlowLog,
+    pub(crate) persistence: Persistence,
+    pub(crate) aof: Aof,
+    pub(crate) replication: Replication,
+    pub(crate) cluster: Cluster,
+    pub(crate) config: ServerConfig,
+    pub(crate) clients: ClientRegistry,
+    pub(crate) acl: Acl,
+    pub(crate) pause: PauseGate,
+    // the clients waiting on keys or a timeout, see blocking.rs
+    pub(crate) blocked: BlockedClients,
+    pub(crate) monitors: Monitors,
+    // toggled by DEBUG SET-ACTIVE-EXPIRE
+    pub(crate) active_expire: AtomicBool,
+    // toggled by DEBUG SET-HOTKEYS, the accesses are then sampled for DEBUG HOTKEYS
+    pub(crate) hotkeys: AtomicBool,
+    // the time of the expiration times and the eviction policies
+    pub(crate) clock: Arc<dyn Clock>,
+    // cancelled by SHUTDOWN, the server stops accepting and the connections are closed
+    pub(crate) shutdown: CancellationToken,
+    // cancelled once the connections are closed after a shutdown, see `network::serve`
+    pub(crate) stopped: CancellationToken,
+    // commands run under the read lock, MULTI/EXEC takes the write lock to run atomically
+    pub(crate) exec_lock: RwLock<()>,
 }
 
 impl Deref for Backend {
@@ -30,9 +108,34 @@ impl Default for Backend {
 impl Default for BackendInner {
     fn default() -> Self {
         Self {
-            map: DashMap::new(),
-            hset: DashMap::new(),
-            hmap: DashMap::new(),
+            keyspace: Keyspace::default(),
+            expires: DashMap::new(),
+            access: DashMap::new(),
+            memory: MemoryCounters::default(),
+            tracking: Tracking::default(),
+            events: EventBus::default(),
+            versions: DashMap::new(),
+            scripts: ScriptCache::default(),
+            functions: FunctionRegistry::default(),
+            commands: CommandRegistry::default(),
+            stats: ServerStats::default(),
+            slowlog: SlowLog::default(),
+            persistence: Persistence::default(),
+            aof: Aof::default(),
+            replication: Replication::default(),
+            cluster: Cluster::default(),
+            config: ServerConfig::default(),
+            clients: ClientRegistry::default(),
+            acl: Acl::default(),
+            pause: PauseGate::default(),
+            blocked: BlockedClients::default(),
+            monitors: Monitors::default(),
+            active_expire: AtomicBool::new(true),
+            hotkeys: AtomicBool::new(false),
+            clock: Arc::new(SystemClock),
+            shutdown: CancellationToken::new(),
+            stopped: CancellationToken::new(),
+            exec_lock: RwLock::new(()),
         }
     }
 }
@@ -42,41 +145,412 @@ impl Backend {
         Self::default()
     }
 
-    pub fn get(&self, key: &str) -> Option<RespFrame> {
-        self.map.get(key).map(|v| v.value().clone())
+    // Asks the server to stop, see `shutdown_requested`.
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
     }
 
-    pub fn set(&self, key: String, value: RespFrame) {
-        self.map.insert(key, value);
+    // Resolves once a shutdown was requested.
+    pub async fn shutdown_requested(&self) {
+        self.shutdown.cancelled().await
     }
 
-    pub fn hget(&self, key: &str, field: &str) -> Option<RespFrame> {
-        self.hmap
-            .get(key)
-            .and_then(|v| v.get(field).map(|v| v.value().clone()))
+    pub fn with_config(config: ServerConfig) -> Self {
+        Self::builder().config(config).build()
     }
 
-    pub fn hset(&self, key: String, field: String, value: RespFrame) {
-        let hmap = self.hmap.entry(key).or_default();
-        hmap.insert(field, value);
+    pub fn builder() -> BackendBuilder {
+        BackendBuilder::default()
     }
 
-    pub fn hgetall(&self, key: &str) -> Option<DashMap<String, RespFrame>> {
-        self.hmap.get(key).map(|v| v.clone())
+    // Milliseconds since the unix epoch on the clock of the backend.
+    pub fn now_ms(&self) -> u64 {
+        self.clock.now_ms()
     }
 
-    // Inserts a key into the set. Returns true if the key was not already in the set.
-    pub fn sadd(&self, key: impl Into<String>, field: impl Into<String>) -> bool {
-        self.hset
-            .entry(key.into())
-            .or_default()
-            .insert(field.into())
+    pub fn config(&self) -> &ServerConfig {
+        &self.config
+    }
+
+    pub fn aof(&self) -> &Aof {
+        &self.aof
+    }
+
+    pub fn stats(&self) -> &ServerStats {
+        &self.stats
+    }
+
+    pub fn commands(&self) -> &CommandRegistry {
+        &self.commands
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<RespFrame> {
+        self.expire_if_needed(key);
+        let value = self.keyspace.read(key).map.get(key).cloned();
+        self.stats.keyspace_lookup(value.is_some());
+        if value.is_some() {
+            self.accessed(key);
+        }
+        value
+    }
+
+    // Whether a command working on keys of a type can use the key: it's of that type or missing.
+    pub(crate) fn check_type(&self, key: &[u8], expected: KeyType) -> Result<(), WrongType> {
+        self.expire_if_needed(key);
+        self.keyspace.read(key).check(key, expected)
+    }
+
+    // Like SET, the key loses its expiration time and a key of another type is replaced.
+    pub fn set(&self, key: Bytes, value: RespFrame) {
+        self.expires.remove(&key);
+        let size = frame_size(&value);
+        let mut shard = self.keyspace.write(&key);
+        let before = shard.memory(&key);
+        shard.hset.remove(&key);
+        shard.hmap.remove(&key);
+        // published while the key is locked, concurrent writes reach the replicas in the same order
+        self.publish(&key, KeyEvent::Set(&value));
+        shard.map.insert(key.clone(), value);
+        drop(shard);
+        self.resize(before, MemoryUsage::strings(entry_size(&key) + size));
+        self.accessed(&key);
+    }
+
+    pub fn hget(&self, key: &[u8], field: &[u8]) -> Option<RespFrame> {
+        self.expire_if_needed(key);
+        let shard = self.keyspace.read(key);
+        let hmap = shard.hmap.get(key);
+        self.stats.keyspace_lookup(hmap.is_some());
+        if hmap.is_some() {
+            self.accessed(key);
+        }
+        hmap.and_then(|v| v.get(field).cloned())
+    }
+
+    pub fn hset(&self, key: Bytes, field: Bytes, value: RespFrame) -> Result<(), WrongType> {
+        self.expire_if_needed(&key);
+        let limits = self.config.encoding_limits();
+        let mut shard = self.keyspace.write(&key);
+        shard.check(&key, KeyType::Hash)?;
+        if !shard.hmap.contains_key(&key) {
+            self.resize(
+                MemoryUsage::default(),
+                MemoryUsage::hashes(entry_size(&key)),
+            );
+        }
+        self.publish(&key, KeyEvent::HSet(&field, &value));
+        let hmap = shard.hmap.entry(key.clone()).or_default();
+        let size = field.len() as u64 + frame_size(&value);
+        let before = match hmap.insert(field.clone(), value, &limits) {
+            Some(old) => field.len() as u64 + frame_size(&old),
+            None => 0,
+        };
+        drop(shard);
+        self.resize(MemoryUsage::hashes(before), MemoryUsage::hashes(size));
+        self.accessed(&key);
+        Ok(())
+    }
+
+    pub fn hgetall(&self, key: &[u8]) -> Option<HashMap<Bytes, RespFrame>> {
+        self.expire_if_needed(key);
+        let hmap = self.keyspace.read(key).hmap.get(key).map(|hash| {
+            hash.iter()
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect()
+        });
+        self.stats.keyspace_lookup(hmap.is_some());
+        if hmap.is_some() {
+            self.accessed(key);
+        }
+        hmap
+    }
+
+    // Removes a key of any type. Returns true if it existed.
+    pub fn del(&self, key: &[u8]) -> bool {
+        self.remove(key, KeyEvent::Del)
+    }
+
+    // Removes a key of any type, publishing the event if it existed.
+    pub(crate) fn remove(&self, key: &[u8], event: KeyEvent) -> bool {
+        let mut shard = self.keyspace.write(key);
+        let size = shard.memory(key);
+        let removed = shard.remove(key);
+        self.expires.remove(key);
+        self.access.remove(key);
+        if removed {
+            self.publish(key, event);
+        }
+        drop(shard);
+        if removed {
+            self.resize(size, MemoryUsage::default());
+        }
+        removed
+    }
+
+    // Whether a key of any type exists, without counting as a keyspace hit or miss.
+    pub fn exists(&self, key: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        self.keyspace.read(key).contains(key)
+    }
+
+    // Number of keys of all types.
+    pub fn dbsize(&self) -> usize {
+        self.keyspace.len()
+    }
+
+    // A consistent point-in-time view of the dataset, taken without stopping the commands: the
+    // writes only wait while the shards are referenced, each shard is copied by its next write.
+    pub fn snapshot(&self) -> DatasetView {
+        let mut expires = vec![];
+        let shards = self.keyspace.snapshot(|| {
+            expires = self
+                .expires
+                .iter()
+                .map(|entry| (entry.key().clone(), *entry.value()))
+                .collect();
+        });
+        DatasetView { shards, expires }
+    }
+
+    // Inserts a member into the set. Returns true if it was not already in the set.
+    pub fn sadd(&self, key: impl Into<Bytes>, member: impl Into<Bytes>) -> Result<bool, WrongType> {
+        let key = key.into();
+        let member = member.into();
+        self.expire_if_needed(&key);
+        let limits = self.config.encoding_limits();
+        let mut shard = self.keyspace.write(&key);
+        shard.check(&key, KeyType::Set)?;
+        if !shard.hset.contains_key(&key) {
+            self.resize(MemoryUsage::default(), MemoryUsage::sets(entry_size(&key)));
+        }
+        let set = shard.hset.entry(key.clone()).or_default();
+        let inserted = set.insert(member.clone(), &limits);
+        if inserted {
+            self.resize(
+                MemoryUsage::default(),
+                MemoryUsage::sets(member.len() as u64),
+            );
+            self.publish(&key, KeyEvent::SAdd(&member));
+        }
+        drop(shard);
+        self.accessed(&key);
+        Ok(inserted)
     }
 
     // Checks if the set contains a specific key.
-    pub fn sismember(&self, key: &str, member: &str) -> bool {
-        self.hset.get(key).map_or(false, |v| v.contains(member))
+    pub fn sismember(&self, key: &[u8], member: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        let shard = self.keyspace.read(key);
+        let set = shard.hset.get(key);
+        self.stats.keyspace_lookup(set.is_some());
+        if set.is_some() {
+            self.accessed(key);
+        }
+        set.is_some_and(|v| v.contains(member))
     }
+
+    // Sets all the keys, only if none of them exists. Returns false if one did. The keys are
+    // locked together, other commands see either all of them set or none.
+    pub fn msetnx(&self, pairs: Vec<(Bytes, RespFrame)>) -> bool {
+        let keys: Vec<Bytes> = pairs.iter().map(|(key, _)| key.clone()).collect();
+        for key in &keys {
+            self.expire_if_needed(key);
+        }
+        let refs: Vec<&[u8]> = keys.iter().map(|key| key.as_ref()).collect();
+        let mut shards = self.keyspace.lock(&refs);
+        if keys.iter().any(|key| shards.shard(key).contains(key)) {
+            return false;
+        }
+        let (mut before, mut after) = (0, 0);
+        for (key, value) in pairs {
+            after += entry_size(&key) + frame_size(&value);
+            self.expires.remove(&key);
+            self.publish(&key, KeyEvent::Set(&value));
+            // a key given twice is set to its last value
+            if let Some(old) = shards.shard(&key).map.insert(key.clone(), value) {
+                before += entry_size(&key) + frame_size(&old);
+            }
+        }
+        drop(shards);
+        self.resize(MemoryUsage::strings(before), MemoryUsage::strings(after));
+        for key in &keys {
+            self.accessed(key);
+        }
+        true
+    }
+
+    // Moves the value and the expiration time of a key to another name, replacing any key of
+    // that name. Returns false if the key doesn't exist.
+    pub fn rename(&self, from: &[u8], to: Bytes) -> bool {
+        self.expire_if_needed(from);
+        self.expire_if_needed(&to);
+        let mut shards = self.keyspace.lock(&[from, &to]);
+        if !shards.shard(from).contains(from) {
+            return false;
+        }
+        if from == to {
+            return true;
+        }
+        let before = shards.shard(from).memory(from) + shards.shard(&to).memory(&to);
+        let source = shards.shard(from);
+        let (string, set, hash) = (
+            source.map.remove(from),
+            source.hset.remove(from),
+            source.hmap.remove(from),
+        );
+        let destination = shards.shard(&to);
+        destination.remove(&to);
+        if let Some(value) = string {
+            destination.map.insert(to.clone(), value);
+        }
+        if let Some(members) = set {
+            destination.hset.insert(to.clone(), members);
+        }
+        if let Some(fields) = hash {
+            destination.hmap.insert(to.clone(), fields);
+        }
+        let after = destination.memory(&to);
+        match self.expires.remove(from) {
+            Some((_, when)) => {
+                self.expires.insert(to.clone(), when);
+            }
+            None => {
+                self.expires.remove(&to);
+            }
+        }
+        self.access.remove(from);
+        self.publish(from, KeyEvent::RenameFrom(&to));
+        self.publish(&to, KeyEvent::RenameTo);
+        drop(shards);
+        self.resize(before, after);
+        self.accessed(&to);
+        true
+    }
+
+    // Moves a member from a set to another, the source set is deleted once empty. Returns false
+    // if the source set doesn't contain the member.
+    pub fn smove(
+        &self,
+        source: &[u8],
+        destination: Bytes,
+        member: Bytes,
+    ) -> Result<bool, WrongType> {
+        self.expire_if_needed(source);
+        self.expire_if_needed(&destination);
+        let limits = self.config.encoding_limits();
+        let mut shards = self.keyspace.lock(&[source, &destination]);
+        shards.shard(source).check(source, KeyType::Set)?;
+        shards
+            .shard(&destination)
+            .check(&destination, KeyType::Set)?;
+        let found = shards
+            .shard(source)
+            .hset
+            .get(source)
+            .is_some_and(|set| set.contains(&member));
+        if !found || source == destination {
+            return Ok(found);
+        }
+        let before =
+            shards.shard(source).memory(source) + shards.shard(&destination).memory(&destination);
+        let from = shards.shard(source);
+        let emptied = from.hset.get_mut(source).is_some_and(|set| {
+            set.remove(&member);
+            set.is_empty()
+        });
+        if emptied {
+            from.hset.remove(source);
+            self.expires.remove(source);
+        }
+        let to = shards.shard(&destination);
+        to.hset
+            .entry(destination.clone())
+            .or_default()
+            .insert(member.clone(), &limits);
+        let after =
+            shards.shard(source).memory(source) + shards.shard(&destination).memory(&destination);
+        self.publish(source, KeyEvent::SMoveFrom(&destination, &member));
+        self.publish(&destination, KeyEvent::SMoveTo(&member));
+        drop(shards);
+        self.resize(before, after);
+        self.accessed(&destination);
+        Ok(true)
+    }
+
+    // Logs a write to the AOF and sends it to the replicas, see the listeners in events.rs. The
+    // command is only built when one of them needs it.
+    pub(crate) fn feed(&self, command: impl FnOnce() -> Vec<RespFrame>) {
+        if !self.aof.enabled() {
+            return self.replication.feed(&self.config, command);
+        }
+        let command = command();
+        self.aof.append(RespArray::new(command.clone()).into());
+        self.replication.feed(&self.config, || command);
+    }
+
+    // Returns the modification counter of a watched key, 0 if it wasn't modified since it was
+    // first watched.
+    pub fn version(&self, key: &[u8]) -> u64 {
+        self.versions.get(key).map_or(0, |v| v.0)
+    }
+
+    // Starts counting the modifications of a key for a connection, returns the count so far.
+    pub(crate) fn watch(&self, key: Bytes) -> u64 {
+        let mut entry = self.versions.entry(key).or_default();
+        entry.1 += 1;
+        entry.0
+    }
+
+    // A connection stops watching a key, which is forgotten once nobody watches it.
+    pub(crate) fn unwatch(&self, key: &[u8]) {
+        self.versions.remove_if_mut(key, |_, (_, watchers)| {
+            *watchers = watchers.saturating_sub(1);
+            *watchers == 0
+        });
+    }
+
+    // Records that a key was modified.
+    pub(crate) fn touch(&self, key: &[u8]) {
+        if let Some(mut entry) = self.versions.get_mut(key) {
+            entry.0 += 1;
+        }
+        self.persistence.mark_dirty();
+    }
+}
+
+// Up to n entries of the map, starting at a random position and wrapping around.
+pub(crate) fn sample<V: Clone>(map: &DashMap<Bytes, V>, n: usize) -> Vec<(Bytes, V)> {
+    let len = map.len();
+    if len == 0 {
+        return vec![];
+    }
+    let n = n.min(len);
+    let mut entries: Vec<(Bytes, V)> = map
+        .iter()
+        .skip(random() as usize % len)
+        .take(n)
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    let missing = n - entries.len();
+    entries.extend(
+        map.iter()
+            .take(missing)
+            .map(|entry| (entry.key().clone(), entry.value().clone())),
+    );
+    entries
+}
+
+// xorshift, good enough to sample keys
+fn random() -> u64 {
+    thread_local!(static STATE: Cell<u64> = Cell::new(now_ms() | 1));
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    })
 }
 
 #[cfg(test)]
@@ -87,10 +561,56 @@ mod tests {
     #[test]
     fn test_sadd() -> Result<()> {
         let backend = Backend::new();
-        let result = backend.sadd("myset", "Hello");
-        assert!(result);
-        let result = backend.sadd("myset", "Hello");
-        assert!(!result);
+        assert_eq!(backend.sadd("myset", "Hello"), Ok(true));
+        assert_eq!(backend.sadd("myset", "Hello"), Ok(false));
         Ok(())
     }
+
+    #[test]
+    fn test_version_changes_on_write() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(backend.watch("myset".into()), 0);
+        backend.sadd("myset", "Hello").unwrap();
+        assert_eq!(backend.version(b"myset"), 1);
+        backend.sadd("myset", "Hello").unwrap();
+        assert_eq!(backend.version(b"myset"), 1);
+        backend.del(b"myset");
+        assert_eq!(backend.version(b"myset"), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_versions_only_of_watched_keys() {
+        let backend = Backend::new();
+        backend.set("k".into(), RespFrame::Integer(1));
+        assert!(backend.versions.is_empty());
+
+        // two connections watch the key, it's kept until both are done
+        backend.watch("k".into());
+        backend.set("k".into(), RespFrame::Integer(2));
+        assert_eq!(backend.watch("k".into()), 1);
+        backend.unwatch(b"k");
+        assert_eq!(backend.version(b"k"), 1);
+        backend.unwatch(b"k");
+        assert!(backend.versions.is_empty());
+    }
+
+    #[test]
+    fn test_sample() {
+        let map = DashMap::new();
+        assert!(sample(&map, 5).is_empty());
+        for i in 0..1000 {
+            map.insert(Bytes::from(format!("key:{}", i)), i);
+        }
+        let entries = sample(&map, 20);
+        assert_eq!(entries.len(), 20);
+        let keys: std::collections::HashSet<_> = entries.iter().map(|(key, _)| key).collect();
+        assert_eq!(keys.len(), 20);
+        assert!(entries
+            .iter()
+            .all(|(key, value)| map.get(key).is_some_and(|v| *v == *value)));
+        // fewer entries than asked for
+        map.retain(|_, value| *value < 3);
+        assert_eq!(sample(&map, 20).len(), 3);
+    }
 }
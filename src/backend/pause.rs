@@ -0,0 +1,96 @@
+use std::sync::Mutex;
+use tokio::{
+    sync::Notify,
+    time::{self, Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseMode {
+    // only commands that may modify the dataset are held back
+    Write,
+    All,
+}
+
+// Set by CLIENT PAUSE, commands wait here before being executed.
+#[derive(Debug, Default)]
+pub struct PauseGate {
+    state: Mutex<Option<(PauseMode, Instant)>>,
+    unpaused: Notify,
+}
+
+impl PauseGate {
+    // Overlapping pauses keep the latest deadline and the most restrictive mode.
+    pub fn pause(&self, mode: PauseMode, duration: Duration) {
+        let now = Instant::now();
+        let until = now + duration;
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        *state = Some(match *state {
+            Some((old_mode, old_until)) if old_until > now => {
+                let mode = if old_mode == PauseMode::All {
+                    old_mode
+                } else {
+                    mode
+                };
+                (mode, until.max(old_until))
+            }
+            _ => (mode, until),
+        });
+    }
+
+    pub fn unpause(&self) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.unpaused.notify_waiters();
+    }
+
+    // When the pause ends for a command, None if it can run right away.
+    pub fn paused_until(&self, write: bool) -> Option<Instant> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match *state {
+            Some((mode, until)) if until > Instant::now() && (write || mode == PauseMode::All) => {
+                Some(until)
+            }
+            _ => None,
+        }
+    }
+
+    pub async fn wait(&self, write: bool) {
+        loop {
+            // subscribe before checking so an UNPAUSE in between is not missed
+            let unpaused = self.unpaused.notified();
+            let Some(until) = self.paused_until(write) else {
+                return;
+            };
+            tokio::select! {
+                _ = time::sleep_until(until) => {}
+                _ = unpaused => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pause_write_only_holds_writes() {
+        let gate = PauseGate::default();
+        gate.pause(PauseMode::Write, Duration::from_millis(50));
+        assert!(gate.paused_until(false).is_none());
+        assert!(gate.paused_until(true).is_some());
+
+        let start = Instant::now();
+        gate.wait(true).await;
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn test_unpause() {
+        let gate = PauseGate::default();
+        gate.pause(PauseMode::All, Duration::from_secs(10));
+        gate.pause(PauseMode::Write, Duration::from_secs(1));
+        assert!(gate.paused_until(false).is_some());
+        gate.unpause();
+        assert!(gate.paused_until(true).is_none());
+    }
+}
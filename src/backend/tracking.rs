@@ -0,0 +1,132 @@
+use crate::{BulkString, RespArray, RespFrame, RespPush};
+use bytes::Bytes;
+use dashmap::{DashMap, DashSet};
+use tokio::sync::mpsc::UnboundedSender;
+
+const INVALIDATE: &str = "invalidate";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TrackingMode {
+    // track the keys the client actually read
+    #[default]
+    Default,
+    // track every key matching one of the prefixes, no matter if it was read
+    Broadcast(Vec<Bytes>),
+}
+
+#[derive(Debug)]
+struct TrackingClient {
+    mode: TrackingMode,
+    sender: UnboundedSender<RespFrame>,
+}
+
+// Server side of client-side caching: remembers which client cached which key and sends
+// invalidation push messages when a key is modified.
+#[derive(Debug, Default)]
+pub struct Tracking {
+    clients: DashMap<u64, TrackingClient>,
+    keys: DashMap<Bytes, DashSet<u64>>,
+}
+
+impl Tracking {
+    pub fn enable(&self, id: u64, mode: TrackingMode, sender: UnboundedSender<RespFrame>) {
+        self.clients.insert(id, TrackingClient { mode, sender });
+    }
+
+    pub fn disable(&self, id: u64) {
+        if self.clients.remove(&id).is_some() {
+            self.keys.retain(|_, ids| {
+                ids.remove(&id);
+                !ids.is_empty()
+            });
+        }
+    }
+
+    pub fn is_enabled(&self, id: u64) -> bool {
+        self.clients.contains_key(&id)
+    }
+
+    // Remember that the client has read (and possibly cached) the key.
+    pub fn track(&self, id: u64, key: impl Into<Bytes>) {
+        match self.clients.get(&id) {
+            Some(client) if client.mode == TrackingMode::Default => {
+                self.keys.entry(key.into()).or_default().insert(id);
+            }
+            _ => {}
+        }
+    }
+
+    // Sends an invalidation message to every client that may have cached the key.
+    pub fn invalidate(&self, key: &[u8]) {
+        if self.clients.is_empty() {
+            return;
+        }
+
+        let mut targets = Vec::new();
+        if let Some((_, ids)) = self.keys.remove(key) {
+            targets.extend(ids);
+        }
+        for client in self.clients.iter() {
+            if let TrackingMode::Broadcast(prefixes) = &client.mode {
+                if prefixes.is_empty() || prefixes.iter().any(|p| key.starts_with(p)) {
+                    targets.push(*client.key());
+                }
+            }
+        }
+
+        for id in targets {
+            if let Some(client) = self.clients.get(&id) {
+                let _ = client.sender.send(invalidate_message(key));
+            }
+        }
+    }
+}
+
+// - ">2\r\n$10\r\ninvalidate\r\n*1\r\n$3\r\nkey\r\n"
+fn invalidate_message(key: &[u8]) -> RespFrame {
+    RespPush::new([
+        BulkString::from(INVALIDATE).into(),
+        RespArray::new([BulkString::from(key).into()]).into(),
+    ])
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_default_tracking_invalidates_once() {
+        let tracking = Tracking::default();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tracking.enable(1, TrackingMode::Default, tx);
+
+        tracking.invalidate(b"foo");
+        assert!(rx.try_recv().is_err());
+
+        tracking.track(1, "foo");
+        tracking.invalidate(b"foo");
+        assert_eq!(rx.try_recv().unwrap(), invalidate_message(b"foo"));
+
+        tracking.invalidate(b"foo");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_broadcast_tracking_uses_prefixes() {
+        let tracking = Tracking::default();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tracking.enable(1, TrackingMode::Broadcast(vec![Bytes::from("user:")]), tx);
+
+        tracking.invalidate(b"order:1");
+        assert!(rx.try_recv().is_err());
+
+        tracking.invalidate(b"user:1");
+        assert_eq!(rx.try_recv().unwrap(), invalidate_message(b"user:1"));
+
+        tracking.disable(1);
+        tracking.invalidate(b"user:1");
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,271 @@
+// Load generator in the spirit of redis-benchmark: each test sends `requests` commands over
+// `clients` connections, `pipeline` at a time, and reports the throughput and the latencies. The
+// tests are the commands the server implements: SET, GET, SADD and HSET.
+//
+//   simple-redis-bench -p 6379 -c 50 -n 100000 -P 16 -t set,get
+use anyhow::{bail, Result};
+use clap::Parser;
+use simple_redis_server::{BulkString, RedisClient, RespArray, RespFrame};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::task::JoinSet;
+
+const TESTS: &[&str] = &["set", "get", "sadd", "hset"];
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Measures the throughput and the latencies of a server"
+)]
+struct Args {
+    #[arg(long, default_value = "127.0.0.1", help = "Server hostname")]
+    host: String,
+    #[arg(short, long, default_value_t = 6379, help = "Server port")]
+    port: u16,
+    #[arg(
+        short,
+        long,
+        default_value_t = 50,
+        help = "Number of parallel connections"
+    )]
+    clients: usize,
+    #[arg(
+        short = 'n',
+        long,
+        default_value_t = 100_000,
+        help = "Total number of requests"
+    )]
+    requests: u64,
+    #[arg(
+        short = 'P',
+        long,
+        default_value_t = 1,
+        help = "Pipeline <numreq> requests, 1 for no pipeline"
+    )]
+    pipeline: usize,
+    #[arg(
+        short,
+        long,
+        default_value_t = 3,
+        help = "Data size of SET/HSET values in bytes"
+    )]
+    data_size: usize,
+    #[arg(
+        short = 'r',
+        long,
+        default_value_t = 0,
+        help = "Use random keys among <keyspacelen>, 0 for a single key"
+    )]
+    keyspace: u64,
+    #[arg(
+        short,
+        long,
+        default_value = "set,get",
+        value_delimiter = ',',
+        help = "Comma separated tests among set, get, sadd and hset"
+    )]
+    tests: Vec<String>,
+}
+
+// What a test measured, the latency of each request is the one of its pipeline.
+#[derive(Debug, Default)]
+struct Report {
+    latencies: Vec<Duration>,
+    errors: u64,
+    // the first error reply, to tell why
+    error: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Arc::new(Args::parse());
+    if args.clients == 0 || args.pipeline == 0 {
+        bail!("clients and pipeline must be at least 1");
+    }
+    let tests: Vec<String> = args.tests.iter().map(|t| t.to_ascii_lowercase()).collect();
+    if let Some(test) = tests.iter().find(|t| !TESTS.contains(&t.as_str())) {
+        bail!("unknown test '{}', try {}", test, TESTS.join(", "));
+    }
+    for test in tests {
+        let start = Instant::now();
+        let report = run(&args, &test).await?;
+        print_report(&args, &test, report, start.elapsed());
+    }
+    Ok(())
+}
+
+// Runs a test over all the connections, they take the requests left in batches of `pipeline`.
+async fn run(args: &Arc<Args>, test: &str) -> Result<Report> {
+    let sent = Arc::new(AtomicU64::new(0));
+    let mut clients = JoinSet::new();
+    for i in 0..args.clients {
+        let mut client = RedisClient::connect((args.host.as_str(), args.port)).await?;
+        let (args, sent, test) = (args.clone(), sent.clone(), test.to_string());
+        clients.spawn(async move {
+            let mut report = Report::default();
+            let mut rng = Rng::new(i as u64);
+            loop {
+                let first = sent.fetch_add(args.pipeline as u64, Ordering::Relaxed);
+                if first >= args.requests {
+                    return Ok::<_, anyhow::Error>(report);
+                }
+                let batch = (args.requests - first).min(args.pipeline as u64);
+                let frames = (0..batch)
+                    .map(|_| request(&args, &test, &mut rng))
+                    .collect();
+                let start = Instant::now();
+                let replies = client.pipeline(frames).await?;
+                let elapsed = start.elapsed();
+                for reply in replies {
+                    report.latencies.push(elapsed);
+                    if let RespFrame::Error(e) = reply {
+                        report.errors += 1;
+                        report.error.get_or_insert(e.to_string());
+                    }
+                }
+            }
+        });
+    }
+    let mut total = Report::default();
+    while let Some(report) = clients.join_next().await {
+        let report = report??;
+        total.latencies.extend(report.latencies);
+        total.errors += report.errors;
+        total.error = total.error.or(report.error);
+    }
+    Ok(total)
+}
+
+fn request(args: &Args, test: &str, rng: &mut Rng) -> RespFrame {
+    let mut key = |prefix: &str| match args.keyspace {
+        0 => prefix.to_string(),
+        keyspace => format!("{}:{:012}", prefix, rng.next() % keyspace),
+    };
+    let value = || "x".repeat(args.data_size);
+    // like redis-benchmark, SADD and HSET spread the members of a single key
+    let args = match test {
+        "set" => vec!["SET".to_string(), key("key"), value()],
+        "get" => vec!["GET".to_string(), key("key")],
+        "sadd" => vec!["SADD".to_string(), "myset".to_string(), key("element")],
+        _ => vec![
+            "HSET".to_string(),
+            "myhash".to_string(),
+            key("element"),
+            value(),
+        ],
+    };
+    RespArray::new(
+        args.into_iter()
+            .map(|arg| BulkString::from(arg).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+// xorshift64*, random enough to spread the keys and without a dependency
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Rng((nanos ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15)) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+fn print_report(args: &Args, test: &str, mut report: Report, elapsed: Duration) {
+    report.latencies.sort_unstable();
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    let percentile = |p: f64| {
+        let rank = (p / 100.0 * report.latencies.len() as f64).ceil() as usize;
+        report
+            .latencies
+            .get(rank.saturating_sub(1))
+            .copied()
+            .map_or(0.0, ms)
+    };
+    let count = report.latencies.len();
+    let avg = match count {
+        0 => 0.0,
+        count => report.latencies.iter().map(|d| ms(*d)).sum::<f64>() / count as f64,
+    };
+    println!("====== {} ======", test.to_ascii_uppercase());
+    println!(
+        "  {} requests completed in {:.2} seconds",
+        count,
+        elapsed.as_secs_f64()
+    );
+    println!("  {} parallel clients", args.clients);
+    println!("  {} bytes payload", args.data_size);
+    println!("  pipeline depth {}", args.pipeline);
+    println!();
+    println!(
+        "  throughput summary: {:.2} requests per second",
+        count as f64 / elapsed.as_secs_f64()
+    );
+    println!("  latency summary (msec):");
+    println!(
+        "  {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+        "avg", "min", "p50", "p95", "p99", "p99.9", "max"
+    );
+    println!(
+        "  {:>9.3} {:>9.3} {:>9.3} {:>9.3} {:>9.3} {:>9.3} {:>9.3}",
+        avg,
+        report.latencies.first().copied().map_or(0.0, ms),
+        percentile(50.0),
+        percentile(95.0),
+        percentile(99.0),
+        percentile(99.9),
+        report.latencies.last().copied().map_or(0.0, ms),
+    );
+    if let Some(error) = report.error {
+        println!("  {} errors, the first one: {}", report.errors, error);
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_redis_server::RedisServer;
+
+    #[tokio::test]
+    async fn test_every_test_runs_without_errors() -> Result<()> {
+        let server = RedisServer::builder().bind("127.0.0.1:0").spawn().await?;
+        let port = server.addr().port().to_string();
+        let args = Args::parse_from([
+            "simple-redis-bench",
+            "-p",
+            &port,
+            "-c",
+            "2",
+            "-n",
+            "50",
+            "-P",
+            "4",
+            "-r",
+            "10",
+        ]);
+        let args = Arc::new(args);
+        for test in TESTS {
+            let report = run(&args, test).await?;
+            assert_eq!(report.latencies.len(), 50, "{}", test);
+            assert_eq!(report.errors, 0, "{}: {:?}", test, report.error);
+        }
+        assert!(server.backend().exists(b"myset") && server.backend().exists(b"myhash"));
+        server.shutdown(Some(false)).await
+    }
+}
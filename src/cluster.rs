@@ -0,0 +1,341 @@
+use crate::replication::random_id;
+use bytes::Bytes;
+use dashmap::DashMap;
+use std::{fmt, ops::RangeInclusive, sync::RwLock};
+use thiserror::Error;
+
+// The keyspace of a cluster is split into 16384 hash slots.
+pub const CLUSTER_SLOTS: u16 = 16384;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ClusterError {
+    #[error("I don't know about node {0}")]
+    UnknownNode(String),
+    #[error("Invalid or out of range slot")]
+    InvalidSlot,
+}
+
+// Why a command can't run on this node, sent back as the error of the command.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Redirect {
+    // the slot is served by another node, the client should update its slot table
+    #[error("MOVED {0} {1}")]
+    Moved(u16, ClusterNode),
+    // the keys are being migrated, only this command should be sent to the other node
+    #[error("ASK {0} {1}")]
+    Ask(u16, ClusterNode),
+    #[error("CROSSSLOT Keys in request don't hash to the same slot")]
+    CrossSlot,
+    #[error("TRYAGAIN Multiple keys request during rehashing of slot")]
+    TryAgain,
+}
+
+// Another node of the cluster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterNode {
+    pub id: String,
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct SlotState {
+    // the node serving the slot, None for this node
+    owner: Option<String>,
+    // MIGRATING to the node with this id
+    migrating: Option<String>,
+    // IMPORTING from the node with this id
+    importing: Option<String>,
+}
+
+// The cluster as seen by this node, enabled by cluster-enabled. There is no cluster bus, the other
+// nodes and the slots they serve are set explicitly. All the slots are served by this node until
+// then.
+#[derive(Debug)]
+pub struct Cluster {
+    myid: String,
+    nodes: DashMap<String, ClusterNode>,
+    slots: RwLock<Vec<SlotState>>,
+}
+
+// CRC16-CCITT (XMODEM), the one used by redis cluster
+const CRC16_TABLE: [u16; 256] = crc16_table();
+
+const fn crc16_table() -> [u16; 256] {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+impl Default for Cluster {
+    fn default() -> Self {
+        Self {
+            myid: random_id(),
+            nodes: DashMap::new(),
+            slots: RwLock::new(vec![SlotState::default(); CLUSTER_SLOTS as usize]),
+        }
+    }
+}
+
+impl fmt::Display for ClusterNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+impl Cluster {
+    // the node id, 40 hex characters
+    pub fn myid(&self) -> &str {
+        &self.myid
+    }
+
+    pub fn add_node(&self, node: ClusterNode) {
+        self.nodes.insert(node.id.clone(), node);
+    }
+
+    pub fn node(&self, id: &str) -> Option<ClusterNode> {
+        self.nodes.get(id).map(|node| node.clone())
+    }
+
+    // The other nodes ordered by id.
+    pub fn nodes(&self) -> Vec<ClusterNode> {
+        let mut nodes: Vec<ClusterNode> = self.nodes.iter().map(|n| n.clone()).collect();
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        nodes
+    }
+
+    // Assigns slots to a node, None or our own id for this node. Ends their migration if any.
+    pub fn set_owner(
+        &self,
+        slots: RangeInclusive<u16>,
+        owner: Option<&str>,
+    ) -> Result<(), ClusterError> {
+        let owner = self.other_node(owner)?;
+        self.update(slots, |state| {
+            *state = SlotState {
+                owner: owner.clone(),
+                ..Default::default()
+            }
+        })
+    }
+
+    // Marks a slot of ours as moving to another node, None to cancel.
+    pub fn set_migrating(&self, slot: u16, target: Option<&str>) -> Result<(), ClusterError> {
+        let target = self.other_node(target)?;
+        self.update(slot..=slot, |state| state.migrating = target.clone())
+    }
+
+    // Marks a slot as moving from another node to this one, None to cancel.
+    pub fn set_importing(&self, slot: u16, source: Option<&str>) -> Result<(), ClusterError> {
+        let source = self.other_node(source)?;
+        self.update(slot..=slot, |state| state.importing = source.clone())
+    }
+
+    // The node serving a slot, None for this node.
+    pub fn owner(&self, slot: u16) -> Option<ClusterNode> {
+        let slots = self.slots.read().unwrap_or_else(|e| e.into_inner());
+        let owner = slots.get(slot as usize)?.owner.as_deref()?;
+        self.node(owner)
+    }
+
+    // The (first slot, last slot, node) ranges of consecutive slots served by the same node,
+    // None for this node.
+    pub fn slot_ranges(&self) -> Vec<(u16, u16, Option<ClusterNode>)> {
+        let slots = self.slots.read().unwrap_or_else(|e| e.into_inner());
+        let mut ranges: Vec<(u16, u16, Option<String>)> = vec![];
+        for (slot, state) in slots.iter().enumerate() {
+            match ranges.last_mut() {
+                Some((_, last, owner)) if *owner == state.owner => *last = slot as u16,
+                _ => ranges.push((slot as u16, slot as u16, state.owner.clone())),
+            }
+        }
+        ranges
+            .into_iter()
+            .map(|(first, last, owner)| (first, last, owner.and_then(|id| self.node(&id))))
+            .collect()
+    }
+
+    // (slot, target node id) of the slots MIGRATING to another node.
+    pub fn migrating(&self) -> Vec<(u16, String)> {
+        self.in_transit(|state| state.migrating.as_ref())
+    }
+
+    // (slot, source node id) of the slots IMPORTING from another node.
+    pub fn importing(&self) -> Vec<(u16, String)> {
+        self.in_transit(|state| state.importing.as_ref())
+    }
+
+    // Whether a command on these keys can run here. `asking` is set by ASKING right before the
+    // command, after an ASK redirection to this node.
+    pub fn check(
+        &self,
+        keys: &[Bytes],
+        asking: bool,
+        exists: impl Fn(&[u8]) -> bool,
+    ) -> Result<(), Redirect> {
+        let Some(slot) = keys.first().map(|key| key_hash_slot(key)) else {
+            return Ok(());
+        };
+        if keys.iter().any(|key| key_hash_slot(key) != slot) {
+            return Err(Redirect::CrossSlot);
+        }
+        let state = self.slots.read().unwrap_or_else(|e| e.into_inner())[slot as usize].clone();
+        if let Some(owner) = state.owner {
+            if asking && state.importing.is_some() {
+                return Ok(());
+            }
+            return match self.node(&owner) {
+                Some(node) => Err(Redirect::Moved(slot, node)),
+                None => Ok(()),
+            };
+        }
+        // the keys that already left are to be found on the target
+        let Some(target) = state.migrating.and_then(|id| self.node(&id)) else {
+            return Ok(());
+        };
+        match keys.iter().filter(|key| !exists(key)).count() {
+            0 => Ok(()),
+            missing if missing < keys.len() => Err(Redirect::TryAgain),
+            _ => Err(Redirect::Ask(slot, target)),
+        }
+    }
+
+    // None or our own id stand for this node, other ids must be known.
+    fn other_node(&self, id: Option<&str>) -> Result<Option<String>, ClusterError> {
+        match id {
+            None => Ok(None),
+            Some(id) if id == self.myid => Ok(None),
+            Some(id) if self.nodes.contains_key(id) => Ok(Some(id.to_string())),
+            Some(id) => Err(ClusterError::UnknownNode(id.to_string())),
+        }
+    }
+
+    fn in_transit(&self, node: impl Fn(&SlotState) -> Option<&String>) -> Vec<(u16, String)> {
+        let slots = self.slots.read().unwrap_or_else(|e| e.into_inner());
+        slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, state)| Some((slot as u16, node(state)?.clone())))
+            .collect()
+    }
+
+    fn update(
+        &self,
+        slots: RangeInclusive<u16>,
+        f: impl Fn(&mut SlotState),
+    ) -> Result<(), ClusterError> {
+        if *slots.end() >= CLUSTER_SLOTS {
+            return Err(ClusterError::InvalidSlot);
+        }
+        let mut states = self.slots.write().unwrap_or_else(|e| e.into_inner());
+        for slot in slots {
+            f(&mut states[slot as usize]);
+        }
+        Ok(())
+    }
+}
+
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, byte| {
+        (crc << 8) ^ CRC16_TABLE[((crc >> 8) as u8 ^ byte) as usize]
+    })
+}
+
+// The hash slot of a key. When the key contains a non-empty {hash tag}, only the tag is hashed,
+// so related keys like {user1000}.following and {user1000}.followers share the same slot.
+pub fn key_hash_slot(key: &[u8]) -> u16 {
+    let tag = key.iter().position(|&b| b == b'{').and_then(|start| {
+        let len = key[start + 1..].iter().position(|&b| b == b'}')?;
+        (len > 0).then(|| &key[start + 1..start + 1 + len])
+    });
+    crc16(tag.unwrap_or(key)) % CLUSTER_SLOTS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+        assert_eq!(key_hash_slot(b"foo"), 12182);
+        assert_eq!(key_hash_slot(b""), 0);
+    }
+
+    #[test]
+    fn test_redirections() {
+        let cluster = Cluster::default();
+        let other = ClusterNode {
+            id: "b".repeat(40),
+            host: "127.0.0.1".to_string(),
+            port: 7001,
+        };
+        let keys = |keys: &[&str]| {
+            keys.iter()
+                .map(|k| Bytes::copy_from_slice(k.as_bytes()))
+                .collect::<Vec<_>>()
+        };
+        let none = |_: &[u8]| false;
+        // foo is in slot 12182, {foo}bar as well, bar in 5061
+        assert_eq!(
+            cluster.check(&keys(&["foo", "{foo}bar"]), false, none),
+            Ok(())
+        );
+        assert_eq!(
+            cluster.check(&keys(&["foo", "bar"]), false, none),
+            Err(Redirect::CrossSlot)
+        );
+        assert!(cluster.set_owner(0..=100, Some(&other.id)).is_err());
+
+        cluster.add_node(other.clone());
+        cluster.set_owner(12000..=12999, Some(&other.id)).unwrap();
+        let moved = cluster.check(&keys(&["foo"]), false, none).unwrap_err();
+        assert_eq!(moved.to_string(), "MOVED 12182 127.0.0.1:7001");
+        assert_eq!(cluster.slot_ranges().len(), 3);
+
+        // importing: only served after ASKING
+        cluster.set_importing(12182, Some(&other.id)).unwrap();
+        assert!(cluster.check(&keys(&["foo"]), false, none).is_err());
+        assert_eq!(cluster.check(&keys(&["foo"]), true, none), Ok(()));
+
+        // migrating: the keys that are still here are served, the others are asked for there
+        cluster.set_owner(12182..=12182, None).unwrap();
+        cluster.set_migrating(12182, Some(&other.id)).unwrap();
+        assert_eq!(cluster.check(&keys(&["foo"]), false, |_| true), Ok(()));
+        assert_eq!(
+            cluster.check(&keys(&["foo"]), false, none),
+            Err(Redirect::Ask(12182, other.clone()))
+        );
+        assert_eq!(
+            cluster.check(&keys(&["foo", "{foo}bar"]), false, |key| key == b"foo"),
+            Err(Redirect::TryAgain)
+        );
+    }
+
+    #[test]
+    fn test_hash_tags() {
+        assert_eq!(
+            key_hash_slot(b"{user1000}.following"),
+            key_hash_slot(b"{user1000}.followers")
+        );
+        assert_eq!(key_hash_slot(b"{user1000}.x"), key_hash_slot(b"user1000"));
+        // an empty tag doesn't count, only the first tag does
+        assert_eq!(key_hash_slot(b"foo{}{bar}"), crc16(b"foo{}{bar}") % 16384);
+        assert_eq!(key_hash_slot(b"foo{{bar}}zap"), key_hash_slot(b"{bar"));
+        assert_eq!(key_hash_slot(b"foo{bar}{zap}"), key_hash_slot(b"bar"));
+    }
+}
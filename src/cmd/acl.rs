@@ -0,0 +1,144 @@
+use super::{AclCmd, CommandError, CommandExecutor, ExecContext, Subcommand, RESP_OK};
+use crate::{
+    acl::{self, AclError},
+    network::Session,
+    Backend, BulkString, RespArray, RespFrame, RespMap, SimpleError,
+};
+use std::path::PathBuf;
+
+impl CommandExecutor for AclCmd {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let backend = ctx.backend;
+        Ok(self.execute_in(ctx.session("ACL")?, backend))
+    }
+}
+
+impl AclCmd {
+    fn execute_in(self, session: &Session, backend: &Backend) -> RespFrame {
+        match self {
+            AclCmd::SetUser(name, rules) => match backend.acl.set_user(&name, &rules) {
+                Ok(()) => RESP_OK.clone(),
+                Err(e) => SimpleError::new(format!("ERR {}", e)).into(),
+            },
+            AclCmd::GetUser(name) => match backend.acl.get_user(&name) {
+                Some(user) => {
+                    let mut flags = vec![if user.enabled { "on" } else { "off" }];
+                    if user.nopass {
+                        flags.push("nopass");
+                    }
+                    let keys = user
+                        .keys
+                        .iter()
+                        .map(|pattern| format!("~{}", pattern))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    let mut map = RespMap::new();
+                    map.insert(BulkString::from("flags"), bulk_strings(flags));
+                    map.insert(
+                        BulkString::from("passwords"),
+                        bulk_strings(user.passwords.clone()),
+                    );
+                    map.insert(
+                        BulkString::from("commands"),
+                        BulkString::from(user.command_rules()),
+                    );
+                    map.insert(BulkString::from("keys"), BulkString::from(keys));
+                    map.into()
+                }
+                None => RespFrame::null(),
+            },
+            AclCmd::DelUser(names) => match backend.acl.del_users(&names) {
+                Ok(deleted) => {
+                    // connections authenticated as a deleted user are closed
+                    backend.clients.kill(|client| names.contains(&client.user));
+                    RespFrame::Integer(deleted as i64)
+                }
+                Err(e) => SimpleError::new(format!("ERR {}", e)).into(),
+            },
+            AclCmd::List => bulk_strings(backend.acl.users().iter().map(|user| user.describe())),
+            AclCmd::Users => bulk_strings(backend.acl.users().into_iter().map(|user| user.name)),
+            AclCmd::WhoAmI => BulkString::from(session.user.as_str()).into(),
+            AclCmd::Cat(None) => bulk_strings(acl::categories()),
+            AclCmd::Cat(Some(category)) => match acl::category_commands(&category) {
+                Some(commands) => bulk_strings(commands),
+                None => SimpleError::new(format!("ERR Unknown category '{}'", category)).into(),
+            },
+            AclCmd::Save => match aclfile(backend).and_then(|path| backend.acl.save(&path)) {
+                Ok(()) => RESP_OK.clone(),
+                Err(e) => SimpleError::new(format!("ERR {}", e)).into(),
+            },
+            AclCmd::Load => match aclfile(backend).and_then(|path| backend.acl.load(&path)) {
+                Ok(()) => RESP_OK.clone(),
+                Err(e) => SimpleError::new(format!("ERR {}", e)).into(),
+            },
+        }
+    }
+}
+
+fn aclfile(backend: &Backend) -> Result<PathBuf, AclError> {
+    match backend.config.snapshot().aclfile {
+        path if path.is_empty() => Err(AclError::NoAclFile),
+        path => Ok(PathBuf::from(path)),
+    }
+}
+
+fn bulk_strings<T: Into<BulkString>>(values: impl IntoIterator<Item = T>) -> RespFrame {
+    RespArray::new(
+        values
+            .into_iter()
+            .map(|value| value.into().into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+impl TryFrom<RespArray> for AclCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = Subcommand::parse(value, "acl")?;
+        let mut args = sub.strings()?;
+        match (sub.name.as_str(), args.len()) {
+            ("setuser", 1..) => {
+                let name = args.remove(0);
+                Ok(AclCmd::SetUser(name, args))
+            }
+            ("getuser", 1) => Ok(AclCmd::GetUser(args.remove(0))),
+            ("deluser", 1..) => Ok(AclCmd::DelUser(args)),
+            ("list", 0) => Ok(AclCmd::List),
+            ("users", 0) => Ok(AclCmd::Users),
+            ("whoami", 0) => Ok(AclCmd::WhoAmI),
+            ("cat", 0) => Ok(AclCmd::Cat(None)),
+            ("cat", 1) => Ok(AclCmd::Cat(Some(args.remove(0)))),
+            ("save", 0) => Ok(AclCmd::Save),
+            ("load", 0) => Ok(AclCmd::Load),
+            (
+                "setuser" | "getuser" | "deluser" | "list" | "users" | "whoami" | "cat" | "save"
+                | "load",
+                _,
+            ) => Err(sub.wrong_arity()),
+            _ => Err(sub.unknown()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecoder;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_acl_setuser_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$3\r\nACL\r\n$7\r\nSETUSER\r\n$5\r\nalice\r\n$2\r\non\r\n$6\r\n+@read\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: AclCmd = frame.try_into()?;
+        assert!(
+            matches!(result, AclCmd::SetUser(name, rules) if name == "alice" && rules == ["on", "+@read"])
+        );
+        Ok(())
+    }
+}
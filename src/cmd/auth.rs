@@ -0,0 +1,87 @@
+use super::{extract_args, Auth, CommandError, CommandExecutor, ExecContext, RESP_OK};
+use crate::{network::Session, Backend, RespArray, RespFrame, SimpleError};
+
+impl CommandExecutor for Auth {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let backend = ctx.backend;
+        Ok(self.execute_in(ctx.session("AUTH")?, backend))
+    }
+}
+
+impl Auth {
+    fn execute_in(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        match check(self.username.as_deref(), &self.password, backend) {
+            Ok(username) => {
+                session.login(username, backend);
+                RESP_OK.clone()
+            }
+            Err(e) => e.into(),
+        }
+    }
+}
+
+// Checks the credentials given to AUTH or HELLO, returns the authenticated user.
+pub(super) fn check(
+    username: Option<&str>,
+    password: &str,
+    backend: &Backend,
+) -> Result<String, SimpleError> {
+    let requirepass = backend.config.requirepass();
+    if username.is_none() && backend.acl.default_nopass(&requirepass) {
+        return Err(SimpleError::new("ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?"));
+    }
+    let username = username.unwrap_or("default");
+    match backend.acl.authenticate(username, password, &requirepass) {
+        true => Ok(username.to_string()),
+        false => Err(SimpleError::new(
+            "WRONGPASS invalid username-password pair or user is disabled.",
+        )),
+    }
+}
+
+impl TryFrom<RespArray> for Auth {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?
+            .into_iter()
+            .map(|arg| match arg {
+                RespFrame::BulkString(arg) => Ok(String::from_utf8(arg.0.into())?),
+                _ => Err(CommandError::InvalidArgument(
+                    "auth arguments must be BulkString".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match args.len() {
+            1 => Ok(Auth {
+                username: None,
+                password: args.remove(0),
+            }),
+            2 => Ok(Auth {
+                password: args.remove(1),
+                username: Some(args.remove(0)),
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "auth command must have 1 or 2 arguments".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecoder;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_auth_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$4\r\nAUTH\r\n$7\r\ndefault\r\n$6\r\nsecret\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Auth = frame.try_into()?;
+        assert_eq!(result.username.as_deref(), Some("default"));
+        assert_eq!(result.password, "secret");
+        Ok(())
+    }
+}
@@ -0,0 +1,505 @@
+use super::{
+    validate_command, Client, ClientKill, ClientTracking, CommandError, CommandExecutor,
+    ExecContext, Monitor, Quit, Subcommand, RESP_OK,
+};
+use crate::{
+    network::Session, Backend, BulkString, PauseMode, RespArray, RespFrame, SimpleError,
+    TrackingMode, VerbatimString, Wakeup,
+};
+use std::time::Duration;
+
+impl CommandExecutor for Client {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let backend = ctx.backend;
+        Ok(self.execute_in(ctx.session("CLIENT")?, backend))
+    }
+}
+
+impl Client {
+    pub(crate) fn execute_in(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        match self {
+            Client::Tracking(tracking) => tracking.execute_in(session, backend),
+            Client::Caching(yes) => {
+                if !backend.tracking.is_enabled(session.id)
+                    || (!session.tracking_optin && !session.tracking_optout)
+                {
+                    return SimpleError::new(
+                        "ERR CLIENT CACHING can be called only when the client is in tracking mode with OPTIN or OPTOUT mode enabled",
+                    )
+                    .into();
+                }
+                if (yes && !session.tracking_optin) || (!yes && !session.tracking_optout) {
+                    return SimpleError::new(format!(
+                        "ERR CLIENT CACHING {} is only valid when tracking is enabled in {} mode.",
+                        if yes { "YES" } else { "NO" },
+                        if yes { "OPTIN" } else { "OPTOUT" },
+                    ))
+                    .into();
+                }
+                session.caching = Some(yes);
+                RESP_OK.clone()
+            }
+            Client::List(ids) => {
+                let lines: String = backend
+                    .clients
+                    .list()
+                    .into_iter()
+                    .filter(|client| ids.is_empty() || ids.contains(&client.id))
+                    .map(|client| client.to_line() + "\n")
+                    .collect::<String>();
+                VerbatimString::text(lines).into()
+            }
+            Client::Id => RespFrame::Integer(session.id as i64),
+            Client::Info => match backend.clients.get(session.id) {
+                Some(client) => VerbatimString::text(client.to_line() + "\n").into(),
+                None => RespFrame::null(),
+            },
+            Client::SetName(name) => {
+                // an empty name removes the current one
+                session.name = (!name.is_empty()).then(|| name.clone());
+                backend
+                    .clients
+                    .update(session.id, |client| client.name = name);
+                RESP_OK.clone()
+            }
+            Client::GetName => match &session.name {
+                Some(name) => BulkString::new(name.clone()).into(),
+                None => RespFrame::null(),
+            },
+            Client::Kill(kill) => kill.execute_in(session, backend),
+            Client::Pause(duration, mode) => {
+                backend.pause.pause(mode, duration);
+                RESP_OK.clone()
+            }
+            Client::Unpause => {
+                backend.pause.unpause();
+                RESP_OK.clone()
+            }
+            Client::Unblock(id, error) => {
+                let wakeup = if error {
+                    Wakeup::Unblocked
+                } else {
+                    Wakeup::TimedOut
+                };
+                RespFrame::Integer(backend.blocked.unblock(id, wakeup) as i64)
+            }
+        }
+    }
+}
+
+impl CommandExecutor for Monitor {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let backend = ctx.backend;
+        Ok(self.execute_in(ctx.session("MONITOR")?, backend))
+    }
+}
+
+impl Monitor {
+    fn execute_in(self, session: &Session, backend: &Backend) -> RespFrame {
+        backend.monitors.add(session.id, session.sender.clone());
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for Quit {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(self.execute_in(ctx.session("QUIT")?))
+    }
+}
+
+impl Quit {
+    fn execute_in(self, session: &mut Session) -> RespFrame {
+        session.closing = true;
+        RESP_OK.clone()
+    }
+}
+
+impl ClientTracking {
+    fn execute_in(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        if !self.on {
+            backend.tracking.disable(session.id);
+            session.tracking_optin = false;
+            session.tracking_optout = false;
+            return RESP_OK.clone();
+        }
+
+        let mode = if self.bcast {
+            TrackingMode::Broadcast(self.prefixes)
+        } else {
+            TrackingMode::Default
+        };
+        backend
+            .tracking
+            .enable(session.id, mode, session.sender.clone());
+        session.tracking_optin = self.optin;
+        session.tracking_optout = self.optout;
+        RESP_OK.clone()
+    }
+}
+
+impl ClientKill {
+    fn execute_in(self, session: &Session, backend: &Backend) -> RespFrame {
+        let killed = backend.clients.kill(|client| {
+            !(self.skipme && client.id == session.id)
+                && self.id.is_none_or(|id| client.id == id)
+                && self
+                    .addr
+                    .as_ref()
+                    .is_none_or(|addr| client.addr.to_string() == *addr)
+                && self
+                    .laddr
+                    .as_ref()
+                    .is_none_or(|addr| client.laddr.to_string() == *addr)
+                && self.name.as_ref().is_none_or(|name| client.name == *name)
+        });
+        match (self.legacy, killed) {
+            (false, killed) => RespFrame::Integer(killed as i64),
+            (true, 0) => SimpleError::new("ERR No such client").into(),
+            (true, _) => RESP_OK.clone(),
+        }
+    }
+
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, CommandError> {
+        let Some(first) = args.next() else {
+            return Err(CommandError::InvalidArgument(
+                "client kill command needs a filter".to_string(),
+            ));
+        };
+        let Some(mut value) = args.next() else {
+            // the old form, CLIENT KILL ip:port
+            return Ok(ClientKill {
+                addr: Some(first),
+                legacy: true,
+                ..Default::default()
+            });
+        };
+
+        let mut kill = ClientKill {
+            skipme: true,
+            ..Default::default()
+        };
+        let mut filter = first;
+        loop {
+            match filter.to_ascii_lowercase().as_str() {
+                "id" => {
+                    kill.id = Some(value.parse().map_err(|_| {
+                        CommandError::InvalidArgument(
+                            "client-id should be greater than 0".to_string(),
+                        )
+                    })?)
+                }
+                "addr" => kill.addr = Some(value),
+                "laddr" => kill.laddr = Some(value),
+                "name" => kill.name = Some(value),
+                "skipme" => kill.skipme = parse_switch(Some(value), "YES", "NO")?,
+                filter => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "unsupported CLIENT KILL filter: {filter}"
+                    )))
+                }
+            }
+            match (args.next(), args.next()) {
+                (None, _) => return Ok(kill),
+                (Some(next_filter), Some(next_value)) => {
+                    filter = next_filter;
+                    value = next_value;
+                }
+                (Some(_), None) => {
+                    return Err(CommandError::InvalidArgument(
+                        "client kill filter needs a value".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Client {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = Subcommand::parse(value, "client")?;
+        let mut args = sub.strings()?.into_iter();
+        match sub.name.as_str() {
+            "tracking" => {
+                let mut tracking = ClientTracking {
+                    on: parse_switch(args.next(), "ON", "OFF")?,
+                    ..Default::default()
+                };
+                while let Some(arg) = args.next() {
+                    match arg.to_ascii_lowercase().as_str() {
+                        "bcast" => tracking.bcast = true,
+                        "optin" => tracking.optin = true,
+                        "optout" => tracking.optout = true,
+                        "prefix" => match args.next() {
+                            Some(prefix) => tracking.prefixes.push(prefix.into()),
+                            None => {
+                                return Err(CommandError::InvalidArgument(
+                                    "PREFIX needs a value".to_string(),
+                                ))
+                            }
+                        },
+                        arg => {
+                            return Err(CommandError::InvalidArgument(format!(
+                                "unsupported CLIENT TRACKING option: {arg}"
+                            )))
+                        }
+                    }
+                }
+                if tracking.optin && tracking.optout {
+                    return Err(CommandError::InvalidArgument(
+                        "You can't use both OPTIN and OPTOUT".to_string(),
+                    ));
+                }
+                if tracking.bcast && (tracking.optin || tracking.optout) {
+                    return Err(CommandError::InvalidArgument(
+                        "OPTIN and OPTOUT are not compatible with BCAST".to_string(),
+                    ));
+                }
+                if !tracking.bcast && !tracking.prefixes.is_empty() {
+                    return Err(CommandError::InvalidArgument(
+                        "PREFIX option requires BCAST mode to be enabled".to_string(),
+                    ));
+                }
+                Ok(Client::Tracking(tracking))
+            }
+            "caching" => {
+                let yes = parse_switch(args.next(), "YES", "NO")?;
+                match args.next() {
+                    None => Ok(Client::Caching(yes)),
+                    Some(_) => Err(sub.wrong_arity()),
+                }
+            }
+            "list" => {
+                let mut ids = vec![];
+                while let Some(arg) = args.next() {
+                    match arg.to_ascii_lowercase().as_str() {
+                        "type" => match args.next() {
+                            Some(kind) if kind.eq_ignore_ascii_case("normal") => {}
+                            _ => {
+                                return Err(CommandError::InvalidArgument(
+                                    "Unknown client type".to_string(),
+                                ))
+                            }
+                        },
+                        "id" => {
+                            for id in args.by_ref() {
+                                ids.push(id.parse().map_err(|_| {
+                                    CommandError::InvalidArgument("Invalid client ID".to_string())
+                                })?);
+                            }
+                            if ids.is_empty() {
+                                return Err(CommandError::InvalidArgument(
+                                    "ID needs at least one client id".to_string(),
+                                ));
+                            }
+                        }
+                        arg => {
+                            return Err(CommandError::InvalidArgument(format!(
+                                "unsupported CLIENT LIST option: {arg}"
+                            )))
+                        }
+                    }
+                }
+                Ok(Client::List(ids))
+            }
+            "setname" => match (args.next(), args.next()) {
+                (Some(name), None) => {
+                    validate_name(&name)?;
+                    Ok(Client::SetName(name))
+                }
+                _ => Err(sub.wrong_arity()),
+            },
+            "id" | "info" | "getname" | "unpause" if args.next().is_some() => {
+                Err(sub.wrong_arity())
+            }
+            "kill" => Ok(Client::Kill(ClientKill::parse(args)?)),
+            "pause" => {
+                let timeout = args
+                    .next()
+                    .and_then(|timeout| timeout.parse().ok())
+                    .ok_or_else(|| {
+                        CommandError::InvalidArgument(
+                            "timeout is not an integer or out of range".to_string(),
+                        )
+                    })?;
+                let mode = match args.next() {
+                    None => PauseMode::All,
+                    Some(mode) if mode.eq_ignore_ascii_case("all") => PauseMode::All,
+                    Some(mode) if mode.eq_ignore_ascii_case("write") => PauseMode::Write,
+                    Some(_) => {
+                        return Err(CommandError::InvalidArgument(
+                            "expected WRITE or ALL".to_string(),
+                        ))
+                    }
+                };
+                if args.next().is_some() {
+                    return Err(CommandError::InvalidArgument(
+                        "client pause command takes at most 2 arguments".to_string(),
+                    ));
+                }
+                Ok(Client::Pause(Duration::from_millis(timeout), mode))
+            }
+            "unblock" => {
+                let id = args.next().and_then(|id| id.parse().ok()).ok_or_else(|| {
+                    CommandError::InvalidArgument(
+                        "value is not an integer or out of range".to_string(),
+                    )
+                })?;
+                let error = match args.next() {
+                    None => false,
+                    Some(mode) if mode.eq_ignore_ascii_case("timeout") => false,
+                    Some(mode) if mode.eq_ignore_ascii_case("error") => true,
+                    Some(_) => {
+                        return Err(CommandError::InvalidArgument(
+                            "CLIENT UNBLOCK reason should be TIMEOUT or ERROR".to_string(),
+                        ))
+                    }
+                };
+                if args.next().is_some() {
+                    return Err(CommandError::InvalidArgument(
+                        "client unblock command takes at most 2 arguments".to_string(),
+                    ));
+                }
+                Ok(Client::Unblock(id, error))
+            }
+            "id" => Ok(Client::Id),
+            "info" => Ok(Client::Info),
+            "getname" => Ok(Client::GetName),
+            "unpause" => Ok(Client::Unpause),
+            _ => Err(sub.unknown()),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Monitor {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["monitor"], 0)?;
+        Ok(Monitor)
+    }
+}
+
+impl TryFrom<RespArray> for Quit {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["quit"], 0)?;
+        Ok(Quit)
+    }
+}
+
+pub(super) fn validate_name(name: &str) -> Result<(), CommandError> {
+    match name.bytes().all(|b| (b'!'..=b'~').contains(&b)) {
+        true => Ok(()),
+        false => Err(CommandError::InvalidArgument(
+            "Client names cannot contain spaces, newlines or special characters.".to_string(),
+        )),
+    }
+}
+
+fn parse_switch(arg: Option<String>, on: &str, off: &str) -> Result<bool, CommandError> {
+    match arg {
+        Some(arg) if arg.eq_ignore_ascii_case(on) => Ok(true),
+        Some(arg) if arg.eq_ignore_ascii_case(off) => Ok(false),
+        _ => Err(CommandError::InvalidArgument(format!(
+            "expected {on} or {off}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecoder;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_client_tracking_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*6\r\n$6\r\nCLIENT\r\n$8\r\nTRACKING\r\n$2\r\non\r\n$5\r\nBCAST\r\n$6\r\nPREFIX\r\n$5\r\nuser:\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Client = frame.try_into()?;
+        match result {
+            Client::Tracking(tracking) => assert_eq!(
+                tracking,
+                ClientTracking {
+                    on: true,
+                    bcast: true,
+                    prefixes: vec!["user:".into()],
+                    ..Default::default()
+                }
+            ),
+            _ => panic!("expected CLIENT TRACKING"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_tracking_rejects_prefix_without_bcast() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$6\r\nclient\r\n$8\r\ntracking\r\n$2\r\non\r\n$6\r\nprefix\r\n$1\r\na\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(Client::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_caching_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nclient\r\n$7\r\ncaching\r\n$3\r\nyes\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Client = frame.try_into()?;
+        assert!(matches!(result, Client::Caching(true)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_list_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*6\r\n$6\r\nclient\r\n$4\r\nlist\r\n$4\r\ntype\r\n$6\r\nnormal\r\n$2\r\nID\r\n$1\r\n7\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Client = frame.try_into()?;
+        assert!(matches!(result, Client::List(ids) if ids == vec![7]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_kill_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nclient\r\n$4\r\nkill\r\n$15\r\n127.0.0.1:50000\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Client = frame.try_into()?;
+        assert!(
+            matches!(result, Client::Kill(kill) if kill.legacy && kill.addr.as_deref() == Some("127.0.0.1:50000"))
+        );
+
+        buf.extend_from_slice(b"*6\r\n$6\r\nclient\r\n$4\r\nkill\r\n$4\r\nname\r\n$3\r\nweb\r\n$6\r\nskipme\r\n$2\r\nno\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Client = frame.try_into()?;
+        match result {
+            Client::Kill(kill) => assert_eq!(
+                kill,
+                ClientKill {
+                    name: Some("web".to_string()),
+                    ..Default::default()
+                }
+            ),
+            _ => panic!("expected CLIENT KILL"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_client_setname_rejects_spaces() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nclient\r\n$7\r\nsetname\r\n$5\r\na b c\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(Client::try_from(frame).is_err());
+        Ok(())
+    }
+}
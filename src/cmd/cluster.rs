@@ -0,0 +1,419 @@
+use super::{
+    keys::request, validate_command, Asking, ClusterCmd, CommandError, CommandExecutor,
+    ExecContext, SlotAction, Subcommand, RESP_OK,
+};
+use crate::{
+    key_hash_slot, network::Session, Backend, BulkString, ClientAddr, ClusterNode, RespArray,
+    RespCodec, RespFrame, SimpleError, CLUSTER_SLOTS,
+};
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use std::{
+    fmt::Write,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+use tracing::{info, warn};
+
+const CLUSTER_DISABLED: &str = "ERR This instance has cluster support disabled";
+
+impl CommandExecutor for ClusterCmd {
+    // This node is reported at the address the client connected to, or on localhost.
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let laddr = ctx
+            .client_id()
+            .and_then(|id| ctx.backend.clients.get(id))
+            .map(|client| client.laddr);
+        let addr = match laddr {
+            Some(ClientAddr::Tcp(addr)) => addr,
+            _ => {
+                let port = ctx.backend.config.snapshot().port;
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port)
+            }
+        };
+        Ok(self.reply(ctx.backend, addr))
+    }
+}
+
+impl ClusterCmd {
+    fn reply(self, backend: &Backend, addr: SocketAddr) -> RespFrame {
+        let cluster = &backend.cluster;
+        let myself = ClusterNode {
+            id: cluster.myid().to_string(),
+            host: addr.ip().to_string(),
+            port: addr.port(),
+        };
+        // (first slot, last slot, node) with this node in place of None
+        let ranges: Vec<(u16, u16, ClusterNode)> = cluster
+            .slot_ranges()
+            .into_iter()
+            .map(|(first, last, node)| (first, last, node.unwrap_or_else(|| myself.clone())))
+            .collect();
+        let mut nodes = cluster.nodes();
+        nodes.insert(0, myself.clone());
+        match self {
+            ClusterCmd::KeySlot(key) => RespFrame::Integer(key_hash_slot(&key) as i64),
+            _ if !backend.config.cluster_enabled() => SimpleError::new(CLUSTER_DISABLED).into(),
+            ClusterCmd::Info => {
+                let assigned: usize = ranges
+                    .iter()
+                    .map(|(first, last, _)| (last - first) as usize + 1)
+                    .sum();
+                let size = nodes
+                    .iter()
+                    .filter(|node| ranges.iter().any(|(_, _, owner)| owner.id == node.id))
+                    .count();
+                BulkString::from(format!(
+                    "cluster_state:ok\r\ncluster_slots_assigned:{0}\r\ncluster_slots_ok:{0}\r\ncluster_slots_pfail:0\r\ncluster_slots_fail:0\r\ncluster_known_nodes:{1}\r\ncluster_size:{2}\r\ncluster_current_epoch:0\r\ncluster_my_epoch:0\r\ncluster_stats_messages_sent:0\r\ncluster_stats_messages_received:0\r\n",
+                    assigned,
+                    nodes.len(),
+                    size,
+                ))
+                .into()
+            }
+            ClusterCmd::MyId => BulkString::from(myself.id).into(),
+            // <id> <ip:port@cport> <flags> <master> <ping-sent> <pong-recv> <config-epoch> <link-state> <slot>...
+            ClusterCmd::Nodes => {
+                let (migrating, importing) = (cluster.migrating(), cluster.importing());
+                let mut text = String::new();
+                for node in &nodes {
+                    let flags = match node.id == myself.id {
+                        true => "myself,master",
+                        false => "master",
+                    };
+                    let _ = write!(
+                        text,
+                        "{} {}@{} {} - 0 0 0 connected",
+                        node.id,
+                        node,
+                        node.port as u32 + 10000,
+                        flags
+                    );
+                    for (first, last, _) in ranges.iter().filter(|(_, _, n)| n.id == node.id) {
+                        let _ = match first == last {
+                            true => write!(text, " {}", first),
+                            false => write!(text, " {}-{}", first, last),
+                        };
+                    }
+                    if node.id == myself.id {
+                        for (slot, target) in &migrating {
+                            let _ = write!(text, " [{}->-{}]", slot, target);
+                        }
+                        for (slot, source) in &importing {
+                            let _ = write!(text, " [{}-<-{}]", slot, source);
+                        }
+                    }
+                    text.push('\n');
+                }
+                BulkString::from(text).into()
+            }
+            ClusterCmd::Slots => {
+                let ranges: Vec<RespFrame> = ranges
+                    .into_iter()
+                    .map(|(first, last, node)| {
+                        let node: Vec<RespFrame> = vec![
+                            BulkString::from(node.host).into(),
+                            RespFrame::Integer(node.port as i64),
+                            BulkString::from(node.id).into(),
+                        ];
+                        let range: Vec<RespFrame> = vec![
+                            RespFrame::Integer(first as i64),
+                            RespFrame::Integer(last as i64),
+                            RespArray::new(node).into(),
+                        ];
+                        RespArray::new(range).into()
+                    })
+                    .collect();
+                RespArray::new(ranges).into()
+            }
+            ClusterCmd::Shards => {
+                let offset = backend.replication.offset() as i64;
+                let shards: Vec<RespFrame> = nodes
+                    .into_iter()
+                    .map(|node| {
+                        let slots: Vec<RespFrame> = ranges
+                            .iter()
+                            .filter(|(_, _, owner)| owner.id == node.id)
+                            .flat_map(|(first, last, _)| {
+                                [
+                                    RespFrame::Integer(*first as i64),
+                                    RespFrame::Integer(*last as i64),
+                                ]
+                            })
+                            .collect();
+                        // the replication offset is only known for this node
+                        let offset = match node.id == myself.id {
+                            true => offset,
+                            false => 0,
+                        };
+                        let node: Vec<RespFrame> = vec![
+                            BulkString::from("id").into(),
+                            BulkString::from(node.id).into(),
+                            BulkString::from("port").into(),
+                            RespFrame::Integer(node.port as i64),
+                            BulkString::from("ip").into(),
+                            BulkString::from(node.host.as_str()).into(),
+                            BulkString::from("endpoint").into(),
+                            BulkString::from(node.host).into(),
+                            BulkString::from("role").into(),
+                            BulkString::from("master").into(),
+                            BulkString::from("replication-offset").into(),
+                            RespFrame::Integer(offset),
+                            BulkString::from("health").into(),
+                            BulkString::from("online").into(),
+                        ];
+                        let shard: Vec<RespFrame> = vec![
+                            BulkString::from("slots").into(),
+                            RespArray::new(slots).into(),
+                            BulkString::from("nodes").into(),
+                            RespArray::new(vec![RespArray::new(node).into()]).into(),
+                        ];
+                        RespArray::new(shard).into()
+                    })
+                    .collect();
+                RespArray::new(shards).into()
+            }
+            ClusterCmd::Meet(host, port) => {
+                let backend = backend.clone();
+                tokio::spawn(async move {
+                    match meet(&backend, &host, port).await {
+                        Ok(id) => info!("Node {} met at {}:{}", id, host, port),
+                        Err(e) => warn!("Unable to meet node {}:{}: {}", host, port, e),
+                    }
+                });
+                RESP_OK.clone()
+            }
+            ClusterCmd::SetSlot(slot, action) => match set_slot(backend, slot, action) {
+                Ok(()) => RESP_OK.clone(),
+                Err(e) => SimpleError::new(format!("ERR {}", e)).into(),
+            },
+            ClusterCmd::GetKeysInSlot(slot, count) => {
+                let keys: Vec<RespFrame> = keys_in_slot(backend, slot)
+                    .into_iter()
+                    .take(count)
+                    .map(|key| BulkString::from(key).into())
+                    .collect();
+                RespArray::new(keys).into()
+            }
+            ClusterCmd::CountKeysInSlot(slot) => {
+                RespFrame::Integer(keys_in_slot(backend, slot).len() as i64)
+            }
+        }
+    }
+}
+
+// There is no cluster bus, the node is only asked for its id. It has to meet us on its own side.
+async fn meet(backend: &Backend, host: &str, port: u16) -> Result<String> {
+    let stream = TcpStream::connect((host, port)).await?;
+    let mut framed = Framed::new(stream, RespCodec::default());
+    let id = match request(&mut framed, &[b"CLUSTER", b"MYID"]).await? {
+        RespFrame::BulkString(id) => String::from_utf8(id.0.into())?,
+        reply => bail!("unexpected reply to CLUSTER MYID: {:?}", reply),
+    };
+    if id == backend.cluster.myid() {
+        bail!("this is myself");
+    }
+    backend.cluster.add_node(ClusterNode {
+        id: id.clone(),
+        host: host.to_string(),
+        port,
+    });
+    Ok(id)
+}
+
+fn set_slot(backend: &Backend, slot: u16, action: SlotAction) -> Result<()> {
+    let cluster = &backend.cluster;
+    let mine = cluster.owner(slot).is_none();
+    match action {
+        SlotAction::Migrating(id) => {
+            if !mine {
+                bail!("I'm not the owner of hash slot {}", slot);
+            }
+            if id == cluster.myid() {
+                bail!("I can't migrate hash slot {} to myself", slot);
+            }
+            cluster.set_migrating(slot, Some(&id))?;
+        }
+        SlotAction::Importing(id) => {
+            if mine {
+                bail!("I'm already the owner of hash slot {}", slot);
+            }
+            if id == cluster.myid() {
+                bail!("I can't import hash slot {} from myself", slot);
+            }
+            cluster.set_importing(slot, Some(&id))?;
+        }
+        SlotAction::Node(id) => {
+            if mine && id != cluster.myid() && !keys_in_slot(backend, slot).is_empty() {
+                bail!("Can't assign hashslot {} to a different node while I still hold keys for this hash slot.", slot);
+            }
+            cluster.set_owner(slot..=slot, Some(&id))?;
+        }
+        SlotAction::Stable => {
+            cluster.set_migrating(slot, None)?;
+            cluster.set_importing(slot, None)?;
+        }
+    }
+    Ok(())
+}
+
+fn keys_in_slot(backend: &Backend, slot: u16) -> Vec<Bytes> {
+    let mut keys = vec![];
+    backend.keyspace.for_each(|shard| {
+        keys.extend(
+            shard
+                .keys()
+                .filter(|key| key_hash_slot(key) == slot)
+                .cloned(),
+        )
+    });
+    keys.sort();
+    keys
+}
+
+impl CommandExecutor for Asking {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let backend = ctx.backend;
+        Ok(self.execute_in(ctx.session("ASKING")?, backend))
+    }
+}
+
+impl Asking {
+    fn execute_in(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        if !backend.config.cluster_enabled() {
+            return SimpleError::new(CLUSTER_DISABLED).into();
+        }
+        session.asking = true;
+        RESP_OK.clone()
+    }
+}
+
+impl TryFrom<RespArray> for Asking {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["asking"], 0)?;
+        Ok(Asking)
+    }
+}
+
+impl TryFrom<RespArray> for ClusterCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = Subcommand::parse(value, "cluster")?;
+        // only the key of KEYSLOT may be binary
+        let args: Vec<String> = sub
+            .args
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+
+        let slot = |slot: &String| match slot.parse::<u16>() {
+            Ok(slot) if slot < CLUSTER_SLOTS => Ok(slot),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid or out of range slot".to_string(),
+            )),
+        };
+        let invalid =
+            || CommandError::InvalidArgument("value is not an integer or out of range".to_string());
+        match (sub.name.as_str(), args.as_slice()) {
+            ("keyslot", [_]) => Ok(ClusterCmd::KeySlot(sub.args[0].clone())),
+            ("info", []) => Ok(ClusterCmd::Info),
+            ("myid", []) => Ok(ClusterCmd::MyId),
+            ("nodes", []) => Ok(ClusterCmd::Nodes),
+            ("slots", []) => Ok(ClusterCmd::Slots),
+            ("shards", []) => Ok(ClusterCmd::Shards),
+            ("meet", [host, port, ..]) if args.len() <= 3 => Ok(ClusterCmd::Meet(
+                host.clone(),
+                port.parse().map_err(|_| invalid())?,
+            )),
+            ("setslot", [n, action, id @ ..]) => {
+                let action = match (action.to_ascii_lowercase().as_str(), id) {
+                    ("importing", [id]) => SlotAction::Importing(id.clone()),
+                    ("migrating", [id]) => SlotAction::Migrating(id.clone()),
+                    ("node", [id]) => SlotAction::Node(id.clone()),
+                    ("stable", []) => SlotAction::Stable,
+                    _ => {
+                        return Err(CommandError::InvalidArgument(
+                            "Invalid CLUSTER SETSLOT action or number of arguments".to_string(),
+                        ))
+                    }
+                };
+                Ok(ClusterCmd::SetSlot(slot(n)?, action))
+            }
+            ("getkeysinslot", [n, count]) => {
+                let count = count.parse().map_err(|_| invalid())?;
+                Ok(ClusterCmd::GetKeysInSlot(slot(n)?, count))
+            }
+            ("countkeysinslot", [n]) => Ok(ClusterCmd::CountKeysInSlot(slot(n)?)),
+            (
+                "keyslot" | "info" | "myid" | "nodes" | "slots" | "shards" | "meet" | "setslot"
+                | "getkeysinslot" | "countkeysinslot",
+                _,
+            ) => Err(sub.wrong_arity()),
+            _ => Err(sub.unknown()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, RespDecoder, ServerConfig};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_cluster_keyslot() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$7\r\nCLUSTER\r\n$7\r\nkeyslot\r\n$7\r\nsomekey\r\n");
+        let cmd: ClusterCmd = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&Backend::new()))?,
+            RespFrame::Integer(11058)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_slots() {
+        let disabled = ClusterCmd::Slots
+            .execute(&mut ExecContext::new(&Backend::new()))
+            .unwrap();
+        assert!(matches!(disabled, RespFrame::Error(_)));
+
+        let backend = Backend::with_config(ServerConfig::new(Config {
+            cluster_enabled: true,
+            ..Default::default()
+        }));
+        let RespFrame::Array(ranges) = ClusterCmd::Slots
+            .execute(&mut ExecContext::new(&backend))
+            .unwrap()
+        else {
+            panic!("CLUSTER SLOTS must reply with an array");
+        };
+        let RespFrame::Array(range) = &ranges[0] else {
+            panic!("a slot range must be an array");
+        };
+        assert_eq!(range[0], RespFrame::Integer(0));
+        assert_eq!(range[1], RespFrame::Integer(16383));
+
+        // the slots served by another node are reported with it
+        backend.cluster.add_node(ClusterNode {
+            id: "b".repeat(40),
+            host: "127.0.0.1".to_string(),
+            port: 7001,
+        });
+        backend
+            .cluster
+            .set_owner(100..=199, Some(&"b".repeat(40)))
+            .unwrap();
+        let RespFrame::Array(ranges) = ClusterCmd::Slots
+            .execute(&mut ExecContext::new(&backend))
+            .unwrap()
+        else {
+            panic!("CLUSTER SLOTS must reply with an array");
+        };
+        assert_eq!(ranges.len(), 3);
+    }
+}
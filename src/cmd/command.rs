@@ -0,0 +1,131 @@
+use super::{
+    spec::{lookup, CommandSpec, COMMANDS},
+    CommandError, CommandExecutor, Commands, ExecContext, Subcommand,
+};
+use crate::{BulkString, RespArray, RespFrame, RespMap, SimpleString};
+
+impl CommandExecutor for Commands {
+    fn execute(self, _ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match self {
+            Commands::List => {
+                RespArray::new(COMMANDS.iter().map(command_info).collect::<Vec<_>>()).into()
+            }
+            Commands::Count => RespFrame::Integer(COMMANDS.len() as i64),
+            Commands::Info(names) => RespArray::new(
+                names
+                    .iter()
+                    .map(|name| match lookup(name) {
+                        Some(spec) => command_info(spec),
+                        None => RespFrame::null(),
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            Commands::Docs(names) => {
+                let mut docs = RespMap::new();
+                let specs: Vec<&CommandSpec> = match names.is_empty() {
+                    true => COMMANDS.iter().collect(),
+                    false => names.iter().filter_map(|name| lookup(name)).collect(),
+                };
+                for spec in specs {
+                    docs.insert(BulkString::from(spec.name), command_docs(spec));
+                }
+                docs.into()
+            }
+        })
+    }
+}
+
+// [name, arity, [flags], first key, last key, step, [acl categories]]
+fn command_info(spec: &CommandSpec) -> RespFrame {
+    let flags = spec
+        .flags
+        .iter()
+        .map(|flag| SimpleString::new(*flag).into())
+        .collect::<Vec<RespFrame>>();
+    let categories = spec
+        .categories()
+        .into_iter()
+        .map(|category| SimpleString::new(category).into())
+        .collect::<Vec<RespFrame>>();
+    RespArray::new([
+        BulkString::from(spec.name).into(),
+        RespFrame::Integer(spec.arity),
+        RespArray::new(flags).into(),
+        RespFrame::Integer(spec.first_key),
+        RespFrame::Integer(spec.last_key),
+        RespFrame::Integer(spec.step),
+        RespArray::new(categories).into(),
+    ])
+    .into()
+}
+
+fn command_docs(spec: &CommandSpec) -> RespFrame {
+    let mut docs = RespMap::new();
+    docs.insert(BulkString::from("summary"), BulkString::from(spec.summary));
+    docs.insert(BulkString::from("since"), BulkString::from(spec.since));
+    docs.insert(BulkString::from("group"), BulkString::from(spec.group));
+    docs.into()
+}
+
+impl TryFrom<RespArray> for Commands {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        // unlike the other containers, COMMAND runs without a subcommand
+        if value.len() == 1 {
+            return Ok(Commands::List);
+        }
+        let sub = Subcommand::parse(value, "command")?;
+        let args = sub.strings()?;
+        match (sub.name.as_str(), args.len()) {
+            ("count", 0) => Ok(Commands::Count),
+            ("info", _) => Ok(Commands::Info(args)),
+            ("docs", _) => Ok(Commands::Docs(args)),
+            ("count", _) => Err(sub.wrong_arity()),
+            _ => Err(sub.unknown()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, RespDecoder};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_command_info_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$7\r\nCOMMAND\r\n$4\r\nINFO\r\n$3\r\nget\r\n$3\r\nfoo\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: Commands = frame.try_into()?;
+        let backend = Backend::new();
+        match cmd.execute(&mut ExecContext::new(&backend))? {
+            RespFrame::Array(infos) => {
+                assert_eq!(infos.len(), 2);
+                assert_eq!(infos[1], RespFrame::null());
+                match &infos[0] {
+                    RespFrame::Array(info) => {
+                        assert_eq!(info[0], BulkString::from("get").into());
+                        assert_eq!(info[1], RespFrame::Integer(2));
+                    }
+                    frame => panic!("unexpected reply: {:?}", frame),
+                }
+            }
+            frame => panic!("unexpected reply: {:?}", frame),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_count() {
+        let backend = Backend::new();
+        assert_eq!(
+            Commands::Count
+                .execute(&mut ExecContext::new(&backend))
+                .unwrap(),
+            RespFrame::Integer(COMMANDS.len() as i64)
+        );
+    }
+}
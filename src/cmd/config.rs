@@ -0,0 +1,144 @@
+use super::{CommandError, CommandExecutor, ConfigCmd, ExecContext, Subcommand, RESP_OK};
+use crate::{Backend, BulkString, RespArray, RespFrame, SimpleError};
+use std::io;
+
+impl CommandExecutor for ConfigCmd {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match self {
+            ConfigCmd::Get(patterns) => {
+                let mut params = vec![];
+                for pattern in patterns {
+                    for param in ctx.backend.config.get(&pattern) {
+                        if !params.contains(&param) {
+                            params.push(param);
+                        }
+                    }
+                }
+                RespArray::new(
+                    params
+                        .into_iter()
+                        .flat_map(|(name, value)| {
+                            [
+                                BulkString::from(name).into(),
+                                BulkString::from(value).into(),
+                            ]
+                        })
+                        .collect::<Vec<RespFrame>>(),
+                )
+                .into()
+            }
+            ConfigCmd::Set(pairs) => match ctx.backend.config.set(&pairs) {
+                Ok(()) => match switch_aof(ctx.backend) {
+                    Ok(()) => RESP_OK.clone(),
+                    Err(e) => SimpleError::new(format!("ERR Starting the AOF: {}", e)).into(),
+                },
+                Err(e) => SimpleError::new(format!("ERR {}", e)).into(),
+            },
+            ConfigCmd::Rewrite => match ctx.backend.config.rewrite() {
+                Ok(()) => RESP_OK.clone(),
+                Err(e) => SimpleError::new(format!("ERR Rewriting config file: {}", e)).into(),
+            },
+        })
+    }
+}
+
+// Turning appendonly on writes the dataset to a new AOF, turning it off stops logging the writes.
+fn switch_aof(backend: &Backend) -> io::Result<()> {
+    let appendonly = backend.config.snapshot().appendonly;
+    if appendonly == backend.aof.enabled() {
+        return Ok(());
+    }
+    if !appendonly {
+        backend.aof.stop();
+        return Ok(());
+    }
+    let started = backend.aof.start(backend, true);
+    if started.is_err() {
+        let _ = backend
+            .config
+            .set(&[("appendonly".to_string(), "no".to_string())]);
+    }
+    started
+}
+
+impl TryFrom<RespArray> for ConfigCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = Subcommand::parse(value, "config")?;
+        let args = sub.strings()?;
+        match sub.name.as_str() {
+            "get" if !args.is_empty() => Ok(ConfigCmd::Get(args)),
+            "set" if !args.is_empty() && args.len() % 2 == 0 => {
+                let mut pairs = vec![];
+                let mut args = args.into_iter();
+                while let (Some(name), Some(value)) = (args.next(), args.next()) {
+                    pairs.push((name, value));
+                }
+                Ok(ConfigCmd::Set(pairs))
+            }
+            "rewrite" if args.is_empty() => Ok(ConfigCmd::Rewrite),
+            "get" | "set" | "rewrite" => Err(sub.wrong_arity()),
+            _ => Err(sub.unknown()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecoder;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_config_set_get() -> Result<()> {
+        let backend = Backend::new();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$6\r\nCONFIG\r\n$3\r\nSET\r\n$7\r\ntimeout\r\n$2\r\n30\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: ConfigCmd = frame.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RESP_OK.clone()
+        );
+
+        buf.extend_from_slice(b"*3\r\n$6\r\nconfig\r\n$3\r\nget\r\n$7\r\ntimeout\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: ConfigCmd = frame.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespArray::new([
+                BulkString::from("timeout").into(),
+                BulkString::from("30").into()
+            ])
+            .into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_set_invalid_value() {
+        let backend = Backend::new();
+        let cmd = ConfigCmd::Set(vec![("timeout".to_string(), "soon".to_string())]);
+        assert!(matches!(
+            cmd.execute(&mut ExecContext::new(&backend)).unwrap(),
+            RespFrame::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_config_rewrite_without_config_file() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$6\r\nconfig\r\n$7\r\nrewrite\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: ConfigCmd = frame.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&Backend::new()))?,
+            SimpleError::new(
+                "ERR Rewriting config file: The server is running without a config file"
+            )
+            .into()
+        );
+        Ok(())
+    }
+}
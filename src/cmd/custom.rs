@@ -0,0 +1,149 @@
+use super::{lookup, Command, CommandError, CommandExecutor, ExecContext};
+use crate::{Backend, RespFrame};
+use bytes::Bytes;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+// What a custom command runs, given the arguments after its name.
+pub type CommandHandler = dyn Fn(Vec<Bytes>, &Backend) -> RespFrame + Send + Sync;
+
+// The commands of the application the server is embedded in, like MYAPP.RATELIMIT, see
+// `BackendBuilder::commands`. They are looked up after the built-in ones, which they can't
+// replace. They have no spec, so COMMAND doesn't list them, and the ACL allows them with the
+// @custom category.
+#[derive(Default)]
+pub struct CommandRegistry {
+    handlers: RwLock<HashMap<String, Arc<CommandHandler>>>,
+}
+
+impl fmt::Debug for CommandRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let handlers = self.handlers.read().unwrap_or_else(|e| e.into_inner());
+        f.debug_set().entries(handlers.keys()).finish()
+    }
+}
+
+impl CommandRegistry {
+    // Adds a command, or replaces the handler of a custom command of the same name. Names are
+    // case insensitive.
+    pub fn register<F>(&self, name: &str, handler: F) -> Result<(), CommandError>
+    where
+        F: Fn(Vec<Bytes>, &Backend) -> RespFrame + Send + Sync + 'static,
+    {
+        if lookup(name).is_some() {
+            return Err(CommandError::InvalidCommand(format!(
+                "'{}' is a built-in command",
+                name
+            )));
+        }
+        self.handlers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.to_ascii_lowercase(), Arc::new(handler));
+        Ok(())
+    }
+
+    // whether there was such a command
+    pub fn unregister(&self, name: &str) -> bool {
+        self.handlers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&name.to_ascii_lowercase())
+            .is_some()
+    }
+
+    // `name` is lowercase, like command_name
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        let handlers = self.handlers.read().unwrap_or_else(|e| e.into_inner());
+        handlers.contains_key(name)
+    }
+
+    // Command::try_from takes the custom commands for unknown ones, they are resolved here.
+    pub(crate) fn resolve(&self, cmd: Command) -> Command {
+        let Command::Unrecognized(cmd) = cmd else {
+            return cmd;
+        };
+        let name = String::from_utf8_lossy(&cmd.name).to_ascii_lowercase();
+        let handlers = self.handlers.read().unwrap_or_else(|e| e.into_inner());
+        match handlers.get(&name) {
+            Some(handler) => CustomCommand {
+                name,
+                args: cmd.args,
+                handler: handler.clone(),
+            }
+            .into(),
+            None => cmd.into(),
+        }
+    }
+}
+
+pub struct CustomCommand {
+    pub(crate) name: String,
+    pub(crate) args: Vec<Bytes>,
+    pub(crate) handler: Arc<CommandHandler>,
+}
+
+impl fmt::Debug for CustomCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomCommand")
+            .field("name", &self.name)
+            .field("args", &self.args)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CommandExecutor for CustomCommand {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok((self.handler)(self.args, ctx.backend))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::Command, BulkString, RespArray};
+    use anyhow::Result;
+
+    #[test]
+    fn test_custom_commands_are_dispatched() -> Result<()> {
+        let commands = CommandRegistry::default();
+        commands.register("myapp.ratelimit", |args, backend| {
+            let key = args.first().cloned().unwrap_or_default();
+            RespFrame::Integer(args.len() as i64 + backend.exists(&key) as i64)
+        })?;
+        assert!(commands.register("GET", |_, _| RespFrame::null()).is_err());
+
+        let backend = Backend::builder().commands(commands).build();
+        let request = RespArray::new([
+            BulkString::from("MyApp.RateLimit").into(),
+            BulkString::from("user:1").into(),
+            BulkString::from("10").into(),
+        ]);
+        let cmd = backend
+            .commands()
+            .resolve(Command::try_from(request.clone())?);
+        assert!(matches!(cmd, Command::Custom(_)));
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(2)
+        );
+        // the commands belong to the backend they were given to
+        let other = Backend::new();
+        assert!(matches!(
+            other
+                .commands()
+                .resolve(Command::try_from(request.clone())?),
+            Command::Unrecognized(_)
+        ));
+
+        assert!(backend.commands().unregister("myapp.ratelimit"));
+        assert!(matches!(
+            backend.commands().resolve(Command::try_from(request)?),
+            Command::Unrecognized(_)
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,302 @@
+use super::{CommandError, CommandExecutor, DebugCmd, ExecContext, ObjectCmd, Subcommand, RESP_OK};
+use crate::{
+    glob_match, load_snapshot, persistence, Backend, BulkString, RespArray, RespEncoder, RespFrame,
+    SimpleError, SimpleString,
+};
+use std::{sync::atomic::Ordering, thread, time::Duration};
+
+// keys reported by DEBUG HOTKEYS without a count, like redis-cli --hotkeys
+const HOTKEYS_COUNT: usize = 16;
+
+impl CommandExecutor for DebugCmd {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match self {
+            DebugCmd::Sleep(duration) => {
+                // blocks the worker on purpose, like a slow command would
+                thread::sleep(duration);
+                RESP_OK.clone()
+            }
+            DebugCmd::Object(key) => match object_info(ctx.backend, &key) {
+                Some((kind, encoding, len)) => {
+                    let idle = ctx.backend.access.get(&key).map_or(0, |access| {
+                        ctx.backend.now_ms().saturating_sub(access.last) / 1000
+                    });
+                    SimpleString::new(format!(
+                        "Value at:0x0 refcount:1 encoding:{} serializedlength:{} lru:0 lru_seconds_idle:{} type:{}",
+                        encoding, len, idle, kind
+                    ))
+                    .into()
+                }
+                None => SimpleError::new("ERR no such key").into(),
+            },
+            DebugCmd::SetActiveExpire(on) => {
+                ctx.backend.active_expire.store(on, Ordering::Relaxed);
+                RESP_OK.clone()
+            }
+            DebugCmd::SetHotKeys(on) => {
+                ctx.backend.hotkeys.store(on, Ordering::Relaxed);
+                RESP_OK.clone()
+            }
+            DebugCmd::StringMatchLen => {
+                stringmatch_fuzz();
+                RESP_OK.clone()
+            }
+            // saves the dataset and loads it back, runs with the exec lock held for writing
+            DebugCmd::Reload => {
+                match persistence::save(ctx.backend).and_then(|_| load_snapshot(ctx.backend)) {
+                    Ok(_) => RESP_OK.clone(),
+                    Err(e) => {
+                        SimpleError::new(format!("ERR Error trying to load the RDB dump: {}", e))
+                            .into()
+                    }
+                }
+            }
+            DebugCmd::HotKeys(count) => {
+                let hot = ctx.backend.hotkeys(count).into_iter().map(|(key, hits)| {
+                    RespArray::new(vec![
+                        BulkString::new(key.to_vec()).into(),
+                        RespFrame::Integer(hits as i64),
+                    ])
+                    .into()
+                });
+                RespArray::new(hot.collect::<Vec<_>>()).into()
+            }
+        })
+    }
+}
+
+impl CommandExecutor for ObjectCmd {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match self {
+            ObjectCmd::Encoding(key) => match object_info(ctx.backend, &key) {
+                Some((_, encoding, _)) => BulkString::from(encoding).into(),
+                None => RespFrame::null(),
+            },
+        })
+    }
+}
+
+// (type, encoding, serialized length) of the value of a key
+fn object_info(backend: &Backend, key: &[u8]) -> Option<(&'static str, &'static str, usize)> {
+    backend.expire_if_needed(key);
+    let shard = backend.keyspace.read(key);
+    if let Some(value) = shard.map.get(key) {
+        let len = match value {
+            RespFrame::BulkString(s) => s.len(),
+            frame => frame.encoded_len(),
+        };
+        let encoding = match value {
+            RespFrame::BulkString(s)
+                if std::str::from_utf8(s).is_ok_and(|s| s.parse::<i64>().is_ok()) =>
+            {
+                "int"
+            }
+            _ if len <= 44 => "embstr",
+            _ => "raw",
+        };
+        return Some(("string", encoding, len));
+    }
+    if let Some(hash) = shard.hmap.get(key) {
+        let len = hash
+            .iter()
+            .map(|(field, value)| field.len() + value.encoded_len())
+            .sum();
+        return Some(("hash", hash.encoding(), len));
+    }
+    if let Some(set) = shard.hset.get(key) {
+        return Some(("set", set.encoding(), set.iter().map(|m| m.len()).sum()));
+    }
+    None
+}
+
+// Feed the glob matcher with random patterns, it must neither panic nor hang.
+fn stringmatch_fuzz() {
+    const ALPHABET: &[u8] = b"*?[]^-\\ab";
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move |n: usize| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) as usize % n
+    };
+    for _ in 0..1000 {
+        let pattern = (0..next(32))
+            .map(|_| ALPHABET[next(ALPHABET.len())])
+            .collect::<Vec<_>>();
+        let string = (0..next(32))
+            .map(|_| ALPHABET[next(ALPHABET.len())])
+            .collect::<Vec<_>>();
+        glob_match(&pattern, &string, next(2) == 0);
+    }
+}
+
+impl TryFrom<RespArray> for DebugCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = Subcommand::parse(value, "debug")?;
+        // only the key of OBJECT may be binary
+        let args: Vec<String> = sub
+            .args
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+
+        match (sub.name.as_str(), args.as_slice()) {
+            ("sleep", [seconds]) => match seconds.parse::<f64>() {
+                Ok(seconds) if seconds >= 0.0 && seconds.is_finite() => {
+                    Ok(DebugCmd::Sleep(Duration::from_secs_f64(seconds)))
+                }
+                _ => Err(CommandError::InvalidArgument(
+                    "value is not a valid float".to_string(),
+                )),
+            },
+            ("object", [_]) => Ok(DebugCmd::Object(sub.args[0].clone())),
+            ("set-active-expire", [on]) => match on.as_str() {
+                "0" => Ok(DebugCmd::SetActiveExpire(false)),
+                "1" => Ok(DebugCmd::SetActiveExpire(true)),
+                _ => Err(CommandError::InvalidArgument(
+                    "value is out of range, must be 0 or 1".to_string(),
+                )),
+            },
+            ("set-hotkeys", [on]) => match on.as_str() {
+                "0" => Ok(DebugCmd::SetHotKeys(false)),
+                "1" => Ok(DebugCmd::SetHotKeys(true)),
+                _ => Err(CommandError::InvalidArgument(
+                    "value is out of range, must be 0 or 1".to_string(),
+                )),
+            },
+            ("stringmatch-len", []) => Ok(DebugCmd::StringMatchLen),
+            ("reload", []) => Ok(DebugCmd::Reload),
+            ("hotkeys", []) => Ok(DebugCmd::HotKeys(HOTKEYS_COUNT)),
+            ("hotkeys", [count]) => match count.parse::<usize>() {
+                Ok(count) => Ok(DebugCmd::HotKeys(count)),
+                Err(_) => Err(CommandError::InvalidArgument(
+                    "value is out of range, must be positive".to_string(),
+                )),
+            },
+            (
+                "sleep" | "object" | "set-active-expire" | "stringmatch-len" | "reload" | "hotkeys"
+                | "set-hotkeys",
+                _,
+            ) => Err(sub.wrong_arity()),
+            _ => Err(sub.unknown()),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for ObjectCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = Subcommand::parse(value, "object")?;
+        match (sub.name.as_str(), sub.args.as_slice()) {
+            ("encoding", [key]) => Ok(ObjectCmd::Encoding(key.clone())),
+            ("encoding", _) => Err(sub.wrong_arity()),
+            _ => Err(sub.unknown()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecoder;
+    use anyhow::Result;
+    use bytes::{Bytes, BytesMut};
+
+    #[test]
+    fn test_debug_sleep_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$5\r\nDEBUG\r\n$5\r\nsleep\r\n$3\r\n0.5\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: DebugCmd = frame.try_into()?;
+        assert!(matches!(result, DebugCmd::Sleep(d) if d == Duration::from_millis(500)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_object_command() {
+        let backend = Backend::new();
+        backend.set("n".into(), BulkString::new("12345").into());
+        let result = DebugCmd::Object("n".into())
+            .execute(&mut ExecContext::new(&backend))
+            .unwrap();
+        assert_eq!(
+            result,
+            SimpleString::new("Value at:0x0 refcount:1 encoding:int serializedlength:5 lru:0 lru_seconds_idle:0 type:string").into()
+        );
+        let result = DebugCmd::Object("missing".into())
+            .execute(&mut ExecContext::new(&backend))
+            .unwrap();
+        assert_eq!(result, SimpleError::new("ERR no such key").into());
+    }
+
+    #[test]
+    fn test_debug_hotkeys() -> Result<()> {
+        let backend = Backend::new();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$5\r\nDEBUG\r\n$11\r\nSET-HOTKEYS\r\n$1\r\n1\r\n");
+        let cmd: DebugCmd = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RESP_OK.clone()
+        );
+        backend.set("k".into(), BulkString::new("v").into());
+        for _ in 0..1000 {
+            backend.get(b"k");
+        }
+        buf.extend_from_slice(b"*3\r\n$5\r\nDEBUG\r\n$7\r\nHOTKEYS\r\n$1\r\n5\r\n");
+        let cmd: DebugCmd = RespArray::decode(&mut buf)?.try_into()?;
+        assert!(matches!(cmd, DebugCmd::HotKeys(5)));
+        let RespFrame::Array(hot) = cmd.execute(&mut ExecContext::new(&backend))? else {
+            panic!("DEBUG HOTKEYS replies an array");
+        };
+        assert_eq!(hot.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_encoding() -> Result<()> {
+        let backend = Backend::new();
+        backend.sadd("s", "1").unwrap();
+        backend
+            .hset("h".into(), "f".into(), BulkString::new("v").into())
+            .unwrap();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nOBJECT\r\n$8\r\nENCODING\r\n$1\r\ns\r\n");
+        let cmd: ObjectCmd = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            BulkString::from("intset").into()
+        );
+
+        backend.sadd("s", "a").unwrap();
+        let encoding = |key: &str| ObjectCmd::Encoding(Bytes::copy_from_slice(key.as_bytes()));
+        assert_eq!(
+            encoding("s").execute(&mut ExecContext::new(&backend))?,
+            BulkString::from("listpack").into()
+        );
+        assert_eq!(
+            encoding("h").execute(&mut ExecContext::new(&backend))?,
+            BulkString::from("listpack").into()
+        );
+        backend
+            .hset(
+                "h".into(),
+                "g".into(),
+                BulkString::new("x".repeat(65)).into(),
+            )
+            .unwrap();
+        assert_eq!(
+            encoding("h").execute(&mut ExecContext::new(&backend))?,
+            BulkString::from("hashtable").into()
+        );
+
+        // SET replaces the set
+        backend.set("s".into(), BulkString::new("x").into());
+        assert_eq!(
+            encoding("s").execute(&mut ExecContext::new(&backend))?,
+            BulkString::from("embstr").into()
+        );
+        Ok(())
+    }
+}
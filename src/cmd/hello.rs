@@ -0,0 +1,135 @@
+use super::{
+    auth, client, extract_args, Client, CommandError, CommandExecutor, ExecContext, Hello,
+};
+use crate::{network::Session, Backend, BulkString, RespArray, RespFrame, RespMap, SimpleError};
+
+impl CommandExecutor for Hello {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let backend = ctx.backend;
+        Ok(self.execute_in(ctx.session("HELLO")?, backend))
+    }
+}
+
+impl Hello {
+    fn execute_in(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        if let Some(protover) = self.protover {
+            if !(2..=3).contains(&protover) {
+                return SimpleError::new("NOPROTO unsupported protocol version").into();
+            }
+        }
+        match self.auth {
+            Some((username, password)) => match auth::check(Some(&username), &password, backend) {
+                Ok(username) => session.login(username, backend),
+                Err(e) => return e.into(),
+            },
+            None if !session.authenticated => {
+                return SimpleError::new("NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time").into();
+            }
+            None => {}
+        }
+        if let Some(name) = self.setname {
+            Client::SetName(name).execute_in(session, backend);
+        }
+        if let Some(protover) = self.protover {
+            session.resp = protover;
+            backend
+                .clients
+                .update(session.id, |client| client.resp = protover);
+        }
+
+        let mut map = RespMap::new();
+        map.insert(BulkString::from("server"), BulkString::from("redis"));
+        map.insert(
+            BulkString::from("version"),
+            BulkString::from(env!("CARGO_PKG_VERSION")),
+        );
+        map.insert(
+            BulkString::from("proto"),
+            RespFrame::Integer(session.resp as i64),
+        );
+        map.insert(
+            BulkString::from("id"),
+            RespFrame::Integer(session.id as i64),
+        );
+        map.insert(BulkString::from("mode"), BulkString::from("standalone"));
+        map.insert(BulkString::from("role"), BulkString::from("master"));
+        map.insert(BulkString::from("modules"), RespArray::new(vec![]));
+        // RESP2 has no map type, the pairs are flattened into an array when encoded
+        map.into()
+    }
+}
+
+impl TryFrom<RespArray> for Hello {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter().map(|arg| match arg {
+            RespFrame::BulkString(arg) => Ok(String::from_utf8(arg.0.into())?),
+            _ => Err(CommandError::InvalidArgument(
+                "hello arguments must be BulkString".to_string(),
+            )),
+        });
+
+        let mut hello = Hello {
+            protover: None,
+            auth: None,
+            setname: None,
+        };
+        let Some(protover) = args.next().transpose()? else {
+            return Ok(hello);
+        };
+        hello.protover = Some(protover.parse().map_err(|_| {
+            CommandError::InvalidArgument(
+                "Protocol version is not an integer or out of range".to_string(),
+            )
+        })?);
+        while let Some(option) = args.next().transpose()? {
+            match option.to_ascii_lowercase().as_str() {
+                "auth" => match (args.next().transpose()?, args.next().transpose()?) {
+                    (Some(username), Some(password)) => hello.auth = Some((username, password)),
+                    _ => {
+                        return Err(CommandError::InvalidArgument(
+                            "Syntax error in HELLO option 'auth'".to_string(),
+                        ))
+                    }
+                },
+                "setname" => match args.next().transpose()? {
+                    Some(name) => {
+                        client::validate_name(&name)?;
+                        hello.setname = Some(name);
+                    }
+                    None => {
+                        return Err(CommandError::InvalidArgument(
+                            "Syntax error in HELLO option 'setname'".to_string(),
+                        ))
+                    }
+                },
+                option => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Syntax error in HELLO option '{option}'"
+                    )))
+                }
+            }
+        }
+        Ok(hello)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecoder;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_hello_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*7\r\n$5\r\nHELLO\r\n$1\r\n3\r\n$4\r\nAUTH\r\n$7\r\ndefault\r\n$2\r\npw\r\n$7\r\nSETNAME\r\n$3\r\nweb\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Hello = frame.try_into()?;
+        assert_eq!(result.protover, Some(3));
+        assert_eq!(result.auth, Some(("default".to_string(), "pw".to_string())));
+        assert_eq!(result.setname.as_deref(), Some("web"));
+        Ok(())
+    }
+}
@@ -1,28 +1,35 @@
 use super::{
-    extract_args, validate_command, CommandError, CommandExecutor, HGet, HGetAll, HMGet, HSet,
-    RESP_OK,
+    extract_args, validate_command, CommandError, CommandExecutor, ExecContext, HGet, HGetAll,
+    HMGet, HSet, RESP_OK,
 };
-use crate::{BulkString, RespArray, RespFrame};
+use crate::{BulkString, KeyType, RespArray, RespFrame};
 
 impl CommandExecutor for HGet {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        match backend.hget(&self.key, &self.field) {
-            Some(value) => value,
-            None => RespFrame::Null(crate::RespNull),
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        if let Err(e) = ctx.backend.check_type(&self.key, KeyType::Hash) {
+            return Ok(e.into());
         }
+        Ok(match ctx.backend.hget(&self.key, &self.field) {
+            Some(value) => value,
+            None => RespFrame::null(),
+        })
     }
 }
 
 impl CommandExecutor for HGetAll {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        let hmap = backend.hmap.get(&self.key);
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        if let Err(e) = ctx.backend.check_type(&self.key, KeyType::Hash) {
+            return Ok(e.into());
+        }
+        let shard = ctx.backend.keyspace.read(&self.key);
+        let hmap = shard.hmap.get(&self.key);
+        ctx.backend.stats.keyspace_lookup(hmap.is_some());
 
-        match hmap {
+        Ok(match hmap {
             Some(hmap) => {
                 let mut data = Vec::with_capacity(hmap.len() * 2);
-                for v in hmap.iter() {
-                    let key = v.key().to_owned();
-                    data.push((key, v.value().clone()));
+                for (key, value) in hmap.iter() {
+                    data.push((key.to_owned(), value.clone()));
                 }
                 if self.sort {
                     data.sort_by(|a, b| a.0.cmp(&b.0));
@@ -35,28 +42,33 @@ impl CommandExecutor for HGetAll {
                 RespArray::new(ret).into()
             }
             None => RespArray::new([]).into(),
-        }
+        })
     }
 }
 
 impl CommandExecutor for HMGet {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        if let Err(e) = ctx.backend.check_type(&self.hash, KeyType::Hash) {
+            return Ok(e.into());
+        }
         let fields = self
             .fields
             .iter()
-            .map(|f| match backend.hget(&self.hash, f) {
+            .map(|f| match ctx.backend.hget(&self.hash, f) {
                 Some(value) => value,
-                None => RespFrame::Null(crate::RespNull),
+                None => RespFrame::null(),
             })
             .collect::<Vec<_>>();
-        RespFrame::Array(RespArray(fields))
+        Ok(RespFrame::Array(RespArray(fields)))
     }
 }
 
 impl CommandExecutor for HSet {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        backend.hset(self.key, self.field, self.value);
-        RESP_OK.clone()
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match ctx.backend.hset(self.key, self.field, self.value) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        })
     }
 }
 
@@ -68,8 +80,8 @@ impl TryFrom<RespArray> for HGet {
         let mut args = extract_args(value, 1)?.into_iter();
         match (args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field))) => Ok(HGet {
-                key: String::from_utf8(key.0)?,
-                field: String::from_utf8(field.0)?,
+                key: key.0,
+                field: field.0,
             }),
             _ => Err(CommandError::InvalidArgument(
                 "Invalid key or field".to_string(),
@@ -86,7 +98,7 @@ impl TryFrom<RespArray> for HGetAll {
         let mut args = extract_args(value, 1)?.into_iter();
         match args.next() {
             Some(RespFrame::BulkString(key)) => Ok(HGetAll {
-                key: String::from_utf8(key.0)?,
+                key: key.0,
                 sort: false,
             }),
             _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
@@ -114,13 +126,13 @@ impl TryFrom<RespArray> for HMGet {
 
         let mut args = extract_args(value, 1)?.into_iter();
         let hash = match args.next() {
-            Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+            Some(RespFrame::BulkString(key)) => key.0,
             _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
         };
         let mut fields = vec![];
         loop {
             match args.next() {
-                Some(RespFrame::BulkString(key)) => fields.push(String::from_utf8(key.0)?),
+                Some(RespFrame::BulkString(key)) => fields.push(key.0),
                 None => break,
                 _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
             };
@@ -138,8 +150,8 @@ impl TryFrom<RespArray> for HSet {
         match (args.next(), args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field)), Some(value)) => {
                 Ok(HSet {
-                    key: String::from_utf8(key.0)?,
-                    field: String::from_utf8(field.0)?,
+                    key: key.0,
+                    field: field.0,
                     value,
                 })
             }
@@ -194,32 +206,32 @@ mod tests {
     fn test_hset_hget_hgetall_commands() -> Result<()> {
         let backend = crate::Backend::new();
         let cmd = HSet {
-            key: "map".to_string(),
-            field: "hello".to_string(),
+            key: "map".into(),
+            field: "hello".into(),
             value: RespFrame::BulkString(b"world".into()),
         };
-        let result = cmd.execute(&backend);
+        let result = cmd.execute(&mut ExecContext::new(&backend))?;
         assert_eq!(result, RESP_OK.clone());
 
         let cmd = HSet {
-            key: "map".to_string(),
-            field: "hello1".to_string(),
+            key: "map".into(),
+            field: "hello1".into(),
             value: RespFrame::BulkString(b"world1".into()),
         };
-        cmd.execute(&backend);
+        cmd.execute(&mut ExecContext::new(&backend))?;
 
         let cmd = HGet {
-            key: "map".to_string(),
-            field: "hello".to_string(),
+            key: "map".into(),
+            field: "hello".into(),
         };
-        let result = cmd.execute(&backend);
+        let result = cmd.execute(&mut ExecContext::new(&backend))?;
         assert_eq!(result, RespFrame::BulkString(b"world".into()));
 
         let cmd = HGetAll {
-            key: "map".to_string(),
+            key: "map".into(),
             sort: true,
         };
-        let result = cmd.execute(&backend);
+        let result = cmd.execute(&mut ExecContext::new(&backend))?;
 
         let expected = RespArray::new([
             BulkString::from("hello").into(),
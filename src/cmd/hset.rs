@@ -1,21 +1,44 @@
-use super::{extract_args, validate_command, CommandError, CommandExecutor, SAdd, SIsMember};
-use crate::{RespArray, RespFrame};
+use super::{
+    extract_args, validate_command, CommandError, CommandExecutor, ExecContext, SAdd, SIsMember,
+    SMove,
+};
+use crate::{KeyType, RespArray, RespFrame};
 
 impl CommandExecutor for SAdd {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        let response = self
-            .members
-            .into_iter()
-            .map(|f| backend.sadd(self.key.clone(), f))
-            .map(|b| RespFrame::Integer(b as i64))
-            .collect();
-        RespFrame::Array(RespArray(response))
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let mut response = Vec::with_capacity(self.members.len());
+        for member in self.members {
+            match ctx.backend.sadd(self.key.clone(), member) {
+                Ok(inserted) => response.push(RespFrame::Integer(inserted as i64)),
+                Err(e) => return Ok(e.into()),
+            }
+        }
+        Ok(RespFrame::Array(RespArray(response)))
     }
 }
 
 impl CommandExecutor for SIsMember {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        RespFrame::Integer(backend.sismember(&self.key, &self.member) as i64)
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        if let Err(e) = ctx.backend.check_type(&self.key, KeyType::Set) {
+            return Ok(e.into());
+        }
+        Ok(RespFrame::Integer(
+            ctx.backend.sismember(&self.key, &self.member) as i64,
+        ))
+    }
+}
+
+impl CommandExecutor for SMove {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(
+            match ctx
+                .backend
+                .smove(&self.source, self.destination, self.member)
+            {
+                Ok(moved) => RespFrame::Integer(moved as i64),
+                Err(e) => e.into(),
+            },
+        )
     }
 }
 
@@ -40,13 +63,13 @@ impl TryFrom<RespArray> for SAdd {
 
         let mut args = extract_args(value, 1)?.into_iter();
         let key = match args.next() {
-            Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+            Some(RespFrame::BulkString(key)) => key.0,
             _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
         };
         let mut members = vec![];
         loop {
             match args.next() {
-                Some(RespFrame::BulkString(key)) => members.push(String::from_utf8(key.0)?),
+                Some(RespFrame::BulkString(key)) => members.push(key.0),
                 None => break,
                 _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
             };
@@ -65,8 +88,8 @@ impl TryFrom<RespArray> for SIsMember {
         match (args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(member))) => {
                 Ok(SIsMember {
-                    key: String::from_utf8(key.0)?,
-                    member: String::from_utf8(member.0)?,
+                    key: key.0,
+                    member: member.0,
                 })
             }
             _ => Err(CommandError::InvalidArgument(
@@ -76,9 +99,33 @@ impl TryFrom<RespArray> for SIsMember {
     }
 }
 
+impl TryFrom<RespArray> for SMove {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["smove"], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next(), args.next()) {
+            (
+                Some(RespFrame::BulkString(source)),
+                Some(RespFrame::BulkString(destination)),
+                Some(RespFrame::BulkString(member)),
+            ) => Ok(SMove {
+                source: source.0,
+                destination: destination.0,
+                member: member.0,
+            }),
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid key or member".to_string(),
+            )),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::RespDecoder;
+    use crate::{BulkString, RespDecoder};
 
     use super::*;
     use anyhow::Result;
@@ -109,6 +156,63 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_smove() -> Result<()> {
+        let backend = crate::Backend::new();
+        backend.sadd("src", "one").unwrap();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$5\r\nSMOVE\r\n$3\r\nsrc\r\n$3\r\ndst\r\n$3\r\none\r\n");
+        let cmd: SMove = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(1)
+        );
+        assert!(backend.sismember(b"dst", b"one"));
+        // the emptied source set is deleted
+        assert!(!backend.exists(b"src"));
+
+        let cmd = SMove {
+            source: "src".into(),
+            destination: "dst".into(),
+            member: "one".into(),
+        };
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_type() -> Result<()> {
+        let backend = crate::Backend::new();
+        backend.sadd("s", "a").unwrap();
+        backend.set("s".into(), BulkString::new("x").into());
+        let sismember = SIsMember {
+            key: "s".into(),
+            member: "a".into(),
+        };
+        let wrong_type: RespFrame = crate::WrongType.into();
+        assert_eq!(
+            sismember.execute(&mut ExecContext::new(&backend))?,
+            wrong_type
+        );
+        let sadd = SAdd {
+            key: "s".into(),
+            members: vec!["b".into()],
+        };
+        assert_eq!(sadd.execute(&mut ExecContext::new(&backend))?, wrong_type);
+        let smove = SMove {
+            source: "t".into(),
+            destination: "s".into(),
+            member: "a".into(),
+        };
+        backend.sadd("t", "a").unwrap();
+        assert_eq!(smove.execute(&mut ExecContext::new(&backend))?, wrong_type);
+        assert_eq!(backend.get(b"s"), Some(BulkString::new("x").into()));
+        Ok(())
+    }
+
     #[test]
     fn test_sismember_from_resp() -> Result<()> {
         let mut buf = BytesMut::new();
@@ -0,0 +1,388 @@
+use super::{
+    extract_args, CommandError, CommandExecutor, ExecContext, Info, MemoryCmd, Subcommand,
+};
+use crate::{backend::KEY_OVERHEAD, Backend, BulkString, RespArray, RespFrame, RespMap};
+use std::fmt::Write;
+
+// like in redis, these are only rendered when asked for, or for all
+const NOT_DEFAULT: &[&str] = &["commandstats", "latencystats"];
+
+// the percentiles of the latency reported by latencystats
+const LATENCY_PERCENTILES: &[f64] = &[50.0, 99.0, 99.9];
+
+const SECTIONS: &[&str] = &[
+    "server",
+    "clients",
+    "memory",
+    "persistence",
+    "stats",
+    "replication",
+    "commandstats",
+    "latencystats",
+    "cluster",
+    "keyspace",
+];
+
+impl CommandExecutor for Info {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let default = self.sections.is_empty() || self.sections.iter().any(|s| s == "default");
+        let all = self
+            .sections
+            .iter()
+            .any(|s| matches!(s.as_str(), "all" | "everything"));
+
+        let mut info = String::new();
+        for section in SECTIONS {
+            if all
+                || (default && !NOT_DEFAULT.contains(section))
+                || self.sections.iter().any(|s| s == section)
+            {
+                if !info.is_empty() {
+                    info.push_str("\r\n");
+                }
+                render_section(&mut info, section, ctx.backend);
+            }
+        }
+        Ok(BulkString::from(info).into())
+    }
+}
+
+fn render_section(info: &mut String, section: &str, backend: &Backend) {
+    let stats = &backend.stats;
+    // writing into a String never fails
+    let _ = match section {
+        "server" => {
+            let uptime = stats.uptime_secs();
+            write!(
+                info,
+                "# Server\r\nredis_version:{}\r\nredis_mode:standalone\r\nos:{} {}\r\nprocess_id:{}\r\nuptime_in_seconds:{}\r\nuptime_in_days:{}\r\n",
+                env!("CARGO_PKG_VERSION"),
+                std::env::consts::OS,
+                std::env::consts::ARCH,
+                std::process::id(),
+                uptime,
+                uptime / 86400,
+            )
+        }
+        "clients" => write!(
+            info,
+            "# Clients\r\nconnected_clients:{}\r\nblocked_clients:{}\r\n",
+            stats.connected_clients(),
+            backend.blocked.len()
+        ),
+        "memory" => {
+            let (maxmemory, policy) = backend.config.maxmemory();
+            let usage = backend.memory_usage();
+            let overhead = backend.dbsize() as u64 * KEY_OVERHEAD;
+            write!(
+                info,
+                "# Memory\r\nused_memory:{}\r\nused_memory_rss:{}\r\nused_memory_peak:{}\r\nused_memory_overhead:{}\r\nused_memory_dataset:{}\r\nused_memory_strings:{}\r\nused_memory_sets:{}\r\nused_memory_hashes:{}\r\nmaxmemory:{}\r\nmaxmemory_policy:{}\r\n",
+                usage.total(),
+                resident_memory().unwrap_or(0),
+                backend.peak_memory(),
+                overhead,
+                usage.total().saturating_sub(overhead),
+                usage.strings,
+                usage.sets,
+                usage.hashes,
+                maxmemory,
+                policy,
+            )
+        }
+        "persistence" => {
+            let persistence = &backend.persistence;
+            let (aof_size, aof_base_size) = backend.aof.sizes();
+            write!(
+                info,
+                "# Persistence\r\nrdb_changes_since_last_save:{}\r\nrdb_bgsave_in_progress:{}\r\nrdb_last_save_time:{}\r\nrdb_last_bgsave_status:{}\r\naof_enabled:{}\r\naof_rewrite_in_progress:{}\r\naof_last_bgrewrite_status:{}\r\naof_last_write_status:{}\r\naof_current_size:{}\r\naof_base_size:{}\r\n",
+                persistence.dirty(),
+                persistence.bgsave_in_progress() as u8,
+                persistence.last_save(),
+                if persistence.last_bgsave_ok() { "ok" } else { "err" },
+                backend.aof.enabled() as u8,
+                backend.aof.rewrite_in_progress() as u8,
+                if backend.aof.last_rewrite_ok() { "ok" } else { "err" },
+                if backend.aof.last_write_ok() { "ok" } else { "err" },
+                aof_size,
+                aof_base_size,
+            )
+        }
+        "stats" => write!(
+            info,
+            "# Stats\r\ntotal_connections_received:{}\r\ntotal_commands_processed:{}\r\nrejected_connections:{}\r\nkeyspace_hits:{}\r\nkeyspace_misses:{}\r\nexpired_keys:{}\r\nevicted_keys:{}\r\n",
+            stats.connections_received(),
+            stats.commands_processed(),
+            stats.rejected_connections(),
+            stats.keyspace_hits(),
+            stats.keyspace_misses(),
+            stats.expired_keys(),
+            stats.evicted_keys(),
+        ),
+        "replication" => {
+            let replication = &backend.replication;
+            let _ = match replication.master() {
+                Some((host, port)) => write!(
+                    info,
+                    "# Replication\r\nrole:slave\r\nmaster_host:{}\r\nmaster_port:{}\r\nmaster_link_status:{}\r\nslave_repl_offset:{}\r\n",
+                    host,
+                    port,
+                    if replication.link_up() { "up" } else { "down" },
+                    replication.offset(),
+                ),
+                None => write!(info, "# Replication\r\nrole:master\r\n"),
+            };
+            let replicas = replication.replicas();
+            let _ = write!(info, "connected_slaves:{}\r\n", replicas.len());
+            for (i, replica) in replicas.iter().enumerate() {
+                let _ = write!(
+                    info,
+                    "slave{}:ip={},port={},state=online,offset={},lag={}\r\n",
+                    i,
+                    replica.ip,
+                    replica.port,
+                    replica.ack_offset,
+                    replica.ack_time.elapsed().as_secs()
+                );
+            }
+            let (replid2, second_replid_offset) = replication.replid2();
+            let backlog = replication.backlog();
+            let (first_byte, histlen) = backlog.unwrap_or_default();
+            write!(
+                info,
+                "master_replid:{}\r\nmaster_replid2:{}\r\nmaster_repl_offset:{}\r\nsecond_repl_offset:{}\r\nrepl_backlog_active:{}\r\nrepl_backlog_size:{}\r\nrepl_backlog_first_byte_offset:{}\r\nrepl_backlog_histlen:{}\r\n",
+                replication.replid(),
+                replid2,
+                replication.offset(),
+                second_replid_offset,
+                backlog.is_some() as u8,
+                backend.config.repl_backlog_size(),
+                first_byte,
+                histlen,
+            )
+        }
+        "commandstats" => {
+            let _ = write!(info, "# Commandstats\r\n");
+            for (name, command) in stats.command_stats() {
+                let calls = command.calls();
+                let _ = write!(
+                    info,
+                    "cmdstat_{}:calls={},usec={},usec_per_call={:.2},rejected_calls={},failed_calls={}\r\n",
+                    name,
+                    calls,
+                    command.usec(),
+                    command.usec() as f64 / calls.max(1) as f64,
+                    command.rejected_calls(),
+                    command.failed_calls(),
+                );
+            }
+            Ok(())
+        }
+        "latencystats" => {
+            let _ = write!(info, "# Latencystats\r\n");
+            for (name, command) in stats.command_stats() {
+                if command.calls() == 0 {
+                    continue;
+                }
+                let percentiles: Vec<String> = LATENCY_PERCENTILES
+                    .iter()
+                    .map(|p| format!("p{}={:.3}", p, command.latency_percentile(*p) as f64))
+                    .collect();
+                let _ = write!(
+                    info,
+                    "latency_percentiles_usec_{}:{}\r\n",
+                    name,
+                    percentiles.join(",")
+                );
+            }
+            Ok(())
+        }
+        "cluster" => write!(
+            info,
+            "# Cluster\r\ncluster_enabled:{}\r\n",
+            backend.config.cluster_enabled() as u8
+        ),
+        "keyspace" => {
+            let _ = write!(info, "# Keyspace\r\n");
+            match backend.dbsize() {
+                0 => Ok(()),
+                keys => write!(
+                    info,
+                    "db0:keys={},expires={},avg_ttl=0\r\n",
+                    keys,
+                    backend.expires.len()
+                ),
+            }
+        }
+        _ => Ok(()),
+    };
+}
+
+impl CommandExecutor for MemoryCmd {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match self {
+            MemoryCmd::Stats => {
+                let usage = ctx.backend.memory_usage();
+                let keys = ctx.backend.dbsize() as u64;
+                let overhead = keys * KEY_OVERHEAD;
+                let mut stats = RespMap::new();
+                for (name, value) in [
+                    ("peak.allocated", ctx.backend.peak_memory()),
+                    ("total.allocated", usage.total()),
+                    ("overhead.total", overhead),
+                    ("keys.count", keys),
+                    (
+                        "keys.bytes-per-key",
+                        usage.total().checked_div(keys).unwrap_or(0),
+                    ),
+                    ("dataset.bytes", usage.total().saturating_sub(overhead)),
+                    ("dataset.strings.bytes", usage.strings),
+                    ("dataset.sets.bytes", usage.sets),
+                    ("dataset.hashes.bytes", usage.hashes),
+                    ("rss.bytes", resident_memory().unwrap_or(0)),
+                ] {
+                    stats.insert(BulkString::from(name), RespFrame::Integer(value as i64));
+                }
+                stats.into()
+            }
+            MemoryCmd::Usage(key) => match ctx.backend.key_memory(&key) {
+                Some(bytes) => RespFrame::Integer(bytes as i64),
+                None => RespFrame::null(),
+            },
+        })
+    }
+}
+
+// Resident set size of the process in bytes, only available on Linux.
+fn resident_memory() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+impl TryFrom<RespArray> for Info {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sections = extract_args(value, 1)?
+            .into_iter()
+            .map(|arg| match arg {
+                RespFrame::BulkString(section) => {
+                    Ok(String::from_utf8(section.0.into())?.to_ascii_lowercase())
+                }
+                _ => Err(CommandError::InvalidArgument("Invalid section".to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Info { sections })
+    }
+}
+
+impl TryFrom<RespArray> for MemoryCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = Subcommand::parse(value, "memory")?;
+        match (sub.name.as_str(), sub.args.as_slice()) {
+            ("stats", []) => Ok(MemoryCmd::Stats),
+            ("usage", [key]) => Ok(MemoryCmd::Usage(key.clone())),
+            ("stats" | "usage", _) => Err(sub.wrong_arity()),
+            _ => Err(sub.unknown()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecoder;
+    use anyhow::Result;
+    use bytes::BytesMut;
+    use std::time::Duration;
+
+    fn info(backend: &Backend, sections: &[&str]) -> String {
+        let cmd = Info {
+            sections: sections.iter().map(|s| s.to_string()).collect(),
+        };
+        match cmd.execute(&mut ExecContext::new(backend)).unwrap() {
+            RespFrame::BulkString(s) => String::from_utf8(s.0.into()).unwrap(),
+            frame => panic!("unexpected reply: {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn test_info_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$4\r\nINFO\r\n$5\r\nStats\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Info = frame.try_into()?;
+        assert_eq!(result.sections, ["stats"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_info_sections() {
+        let backend = Backend::new();
+        backend.set("foo".into(), RespFrame::Integer(1));
+        backend.get(b"foo");
+        backend.get(b"bar");
+
+        let stats = info(&backend, &["stats"]);
+        assert!(stats.starts_with("# Stats\r\n"));
+        assert!(stats.contains("keyspace_hits:1\r\n"));
+        assert!(stats.contains("keyspace_misses:1\r\n"));
+        assert!(!stats.contains("# Server"));
+
+        let all = info(&backend, &[]);
+        for section in ["# Server", "# Clients", "# Memory", "# Replication"] {
+            assert!(all.contains(section));
+        }
+        assert!(all.contains("db0:keys=1,expires=0,avg_ttl=0\r\n"));
+        assert!(!all.contains("# Commandstats"));
+    }
+
+    #[test]
+    fn test_command_stats_sections() {
+        let backend = Backend::new();
+        backend
+            .stats
+            .command_called("get", Duration::from_micros(10), false);
+        backend
+            .stats
+            .command_called("get", Duration::from_micros(30), true);
+        backend.stats.command_rejected("set");
+
+        let stats = info(&backend, &["commandstats"]);
+        assert_eq!(
+            stats,
+            "# Commandstats\r\ncmdstat_get:calls=2,usec=40,usec_per_call=20.00,rejected_calls=0,failed_calls=1\r\ncmdstat_set:calls=0,usec=0,usec_per_call=0.00,rejected_calls=1,failed_calls=0\r\n"
+        );
+        let stats = info(&backend, &["latencystats"]);
+        assert_eq!(
+            stats,
+            "# Latencystats\r\nlatency_percentiles_usec_get:p50=10.000,p99=31.000,p99.9=31.000\r\n"
+        );
+        assert!(info(&backend, &["everything"]).contains("# Latencystats"));
+    }
+
+    #[test]
+    fn test_memory_by_type() {
+        let backend = Backend::new();
+        backend.set("s".into(), RespFrame::BulkString(b"value".into()));
+        backend.sadd("set", "member").unwrap();
+        backend.del(b"set");
+        backend
+            .hset("h".into(), "f".into(), RespFrame::BulkString(b"v".into()))
+            .unwrap();
+
+        let usage = backend.memory_usage();
+        assert_eq!(usage.strings, KEY_OVERHEAD + 1 + 5);
+        assert_eq!(usage.sets, 0);
+        assert_eq!(usage.hashes, KEY_OVERHEAD + 1 + 2);
+        assert_eq!(backend.peak_memory(), usage.strings + KEY_OVERHEAD + 3 + 6);
+        assert_eq!(
+            MemoryCmd::Usage("s".into())
+                .execute(&mut ExecContext::new(&backend))
+                .unwrap(),
+            RespFrame::Integer(usage.strings as i64)
+        );
+        let memory = info(&backend, &["memory"]);
+        assert!(memory.contains(&format!("used_memory_dataset:{}\r\n", 1 + 5 + 1 + 2)));
+    }
+}
@@ -0,0 +1,707 @@
+use super::{
+    extract_args, validate_command, AsyncCommandExecutor, CommandError, CommandExecutor, Del, Dump,
+    ExecContext, Expire, Migrate, Persist, Rename, Restore, Ttl, RESP_OK,
+};
+use crate::{
+    persistence::{dump, restore},
+    Backend, BulkString, RespArray, RespCodec, RespFrame, SimpleError, SimpleString,
+};
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use futures::SinkExt;
+use std::{str::FromStr, time::Duration};
+use tokio::{net::TcpStream, time};
+use tokio_stream::StreamExt;
+use tokio_util::codec::Framed;
+
+// like redis, a timeout of 0 is not forever
+const MIGRATE_DEFAULT_TIMEOUT: u64 = 1000;
+
+// A key MIGRATE sends, as it was when dumped.
+struct Dumped<'a> {
+    key: &'a Bytes,
+    payload: Vec<u8>,
+    // the expiration time in unix milliseconds
+    expire_at: Option<u64>,
+    // the version of the key, it's deleted once moved only if it didn't change
+    version: u64,
+}
+
+impl CommandExecutor for Del {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let deleted = self.keys.iter().filter(|key| ctx.backend.del(key)).count();
+        Ok(RespFrame::Integer(deleted as i64))
+    }
+}
+
+impl CommandExecutor for Expire {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let when = match self.absolute {
+            true => self.millis,
+            false => (ctx.backend.now_ms() as i64).saturating_add(self.millis),
+        };
+        // a time in the past deletes the key
+        let done = match u64::try_from(when) {
+            Ok(when) if when > ctx.backend.now_ms() => ctx.backend.expire_at(&self.key, when),
+            _ => ctx.backend.exists(&self.key) && ctx.backend.del(&self.key),
+        };
+        Ok(RespFrame::Integer(done as i64))
+    }
+}
+
+impl CommandExecutor for Ttl {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        if !ctx.backend.exists(&self.key) {
+            return Ok(RespFrame::Integer(-2));
+        }
+        let Some(when) = ctx.backend.expire_time(&self.key) else {
+            return Ok(RespFrame::Integer(-1));
+        };
+        let ttl = when.saturating_sub(ctx.backend.now_ms());
+        Ok(match self.millis {
+            true => RespFrame::Integer(ttl as i64),
+            false => RespFrame::Integer(((ttl + 500) / 1000) as i64),
+        })
+    }
+}
+
+impl CommandExecutor for Persist {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(ctx.backend.persist(&self.key) as i64))
+    }
+}
+
+impl CommandExecutor for Rename {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match ctx.backend.rename(&self.key, self.newkey) {
+            true => RESP_OK.clone(),
+            false => SimpleError::new("ERR no such key").into(),
+        })
+    }
+}
+
+impl CommandExecutor for Dump {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match dump(ctx.backend, &self.key) {
+            Some(payload) => BulkString::new(payload).into(),
+            None => RespFrame::null(),
+        })
+    }
+}
+
+impl CommandExecutor for Restore {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        if !self.replace && ctx.backend.exists(&self.key) {
+            return Ok(SimpleError::new("BUSYKEY Target key name already exists.").into());
+        }
+        let expire_at = match (self.ttl, self.absttl) {
+            (0, _) => None,
+            (when, true) => Some(when),
+            (ttl, false) => Some(ctx.backend.now_ms().saturating_add(ttl)),
+        };
+        // like redis, a key whose time has passed already is not created
+        if expire_at.is_some_and(|when| when <= ctx.backend.now_ms()) {
+            ctx.backend.del(&self.key);
+            return Ok(RESP_OK.clone());
+        }
+        Ok(
+            match restore(ctx.backend, &self.key, &self.payload, expire_at) {
+                Ok(()) => RESP_OK.clone(),
+                Err(_) => SimpleError::new("ERR DUMP payload version or checksum are wrong").into(),
+            },
+        )
+    }
+}
+
+// MIGRATE waits on the target node, it can't run inside MULTI or a script.
+impl CommandExecutor for Migrate {
+    fn execute(self, _ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Err(CommandError::NoConnection("MIGRATE"))
+    }
+}
+
+impl AsyncCommandExecutor for Migrate {
+    async fn execute_async(self, ctx: &mut ExecContext<'_>) -> Result<RespFrame, CommandError> {
+        Ok(self.migrate(ctx.backend).await)
+    }
+}
+
+impl Migrate {
+    // The keys are dumped, restored on the target, then deleted here unless they changed in the
+    // meantime. They are watched during the transfer, their versions count the writes.
+    async fn migrate(self, backend: &Backend) -> RespFrame {
+        let dumped: Vec<Dumped> = {
+            let _guard = backend.exec_lock.read().unwrap_or_else(|e| e.into_inner());
+            self.keys
+                .iter()
+                .filter_map(|key| {
+                    Some(Dumped {
+                        key,
+                        payload: dump(backend, key)?,
+                        expire_at: backend.expire_time(key),
+                        version: backend.watch(key.clone()),
+                    })
+                })
+                .collect()
+        };
+        if dumped.is_empty() {
+            return SimpleString::new("NOKEY").into();
+        }
+        let reply = self.move_keys(backend, &dumped).await;
+        for dumped in &dumped {
+            backend.unwatch(dumped.key);
+        }
+        reply
+    }
+
+    async fn move_keys(&self, backend: &Backend, dumped: &[Dumped<'_>]) -> RespFrame {
+        // the target takes the keys of a slot it is importing after ASKING
+        let restore: &[u8] = match backend.config.cluster_enabled() {
+            true => b"RESTORE-ASKING",
+            false => b"RESTORE",
+        };
+        let timeout = match self.timeout {
+            0 => MIGRATE_DEFAULT_TIMEOUT,
+            timeout => timeout,
+        };
+        let transfer = self.transfer(backend, restore, dumped);
+        match time::timeout(Duration::from_millis(timeout), transfer).await {
+            Ok(Ok(None)) => {}
+            Ok(Ok(Some(e))) => {
+                return SimpleError::new(format!("ERR Target instance replied with error: {}", e))
+                    .into()
+            }
+            Ok(Err(e)) => {
+                return SimpleError::new(format!("IOERR error with target instance: {}", e)).into()
+            }
+            Err(_) => {
+                return SimpleError::new("IOERR error or timeout reading to target instance").into()
+            }
+        }
+        if !self.copy {
+            let _guard = backend.exec_lock.read().unwrap_or_else(|e| e.into_inner());
+            for dumped in dumped {
+                if backend.version(dumped.key) == dumped.version {
+                    backend.del(dumped.key);
+                }
+            }
+        }
+        RESP_OK.clone()
+    }
+
+    // Restores the keys on the target. Returns the first error it replied with.
+    async fn transfer(
+        &self,
+        backend: &Backend,
+        restore: &[u8],
+        dumped: &[Dumped<'_>],
+    ) -> Result<Option<String>> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        let mut framed = Framed::new(stream, RespCodec::default());
+        if let Some((username, password)) = &self.auth {
+            let mut args: Vec<&[u8]> = vec![b"AUTH"];
+            args.extend(username.iter().map(|u| u.as_bytes()));
+            args.push(password.as_bytes());
+            if let RespFrame::Error(e) = request(&mut framed, &args).await? {
+                return Ok(Some(e.0));
+            }
+        }
+        for dumped in dumped {
+            // the time left, like redis at least 1ms for a key about to expire
+            let ttl = match dumped.expire_at {
+                Some(when) => when.saturating_sub(backend.now_ms()).max(1),
+                None => 0,
+            }
+            .to_string();
+            let mut args: Vec<&[u8]> = vec![restore, dumped.key, ttl.as_bytes(), &dumped.payload];
+            if self.replace {
+                args.push(b"REPLACE");
+            }
+            if let RespFrame::Error(e) = request(&mut framed, &args).await? {
+                return Ok(Some(e.0));
+            }
+        }
+        Ok(None)
+    }
+}
+
+// Sends a command to another node and waits for the reply.
+pub(crate) async fn request(
+    framed: &mut Framed<TcpStream, RespCodec>,
+    args: &[&[u8]],
+) -> Result<RespFrame> {
+    let frames: Vec<RespFrame> = args
+        .iter()
+        .map(|arg| BulkString::new(arg.to_vec()).into())
+        .collect();
+    framed.send(RespArray::new(frames).into()).await?;
+    match framed.next().await {
+        Some(reply) => Ok(reply?),
+        None => bail!("connection closed by the target instance"),
+    }
+}
+
+impl TryFrom<RespArray> for Del {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let keys = bytes_args(value, "del")?;
+        if keys.is_empty() {
+            return Err(CommandError::WrongArity("del".to_string()));
+        }
+        Ok(Del { keys })
+    }
+}
+
+impl TryFrom<RespArray> for Expire {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let name = match value.first() {
+            Some(RespFrame::BulkString(name)) => name.0.clone(),
+            _ => Bytes::new(),
+        };
+        let args = bytes_args(value, "expire")?;
+        let [key, time] = args.as_slice() else {
+            return Err(CommandError::WrongArity("expire".to_string()));
+        };
+        let time: i64 = parse_int(time)?;
+        let (millis, absolute) = match name {
+            name if name.eq_ignore_ascii_case(b"pexpire") => (time, false),
+            name if name.eq_ignore_ascii_case(b"expireat") => (time.saturating_mul(1000), true),
+            name if name.eq_ignore_ascii_case(b"pexpireat") => (time, true),
+            _ => (time.saturating_mul(1000), false),
+        };
+        Ok(Expire {
+            key: key.clone(),
+            millis,
+            absolute,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Ttl {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let millis = matches!(value.first(), Some(RespFrame::BulkString(name)) if name.eq_ignore_ascii_case(b"pttl"));
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(RespFrame::BulkString(key)), None) => Ok(Ttl { key: key.0, millis }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Persist {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["persist"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(key)) => Ok(Persist { key: key.0 }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Rename {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["rename"], 2)?;
+
+        let args = bytes_args(value, "rename")?;
+        match args.as_slice() {
+            [key, newkey] => Ok(Rename {
+                key: key.clone(),
+                newkey: newkey.clone(),
+            }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Dump {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["dump"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(RespFrame::BulkString(key)) => Ok(Dump { key: key.0 }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Restore {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(ttl))) =
+            (args.next(), args.next())
+        else {
+            return Err(CommandError::WrongArity("restore".to_string()));
+        };
+        let Some(RespFrame::BulkString(payload)) = args.next() else {
+            return Err(CommandError::WrongArity("restore".to_string()));
+        };
+        let Ok(ttl) = String::from_utf8(ttl.0.into())?.parse::<u64>() else {
+            return Err(CommandError::InvalidArgument(
+                "Invalid TTL value, must be >= 0".to_string(),
+            ));
+        };
+        let (mut replace, mut absttl) = (false, false);
+        for arg in args {
+            match arg {
+                RespFrame::BulkString(arg) if arg.0.eq_ignore_ascii_case(b"replace") => {
+                    replace = true
+                }
+                RespFrame::BulkString(arg) if arg.0.eq_ignore_ascii_case(b"absttl") => {
+                    absttl = true
+                }
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        Ok(Restore {
+            key: key.0,
+            ttl,
+            payload: payload.0.to_vec(),
+            replace,
+            absttl,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Migrate {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let args = bytes_args(value, "migrate")?;
+        let [host, port, key, db, timeout, options @ ..] = args.as_slice() else {
+            return Err(CommandError::WrongArity("migrate".to_string()));
+        };
+        let port = parse_int(port)?;
+        if parse_int::<u64>(db)? != 0 {
+            return Err(CommandError::InvalidArgument(
+                "DB index is out of range".to_string(),
+            ));
+        }
+        let timeout: i64 = parse_int(timeout)?;
+        // the options are names and passwords, only the keys may be binary
+        let string = |arg: &Bytes| String::from_utf8_lossy(arg).into_owned();
+        let mut cmd = Migrate {
+            host: string(host),
+            port,
+            keys: vec![key.clone()],
+            timeout: timeout.max(0) as u64,
+            copy: false,
+            replace: false,
+            auth: None,
+        };
+        let syntax = || CommandError::InvalidArgument("syntax error".to_string());
+        let mut options = options.iter();
+        while let Some(option) = options.next() {
+            match string(option).to_ascii_lowercase().as_str() {
+                "copy" => cmd.copy = true,
+                "replace" => cmd.replace = true,
+                "auth" => {
+                    let password = options.next().ok_or_else(syntax)?;
+                    cmd.auth = Some((None, string(password)));
+                }
+                "auth2" => {
+                    let (Some(username), Some(password)) = (options.next(), options.next()) else {
+                        return Err(syntax());
+                    };
+                    cmd.auth = Some((Some(string(username)), string(password)));
+                }
+                // the keys are the rest of the arguments
+                "keys" => {
+                    if !key.is_empty() {
+                        return Err(CommandError::InvalidArgument(
+                            "When using MIGRATE KEYS option, the key argument must be set to the empty string".to_string(),
+                        ));
+                    }
+                    cmd.keys = options.by_ref().cloned().collect();
+                }
+                _ => return Err(syntax()),
+            }
+        }
+        Ok(cmd)
+    }
+}
+
+fn bytes_args(value: RespArray, name: &str) -> Result<Vec<Bytes>, CommandError> {
+    extract_args(value, 1)?
+        .into_iter()
+        .map(|arg| match arg {
+            RespFrame::BulkString(arg) => Ok(arg.0),
+            _ => Err(CommandError::InvalidArgument(format!(
+                "{} arguments must be BulkString",
+                name
+            ))),
+        })
+        .collect()
+}
+
+fn parse_int<T: FromStr>(arg: &[u8]) -> Result<T, CommandError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| arg.parse().ok())
+        .ok_or_else(|| {
+            CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecoder;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_dump_restore_del() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("foo".into(), BulkString::from("bar").into());
+        let RespFrame::BulkString(payload) =
+            (Dump { key: "foo".into() }).execute(&mut ExecContext::new(&backend))?
+        else {
+            panic!("DUMP must reply with a bulk string");
+        };
+
+        let restore = |replace| Restore {
+            key: "foo".into(),
+            ttl: 0,
+            payload: payload.0.to_vec(),
+            replace,
+            absttl: false,
+        };
+        let busy = restore(false).execute(&mut ExecContext::new(&backend))?;
+        assert!(matches!(busy, RespFrame::Error(e) if e.0.starts_with("BUSYKEY")));
+        assert_eq!(
+            restore(true).execute(&mut ExecContext::new(&backend))?,
+            RESP_OK.clone()
+        );
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$3\r\nDEL\r\n$3\r\nfoo\r\n$3\r\nbaz\r\n");
+        let cmd: Del = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            restore(false).execute(&mut ExecContext::new(&backend))?,
+            RESP_OK.clone()
+        );
+        assert_eq!(backend.get(b"foo"), Some(BulkString::from("bar").into()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_with_ttl() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("foo".into(), BulkString::from("bar").into());
+        let payload = dump(&backend, b"foo").unwrap();
+        let restore = |args: &[&[u8]]| -> Result<Restore> {
+            let mut frame = vec![
+                BulkString::from("RESTORE").into(),
+                BulkString::from("foo").into(),
+            ];
+            frame.extend(args.iter().map(|arg| BulkString::new(arg.to_vec()).into()));
+            Ok(RespArray::new(frame).try_into()?)
+        };
+
+        let cmd = restore(&[b"60000", &payload, b"REPLACE"])?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RESP_OK.clone()
+        );
+        let when = backend.expire_time(b"foo").unwrap();
+        assert!(when > backend.now_ms() && when <= backend.now_ms() + 60_000);
+
+        let cmd = restore(&[b"4102444800000", &payload, b"REPLACE", b"ABSTTL"])?;
+        cmd.execute(&mut ExecContext::new(&backend))?;
+        assert_eq!(backend.expire_time(b"foo"), Some(4102444800000));
+
+        // a time in the past does not create the key
+        let cmd = restore(&[b"1", &payload, b"REPLACE", b"ABSTTL"])?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RESP_OK.clone()
+        );
+        assert!(!backend.exists(b"foo"));
+
+        assert!(restore(&[b"-1", &payload]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_ttl_persist() -> Result<()> {
+        let backend = Backend::new();
+        let ttl = |millis| Ttl {
+            key: "foo".into(),
+            millis,
+        };
+        assert_eq!(
+            ttl(false).execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(-2)
+        );
+        backend.set("foo".into(), BulkString::from("bar").into());
+        assert_eq!(
+            ttl(false).execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(-1)
+        );
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nEXPIRE\r\n$3\r\nfoo\r\n$2\r\n10\r\n");
+        let cmd: Expire = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            ttl(false).execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(10)
+        );
+        assert_eq!(
+            (Persist { key: "foo".into() }).execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            ttl(true).execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(-1)
+        );
+
+        // a time in the past deletes the key
+        buf.extend_from_slice(b"*3\r\n$9\r\nPEXPIREAT\r\n$3\r\nfoo\r\n$1\r\n1\r\n");
+        let cmd: Expire = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(1)
+        );
+        assert_eq!(backend.get(b"foo"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("foo".into(), BulkString::from("bar").into());
+        backend.expire_at(b"foo", backend.now_ms() + 60_000);
+        backend.sadd("baz", "m").unwrap();
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nRENAME\r\n$3\r\nfoo\r\n$3\r\nbaz\r\n");
+        let cmd: Rename = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RESP_OK.clone()
+        );
+        assert!(!backend.exists(b"foo") && !backend.sismember(b"baz", b"m"));
+        assert_eq!(backend.get(b"baz"), Some(BulkString::from("bar").into()));
+        assert!(backend.expire_time(b"baz").is_some());
+
+        let cmd = Rename {
+            key: "foo".into(),
+            newkey: "baz".into(),
+        };
+        assert!(matches!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Error(_)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*10\r\n$7\r\nMIGRATE\r\n$9\r\n127.0.0.1\r\n$4\r\n7001\r\n$0\r\n\r\n$1\r\n0\r\n$4\r\n5000\r\n$4\r\nCOPY\r\n$4\r\nKEYS\r\n$3\r\nfoo\r\n$3\r\nbar\r\n");
+        let cmd: Migrate = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(cmd.port, 7001);
+        assert_eq!(cmd.keys, vec!["foo".to_string(), "bar".to_string()]);
+        assert!(cmd.copy && !cmd.replace);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migrate_keeps_a_key_written_during_the_transfer() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("moved".into(), BulkString::from("1").into());
+        backend.set("written".into(), BulkString::from("1").into());
+
+        // the target restores the keys, "written" is written here meanwhile
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let source = backend.clone();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let mut framed = Framed::new(stream, RespCodec::default());
+            while framed.next().await.transpose()?.is_some() {
+                source.set("written".into(), BulkString::from("2").into());
+                framed.send(RESP_OK.clone()).await?;
+            }
+            anyhow::Ok(())
+        });
+
+        let cmd = Migrate {
+            host: "127.0.0.1".to_string(),
+            port,
+            keys: vec!["moved".into(), "written".into()],
+            timeout: 5000,
+            copy: false,
+            replace: false,
+            auth: None,
+        };
+        let reply = cmd.migrate(&backend).await;
+        assert_eq!(reply, RESP_OK.clone());
+        assert_eq!(backend.get(b"written"), Some(BulkString::from("2").into()));
+        assert!(!backend.exists(b"moved"));
+        // the keys are not watched anymore
+        assert!(backend.versions.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migrate_sends_the_ttl() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("volatile".into(), BulkString::from("1").into());
+        backend.expire_at(b"volatile", backend.now_ms() + 60_000);
+        backend.set("persistent".into(), BulkString::from("1").into());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let target = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let mut framed = Framed::new(stream, RespCodec::default());
+            let mut ttls = Vec::new();
+            while let Some(RespFrame::Array(RespArray(args))) = framed.next().await.transpose()? {
+                let (RespFrame::BulkString(key), RespFrame::BulkString(ttl)) = (&args[1], &args[2])
+                else {
+                    bail!("RESTORE expected");
+                };
+                ttls.push((
+                    key.0.clone(),
+                    String::from_utf8(ttl.0.to_vec())?.parse::<u64>()?,
+                ));
+                framed.send(RESP_OK.clone()).await?;
+            }
+            anyhow::Ok(ttls)
+        });
+
+        let cmd = Migrate {
+            host: "127.0.0.1".to_string(),
+            port,
+            keys: vec!["volatile".into(), "persistent".into()],
+            timeout: 5000,
+            copy: false,
+            replace: false,
+            auth: None,
+        };
+        assert_eq!(cmd.migrate(&backend).await, RESP_OK.clone());
+        let ttls = target.await??;
+        assert_eq!(ttls.len(), 2);
+        assert_eq!(ttls[0].0, "volatile");
+        assert!(ttls[0].1 > 0 && ttls[0].1 <= 60_000);
+        assert_eq!(ttls[1], (Bytes::from("persistent"), 0));
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use super::{extract_args, CommandError, CommandExecutor, ExecContext, Lolwut};
+use crate::{lolwut, RespArray, RespFrame, VerbatimString};
+
+impl CommandExecutor for Lolwut {
+    fn execute(self, _ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let mut art = lolwut::schotter(self.cols, self.squares_per_row, self.squares_per_col);
+        art.push_str(&format!(
+            "\nGeorg Nees - schotter, plotter on paper, 1968. simple-redis ver. {}\n",
+            env!("CARGO_PKG_VERSION")
+        ));
+        Ok(VerbatimString::text(art).into())
+    }
+}
+
+impl TryFrom<RespArray> for Lolwut {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?
+            .into_iter()
+            .map(|arg| match arg {
+                RespFrame::BulkString(arg) => Ok(String::from_utf8(arg.0.into())?),
+                _ => Err(CommandError::InvalidArgument(
+                    "lolwut arguments must be BulkString".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // there is only one artwork, any version draws it
+        if args
+            .first()
+            .is_some_and(|arg| arg.eq_ignore_ascii_case("version"))
+        {
+            if args.len() < 2 || args[1].parse::<i64>().is_err() {
+                return Err(CommandError::InvalidArgument(
+                    "value is not an integer or out of range".to_string(),
+                ));
+            }
+            args.drain(..2);
+        }
+        if args.len() > 3 {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+
+        // the same defaults and limits as redis
+        let mut params = [(66, 1000), (8, 200), (12, 200)];
+        for (arg, (value, max)) in args.iter().zip(params.iter_mut()) {
+            *value = arg
+                .parse::<usize>()
+                .map_err(|_| {
+                    CommandError::InvalidArgument(
+                        "value is not an integer or out of range".to_string(),
+                    )
+                })?
+                .clamp(1, *max);
+        }
+        let [(cols, _), (squares_per_row, _), (squares_per_col, _)] = params;
+        Ok(Lolwut {
+            cols,
+            squares_per_row,
+            squares_per_col,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, RespDecoder};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_lolwut() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$6\r\nLOLWUT\r\n$7\r\nVERSION\r\n$1\r\n5\r\n$2\r\n10\r\n$1\r\n2\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: Lolwut = frame.try_into()?;
+        assert_eq!(
+            (cmd.cols, cmd.squares_per_row, cmd.squares_per_col),
+            (10, 2, 12)
+        );
+
+        let RespFrame::VerbatimString(art) = cmd.execute(&mut ExecContext::new(&Backend::new()))?
+        else {
+            panic!("LOLWUT should reply with a VerbatimString");
+        };
+        let art = String::from_utf8(art.data)?;
+        assert!(art
+            .lines()
+            .next()
+            .is_some_and(|line| line.chars().count() == 10));
+        assert!(art.ends_with(&format!("ver. {}\n", env!("CARGO_PKG_VERSION"))));
+        Ok(())
+    }
+}
@@ -1,27 +1,37 @@
 use super::{
-    extract_args, validate_command, CommandError, CommandExecutor, Echo, Get, Set, RESP_OK,
+    extract_args, validate_command, CommandError, CommandExecutor, Echo, ExecContext, Get, MSetNx,
+    Set, RESP_OK,
 };
-use crate::{BulkString, RespArray, RespFrame, RespNull};
+use crate::{BulkString, KeyType, RespArray, RespFrame};
 
 impl CommandExecutor for Get {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        match backend.get(&self.key) {
-            Some(value) => value,
-            None => RespFrame::Null(RespNull),
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        if let Err(e) = ctx.backend.check_type(&self.key, KeyType::String) {
+            return Ok(e.into());
         }
+        Ok(match ctx.backend.get(&self.key) {
+            Some(value) => value,
+            None => RespFrame::null(),
+        })
     }
 }
 
 impl CommandExecutor for Set {
-    fn execute(self, backend: &crate::Backend) -> RespFrame {
-        backend.set(self.key, self.value);
-        RESP_OK.clone()
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        ctx.backend.set(self.key, self.value);
+        Ok(RESP_OK.clone())
+    }
+}
+
+impl CommandExecutor for MSetNx {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(ctx.backend.msetnx(self.pairs) as i64))
     }
 }
 
 impl CommandExecutor for Echo {
-    fn execute(self, _backend: &crate::Backend) -> RespFrame {
-        RespFrame::BulkString(BulkString::new(self.message))
+    fn execute(self, _ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::BulkString(BulkString::new(self.message)))
     }
 }
 
@@ -32,9 +42,7 @@ impl TryFrom<RespArray> for Get {
 
         let mut args = extract_args(value, 1)?.into_iter();
         match args.next() {
-            Some(RespFrame::BulkString(key)) => Ok(Get {
-                key: String::from_utf8(key.0)?,
-            }),
+            Some(RespFrame::BulkString(key)) => Ok(Get { key: key.0 }),
             _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
         }
     }
@@ -47,10 +55,7 @@ impl TryFrom<RespArray> for Set {
 
         let mut args = extract_args(value, 1)?.into_iter();
         match (args.next(), args.next()) {
-            (Some(RespFrame::BulkString(key)), Some(value)) => Ok(Set {
-                key: String::from_utf8(key.0)?,
-                value,
-            }),
+            (Some(RespFrame::BulkString(key)), Some(value)) => Ok(Set { key: key.0, value }),
             _ => Err(CommandError::InvalidArgument(
                 "Invalid key or value".to_string(),
             )),
@@ -58,6 +63,25 @@ impl TryFrom<RespArray> for Set {
     }
 }
 
+impl TryFrom<RespArray> for MSetNx {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() < 3 || value.len().is_multiple_of(2) {
+            return Err(CommandError::WrongArity("msetnx".to_string()));
+        }
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut pairs = vec![];
+        while let (Some(key), Some(value)) = (args.next(), args.next()) {
+            match key {
+                RespFrame::BulkString(key) => pairs.push((key.0, value)),
+                _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
+            }
+        }
+        Ok(MSetNx { pairs })
+    }
+}
+
 impl TryFrom<RespArray> for Echo {
     type Error = CommandError;
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
@@ -65,9 +89,7 @@ impl TryFrom<RespArray> for Echo {
 
         let mut args = extract_args(value, 1)?.into_iter();
         match args.next() {
-            Some(RespFrame::BulkString(msg)) => Ok(Echo {
-                message: String::from_utf8(msg.0)?,
-            }),
+            Some(RespFrame::BulkString(msg)) => Ok(Echo { message: msg.0 }),
             _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
         }
     }
@@ -76,9 +98,9 @@ impl TryFrom<RespArray> for Echo {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Backend, RespDecoder};
+    use crate::{Backend, RespDecoder, RespEncoder};
     use anyhow::Result;
-    use bytes::BytesMut;
+    use bytes::{Bytes, BytesMut};
 
     #[test]
     fn test_get_from_resp_array() -> Result<()> {
@@ -105,21 +127,83 @@ mod tests {
     fn test_set_get_command() -> Result<()> {
         let backend = Backend::new();
         let cmd = Set {
-            key: "hello".to_string(),
+            key: "hello".into(),
             value: RespFrame::BulkString(b"world".into()),
         };
-        let result = cmd.execute(&backend);
+        let result = cmd.execute(&mut ExecContext::new(&backend))?;
         assert_eq!(result, RESP_OK.clone());
 
         let cmd = Get {
-            key: "hello".to_string(),
+            key: "hello".into(),
         };
-        let result = cmd.execute(&backend);
+        let result = cmd.execute(&mut ExecContext::new(&backend))?;
         assert_eq!(result, RespFrame::BulkString(b"world".into()));
 
         Ok(())
     }
 
+    #[test]
+    fn test_msetnx_all_or_nothing() -> Result<()> {
+        let backend = Backend::new();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$6\r\nmsetnx\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$1\r\n2\r\n",
+        );
+        let cmd: MSetNx = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(1)
+        );
+
+        let cmd = MSetNx {
+            pairs: vec![
+                ("b".into(), RespFrame::Integer(3)),
+                ("c".into(), RespFrame::Integer(3)),
+            ],
+        };
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(0)
+        );
+        assert_eq!(backend.get(b"b"), Some(RespFrame::BulkString(b"2".into())));
+        assert!(!backend.exists(b"c"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_key() -> Result<()> {
+        let backend = Backend::new();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$3\r\nset\r\n$2\r\n\xff\xfe\r\n$1\r\n\x00\r\n");
+        let cmd: Set = RespArray::decode(&mut buf)?.try_into()?;
+        cmd.execute(&mut ExecContext::new(&backend))?;
+
+        let cmd = Get {
+            key: Bytes::from_static(b"\xff\xfe"),
+        };
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespFrame::BulkString(b"\x00".into())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_get_empty_value() -> Result<()> {
+        let backend = Backend::new();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$3\r\nset\r\n$1\r\nk\r\n$0\r\n\r\n");
+        let cmd: Set = RespArray::decode(&mut buf)?.try_into()?;
+        cmd.execute(&mut ExecContext::new(&backend))?;
+
+        let cmd = Get { key: "k".into() };
+        let reply = cmd.execute(&mut ExecContext::new(&backend))?;
+        assert_eq!(reply, RespFrame::BulkString(b"".into()));
+        // an empty string, not the null of a missing key
+        assert_eq!(reply.encode_to_vec(), b"$0\r\n\r\n");
+        Ok(())
+    }
+
     #[test]
     fn test_echo() -> Result<()> {
         let mut buf = BytesMut::new();
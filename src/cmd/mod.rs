@@ -2,14 +2,47 @@ use enum_dispatch::enum_dispatch;
 use lazy_static::lazy_static;
 use thiserror::Error;
 
-use crate::{Backend, RespArray, RespError, RespFrame, SimpleString};
+use crate::{
+    network::Session, Backend, PauseMode, RespArray, RespError, RespFrame, SimpleError,
+    SimpleString,
+};
+use bytes::Bytes;
+use std::{future::Future, time::Duration};
 
+mod acl;
+mod auth;
+mod client;
+mod cluster;
+mod command;
+mod config;
+mod custom;
+mod debug;
+mod hello;
 mod hmap;
 mod hset;
+mod info;
+mod keys;
+mod lolwut;
 mod map;
+mod replication;
+mod script;
+mod server;
+mod slowlog;
+mod spec;
+mod subcommand;
+mod transaction;
+
+pub use custom::{CommandHandler, CommandRegistry, CustomCommand};
+pub use spec::{command_keys, command_name};
+pub(crate) use spec::{
+    command_names, is_noscript, is_write, lookup, may_write, request_keys, request_name,
+    request_static_name, COMMANDS,
+};
+pub use subcommand::Help;
+use subcommand::Subcommand;
 
 lazy_static! {
-    static ref RESP_OK: RespFrame = SimpleString::new("OK").into();
+    pub(crate) static ref RESP_OK: RespFrame = SimpleString::new("OK").into();
 }
 
 #[derive(Error, Debug)]
@@ -18,6 +51,14 @@ pub enum CommandError {
     InvalidCommand(String),
     #[error("Invalid argument: {0}")]
     InvalidArgument(String),
+    // like redis, "command|subcommand" for subcommands
+    #[error("wrong number of arguments for '{0}' command")]
+    WrongArity(String),
+    #[error("unknown subcommand '{0}'. Try {1} HELP.")]
+    UnknownSubcommand(String, String),
+
+    #[error("{0} must be executed on a client connection")]
+    NoConnection(&'static str),
 
     #[error("{0}")]
     RespError(#[from] RespError),
@@ -25,9 +66,66 @@ pub enum CommandError {
     Utf8Error(#[from] std::string::FromUtf8Error),
 }
 
+// What a command runs with: the server, and the state of the connection it was sent on. The
+// commands replayed from the AOF, streamed by the master or called by a script have no connection.
+pub struct ExecContext<'a> {
+    pub backend: &'a Backend,
+    pub(crate) session: Option<&'a mut Session>,
+}
+
+impl<'a> ExecContext<'a> {
+    pub fn new(backend: &'a Backend) -> Self {
+        ExecContext {
+            backend,
+            session: None,
+        }
+    }
+
+    pub(crate) fn with_session(backend: &'a Backend, session: &'a mut Session) -> Self {
+        ExecContext {
+            backend,
+            session: Some(session),
+        }
+    }
+
+    // the connection, for the commands which can't run without one
+    pub(crate) fn session(&mut self, name: &'static str) -> Result<&mut Session, CommandError> {
+        self.session
+            .as_deref_mut()
+            .ok_or(CommandError::NoConnection(name))
+    }
+
+    // the ACL user of the connection, none for the commands run by the server itself
+    pub(crate) fn user(&self) -> Option<&str> {
+        self.session.as_ref().map(|session| session.user.as_str())
+    }
+
+    // the database selected by the connection
+    pub fn db(&self) -> usize {
+        self.session.as_ref().map_or(0, |session| session.db)
+    }
+
+    // the id of the connection, None without one
+    pub fn client_id(&self) -> Option<u64> {
+        self.session.as_ref().map(|session| session.id)
+    }
+}
+
+// A command fails with an error when it can't run at all, it's replied as "-ERR <error>". The
+// replies of commands that ran are frames, error replies included.
 #[enum_dispatch]
 pub trait CommandExecutor {
-    fn execute(self, backend: &Backend) -> RespFrame;
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError>;
+}
+
+// The commands which wait, on replicas or on another node, await instead of blocking a worker
+// thread. Only their connection waits, without the exec lock, while the others go on. In MULTI,
+// scripts and the AOF, where there is no waiting, they run with execute.
+pub trait AsyncCommandExecutor {
+    fn execute_async(
+        self,
+        ctx: &mut ExecContext,
+    ) -> impl Future<Output = Result<RespFrame, CommandError>> + Send;
 }
 
 #[enum_dispatch(CommandExecutor)]
@@ -42,6 +140,54 @@ pub enum Command {
     HMGet(HMGet),
     SAdd(SAdd),
     SIsMember(SIsMember),
+    Client(Client),
+    Multi(Multi),
+    Exec(Exec),
+    Discard(Discard),
+    Watch(Watch),
+    Unwatch(Unwatch),
+    Monitor(Monitor),
+    Auth(Auth),
+    Quit(Quit),
+    Hello(Hello),
+    Acl(AclCmd),
+    Eval(Eval),
+    EvalSha(EvalSha),
+    Script(Script),
+    Function(Function),
+    FCall(FCall),
+    Info(Info),
+    Commands(Commands),
+    Config(ConfigCmd),
+    Debug(DebugCmd),
+    SlowLog(SlowLogCmd),
+    Shutdown(Shutdown),
+    Save(Save),
+    BgSave(BgSave),
+    LastSave(LastSave),
+    BgRewriteAof(BgRewriteAof),
+    Lolwut(Lolwut),
+    ReplicaOf(ReplicaOf),
+    ReplConf(ReplConf),
+    PSync(PSync),
+    Wait(Wait),
+    Role(Role),
+    Cluster(ClusterCmd),
+    Asking(Asking),
+    Del(Del),
+    Dump(Dump),
+    Restore(Restore),
+    Migrate(Migrate),
+    Expire(Expire),
+    Ttl(Ttl),
+    Persist(Persist),
+    MSetNx(MSetNx),
+    Rename(Rename),
+    SMove(SMove),
+    Object(ObjectCmd),
+    Memory(MemoryCmd),
+    Custom(CustomCommand),
+    Help(Help),
 
     // unrecognized command
     Unrecognized(Unrecognized),
@@ -49,36 +195,36 @@ pub enum Command {
 
 #[derive(Debug)]
 pub struct Get {
-    key: String,
+    key: Bytes,
 }
 
 #[derive(Debug)]
 pub struct Set {
-    key: String,
+    key: Bytes,
     value: RespFrame,
 }
 
 #[derive(Debug)]
 pub struct Echo {
-    message: String,
+    message: Bytes,
 }
 
 #[derive(Debug)]
 pub struct HGet {
-    key: String,
-    field: String,
+    key: Bytes,
+    field: Bytes,
 }
 
 #[derive(Debug)]
 pub struct HSet {
-    key: String,
-    field: String,
+    key: Bytes,
+    field: Bytes,
     value: RespFrame,
 }
 
 #[derive(Debug)]
 pub struct HGetAll {
-    key: String,
+    key: Bytes,
     sort: bool,
 }
 
@@ -91,8 +237,8 @@ pub struct HGetAll {
 // "*5\r\n$5\r\nHMGET\r\n$6\r\nmyhash\r\n$6\r\nfield1\r\n$6\r\nfield2\r\n$7\r\nnofield\r\n"
 #[derive(Debug)]
 pub struct HMGet {
-    hash: String,
-    fields: Vec<String>,
+    hash: Bytes,
+    fields: Vec<Bytes>,
 }
 
 // SADD key member [member ...]
@@ -105,8 +251,8 @@ pub struct HMGet {
 // (integer) 0
 #[derive(Debug)]
 pub struct SAdd {
-    key: String,
-    members: Vec<String>,
+    key: Bytes,
+    members: Vec<Bytes>,
 }
 
 // SISMEMBER key member
@@ -120,12 +266,575 @@ pub struct SAdd {
 // (integer) 0
 #[derive(Debug)]
 pub struct SIsMember {
-    key: String,
-    member: String,
+    key: Bytes,
+    member: Bytes,
+}
+
+// CLIENT TRACKING ON|OFF [PREFIX prefix [PREFIX prefix ...]] [BCAST] [OPTIN] [OPTOUT]
+// CLIENT CACHING YES|NO
+// CLIENT LIST [TYPE NORMAL] [ID client-id [client-id ...]]
+// CLIENT ID
+// CLIENT INFO
+// CLIENT SETNAME connection-name
+// CLIENT GETNAME
+// CLIENT KILL ip:port
+// CLIENT KILL [ID client-id] [ADDR ip:port] [LADDR ip:port] [NAME name] [SKIPME yes|no]
+// CLIENT PAUSE timeout [WRITE|ALL]
+// CLIENT UNPAUSE
+// CLIENT UNBLOCK client-id [TIMEOUT|ERROR]
+// redis> CLIENT TRACKING ON BCAST PREFIX user:
+// OK
+// redis> CLIENT ID
+// (integer) 3
+#[derive(Debug)]
+pub enum Client {
+    Tracking(ClientTracking),
+    Caching(bool),
+    // an empty id list means all the clients
+    List(Vec<u64>),
+    Id,
+    Info,
+    SetName(String),
+    GetName,
+    Kill(ClientKill),
+    Pause(Duration, PauseMode),
+    Unpause,
+    // the client and whether its command replies an error rather than as if it timed out
+    Unblock(u64, bool),
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ClientTracking {
+    on: bool,
+    bcast: bool,
+    prefixes: Vec<Bytes>,
+    optin: bool,
+    optout: bool,
+}
+
+// all the filters must match for a client to be killed
+#[derive(Debug, Default, PartialEq)]
+pub struct ClientKill {
+    id: Option<u64>,
+    addr: Option<String>,
+    laddr: Option<String>,
+    name: Option<String>,
+    skipme: bool,
+    // `CLIENT KILL ip:port` replies OK or an error instead of the number of killed clients
+    legacy: bool,
+}
+
+// MONITOR
+// redis> MONITOR
+// OK
+// 1339518083.107412 [0 127.0.0.1:60866] "keys" "*"
+#[derive(Debug)]
+pub struct Monitor;
+
+// AUTH [username] password
+// redis> AUTH secret
+// OK
+#[derive(Debug)]
+pub struct Auth {
+    username: Option<String>,
+    password: String,
+}
+
+// HELLO [protover [AUTH username password] [SETNAME clientname]]
+// redis> HELLO 3
+// 1# "server" => "redis"
+// 2# "version" => "0.1.0"
+// 3# "proto" => (integer) 3
+// ...
+#[derive(Debug)]
+pub struct Hello {
+    protover: Option<u8>,
+    auth: Option<(String, String)>,
+    setname: Option<String>,
+}
+
+// ACL SETUSER username [rule [rule ...]]
+// ACL GETUSER username
+// ACL DELUSER username [username ...]
+// ACL LIST
+// ACL USERS
+// ACL WHOAMI
+// ACL CAT [category]
+// ACL SAVE
+// ACL LOAD
+// redis> ACL SETUSER alice on >secret ~cached:* +get
+// OK
+// redis> ACL LIST
+// 1) "user alice on #2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b ~cached:* +get"
+// 2) "user default on nopass ~* +@all"
+#[derive(Debug)]
+pub enum AclCmd {
+    SetUser(String, Vec<String>),
+    GetUser(String),
+    DelUser(Vec<String>),
+    List,
+    Users,
+    WhoAmI,
+    Cat(Option<String>),
+    Save,
+    Load,
+}
+
+// QUIT
+// replies OK then closes the connection
+#[derive(Debug)]
+pub struct Quit;
+
+// MULTI
+// SET foo bar: "+QUEUED"
+// EXEC: "*1\r\n+OK\r\n"
+#[derive(Debug)]
+pub struct Multi;
+
+#[derive(Debug)]
+pub struct Exec;
+
+#[derive(Debug)]
+pub struct Discard;
+
+// WATCH key [key ...]
+// EXEC replies with a null if any watched key was modified after WATCH
+#[derive(Debug)]
+pub struct Watch {
+    keys: Vec<Bytes>,
 }
 
 #[derive(Debug)]
-pub struct Unrecognized;
+pub struct Unwatch;
+
+// EVAL script numkeys [key [key ...]] [arg [arg ...]]
+// redis> EVAL "return redis.call('GET', KEYS[1])" 1 foo
+#[derive(Debug)]
+pub struct Eval {
+    script: String,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+}
+
+// EVALSHA sha1 numkeys [key [key ...]] [arg [arg ...]]
+#[derive(Debug)]
+pub struct EvalSha {
+    sha: String,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+}
+
+// SCRIPT LOAD script
+// SCRIPT EXISTS sha1 [sha1 ...]
+// SCRIPT FLUSH [ASYNC|SYNC]
+#[derive(Debug)]
+pub enum Script {
+    Load(String),
+    Exists(Vec<String>),
+    Flush,
+}
+
+// FUNCTION LOAD [REPLACE] function-code
+// FUNCTION DELETE library-name
+// FUNCTION FLUSH [ASYNC|SYNC]
+// FUNCTION LIST
+#[derive(Debug)]
+pub enum Function {
+    Load { code: String, replace: bool },
+    Delete(String),
+    Flush,
+    List,
+}
+
+// FCALL function numkeys [key [key ...]] [arg [arg ...]]
+#[derive(Debug)]
+pub struct FCall {
+    function: String,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+}
+
+// INFO [section [section ...]]
+#[derive(Debug)]
+pub struct Info {
+    sections: Vec<String>,
+}
+
+// COMMAND
+// COMMAND COUNT
+// COMMAND INFO [command-name [command-name ...]]
+// COMMAND DOCS [command-name [command-name ...]]
+#[derive(Debug)]
+pub enum Commands {
+    List,
+    Count,
+    Info(Vec<String>),
+    Docs(Vec<String>),
+}
+
+// CONFIG GET parameter [parameter ...]
+// CONFIG SET parameter value [parameter value ...]
+// CONFIG REWRITE
+#[derive(Debug)]
+pub enum ConfigCmd {
+    Get(Vec<String>),
+    Set(Vec<(String, String)>),
+    Rewrite,
+}
+
+// DEBUG SLEEP seconds
+// DEBUG OBJECT key
+// DEBUG SET-ACTIVE-EXPIRE 0|1
+// DEBUG STRINGMATCH-LEN
+// DEBUG RELOAD
+// DEBUG HOTKEYS [count]
+// DEBUG SET-HOTKEYS 0|1
+// redis> DEBUG OBJECT foo
+// Value at:0x0 refcount:1 encoding:embstr serializedlength:3 lru:0 lru_seconds_idle:0 type:string
+// redis> DEBUG HOTKEYS 1
+// 1) 1) "foo"
+//    2) (integer) 1024
+#[derive(Debug)]
+pub enum DebugCmd {
+    Sleep(Duration),
+    Object(Bytes),
+    SetActiveExpire(bool),
+    StringMatchLen,
+    Reload,
+    HotKeys(usize),
+    SetHotKeys(bool),
+}
+
+// SLOWLOG GET [count]
+// SLOWLOG LEN
+// SLOWLOG RESET
+// redis> SLOWLOG GET 1
+// 1) 1) (integer) 0
+//    2) (integer) 1700000000
+//    3) (integer) 1000953
+//    4) 1) "debug"
+//       2) "sleep"
+//       3) "1"
+//    5) "127.0.0.1:52555"
+//    6) ""
+#[derive(Debug)]
+pub enum SlowLogCmd {
+    // None returns all the entries
+    Get(Option<usize>),
+    Len,
+    Reset,
+}
+
+// SHUTDOWN [NOSAVE|SAVE]
+#[derive(Debug)]
+pub struct Shutdown {
+    // None saves if snapshotting is configured
+    save: Option<bool>,
+}
+
+// SAVE
+// BGSAVE
+// LASTSAVE
+// redis> BGSAVE
+// Background saving started
+// redis> LASTSAVE
+// (integer) 1700000000
+#[derive(Debug)]
+pub struct Save;
+
+#[derive(Debug)]
+pub struct BgSave;
+
+#[derive(Debug)]
+pub struct LastSave;
+
+// BGREWRITEAOF
+// redis> BGREWRITEAOF
+// Background append only file rewriting started
+#[derive(Debug)]
+pub struct BgRewriteAof;
+
+// LOLWUT [VERSION version] [columns [squares-per-row [squares-per-col]]]
+// redis> LOLWUT 18 2 2
+// ⠀⡤⠤⠤⠤⠤⠤⠤⢤⡤⠤⠤⠤⠤⠤⠤⢤⠀
+// ⠀⡇⠀⠀⠀⠀⠀⠀⢸⡇⠀⠀⠀⠀⠀⠀⢸⠀
+// ...
+// Georg Nees - schotter, plotter on paper, 1968. simple-redis ver. 0.1.0
+#[derive(Debug)]
+pub struct Lolwut {
+    cols: usize,
+    squares_per_row: usize,
+    squares_per_col: usize,
+}
+
+// REPLICAOF host port
+// REPLICAOF NO ONE
+// SLAVEOF is an alias
+// redis> REPLICAOF 127.0.0.1 6379
+// OK
+#[derive(Debug)]
+pub struct ReplicaOf {
+    // None for NO ONE
+    master: Option<(String, u16)>,
+}
+
+// REPLCONF listening-port port
+// REPLCONF capa capability
+// sent by a replica to its master before PSYNC
+// REPLCONF ACK offset
+// sent by a replica to acknowledge the replication stream, never replied
+// REPLCONF GETACK *
+// sent by a master in the replication stream to ask for an ACK
+#[derive(Debug)]
+pub struct ReplConf {
+    options: Vec<(String, String)>,
+}
+
+// PSYNC replicationid offset
+// redis> PSYNC ? -1
+// +FULLRESYNC 8de1787ba490483314a4d30f1c628bc5025eb761 0
+// $<length>
+// <snapshot>
+// ... the replication stream
+// redis> PSYNC 8de1787ba490483314a4d30f1c628bc5025eb761 1234
+// +CONTINUE 8de1787ba490483314a4d30f1c628bc5025eb761
+// ... the replication stream from offset 1234
+#[derive(Debug)]
+pub struct PSync {
+    replid: String,
+    offset: i64,
+}
+
+// WAIT numreplicas timeout
+// redis> SET foo bar
+// OK
+// redis> WAIT 1 100
+// (integer) 1
+#[derive(Debug)]
+pub struct Wait {
+    numreplicas: usize,
+    // in milliseconds, 0 blocks forever
+    timeout: u64,
+}
+
+// ROLE
+// redis> ROLE
+// 1) "master"
+// 2) (integer) 3129659
+// 3) 1) 1) "127.0.0.1"
+//       2) "9001"
+//       3) "3129242"
+// on a replica:
+// 1) "slave"
+// 2) "127.0.0.1"
+// 3) (integer) 9000
+// 4) "connected"
+// 5) (integer) 3167038
+#[derive(Debug)]
+pub struct Role;
+
+// CLUSTER KEYSLOT key
+// CLUSTER INFO
+// CLUSTER MYID
+// CLUSTER NODES
+// CLUSTER SLOTS
+// CLUSTER SHARDS
+// CLUSTER MEET ip port
+// CLUSTER SETSLOT slot IMPORTING node-id|MIGRATING node-id|NODE node-id|STABLE
+// CLUSTER GETKEYSINSLOT slot count
+// CLUSTER COUNTKEYSINSLOT slot
+// redis> CLUSTER KEYSLOT somekey
+// (integer) 11058
+// redis> CLUSTER KEYSLOT foo{hash_tag}
+// (integer) 2515
+// redis> CLUSTER SLOTS
+// 1) 1) (integer) 0
+//    2) (integer) 16383
+//    3) 1) "127.0.0.1"
+//       2) (integer) 6379
+//       3) "09dbe9720cda62f7865eabc5fd8857c5d2678366"
+#[derive(Debug)]
+pub enum ClusterCmd {
+    KeySlot(Bytes),
+    Info,
+    MyId,
+    Nodes,
+    Slots,
+    Shards,
+    // learns the id of the node with CLUSTER MYID, in the background
+    Meet(String, u16),
+    SetSlot(u16, SlotAction),
+    GetKeysInSlot(u16, usize),
+    CountKeysInSlot(u16),
+}
+
+#[derive(Debug)]
+pub enum SlotAction {
+    Importing(String),
+    Migrating(String),
+    Node(String),
+    Stable,
+}
+
+// ASKING
+// sent before retrying a command on the node given by an -ASK redirection
+// redis> ASKING
+// OK
+#[derive(Debug)]
+pub struct Asking;
+
+// DEL key [key ...]
+// redis> SET key1 "Hello"
+// OK
+// redis> DEL key1 key2
+// (integer) 1
+#[derive(Debug)]
+pub struct Del {
+    keys: Vec<Bytes>,
+}
+
+// DUMP key
+// redis> SET mykey 10
+// OK
+// redis> DUMP mykey
+// "SRDB\x01\x00\x08\x00\x00\x00\x00\x00\x00\x00$2\r\n10\r\n"
+#[derive(Debug)]
+pub struct Dump {
+    key: Bytes,
+}
+
+// RESTORE key ttl serialized-value [REPLACE] [ABSTTL]
+// ttl in milliseconds, 0 for none, a unix time in milliseconds with ABSTTL. RESTORE-ASKING is sent
+// by MIGRATE, it implies ASKING.
+// redis> RESTORE mykey 0 "SRDB\x01\x00\x08\x00\x00\x00\x00\x00\x00\x00$2\r\n10\r\n"
+// OK
+#[derive(Debug)]
+pub struct Restore {
+    key: Bytes,
+    ttl: u64,
+    payload: Vec<u8>,
+    replace: bool,
+    absttl: bool,
+}
+
+// MIGRATE host port key|"" destination-db timeout [COPY] [REPLACE] [AUTH password]
+//   [AUTH2 username password] [KEYS key [key ...]]
+// the keys are sent with RESTORE then deleted, unless COPY is given
+// redis> MIGRATE 127.0.0.1 7001 "" 0 5000 KEYS foo bar
+// OK
+#[derive(Debug)]
+pub struct Migrate {
+    host: String,
+    port: u16,
+    keys: Vec<Bytes>,
+    // in milliseconds
+    timeout: u64,
+    copy: bool,
+    replace: bool,
+    // AUTH password or AUTH2 username password
+    auth: Option<(Option<String>, String)>,
+}
+
+// EXPIRE key seconds
+// PEXPIRE key milliseconds
+// EXPIREAT key unix-time-seconds
+// PEXPIREAT key unix-time-milliseconds
+// redis> SET mykey "Hello"
+// OK
+// redis> EXPIRE mykey 10
+// (integer) 1
+#[derive(Debug)]
+pub struct Expire {
+    key: Bytes,
+    millis: i64,
+    // a unix time instead of a delay
+    absolute: bool,
+}
+
+// TTL key
+// PTTL key
+// redis> TTL mykey
+// (integer) 10
+#[derive(Debug)]
+pub struct Ttl {
+    key: Bytes,
+    millis: bool,
+}
+
+// PERSIST key
+// redis> PERSIST mykey
+// (integer) 1
+#[derive(Debug)]
+pub struct Persist {
+    key: Bytes,
+}
+
+// MSETNX key value [key value ...]
+// the keys are set together, only if none of them exists
+// redis> MSETNX key1 "Hello" key2 "there"
+// (integer) 1
+// redis> MSETNX key2 "new" key3 "world"
+// (integer) 0
+#[derive(Debug)]
+pub struct MSetNx {
+    pairs: Vec<(Bytes, RespFrame)>,
+}
+
+// RENAME key newkey
+// redis> SET mykey "Hello"
+// OK
+// redis> RENAME mykey myotherkey
+// OK
+#[derive(Debug)]
+pub struct Rename {
+    key: Bytes,
+    newkey: Bytes,
+}
+
+// SMOVE source destination member
+// redis> SADD myset "one"
+// (integer) 1
+// redis> SMOVE myset myotherset "one"
+// (integer) 1
+#[derive(Debug)]
+pub struct SMove {
+    source: Bytes,
+    destination: Bytes,
+    member: Bytes,
+}
+
+// OBJECT ENCODING key
+// small hashes and sets use compact encodings, see backend/encoding.rs
+// redis> SADD myset 1 2 3
+// (integer) 3
+// redis> OBJECT ENCODING myset
+// "intset"
+#[derive(Debug)]
+pub enum ObjectCmd {
+    Encoding(Bytes),
+}
+
+// MEMORY STATS
+// MEMORY USAGE key
+// redis> SET foo bar
+// "OK"
+// redis> MEMORY USAGE foo
+// (integer) 70
+#[derive(Debug)]
+pub enum MemoryCmd {
+    Stats,
+    Usage(Bytes),
+}
+
+// how much of the name and the arguments of an unknown command are shown in its error
+const MAX_ERROR_ARGS_LEN: usize = 128;
+
+// A command the server doesn't have, kept to be named in the error.
+#[derive(Debug)]
+pub struct Unrecognized {
+    name: Bytes,
+    args: Vec<Bytes>,
+}
 
 impl TryFrom<RespFrame> for Command {
     type Error = CommandError;
@@ -140,32 +849,81 @@ impl TryFrom<RespFrame> for Command {
 }
 
 impl CommandExecutor for Unrecognized {
-    fn execute(self, _: &Backend) -> RespFrame {
-        RESP_OK.clone()
+    // "ERR unknown command 'foo', with args beginning with: 'a' 'b' ", as redis replies
+    fn execute(self, _ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let mut args = String::new();
+        for arg in &self.args {
+            if args.len() >= MAX_ERROR_ARGS_LEN {
+                break;
+            }
+            let arg = &arg[..arg.len().min(MAX_ERROR_ARGS_LEN - args.len())];
+            args.push_str(&format!("'{}' ", String::from_utf8_lossy(arg)));
+        }
+        let name = &self.name[..self.name.len().min(MAX_ERROR_ARGS_LEN)];
+        let message = format!(
+            "ERR unknown command '{}', with args beginning with: {}",
+            String::from_utf8_lossy(name),
+            args
+        );
+        Ok(
+            // an error is a single line
+            SimpleError::new(message.replace(['\r', '\n'], " ")).into(),
+        )
     }
 }
 
 impl TryFrom<RespArray> for Command {
     type Error = CommandError;
     fn try_from(v: RespArray) -> Result<Self, Self::Error> {
-        match v.first() {
-            Some(RespFrame::BulkString(ref cmd)) => {
-                match cmd.as_ref().to_ascii_lowercase().as_slice() {
-                    b"get" => Ok(Get::try_from(v)?.into()),
-                    b"set" => Ok(Set::try_from(v)?.into()),
-                    b"echo" => Ok(Echo::try_from(v)?.into()),
-                    b"hget" => Ok(HGet::try_from(v)?.into()),
-                    b"hset" => Ok(HSet::try_from(v)?.into()),
-                    b"hmget" => Ok(HMGet::try_from(v)?.into()),
-                    b"hgetall" => Ok(HGetAll::try_from(v)?.into()),
-                    b"sadd" => Ok(SAdd::try_from(v)?.into()),
-                    b"sismember" => Ok(SIsMember::try_from(v)?.into()),
-                    _ => Ok(Unrecognized.into()),
-                }
+        let name = match v.first() {
+            Some(RespFrame::BulkString(name)) => name.0.clone(),
+            _ => {
+                return Err(CommandError::InvalidCommand(
+                    "Command must have a BulkString as the first argument".to_string(),
+                ))
             }
-            _ => Err(CommandError::InvalidCommand(
-                "Command must have a BulkString as the first argument".to_string(),
-            )),
+        };
+        let text = std::str::from_utf8(&name).unwrap_or_default();
+        if let Some(spec) = lookup(text) {
+            if !spec.arity_matches(v.len()) {
+                return Err(CommandError::WrongArity(spec.name.to_string()));
+            }
+            if let Some(help) = Help::parse(spec.name, &v) {
+                return Ok(help.into());
+            }
+            return (spec.parse)(v);
+        }
+        // the custom commands too, see CommandRegistry::resolve
+        Ok(Unrecognized::from(v).into())
+    }
+}
+
+// A request taken for an unknown command, whatever its name, e.g. one disabled by rename-command.
+impl From<RespArray> for Unrecognized {
+    fn from(v: RespArray) -> Self {
+        let mut args = v.0.into_iter().map(|arg| match arg {
+            RespFrame::BulkString(arg) => arg.0,
+            arg => arg.to_string().into(),
+        });
+        Unrecognized {
+            name: args.next().unwrap_or_default(),
+            // the arguments after the name, as bytes
+            args: args.collect(),
+        }
+    }
+}
+
+impl Command {
+    // whether the connection runs the command with execute_async
+    pub fn is_async(&self) -> bool {
+        matches!(self, Command::Wait(_) | Command::Migrate(_))
+    }
+
+    pub async fn execute_async(self, ctx: &mut ExecContext<'_>) -> Result<RespFrame, CommandError> {
+        match self {
+            Command::Wait(cmd) => cmd.execute_async(ctx).await,
+            Command::Migrate(cmd) => cmd.execute_async(ctx).await,
+            cmd => cmd.execute(ctx),
         }
     }
 }
@@ -176,17 +934,13 @@ fn validate_command(
     n_args: usize,
 ) -> Result<(), CommandError> {
     if value.len() != n_args + names.len() {
-        return Err(CommandError::InvalidArgument(format!(
-            "{} command must have exactly {} argument",
-            names.join(" "),
-            n_args
-        )));
+        return Err(CommandError::WrongArity(names.join("|")));
     }
 
     for (i, name) in names.iter().enumerate() {
         match value[i] {
             RespFrame::BulkString(ref cmd) => {
-                if cmd.as_ref().to_ascii_lowercase() != name.as_bytes() {
+                if !cmd.eq_ignore_ascii_case(name.as_bytes()) {
                     return Err(CommandError::InvalidCommand(format!(
                         "Invalid command: expected {}, got {}",
                         name,
@@ -211,10 +965,68 @@ fn extract_args(value: RespArray, start: usize) -> Result<Vec<RespFrame>, Comman
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{RespDecoder, RespNull};
+    use crate::{BulkString, RespDecoder};
     use anyhow::Result;
     use bytes::BytesMut;
 
+    #[test]
+    fn test_commands_run_with_the_connection_they_need() -> Result<()> {
+        let backend = Backend::new();
+        let err = Quit.execute(&mut ExecContext::new(&backend)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "QUIT must be executed on a client connection"
+        );
+
+        let (sender, _receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut session = Session::new(sender);
+        let mut ctx = ExecContext::with_session(&backend, &mut session);
+        assert_eq!(ctx.db(), 0);
+        assert_eq!(Quit.execute(&mut ctx)?, RESP_OK.clone());
+        assert!(session.closing);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_waiting_commands_are_awaited() -> Result<()> {
+        let backend = Backend::new();
+        let wait = || {
+            Command::try_from(RespArray::new([
+                BulkString::from("WAIT").into(),
+                BulkString::from("0").into(),
+                BulkString::from("0").into(),
+            ]))
+        };
+        assert!(wait()?.is_async());
+        let err = wait()?
+            .execute_async(&mut ExecContext::new(&backend))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "WAIT must be executed on a client connection"
+        );
+
+        let (sender, _receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut session = Session::new(sender);
+        let mut ctx = ExecContext::with_session(&backend, &mut session);
+        assert_eq!(
+            wait()?.execute_async(&mut ctx).await?,
+            RespFrame::Integer(0)
+        );
+        // the others run as they are
+        let echo = Command::try_from(RespArray::new([
+            BulkString::from("ECHO").into(),
+            BulkString::from("hi").into(),
+        ]))?;
+        assert!(!echo.is_async());
+        assert_eq!(
+            echo.execute_async(&mut ctx).await?,
+            BulkString::from("hi").into()
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_lowercase_get() -> Result<()> {
         let mut buf = BytesMut::new();
@@ -222,8 +1034,8 @@ mod tests {
         let frame = RespArray::decode(&mut buf)?;
         let cmd: Command = frame.try_into()?;
         let backend = Backend::new();
-        let ret = cmd.execute(&backend);
-        assert_eq!(ret, RespFrame::Null(RespNull));
+        let ret = cmd.execute(&mut ExecContext::new(&backend))?;
+        assert_eq!(ret, RespFrame::null());
         Ok(())
     }
 
@@ -234,8 +1046,8 @@ mod tests {
         let frame = RespArray::decode(&mut buf)?;
         let cmd: Command = frame.try_into()?;
         let backend = Backend::new();
-        let ret = cmd.execute(&backend);
-        assert_eq!(ret, RespFrame::Null(RespNull));
+        let ret = cmd.execute(&mut ExecContext::new(&backend))?;
+        assert_eq!(ret, RespFrame::null());
         Ok(())
     }
 }
@@ -0,0 +1,396 @@
+use super::{
+    extract_args, validate_command, AsyncCommandExecutor, CommandError, CommandExecutor,
+    ExecContext, PSync, ReplConf, ReplicaOf, Role, Wait, RESP_OK,
+};
+use crate::{
+    network::Session, replication, Backend, BulkString, Replica, RespArray, RespFrame, SimpleError,
+    SimpleString, Snapshot, UNBLOCKED,
+};
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
+use tracing::info;
+
+impl CommandExecutor for ReplicaOf {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match replication::replicaof(ctx.backend, self.master) {
+            true => RESP_OK.clone(),
+            false => SimpleString::new("OK Already connected to specified master").into(),
+        })
+    }
+}
+
+impl CommandExecutor for ReplConf {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let backend = ctx.backend;
+        Ok(self.execute_in(ctx.session("REPLCONF")?, backend))
+    }
+}
+
+impl ReplConf {
+    fn execute_in(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        for (option, value) in self.options {
+            match option.to_ascii_lowercase().as_str() {
+                // the replica doesn't read replies on the replication link
+                "ack" => {
+                    if let (true, Ok(offset)) = (session.replica, value.parse()) {
+                        backend.replication.ack(session.id, offset);
+                    }
+                    session.skip_reply = true;
+                }
+                // only meaningful in the replication stream, see replica::sync_with
+                "getack" => {}
+                "listening-port" => match value.parse() {
+                    Ok(port) => session.replica_port = Some(port),
+                    Err(_) => {
+                        return SimpleError::new("ERR value is not an integer or out of range")
+                            .into()
+                    }
+                },
+                // nothing depends on the capabilities of the replica yet
+                "capa" | "ip-address" => {}
+                _ => {
+                    return SimpleError::new(format!(
+                        "ERR Unrecognized REPLCONF option: {}",
+                        option
+                    ))
+                    .into()
+                }
+            }
+        }
+        RESP_OK.clone()
+    }
+
+    // REPLCONF GETACK, sent by the master to get an acknowledgment of the stream.
+    pub(crate) fn is_getack(&self) -> bool {
+        self.options
+            .iter()
+            .any(|(option, _)| option.eq_ignore_ascii_case("getack"))
+    }
+}
+
+impl CommandExecutor for PSync {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let backend = ctx.backend;
+        Ok(self.execute_in(ctx.session("PSYNC")?, backend))
+    }
+}
+
+impl PSync {
+    // Runs with the exec lock held for writing, no write can slip between the snapshot and the
+    // registration of the replica.
+    fn execute_in(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        let replication = &backend.replication;
+        if replication.master().is_some() && !replication.link_up() {
+            return SimpleError::new("NOMASTERLINK Can't SYNC while not connected with my master")
+                .into();
+        }
+        if let Some(frames) = replication.continue_from(&self.replid, self.offset) {
+            info!(
+                "Partial resynchronization request accepted, sending {} frames from offset {}",
+                frames.len(),
+                self.offset
+            );
+            for frame in frames {
+                let _ = session.sender.send(frame);
+            }
+            register(session, backend);
+            return SimpleString::new(format!("CONTINUE {}", replication.replid())).into();
+        }
+        if self.replid != "?" {
+            info!(
+                "Partial resynchronization not accepted for {}:{}",
+                self.replid, self.offset
+            );
+        }
+        let mut payload = vec![];
+        if let Err(e) = Snapshot::capture(backend).write_to(&mut payload) {
+            return SimpleError::new(format!("ERR {}", e)).into();
+        }
+
+        // the reply is sent first, then the snapshot and the writes in the order of the channel
+        let _ = session.sender.send(BulkString::new(payload).into());
+        register(session, backend);
+        SimpleString::new(format!(
+            "FULLRESYNC {} {}",
+            replication.replid(),
+            replication.offset()
+        ))
+        .into()
+    }
+}
+
+// Inside MULTI there is no waiting, the replicas that already acknowledged the writes are counted.
+impl CommandExecutor for Wait {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let replication = &ctx.backend.replication;
+        Ok(match replication.master() {
+            Some(_) => wait_on_replica(),
+            None => RespFrame::Integer(replication.acked(replication.offset()) as i64),
+        })
+    }
+}
+
+impl AsyncCommandExecutor for Wait {
+    async fn execute_async(self, ctx: &mut ExecContext<'_>) -> Result<RespFrame, CommandError> {
+        let id = ctx.session("WAIT")?.id;
+        Ok(self.wait(id, ctx.backend).await)
+    }
+}
+
+impl Wait {
+    async fn wait(self, id: u64, backend: &Backend) -> RespFrame {
+        let replication = &backend.replication;
+        if replication.master().is_some() {
+            return wait_on_replica();
+        }
+        // a zero timeout blocks forever
+        let timeout = (self.timeout > 0).then(|| Duration::from_millis(self.timeout));
+        let mut blocked = backend.blocked.block(id, vec![], timeout);
+        match replication
+            .wait(&backend.config, self.numreplicas, &mut blocked)
+            .await
+        {
+            Some(acked) => RespFrame::Integer(acked as i64),
+            None => SimpleError::new(UNBLOCKED).into(),
+        }
+    }
+}
+
+fn wait_on_replica() -> RespFrame {
+    SimpleError::new("ERR WAIT cannot be used with replica instances.").into()
+}
+
+impl CommandExecutor for Role {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let replication = &ctx.backend.replication;
+        let offset = RespFrame::Integer(replication.offset() as i64);
+        let frames = match replication.master() {
+            Some((host, port)) => {
+                let state = if replication.link_up() {
+                    "connected"
+                } else {
+                    "connecting"
+                };
+                vec![
+                    BulkString::from("slave").into(),
+                    BulkString::from(host).into(),
+                    RespFrame::Integer(port as i64),
+                    BulkString::from(state).into(),
+                    offset,
+                ]
+            }
+            None => {
+                let replicas: Vec<RespFrame> = replication
+                    .replicas()
+                    .into_iter()
+                    .map(|replica| {
+                        let fields: Vec<RespFrame> = [
+                            replica.ip.to_string(),
+                            replica.port.to_string(),
+                            replica.ack_offset.to_string(),
+                        ]
+                        .into_iter()
+                        .map(|field| BulkString::from(field).into())
+                        .collect();
+                        RespArray::new(fields).into()
+                    })
+                    .collect();
+                vec![
+                    BulkString::from("master").into(),
+                    offset,
+                    RespArray::new(replicas).into(),
+                ]
+            }
+        };
+        Ok(RespArray::new(frames).into())
+    }
+}
+
+// From now on the connection receives the replication stream.
+fn register(session: &mut Session, backend: &Backend) {
+    let ip = backend
+        .clients
+        .get(session.id)
+        .map_or(IpAddr::V4(Ipv4Addr::LOCALHOST), |client| client.addr.ip());
+    let port = session.replica_port.unwrap_or_default();
+    backend
+        .replication
+        .add_replica(session.id, Replica::new(ip, port, session.sender.clone()));
+    session.replica = true;
+    backend
+        .clients
+        .update(session.id, |client| client.replica = true);
+    info!("Replica {}:{} synchronized", ip, port);
+}
+
+impl TryFrom<RespArray> for ReplicaOf {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let args = string_args(value, "replicaof")?;
+        let [host, port] = args.as_slice() else {
+            return Err(CommandError::WrongArity("replicaof".to_string()));
+        };
+        if host.eq_ignore_ascii_case("no") && port.eq_ignore_ascii_case("one") {
+            return Ok(ReplicaOf { master: None });
+        }
+        let port = port
+            .parse()
+            .map_err(|_| CommandError::InvalidArgument("Invalid master port".to_string()))?;
+        Ok(ReplicaOf {
+            master: Some((host.clone(), port)),
+        })
+    }
+}
+
+impl TryFrom<RespArray> for ReplConf {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let args = string_args(value, "replconf")?;
+        if args.len() % 2 != 0 {
+            return Err(CommandError::InvalidArgument("syntax error".to_string()));
+        }
+        let mut options = vec![];
+        let mut args = args.into_iter();
+        while let (Some(option), Some(value)) = (args.next(), args.next()) {
+            options.push((option, value));
+        }
+        Ok(ReplConf { options })
+    }
+}
+
+impl TryFrom<RespArray> for PSync {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let args = string_args(value, "psync")?;
+        let [replid, offset] = args.as_slice() else {
+            return Err(CommandError::WrongArity("psync".to_string()));
+        };
+        let offset = offset.parse().map_err(|_| {
+            CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+        })?;
+        Ok(PSync {
+            replid: replid.clone(),
+            offset,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Wait {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let args = string_args(value, "wait")?;
+        let [numreplicas, timeout] = args.as_slice() else {
+            return Err(CommandError::WrongArity("wait".to_string()));
+        };
+        let invalid =
+            || CommandError::InvalidArgument("value is not an integer or out of range".to_string());
+        let numreplicas = numreplicas.parse().map_err(|_| invalid())?;
+        let timeout: i64 = timeout.parse().map_err(|_| invalid())?;
+        if timeout < 0 {
+            return Err(CommandError::InvalidArgument(
+                "timeout is negative".to_string(),
+            ));
+        }
+        Ok(Wait {
+            numreplicas,
+            timeout: timeout as u64,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Role {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["role"], 0)?;
+        Ok(Role)
+    }
+}
+
+fn string_args(value: RespArray, name: &str) -> Result<Vec<String>, CommandError> {
+    extract_args(value, 1)?
+        .into_iter()
+        .map(|arg| match arg {
+            RespFrame::BulkString(arg) => Ok(String::from_utf8(arg.0.into())?),
+            _ => Err(CommandError::InvalidArgument(format!(
+                "{} arguments must be BulkString",
+                name
+            ))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::Client, RespDecoder};
+    use anyhow::Result;
+    use bytes::BytesMut;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_replicaof_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$9\r\nREPLICAOF\r\n$9\r\nlocalhost\r\n$4\r\n6380\r\n");
+        let cmd: ReplicaOf = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(cmd.master, Some(("localhost".to_string(), 6380)));
+
+        buf.extend_from_slice(b"*3\r\n$7\r\nslaveof\r\n$2\r\nno\r\n$3\r\nONE\r\n");
+        let cmd: ReplicaOf = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(cmd.master, None);
+        // already a master
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&Backend::new()))?,
+            RESP_OK.clone()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_role_master() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*1\r\n$4\r\nROLE\r\n");
+        let cmd: Role = RespArray::decode(&mut buf)?.try_into()?;
+        let RespFrame::Array(reply) = cmd.execute(&mut ExecContext::new(&Backend::new()))? else {
+            panic!("ROLE must reply with an array");
+        };
+        assert_eq!(reply[0], BulkString::from("master").into());
+        assert_eq!(reply[1], RespFrame::Integer(0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wait_client_unblock() -> Result<()> {
+        let backend = Backend::new();
+        let (sender, _replica) = mpsc::unbounded_channel();
+        let ip = "127.0.0.1".parse()?;
+        backend
+            .replication
+            .add_replica(1, Replica::new(ip, 6380, sender.clone()));
+        let id = Session::new(sender).id;
+        let cloned = backend.clone();
+        // more replicas than there are, it never returns on its own
+        let wait = Wait {
+            numreplicas: 2,
+            timeout: 0,
+        };
+        let waiting = tokio::spawn(async move { wait.wait(id, &cloned).await });
+        while backend.blocked.is_empty() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$6\r\nCLIENT\r\n$7\r\nUNBLOCK\r\n");
+        buf.extend_from_slice(
+            format!("${}\r\n{}\r\n$5\r\nERROR\r\n", id.to_string().len(), id).as_bytes(),
+        );
+        let unblock: Client = RespArray::decode(&mut buf)?.try_into()?;
+        let (other, _) = mpsc::unbounded_channel();
+        let reply = unblock.execute_in(&mut Session::new(other), &backend);
+        assert_eq!(reply, RespFrame::Integer(1));
+        assert_eq!(waiting.await?, SimpleError::new(UNBLOCKED).into());
+        assert!(backend.blocked.is_empty());
+        Ok(())
+    }
+}
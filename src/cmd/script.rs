@@ -0,0 +1,334 @@
+use super::{
+    extract_args, CommandError, CommandExecutor, Eval, EvalSha, ExecContext, FCall, Function,
+    Script, Subcommand, RESP_OK,
+};
+use crate::{script, BulkString, RespArray, RespFrame, RespMap, SimpleError};
+use bytes::Bytes;
+
+impl CommandExecutor for Eval {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        ctx.backend.scripts.load(self.script.as_str());
+        Ok(script::eval(
+            ctx.backend,
+            ctx.user(),
+            &self.script,
+            &self.keys,
+            &self.args,
+        ))
+    }
+}
+
+impl CommandExecutor for EvalSha {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match ctx.backend.scripts.get(&self.sha) {
+            Some(body) => script::eval(ctx.backend, ctx.user(), &body, &self.keys, &self.args),
+            None => SimpleError::new("NOSCRIPT No matching script. Please use EVAL.").into(),
+        })
+    }
+}
+
+impl CommandExecutor for Script {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match self {
+            Script::Load(body) => BulkString::from(ctx.backend.scripts.load(body)).into(),
+            Script::Exists(shas) => RespArray::new(
+                shas.iter()
+                    .map(|sha| RespFrame::Integer(ctx.backend.scripts.exists(sha) as i64))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            Script::Flush => {
+                ctx.backend.scripts.flush();
+                RESP_OK.clone()
+            }
+        })
+    }
+}
+
+impl CommandExecutor for Function {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match self {
+            Function::Load { code, replace } => match ctx.backend.functions.load(code, replace) {
+                Ok(name) => BulkString::from(name).into(),
+                Err(e) => SimpleError::new(e).into(),
+            },
+            Function::Delete(name) => match ctx.backend.functions.delete(&name) {
+                true => RESP_OK.clone(),
+                false => SimpleError::new("ERR Library not found").into(),
+            },
+            Function::Flush => {
+                ctx.backend.functions.flush();
+                RESP_OK.clone()
+            }
+            Function::List => {
+                let libraries = ctx
+                    .backend
+                    .functions
+                    .libraries()
+                    .into_iter()
+                    .map(|library| {
+                        let functions = library
+                            .functions
+                            .into_iter()
+                            .map(|name| {
+                                let mut function = RespMap::new();
+                                function.insert(BulkString::from("name"), BulkString::from(name));
+                                function.insert(BulkString::from("description"), RespFrame::null());
+                                function.insert(BulkString::from("flags"), crate::RespSet::new([]));
+                                function.into()
+                            })
+                            .collect::<Vec<RespFrame>>();
+                        let mut map = RespMap::new();
+                        map.insert(
+                            BulkString::from("library_name"),
+                            BulkString::from(library.name),
+                        );
+                        map.insert(BulkString::from("engine"), BulkString::from("LUA"));
+                        map.insert(BulkString::from("functions"), RespArray::new(functions));
+                        map.into()
+                    })
+                    .collect::<Vec<RespFrame>>();
+                RespArray::new(libraries).into()
+            }
+        })
+    }
+}
+
+impl CommandExecutor for FCall {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(script::fcall(
+            ctx.backend,
+            ctx.user(),
+            &self.function,
+            &self.keys,
+            &self.args,
+        ))
+    }
+}
+
+impl TryFrom<RespArray> for Eval {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (script, keys, args) = parse_script_args(value, "eval")?;
+        Ok(Eval { script, keys, args })
+    }
+}
+
+impl TryFrom<RespArray> for EvalSha {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (sha, keys, args) = parse_script_args(value, "evalsha")?;
+        Ok(EvalSha { sha, keys, args })
+    }
+}
+
+impl TryFrom<RespArray> for Script {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = Subcommand::parse(value, "script")?;
+        let mut args = sub.strings()?;
+        match (sub.name.as_str(), args.len()) {
+            ("load", 1) => Ok(Script::Load(args.remove(0))),
+            ("exists", n) if n > 0 => Ok(Script::Exists(args)),
+            ("flush", 0) => Ok(Script::Flush),
+            ("flush", 1)
+                if args[0].eq_ignore_ascii_case("async")
+                    || args[0].eq_ignore_ascii_case("sync") =>
+            {
+                Ok(Script::Flush)
+            }
+            ("load" | "exists" | "flush", _) => Err(sub.wrong_arity()),
+            _ => Err(sub.unknown()),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Function {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = Subcommand::parse(value, "function")?;
+        let mut args = sub.strings()?;
+        match (sub.name.as_str(), args.len()) {
+            ("load", 1) => Ok(Function::Load {
+                code: args.remove(0),
+                replace: false,
+            }),
+            ("load", 2) if args[0].eq_ignore_ascii_case("replace") => Ok(Function::Load {
+                code: args.remove(1),
+                replace: true,
+            }),
+            ("delete", 1) => Ok(Function::Delete(args.remove(0))),
+            ("flush", 0) => Ok(Function::Flush),
+            ("flush", 1)
+                if args[0].eq_ignore_ascii_case("async")
+                    || args[0].eq_ignore_ascii_case("sync") =>
+            {
+                Ok(Function::Flush)
+            }
+            ("list", 0) => Ok(Function::List),
+            ("load" | "delete" | "flush" | "list", _) => Err(sub.wrong_arity()),
+            _ => Err(sub.unknown()),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for FCall {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let (function, keys, args) = parse_script_args(value, "fcall")?;
+        Ok(FCall {
+            function,
+            keys,
+            args,
+        })
+    }
+}
+
+// <name> script numkeys [key [key ...]] [arg [arg ...]]
+fn parse_script_args(
+    value: RespArray,
+    name: &str,
+) -> Result<(String, Vec<Bytes>, Vec<Bytes>), CommandError> {
+    if value.len() < 3 {
+        return Err(CommandError::InvalidArgument(format!(
+            "{name} command needs at least 2 arguments"
+        )));
+    }
+
+    let mut args = extract_args(value, 1)?
+        .into_iter()
+        .map(|arg| match arg {
+            RespFrame::BulkString(arg) => Ok(arg.0),
+            _ => Err(CommandError::InvalidArgument(format!(
+                "{name} arguments must be BulkString"
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // only the script (or function name) and numkeys need to be text
+    let script = String::from_utf8(args.remove(0).to_vec())?;
+    let numkeys: usize = std::str::from_utf8(&args.remove(0))
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| {
+            CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+        })?;
+    if numkeys > args.len() {
+        return Err(CommandError::InvalidArgument(
+            "Number of keys can't be greater than number of args".to_string(),
+        ));
+    }
+    let argv = args.split_off(numkeys);
+    Ok((script, args, argv))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, RespDecoder};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_eval_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(
+            b"*5\r\n$4\r\nEVAL\r\n$8\r\nreturn 1\r\n$1\r\n1\r\n$3\r\nfoo\r\n$3\r\nbar\r\n",
+        );
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Eval = frame.try_into()?;
+        assert_eq!(result.script, "return 1");
+        assert_eq!(result.keys, ["foo"]);
+        assert_eq!(result.args, ["bar"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_numkeys_too_large() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$4\r\neval\r\n$8\r\nreturn 1\r\n$1\r\n1\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(Eval::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_evalsha_command() {
+        let backend = Backend::new();
+        let sha = script::sha1_hex("return ARGV[1]");
+        let cmd = EvalSha {
+            sha: sha.clone(),
+            keys: vec![],
+            args: vec!["hi".into()],
+        };
+        assert!(matches!(
+            cmd.execute(&mut ExecContext::new(&backend)).unwrap(),
+            RespFrame::Error(_)
+        ));
+
+        backend.scripts.load("return ARGV[1]");
+        let cmd = EvalSha {
+            sha,
+            keys: vec![],
+            args: vec!["hi".into()],
+        };
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend)).unwrap(),
+            RespFrame::BulkString(b"hi".into())
+        );
+    }
+
+    #[test]
+    fn test_script_load_exists_flush() -> Result<()> {
+        let backend = Backend::new();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$6\r\nSCRIPT\r\n$4\r\nLOAD\r\n$8\r\nreturn 1\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: Script = frame.try_into()?;
+        let sha = script::sha1_hex("return 1");
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            BulkString::from(sha.clone()).into()
+        );
+
+        let cmd = Script::Exists(vec![sha.to_ascii_uppercase(), "nosuchsha".to_string()]);
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespArray::new([RespFrame::Integer(1), RespFrame::Integer(0)]).into()
+        );
+
+        Script::Flush.execute(&mut ExecContext::new(&backend))?;
+        assert!(!backend.scripts.exists(&sha));
+        Ok(())
+    }
+
+    #[test]
+    fn test_function_load_and_fcall() -> Result<()> {
+        let backend = Backend::new();
+        let code = "#!lua name=lib\nredis.register_function('hello', function(keys, args) return 'hello ' .. args[1] end)";
+        let cmd = Function::Load {
+            code: code.to_string(),
+            replace: false,
+        };
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            BulkString::from("lib").into()
+        );
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*4\r\n$5\r\nFCALL\r\n$5\r\nhello\r\n$1\r\n0\r\n$5\r\nworld\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let cmd: FCall = frame.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            BulkString::from("hello world").into()
+        );
+
+        let cmd = Function::Delete("lib".to_string());
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RESP_OK.clone()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use super::{
+    extract_args, validate_command, BgRewriteAof, BgSave, CommandError, CommandExecutor,
+    ExecContext, LastSave, Save, Shutdown, RESP_OK,
+};
+use crate::{persistence, RespArray, RespFrame, SimpleError, SimpleString};
+use tracing::{info, warn};
+
+impl CommandExecutor for Shutdown {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        info!("User requested shutdown...");
+        Ok(
+            // by default save only if snapshotting is configured
+            match persistence::shutdown(ctx.backend, self.save) {
+                Ok(()) => RESP_OK.clone(),
+                Err(e) => {
+                    warn!("Error trying to save the DB, can't exit: {}", e);
+                    SimpleError::new("ERR Errors trying to SHUTDOWN. Check logs.").into()
+                }
+            },
+        )
+    }
+}
+
+impl CommandExecutor for Save {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        if ctx.backend.persistence.bgsave_in_progress() {
+            return Ok(SimpleError::new("ERR Background save already in progress").into());
+        }
+        Ok(match persistence::save(ctx.backend) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => SimpleError::new(format!("ERR {}", e)).into(),
+        })
+    }
+}
+
+impl CommandExecutor for BgSave {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match persistence::bgsave(ctx.backend) {
+            true => SimpleString::new("Background saving started").into(),
+            false => SimpleError::new("ERR Background save already in progress").into(),
+        })
+    }
+}
+
+impl CommandExecutor for BgRewriteAof {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match persistence::bgrewrite(ctx.backend) {
+            true => SimpleString::new("Background append only file rewriting started").into(),
+            false => {
+                SimpleError::new("ERR Background append only file rewriting already in progress")
+                    .into()
+            }
+        })
+    }
+}
+
+impl CommandExecutor for LastSave {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(RespFrame::Integer(
+            ctx.backend.persistence.last_save() as i64
+        ))
+    }
+}
+
+impl TryFrom<RespArray> for Shutdown {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut save = None;
+        for arg in extract_args(value, 1)? {
+            match arg {
+                RespFrame::BulkString(arg) if arg.eq_ignore_ascii_case(b"save") => {
+                    save = Some(true)
+                }
+                RespFrame::BulkString(arg) if arg.eq_ignore_ascii_case(b"nosave") => {
+                    save = Some(false)
+                }
+                _ => {
+                    return Err(CommandError::InvalidArgument(
+                        "shutdown only accepts SAVE or NOSAVE".to_string(),
+                    ))
+                }
+            }
+        }
+        Ok(Shutdown { save })
+    }
+}
+
+impl TryFrom<RespArray> for Save {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["save"], 0)?;
+        Ok(Save)
+    }
+}
+
+impl TryFrom<RespArray> for BgSave {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        // BGSAVE SCHEDULE is accepted, the save starts right away anyway
+        match extract_args(value, 1)?.as_slice() {
+            [] => Ok(BgSave),
+            [RespFrame::BulkString(arg)] if arg.eq_ignore_ascii_case(b"schedule") => Ok(BgSave),
+            _ => Err(CommandError::InvalidArgument(
+                "bgsave only accepts SCHEDULE".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for BgRewriteAof {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bgrewriteaof"], 0)?;
+        Ok(BgRewriteAof)
+    }
+}
+
+impl TryFrom<RespArray> for LastSave {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lastsave"], 0)?;
+        Ok(LastSave)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, BulkString, RespDecoder};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[tokio::test]
+    async fn test_shutdown_command() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$8\r\nSHUTDOWN\r\n$6\r\nNOSAVE\r\n");
+        let cmd: Shutdown = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(cmd.save, Some(false));
+
+        let backend = Backend::new();
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RESP_OK.clone()
+        );
+        backend.shutdown_requested().await;
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_command() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("simple-redis-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let backend = Backend::new();
+        backend
+            .config
+            .set(&[("dir".to_string(), dir.display().to_string())])?;
+        backend.set("k".into(), BulkString::new("v").into());
+        assert_eq!(backend.persistence.dirty(), 1);
+
+        assert_eq!(
+            Save.execute(&mut ExecContext::new(&backend))?,
+            RESP_OK.clone()
+        );
+        assert_eq!(backend.persistence.dirty(), 0);
+        assert!(dir.join("dump.rdb").exists());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
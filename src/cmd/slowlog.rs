@@ -0,0 +1,85 @@
+use super::{CommandError, CommandExecutor, ExecContext, SlowLogCmd, Subcommand, RESP_OK};
+use crate::{RespArray, RespFrame};
+
+impl CommandExecutor for SlowLogCmd {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(match self {
+            SlowLogCmd::Get(count) => RespArray::new(
+                ctx.backend
+                    .slowlog
+                    .get(count)
+                    .iter()
+                    .map(|entry| entry.to_frame())
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            SlowLogCmd::Len => RespFrame::Integer(ctx.backend.slowlog.len() as i64),
+            SlowLogCmd::Reset => {
+                ctx.backend.slowlog.reset();
+                RESP_OK.clone()
+            }
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SlowLogCmd {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let sub = Subcommand::parse(value, "slowlog")?;
+        match (sub.name.as_str(), sub.strings()?.as_slice()) {
+            ("get", []) => Ok(SlowLogCmd::Get(Some(10))),
+            ("get", [count]) => {
+                match count.parse::<i64>() {
+                    // -1 returns the whole log
+                    Ok(-1) => Ok(SlowLogCmd::Get(None)),
+                    Ok(count) if count >= 0 => Ok(SlowLogCmd::Get(Some(count as usize))),
+                    _ => Err(CommandError::InvalidArgument(
+                        "count should be greater than or equal to -1".to_string(),
+                    )),
+                }
+            }
+            ("len", []) => Ok(SlowLogCmd::Len),
+            ("reset", []) => Ok(SlowLogCmd::Reset),
+            ("get" | "len" | "reset", _) => Err(sub.wrong_arity()),
+            _ => Err(sub.unknown()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, RespDecoder};
+    use anyhow::Result;
+    use bytes::BytesMut;
+    use std::time::Duration;
+
+    #[test]
+    fn test_slowlog_command() -> Result<()> {
+        let backend = Backend::new();
+        let args = RespArray::new([b"debug".into(), b"sleep".into(), b"1".into()]).into();
+        backend.slowlog.record(
+            &args,
+            Duration::from_secs(1),
+            "127.0.0.1:5000".to_string(),
+            String::new(),
+            128,
+        );
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$7\r\nSLOWLOG\r\n$3\r\nlen\r\n");
+        let cmd: SlowLogCmd = RespArray::decode(&mut buf)?.try_into()?;
+        assert_eq!(
+            cmd.execute(&mut ExecContext::new(&backend))?,
+            RespFrame::Integer(1)
+        );
+
+        buf.extend_from_slice(b"*3\r\n$7\r\nSLOWLOG\r\n$3\r\nget\r\n$2\r\n-1\r\n");
+        let cmd: SlowLogCmd = RespArray::decode(&mut buf)?.try_into()?;
+        match cmd.execute(&mut ExecContext::new(&backend))? {
+            RespFrame::Array(entries) => assert_eq!(entries.len(), 1),
+            frame => panic!("unexpected reply: {:?}", frame),
+        }
+        Ok(())
+    }
+}
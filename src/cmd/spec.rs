@@ -0,0 +1,530 @@
+use super::{
+    subcommand::{is_container, CONTAINERS},
+    Command, CommandError,
+};
+use crate::{RespArray, RespFrame};
+use bytes::Bytes;
+use lazy_static::lazy_static;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+// the positions of the keys in a request, the name at 0
+type FindKeys = fn(&[RespFrame]) -> Vec<usize>;
+
+// Static metadata of the supported commands, reported by COMMAND, and the parser of each. It's
+// the one list of the commands: requests are dispatched by it and ACL, cluster routing and
+// COMMAND read their flags and keys from it.
+#[derive(Debug)]
+pub(crate) struct CommandSpec {
+    pub(crate) name: &'static str,
+    // positive: exact number of arguments including the name, negative: at least -arity
+    pub(crate) arity: i64,
+    pub(crate) flags: &'static [&'static str],
+    pub(crate) first_key: i64,
+    pub(crate) last_key: i64,
+    pub(crate) step: i64,
+    // the positions of the keys of the commands whose keys move, like EVAL, instead of the range
+    pub(crate) find_keys: Option<FindKeys>,
+    pub(crate) group: &'static str,
+    pub(crate) summary: &'static str,
+    pub(crate) since: &'static str,
+    // the request, its arity checked already, into the command
+    pub(crate) parse: fn(RespArray) -> Result<Command, CommandError>,
+}
+
+macro_rules! spec {
+    ($name:literal, $cmd:ident, $arity:literal, [$($flag:literal),*], $first:literal, $last:literal, $step:literal, $group:literal, $since:literal, $summary:literal) => {
+        spec!($name, $cmd, $arity, [$($flag),*], $first, $last, $step, $group, $since, $summary, None)
+    };
+    ($name:literal, $cmd:ident, $arity:literal, [$($flag:literal),*], $first:literal, $last:literal, $step:literal, $group:literal, $since:literal, $summary:literal, keys: $find:ident) => {
+        spec!($name, $cmd, $arity, [$($flag),*], $first, $last, $step, $group, $since, $summary, Some($find))
+    };
+    ($name:literal, $cmd:ident, $arity:literal, [$($flag:literal),*], $first:literal, $last:literal, $step:literal, $group:literal, $since:literal, $summary:literal, $find:expr) => {
+        CommandSpec {
+            name: $name,
+            arity: $arity,
+            flags: &[$($flag),*],
+            first_key: $first,
+            last_key: $last,
+            step: $step,
+            find_keys: $find,
+            group: $group,
+            summary: $summary,
+            since: $since,
+            parse: |v| Ok(super::$cmd::try_from(v)?.into()),
+        }
+    };
+}
+
+#[rustfmt::skip]
+pub(crate) const COMMANDS: &[CommandSpec] = &[
+    spec!("get", Get, 2, ["readonly", "fast"], 1, 1, 1, "string", "1.0.0", "Returns the string value of a key."),
+    spec!("set", Set, 3, ["write", "denyoom"], 1, 1, 1, "string", "1.0.0", "Sets the string value of a key."),
+    spec!("echo", Echo, 2, ["fast"], 0, 0, 0, "connection", "1.0.0", "Returns the given string."),
+    spec!("hget", HGet, 3, ["readonly", "fast"], 1, 1, 1, "hash", "2.0.0", "Returns the value of a field in a hash."),
+    spec!("hset", HSet, 4, ["write", "denyoom", "fast"], 1, 1, 1, "hash", "2.0.0", "Sets the value of a field in a hash."),
+    spec!("hmget", HMGet, -3, ["readonly", "fast"], 1, 1, 1, "hash", "2.0.0", "Returns the values of all fields in a hash."),
+    spec!("hgetall", HGetAll, 2, ["readonly"], 1, 1, 1, "hash", "2.0.0", "Returns all fields and values in a hash."),
+    spec!("sadd", SAdd, -3, ["write", "denyoom", "fast"], 1, 1, 1, "set", "1.0.0", "Adds one or more members to a set."),
+    spec!("sismember", SIsMember, 3, ["readonly", "fast"], 1, 1, 1, "set", "1.0.0", "Determines whether a member belongs to a set."),
+    spec!("client", Client, -2, ["noscript", "loading", "stale"], 0, 0, 0, "connection", "2.4.0", "A container for client connection commands."),
+    spec!("monitor", Monitor, 1, ["admin", "noscript", "loading", "stale"], 0, 0, 0, "server", "1.0.0", "Listens for all requests received by the server in real-time."),
+    spec!("auth", Auth, -2, ["noscript", "loading", "stale", "fast", "no_auth"], 0, 0, 0, "connection", "1.0.0", "Authenticates the connection."),
+    spec!("hello", Hello, -1, ["noscript", "loading", "stale", "fast", "no_auth"], 0, 0, 0, "connection", "6.0.0", "Handshakes with the Redis server."),
+    spec!("acl", AclCmd, -2, ["admin", "noscript", "loading", "stale"], 0, 0, 0, "server", "6.0.0", "A container for Access List Control commands."),
+    spec!("quit", Quit, -1, ["noscript", "loading", "stale", "fast", "no_auth"], 0, 0, 0, "connection", "1.0.0", "Closes the connection."),
+    spec!("multi", Multi, 1, ["noscript", "loading", "stale", "fast"], 0, 0, 0, "transactions", "1.2.0", "Starts a transaction."),
+    spec!("exec", Exec, 1, ["noscript", "loading", "stale"], 0, 0, 0, "transactions", "1.2.0", "Executes all commands in a transaction."),
+    spec!("discard", Discard, 1, ["noscript", "loading", "stale", "fast"], 0, 0, 0, "transactions", "2.0.0", "Discards a transaction."),
+    spec!("watch", Watch, -2, ["noscript", "loading", "stale", "fast"], 1, -1, 1, "transactions", "2.2.0", "Monitors changes to keys to determine the execution of a transaction."),
+    spec!("unwatch", Unwatch, 1, ["noscript", "loading", "stale", "fast"], 0, 0, 0, "transactions", "2.2.0", "Forgets about watched keys of a transaction."),
+    spec!("eval", Eval, -3, ["noscript", "may_replicate", "stale", "movablekeys"], 0, 0, 0, "scripting", "2.6.0", "Executes a server-side Lua script.", keys: script_keys),
+    spec!("evalsha", EvalSha, -3, ["noscript", "may_replicate", "stale", "movablekeys"], 0, 0, 0, "scripting", "2.6.0", "Executes a server-side Lua script by SHA1 digest.", keys: script_keys),
+    spec!("script", Script, -2, ["noscript"], 0, 0, 0, "scripting", "2.6.0", "A container for Lua scripts management commands."),
+    spec!("function", Function, -2, ["noscript"], 0, 0, 0, "scripting", "7.0.0", "A container for function commands."),
+    spec!("fcall", FCall, -3, ["noscript", "may_replicate", "stale", "movablekeys"], 0, 0, 0, "scripting", "7.0.0", "Invokes a function.", keys: script_keys),
+    spec!("info", Info, -1, ["loading", "stale"], 0, 0, 0, "server", "1.0.0", "Returns information and statistics about the server."),
+    spec!("config", ConfigCmd, -2, ["admin", "noscript", "loading", "stale"], 0, 0, 0, "server", "2.0.0", "A container for server configuration commands."),
+    spec!("debug", DebugCmd, -2, ["admin", "noscript", "loading", "stale"], 0, 0, 0, "server", "1.0.0", "A container for debugging commands."),
+    spec!("slowlog", SlowLogCmd, -2, ["admin", "loading", "stale"], 0, 0, 0, "server", "2.2.12", "A container for slow log commands."),
+    spec!("shutdown", Shutdown, -1, ["admin", "noscript", "loading", "stale"], 0, 0, 0, "server", "1.0.0", "Synchronously saves the database(s) to disk and shuts down the Redis server."),
+    spec!("save", Save, 1, ["admin", "noscript"], 0, 0, 0, "server", "1.0.0", "Synchronously saves the database(s) to disk."),
+    spec!("bgsave", BgSave, -1, ["admin", "noscript"], 0, 0, 0, "server", "1.0.0", "Asynchronously saves the database(s) to disk."),
+    spec!("bgrewriteaof", BgRewriteAof, 1, ["admin", "noscript"], 0, 0, 0, "server", "1.0.0", "Asynchronously rewrites the append-only file to disk."),
+    spec!("lastsave", LastSave, 1, ["loading", "stale", "fast"], 0, 0, 0, "server", "1.0.0", "Returns the Unix timestamp of the last successful save to disk."),
+    spec!("lolwut", Lolwut, -1, ["readonly", "fast"], 0, 0, 0, "server", "5.0.0", "Displays computer art and the Redis version"),
+    spec!("replicaof", ReplicaOf, 3, ["admin", "noscript", "stale"], 0, 0, 0, "server", "5.0.0", "Configures a server as replica of another, or promotes it to a master."),
+    spec!("slaveof", ReplicaOf, 3, ["admin", "noscript", "stale"], 0, 0, 0, "server", "1.0.0", "Sets a Redis server as a replica of another, or promotes it to being a master."),
+    spec!("replconf", ReplConf, -1, ["admin", "noscript", "loading", "stale"], 0, 0, 0, "server", "3.0.0", "An internal command for configuring the replication stream."),
+    spec!("psync", PSync, -3, ["admin", "noscript"], 0, 0, 0, "server", "2.8.0", "An internal command used in replication."),
+    spec!("cluster", ClusterCmd, -2, [], 0, 0, 0, "cluster", "3.0.0", "A container for Redis Cluster commands."),
+    spec!("del", Del, -2, ["write"], 1, -1, 1, "generic", "1.0.0", "Deletes one or more keys."),
+    spec!("expire", Expire, 3, ["write", "fast"], 1, 1, 1, "generic", "1.0.0", "Sets the expiration time of a key in seconds."),
+    spec!("pexpire", Expire, 3, ["write", "fast"], 1, 1, 1, "generic", "2.6.0", "Sets the expiration time of a key in milliseconds."),
+    spec!("expireat", Expire, 3, ["write", "fast"], 1, 1, 1, "generic", "1.2.0", "Sets the expiration time of a key to a Unix timestamp."),
+    spec!("pexpireat", Expire, 3, ["write", "fast"], 1, 1, 1, "generic", "2.6.0", "Sets the expiration time of a key to a Unix milliseconds timestamp."),
+    spec!("ttl", Ttl, 2, ["readonly", "fast"], 1, 1, 1, "generic", "1.0.0", "Returns the expiration time in seconds of a key."),
+    spec!("pttl", Ttl, 2, ["readonly", "fast"], 1, 1, 1, "generic", "2.6.0", "Returns the expiration time in milliseconds of a key."),
+    spec!("persist", Persist, 2, ["write", "fast"], 1, 1, 1, "generic", "2.2.0", "Removes the expiration time of a key."),
+    spec!("dump", Dump, 2, ["readonly"], 1, 1, 1, "generic", "2.6.0", "Returns a serialized representation of the value stored at a key."),
+    spec!("restore", Restore, -4, ["write", "denyoom"], 1, 1, 1, "generic", "2.6.0", "Creates a key from the serialized representation of a value."),
+    spec!("restore-asking", Restore, -4, ["write", "denyoom", "asking"], 1, 1, 1, "server", "3.0.0", "An internal command for migrating keys in a cluster."),
+    spec!("migrate", Migrate, -6, ["write", "movablekeys"], 3, 3, 1, "generic", "2.6.0", "Atomically transfers a key from one Redis instance to another.", keys: migrate_keys),
+    spec!("asking", Asking, 1, ["fast"], 0, 0, 0, "cluster", "3.0.0", "Signals that a cluster client is following an -ASK redirect."),
+    spec!("role", Role, 1, ["noscript", "loading", "stale", "fast"], 0, 0, 0, "server", "2.8.12", "Returns the replication role."),
+    spec!("wait", Wait, 3, ["noscript"], 0, 0, 0, "generic", "3.0.0", "Blocks until the asynchronous replication of all preceding write commands sent by the connection is completed."),
+    spec!("command", Commands, -1, ["loading", "stale"], 0, 0, 0, "server", "2.8.13", "Returns detailed information about all commands."),
+    spec!("msetnx", MSetNx, -3, ["write", "denyoom"], 1, -1, 2, "string", "1.0.1", "Atomically modifies the string values of one or more keys only when all keys don't exist."),
+    spec!("rename", Rename, 3, ["write"], 1, 2, 1, "generic", "1.0.0", "Renames a key and overwrites the destination."),
+    spec!("object", ObjectCmd, -2, ["readonly"], 2, 2, 1, "generic", "2.2.3", "A container for object introspection commands."),
+    spec!("smove", SMove, 4, ["write", "fast"], 1, 2, 1, "set", "1.0.0", "Moves a member from one set to another."),
+    spec!("memory", MemoryCmd, -2, ["readonly"], 2, 2, 1, "server", "4.0.0", "A container for memory diagnostics commands."),
+];
+
+// The name of the command in a request, with the subcommand for containers, e.g. `client|list`.
+pub fn command_name(frame: &RespFrame) -> String {
+    match frame {
+        RespFrame::Array(args) => request_name(args),
+        _ => "NULL".to_string(),
+    }
+}
+
+// command_name of the arguments of a request
+pub(crate) fn request_name(args: &[RespFrame]) -> String {
+    let name = match args.first() {
+        Some(RespFrame::BulkString(name)) => String::from_utf8_lossy(name).to_ascii_lowercase(),
+        _ => return "NULL".to_string(),
+    };
+    match args.get(1) {
+        Some(RespFrame::BulkString(sub)) if is_container(&name) => {
+            format!(
+                "{}|{}",
+                name,
+                String::from_utf8_lossy(sub).to_ascii_lowercase()
+            )
+        }
+        _ => name,
+    }
+}
+
+// command_name of a request, without allocating for the commands and subcommands of the registry:
+// their names are static, the others are lowercased into a new string.
+pub(crate) fn request_static_name(frame: &RespFrame) -> Cow<'static, str> {
+    let RespFrame::Array(args) = frame else {
+        return "NULL".into();
+    };
+    match static_name(args) {
+        Some(name) => name.into(),
+        None => request_name(args).into(),
+    }
+}
+
+fn static_name(args: &[RespFrame]) -> Option<&'static str> {
+    let Some(RespFrame::BulkString(name)) = args.first() else {
+        return None;
+    };
+    let spec = lookup(std::str::from_utf8(name).ok()?)?;
+    let sub = match args.get(1) {
+        Some(RespFrame::BulkString(sub)) if is_container(spec.name) => sub,
+        _ => return Some(spec.name),
+    };
+    // container|subcommand lowercased in a buffer on the stack, like lookup
+    let mut buf = [0u8; 2 * MAX_NAME_LEN + 1];
+    let full = buf.get_mut(..spec.name.len() + 1 + sub.len())?;
+    let (container, rest) = full.split_at_mut(spec.name.len());
+    container.copy_from_slice(spec.name.as_bytes());
+    rest[0] = b'|';
+    rest[1..].copy_from_slice(sub);
+    rest.make_ascii_lowercase();
+    NAMES
+        .get(std::str::from_utf8(full).ok()?)
+        .map(String::as_str)
+}
+
+// The names of the commands and of the subcommands of containers, like command_name gives them.
+pub(crate) fn command_names() -> Vec<String> {
+    let mut names: Vec<String> = COMMANDS.iter().map(|spec| spec.name.to_string()).collect();
+    for (container, subcommands) in CONTAINERS {
+        let usages = subcommands.iter().map(|(usage, _)| *usage).chain(["HELP"]);
+        // the first word of the usage, "(no subcommand)" aside
+        for sub in usages.filter_map(|usage| usage.split(' ').next()) {
+            if sub.bytes().all(|b| b.is_ascii_uppercase() || b == b'-') {
+                names.push(format!("{}|{}", container, sub.to_ascii_lowercase()));
+            }
+        }
+    }
+    names
+}
+
+// The keys a request refers to, from the key positions of the command spec. It's what ACL key
+// patterns are checked against, before the request is parsed.
+pub fn command_keys(frame: &RespFrame) -> Vec<Bytes> {
+    match frame {
+        RespFrame::Array(args) => request_keys(args),
+        _ => vec![],
+    }
+}
+
+// command_keys of the arguments of a request
+pub(crate) fn request_keys(args: &[RespFrame]) -> Vec<Bytes> {
+    let Some(spec) = args.first().and_then(|name| match name {
+        RespFrame::BulkString(name) => lookup(std::str::from_utf8(name).ok()?),
+        _ => None,
+    }) else {
+        return vec![];
+    };
+    spec.key_positions(args)
+        .into_iter()
+        .filter_map(|i| match args.get(i) {
+            Some(RespFrame::BulkString(arg)) => Some(arg.0.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// EVAL script numkeys key [key ...] arg [arg ...], and EVALSHA and FCALL alike
+fn script_keys(args: &[RespFrame]) -> Vec<usize> {
+    let numkeys = match args.get(2) {
+        Some(RespFrame::BulkString(n)) => std::str::from_utf8(n)
+            .ok()
+            .and_then(|n| n.parse::<usize>().ok())
+            .unwrap_or(0),
+        _ => 0,
+    };
+    (3..args.len().min(3 + numkeys)).collect()
+}
+
+// MIGRATE host port key|"" db timeout [...] [KEYS key [key ...]]
+fn migrate_keys(args: &[RespFrame]) -> Vec<usize> {
+    let arg = |i: usize| match args.get(i) {
+        Some(RespFrame::BulkString(arg)) => Some(arg.as_ref()),
+        _ => None,
+    };
+    if arg(3).is_some_and(|key| !key.is_empty()) {
+        return vec![3];
+    }
+    match (6..args.len()).find(|&i| arg(i).is_some_and(|a| a.eq_ignore_ascii_case(b"keys"))) {
+        Some(i) => (i + 1..args.len()).collect(),
+        None => vec![],
+    }
+}
+
+lazy_static! {
+    static ref REGISTRY: HashMap<&'static str, &'static CommandSpec> =
+        COMMANDS.iter().map(|spec| (spec.name, spec)).collect();
+    // command_names, the static names of request_static_name
+    static ref NAMES: HashSet<String> = command_names().into_iter().collect();
+}
+
+// longer names are no command, they are not looked up
+const MAX_NAME_LEN: usize = 32;
+
+// The name is lowercased in a buffer on the stack, dispatching a request doesn't allocate.
+pub(crate) fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    let mut buf = [0u8; MAX_NAME_LEN];
+    let lowercase = buf.get_mut(..name.len())?;
+    lowercase.copy_from_slice(name.as_bytes());
+    lowercase.make_ascii_lowercase();
+    REGISTRY.get(std::str::from_utf8(lowercase).ok()?).copied()
+}
+
+// The subcommands which write, their containers are not flagged as they also read.
+const WRITE_SUBCOMMANDS: &[&str] = &["function|delete", "function|flush", "function|load"];
+
+// Whether a command, named like command_name does, writes to the dataset: it's refused on a
+// read-only replica. Scripts are not, the commands they call are checked instead.
+pub(crate) fn is_write(name: &str) -> bool {
+    match name.split_once('|') {
+        Some(_) if WRITE_SUBCOMMANDS.contains(&name) => true,
+        Some((container, _)) => lookup(container).is_some_and(CommandSpec::is_write),
+        None => lookup(name).is_some_and(CommandSpec::is_write),
+    }
+}
+
+// Whether CLIENT PAUSE WRITE holds a command back, named like command_name does: it writes, or
+// it's a script which may call writes.
+pub(crate) fn may_write(name: &str) -> bool {
+    is_write(name) || lookup(name).is_some_and(CommandSpec::may_replicate)
+}
+
+// Whether redis.call refuses a command, named like command_name does. A subcommand follows its
+// container.
+pub(crate) fn is_noscript(name: &str) -> bool {
+    let container = name
+        .split_once('|')
+        .map_or(name, |(container, _)| container);
+    lookup(container).is_some_and(CommandSpec::noscript)
+}
+
+impl CommandSpec {
+    pub(crate) fn key_positions(&self, args: &[RespFrame]) -> Vec<usize> {
+        if let Some(find_keys) = self.find_keys {
+            return find_keys(args);
+        }
+        if self.first_key <= 0 {
+            return vec![];
+        }
+        let last = match self.last_key {
+            last if last < 0 => args.len() as i64 + last,
+            last => last,
+        };
+        (self.first_key..=last.min(args.len() as i64 - 1))
+            .step_by(self.step.max(1) as usize)
+            .map(|i| i as usize)
+            .collect()
+    }
+
+    pub(crate) fn arity_matches(&self, len: usize) -> bool {
+        match self.arity {
+            arity if arity >= 0 => len as i64 == arity,
+            arity => len as i64 >= -arity,
+        }
+    }
+
+    pub(crate) fn is_write(&self) -> bool {
+        self.flags.contains(&"write")
+    }
+
+    // scripts, the commands they call may write
+    pub(crate) fn may_replicate(&self) -> bool {
+        self.flags.contains(&"may_replicate")
+    }
+
+    // not allowed from redis.call, e.g. transactions, replication and the server admin
+    pub(crate) fn noscript(&self) -> bool {
+        self.flags.contains(&"noscript")
+    }
+
+    // only reads keys, what a cluster replica could serve
+    pub(crate) fn is_readonly(&self) -> bool {
+        self.flags.contains(&"readonly")
+    }
+
+    // ACL category derived from the flags and the group, e.g. @read, @fast, @hash
+    pub(crate) fn categories(&self) -> Vec<String> {
+        let mut categories = vec![];
+        if self.is_readonly() {
+            categories.push("@read".to_string());
+        }
+        if self.is_write() {
+            categories.push("@write".to_string());
+        }
+        if self.flags.contains(&"fast") {
+            categories.push("@fast".to_string());
+        }
+        if !self.flags.contains(&"fast") {
+            categories.push("@slow".to_string());
+        }
+        categories.push(format!("@{}", self.group));
+        categories
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(args: &[&str]) -> RespArray {
+        RespArray::new(
+            args.iter()
+                .map(|arg| crate::BulkString::from(*arg).into())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn test_lookup_is_case_insensitive() {
+        assert_eq!(lookup("HGETALL").map(|spec| spec.name), Some("hgetall"));
+        assert!(lookup("nosuchcommand").is_none());
+        assert!(lookup(&"get".repeat(MAX_NAME_LEN)).is_none());
+        assert!(COMMANDS.iter().all(|spec| spec.name.len() <= MAX_NAME_LEN));
+    }
+
+    #[test]
+    fn test_requests_are_dispatched_by_the_registry() {
+        assert_eq!(REGISTRY.len(), COMMANDS.len());
+        assert!(matches!(
+            Command::try_from(request(&["PExpire", "k", "10"])),
+            Ok(Command::Expire(_))
+        ));
+        let err = Command::try_from(request(&["get", "a", "b"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "wrong number of arguments for 'get' command"
+        );
+        assert!(matches!(
+            Command::try_from(request(&["nosuchcommand"])),
+            Ok(Command::Unrecognized(_))
+        ));
+    }
+
+    #[test]
+    fn test_write_commands_follow_their_flags() {
+        assert!(is_write("set") && is_write("RESTORE"));
+        assert!(!is_write("get") && !is_write("eval") && !is_write("nosuchcommand"));
+        assert!(is_write("function|load") && !is_write("function|list"));
+        assert!(!is_write("client|kill"));
+
+        // CLIENT PAUSE WRITE holds back the writes and the scripts
+        assert!(may_write("set") && may_write("function|flush"));
+        assert!(may_write("eval") && may_write("EVALSHA") && may_write("fcall"));
+        assert!(!may_write("get") && !may_write("function|list") && !may_write("nosuchcommand"));
+
+        // redis.call refuses the commands flagged noscript, and their subcommands
+        assert!(is_noscript("save") && is_noscript("PSYNC") && is_noscript("acl|setuser"));
+        assert!(!is_noscript("set") && !is_noscript("nosuchcommand"));
+    }
+
+    #[test]
+    fn test_command_name() {
+        let frame = crate::RespArray::new([b"CLIENT".into(), b"List".into()]).into();
+        assert_eq!(command_name(&frame), "client|list");
+        let frame = crate::RespArray::new([b"GET".into(), b"key".into()]).into();
+        assert_eq!(command_name(&frame), "get");
+    }
+
+    #[test]
+    fn test_commands_have_the_keys_of_their_spec() {
+        let requests: &[(&[&str], &[&str])] = &[
+            (&["get", "k"], &["k"]),
+            (&["set", "k", "v"], &["k"]),
+            (&["hget", "k", "f"], &["k"]),
+            (&["hset", "k", "f", "v"], &["k"]),
+            (&["hmget", "k", "f", "g"], &["k"]),
+            (&["hgetall", "k"], &["k"]),
+            (&["sadd", "k", "m"], &["k"]),
+            (&["sismember", "k", "m"], &["k"]),
+            (&["watch", "a", "b"], &["a", "b"]),
+            (&["eval", "return 1", "2", "a", "b", "arg"], &["a", "b"]),
+            (
+                &[
+                    "evalsha",
+                    "e0e1f9fabfc9d4800c877a703b823ac0578ff8db",
+                    "1",
+                    "a",
+                    "arg",
+                ],
+                &["a"],
+            ),
+            (&["fcall", "f", "1", "a", "arg"], &["a"]),
+            (&["del", "a", "b"], &["a", "b"]),
+            (&["expire", "k", "10"], &["k"]),
+            (&["pexpire", "k", "10"], &["k"]),
+            (&["expireat", "k", "10"], &["k"]),
+            (&["pexpireat", "k", "10"], &["k"]),
+            (&["ttl", "k"], &["k"]),
+            (&["pttl", "k"], &["k"]),
+            (&["persist", "k"], &["k"]),
+            (&["dump", "k"], &["k"]),
+            (&["restore", "k", "0", "payload"], &["k"]),
+            (&["restore-asking", "k", "0", "payload"], &["k"]),
+            (
+                &["migrate", "host", "6380", "", "0", "1000", "KEYS", "a", "b"],
+                &["a", "b"],
+            ),
+            (&["msetnx", "a", "1", "b", "2"], &["a", "b"]),
+            (&["rename", "a", "b"], &["a", "b"]),
+            (&["object", "encoding", "k"], &["k"]),
+            (&["smove", "a", "b", "m"], &["a", "b"]),
+            (&["memory", "usage", "k"], &["k"]),
+        ];
+        for (args, keys) in requests {
+            assert_eq!(request_keys(&request(args)), *keys, "{}", args[0]);
+        }
+        // and each command with keys is in there
+        for spec in COMMANDS {
+            if spec.first_key > 0 || spec.find_keys.is_some() {
+                assert!(
+                    requests.iter().any(|(args, _)| args[0] == spec.name),
+                    "{}",
+                    spec.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_request_static_name() {
+        for args in [
+            &["GET", "k"][..],
+            &["Client", "LIST"],
+            &["client"],
+            &["config", "get", "port"],
+            &["client", "nosuchsubcommand"],
+            &["nosuchcommand", "a"],
+        ] {
+            let frame: RespFrame = request(args).into();
+            let name = request_static_name(&frame);
+            assert_eq!(name, command_name(&frame));
+            // only the names out of the registry are allocated
+            let known = !args.iter().any(|arg| arg.starts_with("nosuch"));
+            assert_eq!(matches!(name, Cow::Borrowed(_)), known, "{:?}", args);
+        }
+        // each name of the registry is found
+        for name in command_names() {
+            let frame = request(&name.split('|').collect::<Vec<_>>()).into();
+            assert!(
+                matches!(request_static_name(&frame), Cow::Borrowed(_)),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_command_keys() {
+        let frame = crate::RespArray::new([b"WATCH".into(), b"a".into(), b"b".into()]).into();
+        assert_eq!(command_keys(&frame), ["a", "b"]);
+        let frame = crate::RespArray::new([
+            b"eval".into(),
+            b"return 1".into(),
+            b"1".into(),
+            b"k".into(),
+            b"arg".into(),
+        ])
+        .into();
+        assert_eq!(command_keys(&frame), ["k"]);
+    }
+}
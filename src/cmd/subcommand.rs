@@ -0,0 +1,278 @@
+use super::{CommandError, CommandExecutor, ExecContext};
+use crate::{RespArray, RespFrame, SimpleString};
+use bytes::Bytes;
+
+// The commands whose first argument is a subcommand, e.g. CLIENT LIST, with the usage and the
+// summary of each subcommand, replied to HELP.
+#[rustfmt::skip]
+pub(super) const CONTAINERS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "acl",
+        &[
+            ("CAT [<category>]", "List all commands that belong to <category>, or all command categories when no category is specified."),
+            ("DELUSER <username> [<username> ...]", "Delete a list of users."),
+            ("GETUSER <username>", "Get the user's details."),
+            ("LIST", "Show users details in config file format."),
+            ("LOAD", "Reload users from the ACL file."),
+            ("SAVE", "Save the current config to the ACL file."),
+            ("SETUSER <username> <attribute> [<attribute> ...]", "Create or modify a user with the specified attributes."),
+            ("USERS", "List all the registered usernames."),
+            ("WHOAMI", "Return the current connection username."),
+        ],
+    ),
+    (
+        "client",
+        &[
+            ("CACHING (YES|NO)", "Enable/disable tracking of the keys for next command in OPTIN/OPTOUT modes."),
+            ("GETNAME", "Return the name of the current connection."),
+            ("ID", "Return the ID of the current connection."),
+            ("INFO", "Return information about the current client connection."),
+            ("KILL <option> <value> [<option> <value> [...]]", "Kill connections. Options are ID, ADDR, LADDR, USER and SKIPME."),
+            ("LIST [TYPE NORMAL] [ID <id> [<id> ...]]", "Return information about client connections."),
+            ("PAUSE <timeout> [WRITE|ALL]", "Suspend all, or just write, clients for <timeout> milliseconds."),
+            ("SETNAME <name>", "Assign the name <name> to the current connection."),
+            ("TRACKING (ON|OFF) [PREFIX <prefix>] [BCAST] [OPTIN] [OPTOUT]", "Control server assisted client side caching."),
+            ("UNBLOCK <clientid> [TIMEOUT|ERROR]", "Unblock the specified blocked client."),
+            ("UNPAUSE", "Stop the current client pause, resuming traffic."),
+        ],
+    ),
+    (
+        "cluster",
+        &[
+            ("COUNTKEYSINSLOT <slot>", "Return the number of keys in <slot>."),
+            ("GETKEYSINSLOT <slot> <count>", "Return key names stored by current node in a slot."),
+            ("INFO", "Return information about the cluster."),
+            ("KEYSLOT <key>", "Return the hash slot for <key>."),
+            ("MEET <ip> <port>", "Connect nodes into a working cluster."),
+            ("MYID", "Return the node id."),
+            ("NODES", "Return cluster configuration seen by node."),
+            ("SETSLOT <slot> (IMPORTING <node-id>|MIGRATING <node-id>|STABLE|NODE <node-id>)", "Set slot state."),
+            ("SHARDS", "Return information about slot range mappings and the nodes associated with them."),
+            ("SLOTS", "Return information about slots range mappings."),
+        ],
+    ),
+    (
+        "command",
+        &[
+            ("(no subcommand)", "Return details about all Redis commands."),
+            ("COUNT", "Return the total number of commands in this Redis server."),
+            ("DOCS [<command-name> ...]", "Return documentation details about multiple Redis commands."),
+            ("INFO [<command-name> ...]", "Return details about multiple Redis commands."),
+        ],
+    ),
+    (
+        "config",
+        &[
+            ("GET <pattern>", "Return parameters matching the glob-like <pattern> and their values."),
+            ("REWRITE", "Rewrite the configuration file."),
+            ("SET <directive> <value> [<directive> <value> ...]", "Set the configuration <directive> to <value>."),
+        ],
+    ),
+    (
+        "debug",
+        &[
+            ("HOTKEYS [<count>]", "Return the most accessed keys."),
+            ("OBJECT <key>", "Show low level info about the <key> and associated value."),
+            ("RELOAD", "Save the RDB on disk and reload it back to memory."),
+            ("SET-ACTIVE-EXPIRE (0|1)", "Setting it to 0 disables expiring keys in the background."),
+            ("SET-HOTKEYS (0|1)", "Setting it to 1 samples the accesses to the keys for HOTKEYS."),
+            ("SLEEP <seconds>", "Stop the server for <seconds>. Decimals allowed."),
+            ("STRINGMATCH-LEN", "Run a fuzz tester against the stringmatchlen() function."),
+        ],
+    ),
+    (
+        "function",
+        &[
+            ("DELETE <library-name>", "Delete the library called <library-name>."),
+            ("FLUSH [ASYNC|SYNC]", "Delete all the libraries."),
+            ("LIST", "Return general information on all the libraries."),
+            ("LOAD [REPLACE] <library-code>", "Create a new library with the given library name and code."),
+        ],
+    ),
+    (
+        "memory",
+        &[
+            ("STATS", "Return information about the memory usage of the server."),
+            ("USAGE <key>", "Return memory in bytes used by <key> and its value."),
+        ],
+    ),
+    (
+        "object",
+        &[
+            ("ENCODING <key>", "Return the kind of internal representation used in order to store the value associated with a <key>."),
+        ],
+    ),
+    (
+        "script",
+        &[
+            ("EXISTS <sha1> [<sha1> ...]", "Return information about the existence of the scripts in the script cache."),
+            ("FLUSH [ASYNC|SYNC]", "Flush the Lua scripts cache."),
+            ("LOAD <script>", "Load a script into the scripts cache without executing it."),
+        ],
+    ),
+    (
+        "slowlog",
+        &[
+            ("GET [<count>]", "Return top <count> entries from the slowlog (default: 10, -1 mean all)."),
+            ("LEN", "Return the length of the slowlog."),
+            ("RESET", "Reset the slowlog."),
+        ],
+    ),
+];
+
+pub(super) fn is_container(name: &str) -> bool {
+    CONTAINERS.iter().any(|(container, _)| *container == name)
+}
+
+// A request to a container, split into its subcommand and the arguments after it. The subcommand
+// is matched lowercased, the errors are the ones of redis.
+#[derive(Debug)]
+pub(crate) struct Subcommand {
+    container: &'static str,
+    pub(crate) name: String,
+    pub(crate) args: Vec<Bytes>,
+}
+
+impl Subcommand {
+    pub(crate) fn parse(value: RespArray, container: &'static str) -> Result<Self, CommandError> {
+        let mut args = value
+            .0
+            .into_iter()
+            .skip(1)
+            .map(|arg| match arg {
+                RespFrame::BulkString(arg) => Ok(arg.0),
+                _ => Err(CommandError::InvalidArgument(format!(
+                    "{} arguments must be BulkString",
+                    container
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if args.is_empty() {
+            return Err(CommandError::WrongArity(container.to_string()));
+        }
+        let name = String::from_utf8_lossy(&args.remove(0)).to_ascii_lowercase();
+        Ok(Subcommand {
+            container,
+            name,
+            args,
+        })
+    }
+
+    // the arguments, for the subcommands which only take text
+    pub(crate) fn strings(&self) -> Result<Vec<String>, CommandError> {
+        self.args
+            .iter()
+            .map(|arg| Ok(String::from_utf8(arg.to_vec())?))
+            .collect()
+    }
+
+    pub(crate) fn unknown(&self) -> CommandError {
+        CommandError::UnknownSubcommand(self.name.clone(), self.container.to_ascii_uppercase())
+    }
+
+    pub(crate) fn wrong_arity(&self) -> CommandError {
+        CommandError::WrongArity(format!("{}|{}", self.container, self.name))
+    }
+}
+
+// <CONTAINER> HELP, which every container answers the same way.
+#[derive(Debug)]
+pub struct Help {
+    container: &'static str,
+}
+
+impl Help {
+    // the help requested by a request to a container, if it's one
+    pub(super) fn parse(container: &'static str, value: &RespArray) -> Option<Help> {
+        match value.as_slice() {
+            [_, RespFrame::BulkString(sub)] if sub.eq_ignore_ascii_case(b"help") => {
+                is_container(container).then_some(Help { container })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl CommandExecutor for Help {
+    fn execute(self, _ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let subcommands = CONTAINERS
+            .iter()
+            .find(|(container, _)| *container == self.container)
+            .map(|(_, subcommands)| *subcommands)
+            .unwrap_or_default();
+        let mut lines = vec![format!(
+            "{} <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+            self.container.to_ascii_uppercase()
+        )];
+        for (usage, summary) in subcommands.iter().chain([&("HELP", "Print this help.")]) {
+            lines.push(usage.to_string());
+            lines.push(format!("    {}", summary));
+        }
+        Ok(RespArray::new(
+            lines
+                .into_iter()
+                .map(|line| SimpleString::new(line).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::Command, Backend, BulkString};
+    use anyhow::Result;
+
+    fn request(args: &[&str]) -> RespArray {
+        RespArray::new(
+            args.iter()
+                .map(|arg| BulkString::from(*arg).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+    }
+
+    #[test]
+    fn test_subcommands_are_matched_case_insensitively() -> Result<()> {
+        let sub = Subcommand::parse(request(&["CONFIG", "Get", "maxmemory"]), "config")?;
+        assert_eq!(sub.name, "get");
+        assert_eq!(sub.strings()?, vec!["maxmemory".to_string()]);
+        assert_eq!(
+            sub.wrong_arity().to_string(),
+            "wrong number of arguments for 'config|get' command"
+        );
+
+        let err = Command::try_from(request(&["config", "nope"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown subcommand 'nope'. Try CONFIG HELP."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_containers_answer_help() -> Result<()> {
+        let backend = Backend::new();
+        for (container, _) in CONTAINERS {
+            let cmd = Command::try_from(request(&[container, "HELP"]))?;
+            let RespFrame::Array(lines) = cmd.execute(&mut ExecContext::new(&backend))? else {
+                panic!("HELP must reply an array");
+            };
+            assert_eq!(
+                lines.first(),
+                Some(
+                    &SimpleString::new(format!(
+                        "{} <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                        container.to_ascii_uppercase()
+                    ))
+                    .into()
+                )
+            );
+            assert_eq!(
+                lines.last(),
+                Some(&SimpleString::new("    Print this help.").into())
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+use super::{
+    extract_args, validate_command, CommandError, CommandExecutor, Discard, Exec, ExecContext,
+    Multi, Unwatch, Watch, RESP_OK,
+};
+use crate::{network::Session, Backend, RespArray, RespFrame, SimpleError};
+
+// MULTI, EXEC and DISCARD queue and run the commands of the connection, so they are executed by the
+// network layer. Reaching these executors means there is no transaction to work on.
+impl CommandExecutor for Multi {
+    fn execute(self, _ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Err(CommandError::NoConnection("MULTI"))
+    }
+}
+
+impl CommandExecutor for Exec {
+    fn execute(self, _ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(SimpleError::new("ERR EXEC without MULTI").into())
+    }
+}
+
+impl CommandExecutor for Discard {
+    fn execute(self, _ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        Ok(SimpleError::new("ERR DISCARD without MULTI").into())
+    }
+}
+
+impl CommandExecutor for Watch {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let backend = ctx.backend;
+        Ok(self.execute_in(ctx.session("WATCH")?, backend))
+    }
+}
+
+impl CommandExecutor for Unwatch {
+    fn execute(self, ctx: &mut ExecContext) -> Result<RespFrame, CommandError> {
+        let backend = ctx.backend;
+        Ok(self.execute_in(ctx.session("UNWATCH")?, backend))
+    }
+}
+
+impl Watch {
+    fn execute_in(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        for key in self.keys {
+            if !session.watched.iter().any(|(k, _)| *k == key) {
+                let version = backend.watch(key.clone());
+                session.watched.push((key, version));
+            }
+        }
+        RESP_OK.clone()
+    }
+}
+
+impl Unwatch {
+    fn execute_in(self, session: &mut Session, backend: &Backend) -> RespFrame {
+        session.unwatch(backend);
+        RESP_OK.clone()
+    }
+}
+
+impl TryFrom<RespArray> for Multi {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["multi"], 0)?;
+        Ok(Multi)
+    }
+}
+
+impl TryFrom<RespArray> for Exec {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["exec"], 0)?;
+        Ok(Exec)
+    }
+}
+
+impl TryFrom<RespArray> for Discard {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["discard"], 0)?;
+        Ok(Discard)
+    }
+}
+
+impl TryFrom<RespArray> for Watch {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        if value.len() < 2 {
+            return Err(CommandError::InvalidArgument(
+                "watch command needs at least 1 argument".to_string(),
+            ));
+        }
+        validate_command(&value, &["watch"], value.len() - 1)?;
+
+        let keys = extract_args(value, 1)?
+            .into_iter()
+            .map(|arg| match arg {
+                RespFrame::BulkString(key) => Ok(key.0),
+                _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Watch { keys })
+    }
+}
+
+impl TryFrom<RespArray> for Unwatch {
+    type Error = CommandError;
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["unwatch"], 0)?;
+        Ok(Unwatch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecoder;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_multi_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*1\r\n$5\r\nMULTI\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let _: Multi = frame.try_into()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_exec_with_arguments_is_rejected() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*2\r\n$4\r\nexec\r\n$3\r\nfoo\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        assert!(Exec::try_from(frame).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*3\r\n$5\r\nWATCH\r\n$3\r\nfoo\r\n$3\r\nbar\r\n");
+        let frame = RespArray::decode(&mut buf)?;
+        let result: Watch = frame.try_into()?;
+        assert_eq!(result.keys, ["foo", "bar"]);
+        Ok(())
+    }
+}
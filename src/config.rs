@@ -0,0 +1,1496 @@
+use crate::{cmd::lookup, glob::glob_match, DecodeLimits};
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+use thiserror::Error;
+
+const REWRITE_SIGNATURE: &str = "# Generated by CONFIG REWRITE";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("Unknown option or number of arguments for CONFIG SET - '{0}'")]
+    UnknownOption(String),
+    #[error("CONFIG SET failed (possibly related to argument '{0}') - can't set immutable config")]
+    Immutable(String),
+    #[error("CONFIG SET failed (possibly related to argument '{0}') - {1}")]
+    InvalidValue(String, String),
+    #[error("The server is running without a config file")]
+    NoConfigFile,
+    #[error("Invalid value of '{0}' - {1}")]
+    BadValue(String, String),
+    #[error("Bad directive or wrong number of arguments at line {0} of {1}")]
+    BadDirective(usize, String),
+    #[error("{0}")]
+    Io(String),
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    // the addresses listened on separated by spaces, empty to not accept TCP connections
+    pub bind: String,
+    // 0 to let the OS pick a port
+    pub port: u16,
+    // run in the background, detached from the terminal
+    pub daemonize: bool,
+    // tell systemd when the server is ready and stopping: no, systemd, or auto when it's started
+    // by systemd
+    pub supervised: String,
+    // the least severe events logged: debug, verbose, notice, warning or nothing
+    pub loglevel: String,
+    // log to this file rather than the standard output, empty for the standard output
+    pub logfile: String,
+    // start a new log file: never, hourly or daily, the files are suffixed with their date
+    pub logfile_rotation: String,
+    // the lines logged: full, compact, pretty or json, one object per event
+    pub log_format: String,
+    // accept TLS connections on this port with the certificate and key of these files, 0 to disable
+    pub tls_port: u16,
+    pub tls_cert_file: String,
+    pub tls_key_file: String,
+    // the CAs the client certificates are verified with
+    pub tls_ca_cert_file: String,
+    // whether the TLS clients must present a certificate: yes, optional or no
+    pub tls_auth_clients: String,
+    // accept connections on this unix socket too, empty to disable
+    pub unixsocket: String,
+    // octal permissions of the unix socket, 0 to keep the default ones
+    pub unixsocketperm: u32,
+    // the connections the kernel queues until they are accepted
+    pub tcp_backlog: u32,
+    // seconds a connection is idle before keepalive probes are sent, 0 to disable
+    pub tcp_keepalive: u64,
+    // send the replies right away rather than waiting to fill a packet (Nagle's algorithm)
+    pub tcp_nodelay: bool,
+    pub maxmemory: u64,
+    pub maxmemory_policy: String,
+    // how slowly the LFU counters grow and the minutes it takes them to decay by one
+    pub lfu_log_factor: u64,
+    pub lfu_decay_time: u64,
+    pub maxclients: u64,
+    // <class> <hard limit> <soft limit> <soft seconds> for the normal, replica and pubsub clients,
+    // a client whose replies outgrow the hard limit is disconnected, 0 for no limit
+    pub client_output_buffer_limit: Vec<(String, u64, u64, u64)>,
+    // close the connection after a client is idle for N seconds, 0 to disable
+    pub timeout: u64,
+    // seconds the connections have to finish their commands on shutdown
+    pub shutdown_timeout: u64,
+    // whether to save on these signals: default (if save points are configured), save or nosave
+    pub shutdown_on_sigterm: String,
+    pub shutdown_on_sigint: String,
+    pub appendonly: bool,
+    // the AOF is written in `dir`
+    pub appendfilename: String,
+    pub appendfsync: String,
+    // rewrite the AOF once it grew by this percentage since the last rewrite, 0 to disable
+    pub auto_aof_rewrite_percentage: u64,
+    pub auto_aof_rewrite_min_size: u64,
+    // snapshot after <seconds> if at least <changes> keys changed
+    pub save: Vec<(u64, u64)>,
+    pub dir: String,
+    pub dbfilename: String,
+    pub databases: u64,
+    // log commands slower than N microseconds, negative to disable
+    pub slowlog_log_slower_than: i64,
+    pub slowlog_max_len: u64,
+    // password of the default user, empty to not require AUTH
+    pub requirepass: String,
+    // users are loaded from and saved to this file, empty to keep them in memory only
+    pub aclfile: String,
+    // bytes of the replication stream kept for replicas to resume after a disconnection
+    pub repl_backlog_size: u64,
+    // refuse writes from clients other than the master when following one
+    pub replica_read_only: bool,
+    // report a single node cluster owning all the slots through CLUSTER
+    pub cluster_enabled: bool,
+    // rename-command <name> <new name> directives of the config file, lowercased, an empty new
+    // name disables the command
+    pub rename_command: Vec<(String, String)>,
+    pub encoding_limits: EncodingLimits,
+    // what the frames of the clients may declare, proto-max-bulk-len caps the bulk strings
+    pub decode_limits: DecodeLimits,
+}
+
+// Small hashes and sets use compact encodings until they have more entries, or longer ones, than
+// these.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodingLimits {
+    pub hash_max_listpack_entries: u64,
+    pub hash_max_listpack_value: u64,
+    pub set_max_intset_entries: u64,
+    pub set_max_listpack_entries: u64,
+    pub set_max_listpack_value: u64,
+}
+
+impl Default for EncodingLimits {
+    fn default() -> Self {
+        Self {
+            hash_max_listpack_entries: 128,
+            hash_max_listpack_value: 64,
+            set_max_intset_entries: 512,
+            set_max_listpack_entries: 128,
+            set_max_listpack_value: 64,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0".to_string(),
+            port: 6379,
+            daemonize: false,
+            supervised: "no".to_string(),
+            loglevel: "notice".to_string(),
+            logfile: String::new(),
+            logfile_rotation: "never".to_string(),
+            log_format: "full".to_string(),
+            tls_port: 0,
+            tls_cert_file: String::new(),
+            tls_key_file: String::new(),
+            tls_ca_cert_file: String::new(),
+            tls_auth_clients: "yes".to_string(),
+            unixsocket: String::new(),
+            unixsocketperm: 0,
+            tcp_backlog: 511,
+            tcp_keepalive: 300,
+            tcp_nodelay: true,
+            maxmemory: 0,
+            maxmemory_policy: "noeviction".to_string(),
+            lfu_log_factor: 10,
+            lfu_decay_time: 1,
+            maxclients: 10000,
+            client_output_buffer_limit: vec![
+                ("normal".to_string(), 0, 0, 0),
+                (
+                    "replica".to_string(),
+                    256 * 1024 * 1024,
+                    64 * 1024 * 1024,
+                    60,
+                ),
+                ("pubsub".to_string(), 32 * 1024 * 1024, 8 * 1024 * 1024, 60),
+            ],
+            timeout: 0,
+            shutdown_timeout: 10,
+            shutdown_on_sigterm: "default".to_string(),
+            shutdown_on_sigint: "default".to_string(),
+            appendonly: false,
+            appendfilename: "appendonly.aof".to_string(),
+            appendfsync: "everysec".to_string(),
+            auto_aof_rewrite_percentage: 100,
+            auto_aof_rewrite_min_size: 64 * 1024 * 1024,
+            save: vec![(3600, 1), (300, 100), (60, 10000)],
+            dir: ".".to_string(),
+            dbfilename: "dump.rdb".to_string(),
+            databases: 16,
+            slowlog_log_slower_than: 10000,
+            slowlog_max_len: 128,
+            requirepass: String::new(),
+            aclfile: String::new(),
+            repl_backlog_size: 1024 * 1024,
+            replica_read_only: true,
+            cluster_enabled: false,
+            rename_command: vec![],
+            encoding_limits: EncodingLimits::default(),
+            decode_limits: DecodeLimits::default(),
+        }
+    }
+}
+
+// What a name stands for once the commands are renamed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandRename {
+    // the new name of a command, which runs under its original name
+    Alias(String),
+    // a command which was renamed or disabled, unknown under this name
+    Hidden,
+}
+
+// A configuration parameter, as seen by CONFIG GET/SET.
+struct Param {
+    name: &'static str,
+    mutable: bool,
+    get: fn(&Config) -> String,
+    set: fn(&mut Config, &str) -> Result<(), String>,
+}
+
+const MAXMEMORY_POLICIES: &[&str] = &[
+    "noeviction",
+    "allkeys-lru",
+    "volatile-lru",
+    "allkeys-lfu",
+    "volatile-lfu",
+    "allkeys-random",
+    "volatile-random",
+    "volatile-ttl",
+];
+
+const SHUTDOWN_ON_SIGNAL: &[&str] = &["default", "save", "nosave"];
+
+const LOGLEVELS: &[&str] = &["debug", "verbose", "notice", "warning", "nothing"];
+
+const SUPERVISED: &[&str] = &["no", "systemd", "auto"];
+
+const LOGFILE_ROTATIONS: &[&str] = &["never", "hourly", "daily"];
+
+const LOG_FORMATS: &[&str] = &["full", "compact", "pretty", "json"];
+
+const PARAMS: &[Param] = &[
+    Param {
+        name: "bind",
+        mutable: false,
+        get: |c| c.bind.clone(),
+        set: |c, v| {
+            c.bind = v.to_string();
+            Ok(())
+        },
+    },
+    Param {
+        name: "port",
+        mutable: false,
+        get: |c| c.port.to_string(),
+        set: |c, v| {
+            c.port = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "daemonize",
+        mutable: false,
+        get: |c| format_bool(c.daemonize),
+        set: |c, v| {
+            c.daemonize = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "supervised",
+        mutable: false,
+        get: |c| c.supervised.clone(),
+        set: |c, v| {
+            c.supervised = parse_enum(v, SUPERVISED)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "loglevel",
+        mutable: false,
+        get: |c| c.loglevel.clone(),
+        set: |c, v| {
+            c.loglevel = parse_enum(v, LOGLEVELS)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "logfile",
+        mutable: false,
+        get: |c| c.logfile.clone(),
+        set: |c, v| {
+            c.logfile = v.to_string();
+            Ok(())
+        },
+    },
+    Param {
+        name: "logfile-rotation",
+        mutable: false,
+        get: |c| c.logfile_rotation.clone(),
+        set: |c, v| {
+            c.logfile_rotation = parse_enum(v, LOGFILE_ROTATIONS)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "log-format",
+        mutable: false,
+        get: |c| c.log_format.clone(),
+        set: |c, v| {
+            c.log_format = parse_enum(v, LOG_FORMATS)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "tls-port",
+        mutable: false,
+        get: |c| c.tls_port.to_string(),
+        set: |c, v| {
+            c.tls_port = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "tls-cert-file",
+        mutable: false,
+        get: |c| c.tls_cert_file.clone(),
+        set: |c, v| {
+            c.tls_cert_file = v.to_string();
+            Ok(())
+        },
+    },
+    Param {
+        name: "tls-key-file",
+        mutable: false,
+        get: |c| c.tls_key_file.clone(),
+        set: |c, v| {
+            c.tls_key_file = v.to_string();
+            Ok(())
+        },
+    },
+    Param {
+        name: "tls-ca-cert-file",
+        mutable: false,
+        get: |c| c.tls_ca_cert_file.clone(),
+        set: |c, v| {
+            c.tls_ca_cert_file = v.to_string();
+            Ok(())
+        },
+    },
+    Param {
+        name: "tls-auth-clients",
+        mutable: false,
+        get: |c| c.tls_auth_clients.clone(),
+        set: |c, v| {
+            c.tls_auth_clients = parse_enum(v, &["yes", "optional", "no"])?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "unixsocket",
+        mutable: false,
+        get: |c| c.unixsocket.clone(),
+        set: |c, v| {
+            c.unixsocket = v.to_string();
+            Ok(())
+        },
+    },
+    Param {
+        name: "unixsocketperm",
+        mutable: false,
+        get: |c| format!("{:o}", c.unixsocketperm),
+        set: |c, v| {
+            c.unixsocketperm = u32::from_str_radix(v, 8)
+                .map_err(|_| "argument couldn't be parsed into an octal number".to_string())?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "tcp-backlog",
+        mutable: false,
+        get: |c| c.tcp_backlog.to_string(),
+        set: |c, v| {
+            c.tcp_backlog = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "tcp-keepalive",
+        mutable: true,
+        get: |c| c.tcp_keepalive.to_string(),
+        set: |c, v| {
+            c.tcp_keepalive = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "tcp-nodelay",
+        mutable: true,
+        get: |c| format_bool(c.tcp_nodelay),
+        set: |c, v| {
+            c.tcp_nodelay = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "maxmemory",
+        mutable: true,
+        get: |c| c.maxmemory.to_string(),
+        set: |c, v| {
+            c.maxmemory = parse_memory(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "maxmemory-policy",
+        mutable: true,
+        get: |c| c.maxmemory_policy.clone(),
+        set: |c, v| {
+            c.maxmemory_policy = parse_enum(v, MAXMEMORY_POLICIES)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "maxclients",
+        mutable: true,
+        get: |c| c.maxclients.to_string(),
+        set: |c, v| {
+            c.maxclients = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "client-output-buffer-limit",
+        mutable: true,
+        get: |c| {
+            c.client_output_buffer_limit
+                .iter()
+                .map(|(class, hard, soft, secs)| format!("{} {} {} {}", class, hard, soft, secs))
+                .collect::<Vec<_>>()
+                .join(" ")
+        },
+        set: |c, v| parse_output_buffer_limits(&mut c.client_output_buffer_limit, v),
+    },
+    Param {
+        name: "timeout",
+        mutable: true,
+        get: |c| c.timeout.to_string(),
+        set: |c, v| {
+            c.timeout = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "shutdown-timeout",
+        mutable: true,
+        get: |c| c.shutdown_timeout.to_string(),
+        set: |c, v| {
+            c.shutdown_timeout = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "shutdown-on-sigterm",
+        mutable: true,
+        get: |c| c.shutdown_on_sigterm.clone(),
+        set: |c, v| {
+            c.shutdown_on_sigterm = parse_enum(v, SHUTDOWN_ON_SIGNAL)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "shutdown-on-sigint",
+        mutable: true,
+        get: |c| c.shutdown_on_sigint.clone(),
+        set: |c, v| {
+            c.shutdown_on_sigint = parse_enum(v, SHUTDOWN_ON_SIGNAL)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "appendonly",
+        mutable: true,
+        get: |c| format_bool(c.appendonly),
+        set: |c, v| {
+            c.appendonly = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "appendfilename",
+        mutable: false,
+        get: |c| c.appendfilename.clone(),
+        set: |c, v| {
+            c.appendfilename = v.to_string();
+            Ok(())
+        },
+    },
+    Param {
+        name: "appendfsync",
+        mutable: true,
+        get: |c| c.appendfsync.clone(),
+        set: |c, v| {
+            c.appendfsync = parse_enum(v, &["always", "everysec", "no"])?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "auto-aof-rewrite-percentage",
+        mutable: true,
+        get: |c| c.auto_aof_rewrite_percentage.to_string(),
+        set: |c, v| {
+            c.auto_aof_rewrite_percentage = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "auto-aof-rewrite-min-size",
+        mutable: true,
+        get: |c| c.auto_aof_rewrite_min_size.to_string(),
+        set: |c, v| {
+            c.auto_aof_rewrite_min_size = parse_memory(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "proto-max-bulk-len",
+        mutable: true,
+        get: |c| c.decode_limits.max_bulk_len.to_string(),
+        set: |c, v| {
+            // like in redis, a bulk string of 1mb is always accepted
+            match usize::try_from(parse_memory(v)?) {
+                Ok(len) if len >= 1024 * 1024 => c.decode_limits.max_bulk_len = len,
+                _ => {
+                    return Err("argument must be between 1mb and the available memory".to_string())
+                }
+            }
+            Ok(())
+        },
+    },
+    Param {
+        name: "hash-max-listpack-entries",
+        mutable: true,
+        get: |c| c.encoding_limits.hash_max_listpack_entries.to_string(),
+        set: |c, v| {
+            c.encoding_limits.hash_max_listpack_entries = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "hash-max-listpack-value",
+        mutable: true,
+        get: |c| c.encoding_limits.hash_max_listpack_value.to_string(),
+        set: |c, v| {
+            c.encoding_limits.hash_max_listpack_value = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "set-max-intset-entries",
+        mutable: true,
+        get: |c| c.encoding_limits.set_max_intset_entries.to_string(),
+        set: |c, v| {
+            c.encoding_limits.set_max_intset_entries = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "set-max-listpack-entries",
+        mutable: true,
+        get: |c| c.encoding_limits.set_max_listpack_entries.to_string(),
+        set: |c, v| {
+            c.encoding_limits.set_max_listpack_entries = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "set-max-listpack-value",
+        mutable: true,
+        get: |c| c.encoding_limits.set_max_listpack_value.to_string(),
+        set: |c, v| {
+            c.encoding_limits.set_max_listpack_value = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "save",
+        mutable: true,
+        get: |c| {
+            c.save
+                .iter()
+                .map(|(secs, changes)| format!("{} {}", secs, changes))
+                .collect::<Vec<_>>()
+                .join(" ")
+        },
+        set: |c, v| {
+            c.save = parse_save(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "dir",
+        mutable: true,
+        get: |c| c.dir.clone(),
+        set: |c, v| {
+            c.dir = v.to_string();
+            Ok(())
+        },
+    },
+    Param {
+        name: "dbfilename",
+        mutable: true,
+        get: |c| c.dbfilename.clone(),
+        set: |c, v| {
+            c.dbfilename = v.to_string();
+            Ok(())
+        },
+    },
+    Param {
+        name: "databases",
+        mutable: false,
+        get: |c| c.databases.to_string(),
+        set: |c, v| {
+            c.databases = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "slowlog-log-slower-than",
+        mutable: true,
+        get: |c| c.slowlog_log_slower_than.to_string(),
+        set: |c, v| {
+            c.slowlog_log_slower_than = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "lfu-log-factor",
+        mutable: true,
+        get: |c| c.lfu_log_factor.to_string(),
+        set: |c, v| {
+            c.lfu_log_factor = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "lfu-decay-time",
+        mutable: true,
+        get: |c| c.lfu_decay_time.to_string(),
+        set: |c, v| {
+            c.lfu_decay_time = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "slowlog-max-len",
+        mutable: true,
+        get: |c| c.slowlog_max_len.to_string(),
+        set: |c, v| {
+            c.slowlog_max_len = parse_number(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "requirepass",
+        mutable: true,
+        get: |c| c.requirepass.clone(),
+        set: |c, v| {
+            c.requirepass = v.to_string();
+            Ok(())
+        },
+    },
+    Param {
+        name: "aclfile",
+        mutable: false,
+        get: |c| c.aclfile.clone(),
+        set: |c, v| {
+            c.aclfile = v.to_string();
+            Ok(())
+        },
+    },
+    Param {
+        name: "repl-backlog-size",
+        mutable: true,
+        get: |c| c.repl_backlog_size.to_string(),
+        set: |c, v| {
+            c.repl_backlog_size = parse_memory(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "replica-read-only",
+        mutable: true,
+        get: |c| format_bool(c.replica_read_only),
+        set: |c, v| {
+            c.replica_read_only = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    Param {
+        name: "cluster-enabled",
+        mutable: false,
+        get: |c| format_bool(c.cluster_enabled),
+        set: |c, v| {
+            c.cluster_enabled = parse_bool(v)?;
+            Ok(())
+        },
+    },
+];
+
+// The configuration shared by the whole server, readable and mutable at runtime.
+#[derive(Debug, Default)]
+pub struct ServerConfig {
+    config: RwLock<Config>,
+    // the config file the server was started with, used by CONFIG REWRITE
+    file: Option<PathBuf>,
+    // the directives of the config files which are not supported
+    ignored: Vec<String>,
+}
+
+impl ServerConfig {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config: RwLock::new(config),
+            file: None,
+            ignored: vec![],
+        }
+    }
+
+    // Loads a config file made of `name value` directives, like redis.conf, with the files it
+    // includes. Unknown directives are ignored, and kept as is by CONFIG REWRITE.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let mut loader = Loader {
+            config: Config::default(),
+            files: vec![],
+            saved: false,
+            ignored: vec![],
+        };
+        loader.load(path)?;
+        Ok(Self {
+            config: RwLock::new(loader.config),
+            file: Some(path.to_path_buf()),
+            ignored: loader.ignored,
+        })
+    }
+
+    // The names of the directives of the config files which were ignored, for the server to
+    // report them once it logs.
+    pub fn ignored_directives(&self) -> &[String] {
+        &self.ignored
+    }
+
+    // A copy of the current configuration.
+    pub fn snapshot(&self) -> Config {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    // Returns the (name, value) of all the parameters matching the glob pattern.
+    pub fn get(&self, pattern: &str) -> Vec<(String, String)> {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        PARAMS
+            .iter()
+            .filter(|p| glob_match(pattern.as_bytes(), p.name.as_bytes(), true))
+            .map(|p| (p.name.to_string(), (p.get)(&config)))
+            .collect()
+    }
+
+    // Sets all the parameters or none of them.
+    pub fn set(&self, pairs: &[(String, String)]) -> Result<(), ConfigError> {
+        let mut config = self.config.write().unwrap_or_else(|e| e.into_inner());
+        let mut updated = config.clone();
+        for (name, value) in pairs {
+            let param = find_param(name).ok_or_else(|| ConfigError::UnknownOption(name.clone()))?;
+            if !param.mutable {
+                return Err(ConfigError::Immutable(name.clone()));
+            }
+            (param.set)(&mut updated, value)
+                .map_err(|e| ConfigError::InvalidValue(name.clone(), e))?;
+        }
+        *config = updated;
+        Ok(())
+    }
+
+    // Sets parameters at startup, the immutable ones too, like the options of the command line
+    // override the directives of the config file.
+    pub fn apply(&self, pairs: &[(String, String)]) -> Result<(), ConfigError> {
+        let mut config = self.config.write().unwrap_or_else(|e| e.into_inner());
+        for (name, value) in pairs {
+            let param = find_param(name).ok_or_else(|| ConfigError::UnknownOption(name.clone()))?;
+            (param.set)(&mut config, value).map_err(|e| ConfigError::BadValue(name.clone(), e))?;
+        }
+        Ok(())
+    }
+
+    // the hard output buffer limit of a class of clients, 0 for none
+    pub fn output_buffer_limit(&self, class: &str) -> u64 {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        config
+            .client_output_buffer_limit
+            .iter()
+            .find(|(name, ..)| name == class)
+            .map_or(0, |(_, hard, ..)| *hard)
+    }
+
+    // What a command name sent by a client stands for under rename-command, None when it's not
+    // affected.
+    pub fn command_rename(&self, name: &str) -> Option<CommandRename> {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        config.rename_command.iter().find_map(|(from, to)| {
+            if from.eq_ignore_ascii_case(name) {
+                Some(CommandRename::Hidden)
+            } else if !to.is_empty() && to.eq_ignore_ascii_case(name) {
+                Some(CommandRename::Alias(from.clone()))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn timeout(&self) -> u64 {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .timeout
+    }
+
+    // Records the port listened on when the OS picked it, port 0.
+    pub(crate) fn bound(&self, port: u16) {
+        let mut config = self.config.write().unwrap_or_else(|e| e.into_inner());
+        if config.port == 0 {
+            config.port = port;
+        }
+    }
+
+    // (tcp-nodelay, tcp-keepalive), applied to each connection accepted
+    pub fn tcp_options(&self) -> (bool, u64) {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        (config.tcp_nodelay, config.tcp_keepalive)
+    }
+
+    pub fn maxclients(&self) -> u64 {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .maxclients
+    }
+
+    pub fn shutdown_timeout(&self) -> u64 {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .shutdown_timeout
+    }
+
+    pub fn requirepass(&self) -> String {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .requirepass
+            .clone()
+    }
+
+    pub fn repl_backlog_size(&self) -> u64 {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .repl_backlog_size
+    }
+
+    pub fn replica_read_only(&self) -> bool {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .replica_read_only
+    }
+
+    // (auto-aof-rewrite-percentage, auto-aof-rewrite-min-size)
+    pub fn auto_aof_rewrite(&self) -> (u64, u64) {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        (
+            config.auto_aof_rewrite_percentage,
+            config.auto_aof_rewrite_min_size,
+        )
+    }
+
+    // (maxmemory in bytes, maxmemory-policy)
+    pub fn maxmemory(&self) -> (u64, String) {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        (config.maxmemory, config.maxmemory_policy.clone())
+    }
+
+    // Whether maxmemory is set with a policy evicting the keys by their accesses, LRU or LFU.
+    pub fn evicts_by_access(&self) -> bool {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        config.maxmemory > 0
+            && (config.maxmemory_policy.ends_with("-lru")
+                || config.maxmemory_policy.ends_with("-lfu"))
+    }
+
+    pub fn decode_limits(&self) -> DecodeLimits {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .decode_limits
+    }
+
+    pub fn encoding_limits(&self) -> EncodingLimits {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .encoding_limits
+    }
+
+    // (lfu-log-factor, lfu-decay-time)
+    pub fn lfu(&self) -> (u64, u64) {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        (config.lfu_log_factor, config.lfu_decay_time)
+    }
+
+    pub fn cluster_enabled(&self) -> bool {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .cluster_enabled
+    }
+
+    // (threshold in microseconds, max number of entries) of the slow log
+    pub fn slowlog(&self) -> (i64, u64) {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        (config.slowlog_log_slower_than, config.slowlog_max_len)
+    }
+
+    // Writes the current configuration back to the config file. Directives of known
+    // parameters are updated in place, everything else (comments, unknown directives) is kept,
+    // and parameters which differ from the default are appended at the end.
+    pub fn rewrite(&self) -> Result<(), ConfigError> {
+        let path = self.file.as_ref().ok_or(ConfigError::NoConfigFile)?;
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let config = self.snapshot();
+        let default = Config::default();
+
+        let mut lines = vec![];
+        let mut seen = HashSet::new();
+        for line in content.lines() {
+            if line == REWRITE_SIGNATURE {
+                continue;
+            }
+            // the names are case insensitive, like when the file is loaded
+            let param = split_args(line)
+                .and_then(|args| args.into_iter().next())
+                .and_then(|name| find_param(&name.to_ascii_lowercase()));
+            match param {
+                // only keep the first occurrence of a known directive
+                Some(param) if seen.insert(param.name) => lines.push(directive(param, &config)),
+                Some(_) => {}
+                None => lines.push(line.to_string()),
+            }
+        }
+
+        let mut appended = false;
+        for param in PARAMS.iter().filter(|p| !seen.contains(p.name)) {
+            if (param.get)(&config) != (param.get)(&default) {
+                if !appended {
+                    lines.push(REWRITE_SIGNATURE.to_string());
+                    appended = true;
+                }
+                lines.push(directive(param, &config));
+            }
+        }
+
+        // write to a temp file then rename, so that the config file is never left half written
+        let tmp = path.with_extension("rewrite.tmp");
+        fs::write(&tmp, lines.join("\n") + "\n")?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+// Reads config files into a config, the included ones in place of their include directive.
+struct Loader {
+    config: Config,
+    // the files being read, an include of one of them would never end
+    files: Vec<PathBuf>,
+    // whether a save directive was read, the next ones add save points rather than replace them
+    saved: bool,
+    ignored: Vec<String>,
+}
+
+impl Loader {
+    fn load(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            ConfigError::Io(format!(
+                "can't open config file '{}': {}",
+                path.display(),
+                e
+            ))
+        })?;
+        self.files
+            .push(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+        for (i, line) in content.lines().enumerate() {
+            let bad = || ConfigError::BadDirective(i + 1, path.display().to_string());
+            let args = match split_args(line) {
+                Some(args) if args.is_empty() || args[0].starts_with('#') => continue,
+                Some(args) => args,
+                None => return Err(bad()),
+            };
+            let name = args[0].to_ascii_lowercase();
+            match name.as_str() {
+                "include" => {
+                    let [_, pattern] = args.as_slice() else {
+                        return Err(bad());
+                    };
+                    for file in include_files(Path::new(pattern))? {
+                        let canonical = fs::canonicalize(&file).unwrap_or_else(|_| file.clone());
+                        if self.files.contains(&canonical) {
+                            return Err(bad());
+                        }
+                        self.load(&file)?;
+                    }
+                }
+                "rename-command" => {
+                    let rename =
+                        parse_rename(&args[1..], &self.config.rename_command).ok_or_else(bad)?;
+                    self.config.rename_command.push(rename);
+                }
+                // like in redis.conf, each save directive adds save points
+                "save" if self.saved => {
+                    let points = parse_save(&args[1..].join(" ")).map_err(|_| bad())?;
+                    self.config.save.extend(points);
+                }
+                _ => match find_param(&name) {
+                    Some(param) => {
+                        (param.set)(&mut self.config, &args[1..].join(" ")).map_err(|_| bad())?;
+                        self.saved |= name == "save";
+                    }
+                    None => self.ignored.push(args[0].clone()),
+                },
+            }
+        }
+        self.files.pop();
+        Ok(())
+    }
+}
+
+// The files of an include directive, the names matching its pattern in their directory, in order.
+fn include_files(pattern: &Path) -> Result<Vec<PathBuf>, ConfigError> {
+    let name = pattern.file_name().unwrap_or_default().to_string_lossy();
+    if !name.contains(['*', '?', '[']) {
+        return Ok(vec![pattern.to_path_buf()]);
+    }
+    let dir = match pattern.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let matched = path
+            .file_name()
+            .is_some_and(|file| glob_match(name.as_bytes(), file.as_encoded_bytes(), false));
+        if matched && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// <name> <new name>, the command must exist and the new name must not
+fn parse_rename(args: &[String], renames: &[(String, String)]) -> Option<(String, String)> {
+    let [from, to] = args else {
+        return None;
+    };
+    let (from, to) = (from.to_ascii_lowercase(), to.to_ascii_lowercase());
+    let renamed = |name: &str| renames.iter().any(|(f, t)| f == name || t == name);
+    if lookup(&from).is_none() || renamed(&from) {
+        return None;
+    }
+    if !to.is_empty() && (lookup(&to).is_some() || renamed(&to)) {
+        return None;
+    }
+    Some((from, to))
+}
+
+fn directive(param: &Param, config: &Config) -> String {
+    let value = (param.get)(config);
+    match param.name {
+        // a list of `seconds changes` pairs
+        "save" | "client-output-buffer-limit" if !value.is_empty() => {
+            format!("{} {}", param.name, value)
+        }
+        _ => format!("{} {}", param.name, quote(&value)),
+    }
+}
+
+fn quote(v: &str) -> String {
+    if !v.is_empty() && !v.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'') {
+        return v.to_string();
+    }
+    format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Splits a config line into arguments, handling double and single quotes like Redis.
+// Returns None on unbalanced quotes.
+pub(crate) fn split_args(line: &str) -> Option<Vec<String>> {
+    let mut args = vec![];
+    let mut chars = line.trim().chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            return Some(args);
+        };
+
+        let mut arg = String::new();
+        if first == '"' || first == '\'' {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    '\\' if first == '"' => match chars.next()? {
+                        'n' => arg.push('\n'),
+                        'r' => arg.push('\r'),
+                        't' => arg.push('\t'),
+                        c => arg.push(c),
+                    },
+                    '\\' if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                        arg.push('\'');
+                    }
+                    c if c == first => break,
+                    c => arg.push(c),
+                }
+            }
+            // a closing quote must be followed by a space or nothing
+            if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                return None;
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                arg.push(c);
+            }
+        }
+        args.push(arg);
+    }
+}
+
+fn find_param(name: &str) -> Option<&'static Param> {
+    PARAMS.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+fn parse_number<T: std::str::FromStr>(v: &str) -> Result<T, String> {
+    v.parse()
+        .map_err(|_| "argument couldn't be parsed into an integer".to_string())
+}
+
+fn parse_bool(v: &str) -> Result<bool, String> {
+    match v.to_ascii_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err("argument must be 'yes' or 'no'".to_string()),
+    }
+}
+
+fn format_bool(v: bool) -> String {
+    match v {
+        true => "yes".to_string(),
+        false => "no".to_string(),
+    }
+}
+
+fn parse_enum(v: &str, values: &[&str]) -> Result<String, String> {
+    let v = v.to_ascii_lowercase();
+    match values.contains(&v.as_str()) {
+        true => Ok(v),
+        false => Err("argument(s) must be one of the following: ".to_string() + &values.join(", ")),
+    }
+}
+
+// Parses memory sizes like "100", "1k", "10kb", "1gb": k/m/g are powers of 1000, kb/mb/gb of 1024.
+pub fn parse_memory(v: &str) -> Result<u64, String> {
+    let v = v.to_ascii_lowercase();
+    let digits = v.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier = match &v[digits.len()..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err("argument must be a memory value".to_string()),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| "argument must be a memory value".to_string())
+}
+
+// Only the classes given are changed, like in redis. "slave" is the old name of "replica".
+fn parse_output_buffer_limits(
+    limits: &mut [(String, u64, u64, u64)],
+    v: &str,
+) -> Result<(), String> {
+    let parts = v.split_whitespace().collect::<Vec<_>>();
+    if parts.is_empty() || parts.len() % 4 != 0 {
+        return Err("Wrong number of arguments in buffer limit configuration.".to_string());
+    }
+    let mut updated = limits.to_vec();
+    for part in parts.chunks(4) {
+        let class = match part[0].to_ascii_lowercase().as_str() {
+            "slave" => "replica".to_string(),
+            class => class.to_string(),
+        };
+        let Some(limit) = updated.iter_mut().find(|(name, ..)| *name == class) else {
+            return Err(
+                "Invalid client class specified in buffer limit configuration.".to_string(),
+            );
+        };
+        match (
+            parse_memory(part[1]),
+            parse_memory(part[2]),
+            part[3].parse(),
+        ) {
+            (Ok(hard), Ok(soft), Ok(secs)) => *limit = (class, hard, soft, secs),
+            _ => {
+                return Err(
+                    "Error in hard, soft or soft_seconds setting in buffer limit \
+                            configuration."
+                        .to_string(),
+                )
+            }
+        }
+    }
+    limits.clone_from_slice(&updated);
+    Ok(())
+}
+
+fn parse_save(v: &str) -> Result<Vec<(u64, u64)>, String> {
+    let parts = v.split_whitespace().collect::<Vec<_>>();
+    if parts.len() % 2 != 0 {
+        return Err("Invalid save parameters".to_string());
+    }
+    parts
+        .chunks(2)
+        .map(|pair| match (pair[0].parse(), pair[1].parse()) {
+            (Ok(secs), Ok(changes)) => Ok((secs, changes)),
+            _ => Err("Invalid save parameters".to_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_get_with_pattern() {
+        let config = ServerConfig::default();
+        let params = config.get("maxmemory*");
+        assert_eq!(
+            params,
+            [
+                ("maxmemory".to_string(), "0".to_string()),
+                ("maxmemory-policy".to_string(), "noeviction".to_string()),
+            ]
+        );
+        assert_eq!(config.get("SAVE")[0].1, "3600 1 300 100 60 10000");
+    }
+
+    #[test]
+    fn test_config_set() {
+        let config = ServerConfig::default();
+        config
+            .set(&[
+                ("maxmemory".to_string(), "1mb".to_string()),
+                ("appendonly".to_string(), "yes".to_string()),
+            ])
+            .unwrap();
+        let snapshot = config.snapshot();
+        assert_eq!(snapshot.maxmemory, 1024 * 1024);
+        assert!(snapshot.appendonly);
+
+        assert_eq!(
+            config.set(&[("port".to_string(), "1".to_string())]),
+            Err(ConfigError::Immutable("port".to_string()))
+        );
+        assert!(config
+            .set(&[("nosuchparam".to_string(), "1".to_string())])
+            .is_err());
+
+        // nothing is changed if any parameter is invalid
+        assert!(config
+            .set(&[
+                ("timeout".to_string(), "10".to_string()),
+                ("maxclients".to_string(), "many".to_string()),
+            ])
+            .is_err());
+        assert_eq!(config.timeout(), 0);
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(
+            split_args("  save 3600 1  "),
+            Some(vec![
+                "save".to_string(),
+                "3600".to_string(),
+                "1".to_string()
+            ])
+        );
+        assert_eq!(
+            split_args(r#"dir "/var/lib/my redis" 'it\'s'"#),
+            Some(vec![
+                "dir".to_string(),
+                "/var/lib/my redis".to_string(),
+                "it's".to_string()
+            ])
+        );
+        assert_eq!(split_args(r#"dir "/tmp"#), None);
+    }
+
+    #[test]
+    fn test_config_rewrite() -> Result<(), ConfigError> {
+        let path = std::env::temp_dir().join(format!("config-rewrite-{}.conf", std::process::id()));
+        fs::write(
+            &path,
+            "# my config\nport 7000\nmaxmemory 1mb\nactiverehashing yes\nmaxmemory 2mb\n",
+        )?;
+
+        let config = ServerConfig::from_file(&path)?;
+        assert_eq!(config.snapshot().port, 7000);
+        assert_eq!(config.snapshot().maxmemory, 2 * 1024 * 1024);
+
+        config.set(&[
+            ("maxmemory".to_string(), "100".to_string()),
+            ("timeout".to_string(), "30".to_string()),
+        ])?;
+        config.rewrite()?;
+
+        let content = fs::read_to_string(&path)?;
+        fs::remove_file(&path)?;
+        assert_eq!(
+            content,
+            "# my config\nport 7000\nmaxmemory 100\nactiverehashing yes\n# Generated by CONFIG REWRITE\ntimeout 30\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_rewrite_uppercase_directive() -> Result<(), ConfigError> {
+        let path =
+            std::env::temp_dir().join(format!("config-rewrite-case-{}.conf", std::process::id()));
+        fs::write(
+            &path,
+            "PORT 7000
+MaxMemory 1mb
+",
+        )?;
+
+        let config = ServerConfig::from_file(&path)?;
+        config.set(&[("maxmemory".to_string(), "100".to_string())])?;
+        config.rewrite()?;
+
+        let content = fs::read_to_string(&path)?;
+        fs::remove_file(&path)?;
+        assert_eq!(
+            content,
+            "port 7000
+maxmemory 100
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_command_directives() -> Result<(), ConfigError> {
+        let path = std::env::temp_dir().join(format!("simple-redis-rename-{}", std::process::id()));
+        fs::write(
+            &path,
+            "rename-command SHUTDOWN \"\"\nrename-command config cfg\n",
+        )?;
+        let config = ServerConfig::from_file(&path)?;
+        assert_eq!(
+            config.command_rename("shutdown"),
+            Some(CommandRename::Hidden)
+        );
+        assert_eq!(config.command_rename("CONFIG"), Some(CommandRename::Hidden));
+        assert_eq!(
+            config.command_rename("Cfg"),
+            Some(CommandRename::Alias("config".to_string()))
+        );
+        assert_eq!(config.command_rename("get"), None);
+
+        // the command must exist, and the new name must not
+        for directive in ["rename-command nope x", "rename-command get set"] {
+            fs::write(&path, directive)?;
+            assert!(matches!(
+                ServerConfig::from_file(&path),
+                Err(ConfigError::BadDirective(1, _))
+            ));
+        }
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_redis_conf_with_includes() -> Result<(), ConfigError> {
+        let dir = std::env::temp_dir().join(format!("simple-redis-include-{}", std::process::id()));
+        fs::create_dir_all(dir.join("conf.d"))?;
+        fs::write(
+            dir.join("redis.conf"),
+            format!(
+                "bind 127.0.0.1 -::1\nprotected-mode yes\nsave 900 1\nsave 300 10\ninclude {}\nMaxMemory 1gb\n",
+                dir.join("conf.d/*.conf").display()
+            ),
+        )?;
+        fs::write(dir.join("conf.d/a.conf"), "port 7000\nmaxmemory 1mb\n")?;
+        fs::write(dir.join("conf.d/b.conf"), "port 7001\n")?;
+        fs::write(dir.join("conf.d/ignored.txt"), "port 7002\n")?;
+
+        let config = ServerConfig::from_file(dir.join("redis.conf"))?;
+        let snapshot = config.snapshot();
+        assert_eq!(snapshot.bind, "127.0.0.1 -::1");
+        assert_eq!(snapshot.save, [(900, 1), (300, 10)]);
+        // the includes are read in order, in place of the directive
+        assert_eq!(snapshot.port, 7001);
+        assert_eq!(snapshot.maxmemory, 1024 * 1024 * 1024);
+        assert_eq!(config.ignored_directives(), ["protected-mode".to_string()]);
+
+        // a file including itself, under another path
+        fs::write(
+            dir.join("conf.d/a.conf"),
+            format!(
+                "include {}\n",
+                dir.join("conf.d/../conf.d/a.conf").display()
+            ),
+        )?;
+        assert!(matches!(
+            ServerConfig::from_file(dir.join("conf.d/a.conf")),
+            Err(ConfigError::BadDirective(1, _))
+        ));
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_rewrite_without_file() {
+        let config = ServerConfig::default();
+        assert_eq!(config.rewrite(), Err(ConfigError::NoConfigFile));
+    }
+
+    #[test]
+    fn test_config_set_output_buffer_limit() {
+        let config = ServerConfig::default();
+        assert!(config
+            .set(&[(
+                "client-output-buffer-limit".to_string(),
+                "pubsub 1mb 0 0".to_string()
+            )])
+            .is_ok());
+        assert_eq!(config.output_buffer_limit("pubsub"), 1024 * 1024);
+        assert_eq!(config.output_buffer_limit("replica"), 256 * 1024 * 1024);
+        assert_eq!(
+            config.get("client-output-buffer-limit")[0].1,
+            "normal 0 0 0 replica 268435456 67108864 60 pubsub 1048576 0 0"
+        );
+        assert!(config
+            .set(&[(
+                "client-output-buffer-limit".to_string(),
+                "other 1 1 1".to_string()
+            )])
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(parse_memory("100"), Ok(100));
+        assert_eq!(parse_memory("1k"), Ok(1000));
+        assert_eq!(parse_memory("1KB"), Ok(1024));
+        assert_eq!(parse_memory("2gb"), Ok(2 * 1024 * 1024 * 1024));
+        assert!(parse_memory("1tb").is_err());
+        assert!(parse_memory("mb").is_err());
+    }
+}
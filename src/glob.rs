@@ -0,0 +1,99 @@
+// Glob-style pattern matching as done by Redis for KEYS, CONFIG GET, etc.
+// Supports `*`, `?`, `[abc]`, `[^abc]`, `[a-z]` and `\` escapes.
+pub fn glob_match(pattern: &[u8], s: &[u8], nocase: bool) -> bool {
+    let eq = |a: u8, b: u8| match nocase {
+        true => a.eq_ignore_ascii_case(&b),
+        false => a == b,
+    };
+
+    let (mut p, mut i) = (0, 0);
+    // position to backtrack to after the last `*`
+    let mut star: Option<(usize, usize)> = None;
+    while i < s.len() {
+        let matched = match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, i));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => match_class(pattern, p, s[i], nocase),
+            Some(b'\\') if p + 1 < pattern.len() => eq(pattern[p + 1], s[i]).then_some(p + 2),
+            Some(&c) => eq(c, s[i]).then_some(p + 1),
+            None => None,
+        };
+        match (matched, star) {
+            (Some(next), _) => {
+                p = next;
+                i += 1;
+            }
+            (None, Some((sp, si))) => {
+                p = sp + 1;
+                i = si + 1;
+                star = Some((sp, si + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+// Matches `c` against the class starting at `pattern[start] == b'['`, returning the position
+// after the class on success.
+fn match_class(pattern: &[u8], start: usize, c: u8, nocase: bool) -> Option<usize> {
+    let fold = |b: u8| if nocase { b.to_ascii_lowercase() } else { b };
+    let c = fold(c);
+    let mut p = start + 1;
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+
+    let mut matched = false;
+    loop {
+        match pattern.get(p) {
+            // unterminated class, like Redis treat the end of the pattern as `]`
+            None => break,
+            Some(b']') => {
+                p += 1;
+                break;
+            }
+            Some(b'\\') if p + 1 < pattern.len() => {
+                matched |= fold(pattern[p + 1]) == c;
+                p += 2;
+            }
+            Some(&lo) if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() => {
+                let (lo, hi) = (fold(lo), fold(pattern[p + 2]));
+                let (lo, hi) = if lo > hi { (hi, lo) } else { (lo, hi) };
+                matched |= lo <= c && c <= hi;
+                p += 3;
+            }
+            Some(&b) => {
+                matched |= fold(b) == c;
+                p += 1;
+            }
+        }
+    }
+    (matched != negate).then_some(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*", b"anything", false));
+        assert!(glob_match(b"h?llo", b"hello", false));
+        assert!(glob_match(b"h*llo", b"heeeello", false));
+        assert!(glob_match(b"h[ae]llo", b"hallo", false));
+        assert!(!glob_match(b"h[^e]llo", b"hello", false));
+        assert!(glob_match(b"h[a-b]llo", b"hbllo", false));
+        assert!(glob_match(b"max*", b"maxmemory-policy", false));
+        assert!(!glob_match(b"max*", b"appendonly", false));
+        assert!(glob_match(b"a\\*b", b"a*b", false));
+        assert!(!glob_match(b"a\\*b", b"axb", false));
+        assert!(glob_match(b"MAXMEMORY", b"maxmemory", true));
+        assert!(!glob_match(b"MAXMEMORY", b"maxmemory", false));
+    }
+}
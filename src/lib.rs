@@ -1,8 +1,35 @@
+mod acl;
 mod backend;
+mod cluster;
 pub mod cmd;
+mod config;
+mod glob;
+mod lolwut;
 pub mod network;
+mod persistence;
+mod replication;
 mod resp;
+mod runtime;
+mod script;
+mod server;
+mod shutdown;
+mod slowlog;
+mod stats;
+mod systemd;
 
+pub use acl::{Acl, AclError, User};
 pub use backend::*;
+pub use cluster::{
+    crc16, key_hash_slot, Cluster, ClusterError, ClusterNode, Redirect, CLUSTER_SLOTS,
+};
+pub use config::{CommandRename, Config, ConfigError, EncodingLimits, ServerConfig};
+pub use glob::glob_match;
 pub use network::*;
+pub use persistence::{apply_save_rules, load_dataset, load_snapshot, Aof, Persistence, Snapshot};
+pub use replication::{Replica, Replication};
 pub use resp::*;
+pub use runtime::{runtime, RUNTIME_FLAVOR};
+pub use server::{RedisServer, RedisServerBuilder, ServerHandle};
+pub use shutdown::{shutdown_on_signals, ShutdownHandle};
+pub use slowlog::{SlowLog, SlowLogEntry};
+pub use stats::{CommandStats, ServerStats};
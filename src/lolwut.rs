@@ -0,0 +1,169 @@
+use std::{
+    f32::consts::{FRAC_PI_2, FRAC_PI_4},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// A black and white canvas rendered with braille characters, each one covering 2x4 pixels.
+#[derive(Debug)]
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    // pixels outside of the canvas are ignored
+    pub fn set(&mut self, x: i64, y: i64) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        self.pixels[y as usize * self.width + x as usize] = true;
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+
+    // Bresenham's line algorithm
+    pub fn draw_line(&mut self, (mut x1, mut y1): (i64, i64), (x2, y2): (i64, i64)) {
+        let dx = (x2 - x1).abs();
+        let dy = (y2 - y1).abs();
+        let sx = if x1 < x2 { 1 } else { -1 };
+        let sy = if y1 < y2 { 1 } else { -1 };
+        let mut err = dx - dy;
+        loop {
+            self.set(x1, y1);
+            if x1 == x2 && y1 == y2 {
+                break;
+            }
+            let e2 = err * 2;
+            if e2 > -dy {
+                err -= dy;
+                x1 += sx;
+            }
+            if e2 < dx {
+                err += dx;
+                y1 += sy;
+            }
+        }
+    }
+
+    // A square centered at (x, y), rotated by `angle` radians.
+    pub fn draw_square(&mut self, x: f32, y: f32, size: f32, angle: f32) {
+        // the distance from the center to the corners
+        let radius = (size / std::f32::consts::SQRT_2).round();
+        let corners: Vec<(i64, i64)> = (0..4)
+            .map(|i| {
+                let a = angle + FRAC_PI_4 + FRAC_PI_2 * i as f32;
+                ((a.sin() * radius + x) as i64, (a.cos() * radius + y) as i64)
+            })
+            .collect();
+        for i in 0..4 {
+            self.draw_line(corners[i], corners[(i + 1) % 4]);
+        }
+    }
+
+    // One line of text per 4 rows of pixels.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        for y in (0..self.height).step_by(4) {
+            for x in (0..self.width).step_by(2) {
+                // braille dots are numbered down the left column then down the right one
+                let dots = [
+                    (0, 0, 0x01),
+                    (0, 1, 0x02),
+                    (0, 2, 0x04),
+                    (1, 0, 0x08),
+                    (1, 1, 0x10),
+                    (1, 2, 0x20),
+                    (0, 3, 0x40),
+                    (1, 3, 0x80),
+                ];
+                let bits = dots
+                    .iter()
+                    .filter(|(dx, dy, _)| self.get(x + dx, y + dy))
+                    .fold(0, |bits, (_, _, bit)| bits | bit);
+                text.push(char::from_u32(0x2800 + bits).unwrap_or(' '));
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+// Georg Nees' "Schotter": a grid of squares getting more and more disordered towards the bottom.
+pub fn schotter(cols: usize, squares_per_row: usize, squares_per_col: usize) -> String {
+    let width = cols * 2;
+    let padding = if width > 4 { 2 } else { 0 };
+    let side = (width - padding * 2) as f32 / squares_per_row as f32;
+    let height = (side * squares_per_col as f32) as usize + padding * 2;
+    let mut canvas = Canvas::new(width, height);
+    let mut rng = Rng::new();
+    for y in 0..squares_per_col {
+        for x in 0..squares_per_row {
+            let mut sx = x as f32 * side + side / 2.0 + padding as f32;
+            let mut sy = y as f32 * side + side / 2.0 + padding as f32;
+            let mut angle = 0.0;
+            // the first two rows stay in order
+            if y > 1 {
+                let disorder = y as f32 / squares_per_col as f32;
+                angle = rng.signed() * disorder;
+                sx += rng.signed() * disorder * side / 3.0;
+                sy += rng.signed() * disorder * side / 3.0;
+            }
+            canvas.draw_square(sx, sy, side, angle);
+        }
+    }
+    canvas.render()
+}
+
+// xorshift, good enough for art
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Self(seed | 1)
+    }
+
+    // uniform in [-1, 1]
+    fn signed(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canvas_render() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas.draw_line((0, 0), (0, 3));
+        canvas.set(3, 3);
+        assert_eq!(canvas.render(), "\u{2847}\u{2880}\n");
+    }
+
+    #[test]
+    fn test_schotter_size() {
+        let art = schotter(66, 8, 12);
+        let lines: Vec<&str> = art.lines().collect();
+        assert!(lines.iter().all(|line| line.chars().count() == 66));
+        // 12 squares of 16 pixels plus the padding, 4 pixels per line
+        assert_eq!(lines.len(), 49);
+    }
+}
@@ -1,30 +1,277 @@
-use anyhow::Result;
-use simple_redis_server::{network, Backend};
-use tokio::net::TcpListener;
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use simple_redis_server::{
+    load_dataset, runtime, shutdown_on_signals, Backend, Config, RedisServer, ServerConfig,
+    RUNTIME_FLAVOR,
+};
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+};
 use tracing::{info, warn};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    fmt::{format::FmtSpan, writer::BoxMakeWriter},
+    prelude::*,
+    EnvFilter,
+};
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-
-    let addr = "0.0.0.0:6379";
-    let listener = TcpListener::bind(addr).await?;
-    info!("Simple-Redis-Server is listening on {}", addr);
-
-    let backend = Backend::new();
-    loop {
-        let (stream, raddr) = listener.accept().await?;
-        info!("Accepted connection from: {}", raddr);
-        let cloned_backend = backend.clone();
-        tokio::spawn(async move {
-            match network::handle_stream(stream, cloned_backend).await {
-                Ok(_) => {
-                    info!("Connection from {} exited", raddr);
-                }
-                Err(e) => {
-                    warn!("handle error for {}: {:?}", raddr, e);
-                }
-            }
-        });
+// simple-redis-server [/path/to/redis.conf] [--port 7000] [--maxmemory 1gb] ..., the options are
+// the parameters of CONFIG GET/SET and take precedence over the config file.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    #[arg(help = "The config file, made of redis.conf directives")]
+    config: Option<PathBuf>,
+    #[arg(long, help = "Listen on these addresses, separated by spaces")]
+    bind: Option<String>,
+    #[arg(long, help = "Listen on this TCP port, 0 to let the OS pick one")]
+    port: Option<String>,
+    #[arg(long, help = "Listen on this unix socket too")]
+    unixsocket: Option<String>,
+    #[arg(
+        long,
+        help = "The memory the dataset may use, e.g. 100mb, 0 for no limit"
+    )]
+    maxmemory: Option<String>,
+    #[arg(long, help = "What to evict once maxmemory is reached")]
+    maxmemory_policy: Option<String>,
+    #[arg(long, help = "The password of the default user")]
+    requirepass: Option<String>,
+    #[arg(
+        long,
+        help = "The working directory, where the RDB and the AOF are written"
+    )]
+    dir: Option<String>,
+    #[arg(long, help = "The name of the RDB file")]
+    dbfilename: Option<String>,
+    #[arg(long, help = "Log the writes to the AOF: yes or no")]
+    appendonly: Option<String>,
+    #[arg(long, help = "Report a single node cluster: yes or no")]
+    cluster_enabled: Option<String>,
+    #[arg(long, help = "Run in the background: yes or no")]
+    daemonize: Option<String>,
+    #[arg(
+        long,
+        help = "Notify systemd of the state of the server: no, systemd or auto"
+    )]
+    supervised: Option<String>,
+    #[arg(
+        long,
+        help = "debug, verbose, notice, warning or nothing, unless RUST_LOG is set"
+    )]
+    loglevel: Option<String>,
+    #[arg(long, help = "Log to this file rather than the standard output")]
+    logfile: Option<String>,
+    #[arg(long, help = "Start a new logfile: never, hourly or daily")]
+    logfile_rotation: Option<String>,
+    #[arg(long, help = "full, compact, pretty or json lines")]
+    log_format: Option<String>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Load the keys of a JSON dataset at startup"
+    )]
+    load_json: Option<PathBuf>,
+}
+
+impl Args {
+    // the (name, value) of the parameters given, as CONFIG SET takes them
+    fn params(&self) -> Vec<(String, String)> {
+        [
+            ("bind", &self.bind),
+            ("port", &self.port),
+            ("unixsocket", &self.unixsocket),
+            ("maxmemory", &self.maxmemory),
+            ("maxmemory-policy", &self.maxmemory_policy),
+            ("requirepass", &self.requirepass),
+            ("dir", &self.dir),
+            ("dbfilename", &self.dbfilename),
+            ("appendonly", &self.appendonly),
+            ("cluster-enabled", &self.cluster_enabled),
+            ("daemonize", &self.daemonize),
+            ("supervised", &self.supervised),
+            ("loglevel", &self.loglevel),
+            ("logfile", &self.logfile),
+            ("logfile-rotation", &self.logfile_rotation),
+            ("log-format", &self.log_format),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.clone()?)))
+        .collect()
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let config = match &args.config {
+        Some(path) => ServerConfig::from_file(path)?,
+        None => ServerConfig::default(),
+    };
+    config.apply(&args.params())?;
+    let snapshot = config.snapshot();
+    // before the runtime starts its threads, which the child would not have
+    if snapshot.daemonize {
+        daemonize()?;
+    }
+    let _guard = init_tracing(&snapshot)?;
+    for name in config.ignored_directives() {
+        warn!("Ignoring unsupported config directive: {}", name);
+    }
+    info!(
+        version = env!("CARGO_PKG_VERSION"),
+        pid = std::process::id(),
+        runtime = RUNTIME_FLAVOR,
+        port = snapshot.port,
+        "Simple-Redis-Server is starting"
+    );
+    runtime()?.block_on(run(Backend::with_config(config), args.load_json))
+}
+
+async fn run(backend: Backend, json: Option<PathBuf>) -> Result<()> {
+    // a snapshot or AOF that can't be read is not overwritten, better stop
+    load_dataset(&backend)?;
+    // the keys of the JSON file replace the loaded ones of the same name
+    if let Some(path) = json {
+        let path = path.display().to_string();
+        let file = File::open(&path).with_context(|| format!("can't open {}", path))?;
+        let keys = backend.load_json(BufReader::new(file))?;
+        info!("{} keys loaded from {}", keys, path);
+    }
+    let server = RedisServer::builder().backend(backend).spawn().await?;
+
+    let handle = server.backend().shutdown_handle();
+    tokio::spawn(async move {
+        if let Err(e) = shutdown_on_signals(handle).await {
+            warn!("Can't handle the signals: {}", e);
+        }
+    });
+    server.wait().await?;
+    info!("Simple-Redis-Server is now ready to exit, bye bye...");
+    Ok(())
+}
+
+// Detaches from the terminal like redis: the parent exits, the child leads a new session and its
+// standard streams go to /dev/null.
+fn daemonize() -> Result<()> {
+    // SAFETY: the process has a single thread yet
+    match unsafe { libc::fork() } {
+        -1 => return Err(io::Error::last_os_error()).context("can't fork"),
+        0 => {}
+        _ => std::process::exit(0),
+    }
+    // SAFETY: setsid and dup2 only take plain values
+    if unsafe { libc::setsid() } == -1 {
+        return Err(io::Error::last_os_error()).context("can't start a new session");
+    }
+    let null = File::options().read(true).write(true).open("/dev/null")?;
+    for fd in 0..3 {
+        if unsafe { libc::dup2(null.as_raw_fd(), fd) } == -1 {
+            return Err(io::Error::last_os_error()).context("can't redirect to /dev/null");
+        }
+    }
+    Ok(())
+}
+
+// RUST_LOG filters the events and the spans, loglevel when it's not set, debug adds a span per
+// command with its name, first key and duration. SIMPLE_REDIS_LOG_FORMAT, or log-format, picks
+// full, compact, pretty or json lines, written to logfile if there is one. An embedding
+// application installs its own subscriber instead, like an OTLP exporter. The guard flushes the
+// lines still buffered for the logfile when dropped.
+fn init_tracing(config: &Config) -> Result<Option<WorkerGuard>> {
+    // the levels of redis, verbose is the debug of tracing as the spans of the commands are
+    let level = match config.loglevel.as_str() {
+        "debug" => "trace",
+        "verbose" => "debug",
+        "warning" => "warn",
+        "nothing" => "off",
+        _ => "info",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    let (writer, guard) = match config.logfile.as_str() {
+        "" => (BoxMakeWriter::new(io::stdout), None),
+        logfile => {
+            let (writer, guard) = tracing_appender::non_blocking(log_appender(config, logfile)?);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+    };
+    // a line is written when a span closes, with the time it took
+    let layer = tracing_subscriber::fmt::layer()
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(guard.is_none())
+        .with_writer(writer);
+    let format = std::env::var("SIMPLE_REDIS_LOG_FORMAT").unwrap_or(config.log_format.clone());
+    let layer = match format.as_str() {
+        "full" => layer.boxed(),
+        "compact" => layer.compact().boxed(),
+        "pretty" => layer.pretty().boxed(),
+        "json" => layer.json().boxed(),
+        format => bail!(
+            "unknown log format '{}', try full, compact, pretty or json",
+            format
+        ),
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(layer)
+        .try_init()?;
+    Ok(guard)
+}
+
+// The logfile, rotated by the date when logfile-rotation is hourly or daily.
+fn log_appender(config: &Config, logfile: &str) -> Result<RollingFileAppender> {
+    let path = Path::new(logfile);
+    let rotation = match config.logfile_rotation.as_str() {
+        "hourly" => Rotation::HOURLY,
+        "daily" => Rotation::DAILY,
+        _ => Rotation::NEVER,
+    };
+    let name = path
+        .file_name()
+        .with_context(|| format!("logfile '{}' is not a file", logfile))?;
+    RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(name.to_string_lossy())
+        .build(path.parent().unwrap_or(Path::new("")))
+        .with_context(|| format!("can't open the logfile '{}'", logfile))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_override_the_config() -> Result<()> {
+        let args = Args::try_parse_from([
+            "simple-redis-server",
+            "--port",
+            "7000",
+            "--maxmemory",
+            "1mb",
+            "--cluster-enabled",
+            "yes",
+            "--load-json",
+            "dataset.json",
+        ])?;
+        assert_eq!(args.load_json, Some(PathBuf::from("dataset.json")));
+
+        let config = ServerConfig::default();
+        config.apply(&args.params())?;
+        let snapshot = config.snapshot();
+        assert_eq!(snapshot.port, 7000);
+        assert_eq!(snapshot.maxmemory, 1024 * 1024);
+        assert!(snapshot.cluster_enabled);
+
+        let args = Args::try_parse_from(["simple-redis-server", "--loglevel", "loud"])?;
+        assert_eq!(
+            config.apply(&args.params()).unwrap_err().to_string(),
+            "Invalid value of 'loglevel' - argument(s) must be one of the following: debug, verbose, notice, warning, nothing"
+        );
+        Ok(())
     }
 }
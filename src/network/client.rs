@@ -0,0 +1,158 @@
+use crate::{BulkString, CodecError, FromFrameError, RespArray, RespCodec, RespFrame};
+use bytes::Bytes;
+use futures::SinkExt;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpStream, ToSocketAddrs},
+};
+use tokio_stream::StreamExt;
+use tokio_util::codec::Framed;
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+    #[error("connection closed by the server")]
+    Closed,
+    // the error reply of a command, like "ERR wrong number of arguments"
+    #[error("{0}")]
+    Reply(String),
+    #[error("unexpected reply: {0:?}")]
+    UnexpectedReply(RespFrame),
+}
+
+impl From<FromFrameError> for ClientError {
+    fn from(e: FromFrameError) -> Self {
+        ClientError::UnexpectedReply(e.frame)
+    }
+}
+
+// A connection to a server, one request at a time. The typed helpers turn the error replies into
+// `ClientError::Reply`, `send` returns them as they are.
+#[derive(Debug)]
+pub struct RedisClient<S = TcpStream> {
+    framed: Framed<S, RespCodec>,
+}
+
+impl RedisClient {
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self, ClientError> {
+        let stream = TcpStream::connect(addr).await.map_err(CodecError::from)?;
+        Ok(Self::new(stream))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> RedisClient<S> {
+    // over a connection already open, like a unix socket or a TLS stream
+    pub fn new(stream: S) -> Self {
+        Self {
+            framed: Framed::new(stream, RespCodec::default()),
+        }
+    }
+
+    // Sends a frame and waits for the reply.
+    pub async fn send(&mut self, frame: RespFrame) -> Result<RespFrame, ClientError> {
+        self.framed.send(frame).await?;
+        self.next().await
+    }
+
+    // Sends the frames in one write and waits for their replies, in order, the error replies
+    // included.
+    pub async fn pipeline(
+        &mut self,
+        frames: Vec<RespFrame>,
+    ) -> Result<Vec<RespFrame>, ClientError> {
+        let len = frames.len();
+        for frame in frames {
+            self.framed.feed(frame).await?;
+        }
+        self.framed.flush().await?;
+        let mut replies = Vec::with_capacity(len);
+        for _ in 0..len {
+            replies.push(self.next().await?);
+        }
+        Ok(replies)
+    }
+
+    // the next frame from the server, like a message of a subscribed channel
+    pub async fn next(&mut self) -> Result<RespFrame, ClientError> {
+        Ok(self.framed.next().await.ok_or(ClientError::Closed)??)
+    }
+
+    // Sends a command made of its arguments, an error reply is an error.
+    pub async fn command(&mut self, args: &[impl AsRef<[u8]>]) -> Result<RespFrame, ClientError> {
+        let args: Vec<RespFrame> = args
+            .iter()
+            .map(|arg| BulkString::from(arg.as_ref()).into())
+            .collect();
+        match self.send(RespArray::new(args).into()).await? {
+            RespFrame::Error(e) => Err(ClientError::Reply(e.0)),
+            reply => Ok(reply),
+        }
+    }
+
+    pub async fn get(&mut self, key: impl AsRef<[u8]>) -> Result<Option<Bytes>, ClientError> {
+        match self.command(&[b"GET", key.as_ref()]).await? {
+            // a missing key is a null
+            RespFrame::BulkString(s) => Ok(Some(s.0)),
+            RespFrame::Null(_) => Ok(None),
+            reply => Err(ClientError::UnexpectedReply(reply)),
+        }
+    }
+
+    pub async fn set(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        value: impl AsRef<[u8]>,
+    ) -> Result<(), ClientError> {
+        match self
+            .command(&[b"SET", key.as_ref(), value.as_ref()])
+            .await?
+        {
+            RespFrame::SimpleString(s) if s.0 == "OK" => Ok(()),
+            reply => Err(ClientError::UnexpectedReply(reply)),
+        }
+    }
+
+    // the number of keys removed
+    pub async fn del(&mut self, keys: &[impl AsRef<[u8]>]) -> Result<i64, ClientError> {
+        let mut args = vec![b"DEL".as_slice()];
+        args.extend(keys.iter().map(|key| key.as_ref()));
+        Ok(self.command(&args).await?.try_into()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handle_stream, Backend};
+    use tokio::net::UnixStream;
+
+    #[tokio::test]
+    async fn test_client_commands() -> anyhow::Result<()> {
+        let (client, server) = UnixStream::pair()?;
+        tokio::spawn(handle_stream(server, Backend::new()));
+        let mut client = RedisClient::new(client);
+        assert_eq!(client.get("foo").await?, None);
+        client.set("foo", "bar").await?;
+        assert_eq!(client.get("foo").await?, Some(Bytes::from("bar")));
+        assert_eq!(client.del(&["foo", "missing"]).await?, 1);
+        assert!(matches!(
+            client.command(&["DEBUG", "OBJECT", "foo"]).await,
+            Err(ClientError::Reply(e)) if e == "ERR no such key"
+        ));
+
+        let echo = |arg: &str| -> RespFrame {
+            RespArray::new(vec![
+                BulkString::from("ECHO").into(),
+                BulkString::from(arg).into(),
+            ])
+            .into()
+        };
+        assert_eq!(
+            client.pipeline(vec![echo("a"), echo("b")]).await?,
+            [BulkString::from("a").into(), BulkString::from("b").into()]
+        );
+        Ok(())
+    }
+}
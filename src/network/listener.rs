@@ -0,0 +1,638 @@
+use super::{handle_stream, tls_acceptor, ClientAddr, ClientStream};
+use crate::{systemd, Backend, Config, RespCodec, RespFrame, SimpleError};
+use anyhow::{bail, Result};
+use futures::SinkExt;
+use socket2::{SockRef, Socket, TcpKeepalive};
+use std::{
+    fs::{self, Permissions},
+    future, io,
+    net::SocketAddr,
+    os::{fd::OwnedFd, unix::fs::PermissionsExt},
+    path::PathBuf,
+    sync::Arc,
+    task::Poll,
+    time::Duration,
+};
+use tokio::{
+    net::{lookup_host, TcpListener, TcpSocket, TcpStream, UnixListener, UnixStream},
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time,
+};
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+use tokio_util::{codec::Framed, task::TaskTracker};
+use tracing::{field, info, info_span, warn, Instrument};
+
+// The sockets the server accepts connections on: port, tls-port and unixsocket.
+pub struct Listeners {
+    tcp: Vec<TcpListener>,
+    tls: Option<(TlsAcceptor, Vec<TcpListener>)>,
+    unix: Option<(UnixListener, PathBuf)>,
+    // passed by systemd socket activation, which owns the socket file
+    activated: bool,
+}
+
+const MAXCLIENTS: &str = "ERR max number of clients reached";
+
+// After a failed accept, e.g. EMFILE once out of file descriptors, the server waits before
+// accepting again instead of spinning on the error.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+// A client that doesn't finish the TLS handshake in time is closed, it holds a client slot.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The connections the server takes at most, maxclients. Lowering it with CONFIG SET doesn't close
+// the connections already open.
+struct ClientSlots {
+    semaphore: Arc<Semaphore>,
+    // the permits of the semaphore, taken or not
+    total: usize,
+}
+
+// A connection just accepted, the TLS handshake is done by `serve`.
+pub struct Connection {
+    pub peer: ClientAddr,
+    stream: Stream,
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    Tls(TcpStream, TlsAcceptor),
+    Unix(UnixStream),
+}
+
+impl Listeners {
+    // Binds the sockets of the configuration. Nothing is listened on for an empty bind, a tls-port
+    // of 0 or an empty unixsocket. The sockets of systemd socket activation replace bind, port and
+    // unixsocket.
+    pub async fn bind(config: &Config) -> Result<Self> {
+        let activated = systemd::listen_fds();
+        if !activated.is_empty() {
+            return Self::activated(activated, config).await;
+        }
+        let tcp = listen_all(&config.bind, config.port, config.tcp_backlog, "").await?;
+        let tls = listen_tls(config).await?;
+        let unix = match config.unixsocket.as_str() {
+            "" => None,
+            path => Some((
+                listen_unix(path, config.unixsocketperm)?,
+                PathBuf::from(path),
+            )),
+        };
+        if tcp.is_empty() && tls.is_none() && unix.is_none() {
+            bail!("bind, tls-port and unixsocket are all disabled, no client can connect");
+        }
+        Ok(Self {
+            tcp,
+            tls,
+            unix,
+            activated: false,
+        })
+    }
+
+    // The TCP and unix sockets systemd listens on for the server, tls-port is still bound.
+    async fn activated(fds: Vec<OwnedFd>, config: &Config) -> Result<Self> {
+        let (mut tcp, mut unix) = (vec![], None);
+        for fd in fds {
+            let socket = Socket::from(fd);
+            socket.set_nonblocking(true)?;
+            match socket.local_addr()?.as_socket() {
+                Some(addr) => {
+                    info!(
+                        "Simple-Redis-Server is listening for connections on {} (systemd)",
+                        addr
+                    );
+                    tcp.push(TcpListener::from_std(socket.into())?);
+                }
+                None if unix.is_none() => {
+                    let listener = UnixListener::from_std(socket.into())?;
+                    let path = listener.local_addr()?.as_pathname().map(PathBuf::from);
+                    let path = path.unwrap_or_default();
+                    info!(
+                        "Simple-Redis-Server is listening for connections on {} (systemd)",
+                        path.display()
+                    );
+                    unix = Some((listener, path));
+                }
+                None => bail!("systemd passed more than one unix socket"),
+            }
+        }
+        Ok(Self {
+            tcp,
+            tls: listen_tls(config).await?,
+            unix,
+            activated: true,
+        })
+    }
+
+    // The addresses the plain TCP connections are accepted on, with the port the OS picked for
+    // port 0. They can be connected to as soon as `bind` returns.
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        local_addrs(&self.tcp)
+    }
+
+    // The same for the TLS connections.
+    pub fn tls_local_addrs(&self) -> Vec<SocketAddr> {
+        self.tls
+            .as_ref()
+            .map(|(_, listeners)| local_addrs(listeners))
+            .unwrap_or_default()
+    }
+
+    pub async fn accept(&self) -> io::Result<Connection> {
+        tokio::select! {
+            accepted = accept(&self.tcp) => {
+                let (stream, peer) = accepted?;
+                Ok(Connection {
+                    peer: ClientAddr::Tcp(peer),
+                    stream: Stream::Tcp(stream),
+                })
+            }
+            accepted = accept(self.tls.as_ref().map_or(&[], |(_, listeners)| listeners)) => {
+                let (stream, peer) = accepted?;
+                let acceptor = self.tls.as_ref().map(|(acceptor, _)| acceptor.clone());
+                Ok(Connection {
+                    peer: ClientAddr::Tcp(peer),
+                    stream: Stream::Tls(stream, acceptor.expect("accepted on tls-port")),
+                })
+            }
+            accepted = accept_unix(self.unix.as_ref()) => {
+                let (stream, path) = accepted?;
+                Ok(Connection {
+                    peer: ClientAddr::Unix(path),
+                    stream: Stream::Unix(stream),
+                })
+            }
+        }
+    }
+}
+
+// The socket file is removed once the server stops listening, like redis.
+impl Drop for Listeners {
+    fn drop(&mut self) {
+        if let Some((_, path)) = self.unix.as_ref().filter(|_| !self.activated) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// Accepts and serves the clients until a shutdown is requested. The server then stops accepting,
+// the connections finish their in-flight command and are closed, those still running after
+// shutdown-timeout are left behind, and the AOF is flushed.
+pub async fn serve(listeners: Listeners, backend: Backend) -> Result<()> {
+    // the port picked by the OS is the one announced to the master and the cluster
+    if let Some(addr) = listeners.local_addrs().first() {
+        backend.config.bound(addr.port());
+    }
+    // systemd waits for READY=1 before starting the units after this one
+    let supervised = backend.config.snapshot().supervised != "no";
+    if supervised {
+        if let Err(e) = systemd::notify("READY=1\nSTATUS=Ready to accept connections") {
+            warn!("Can't notify systemd: {}", e);
+        }
+    }
+    let connections = TaskTracker::new();
+    let mut slots = ClientSlots::default();
+    loop {
+        let connection = tokio::select! {
+            accepted = listeners.accept() => match accepted {
+                Ok(connection) => connection,
+                Err(e) => {
+                    warn!(error = %e, "Can't accept connection");
+                    time::sleep(ACCEPT_BACKOFF).await;
+                    continue;
+                }
+            },
+            _ = backend.shutdown_requested() => break,
+        };
+        let raddr = connection.peer.clone();
+        let (nodelay, keepalive) = backend.config.tcp_options();
+        if let Err(e) = connection.tune(nodelay, keepalive) {
+            warn!(peer = %raddr, error = %e, "Can't set the socket options");
+        }
+        // the client is told why before the connection is closed
+        let Some(slot) = slots.acquire(backend.config.maxclients()) else {
+            warn!(peer = %raddr, reason = MAXCLIENTS, "Rejecting connection");
+            backend.stats.connection_rejected();
+            connections.spawn(connection.reject(SimpleError::new(MAXCLIENTS).into()));
+            continue;
+        };
+        info!(peer = %raddr, "Accepted connection");
+        let cloned_backend = backend.clone();
+        // the events and the command spans of the connection are nested in its span, the client
+        // id is recorded once the session starts
+        let span = info_span!("connection", peer = %raddr, client = field::Empty);
+        connections.spawn(
+            async move {
+                let _slot = slot;
+                // the peer is a field of the span
+                match connection.serve(cloned_backend).await {
+                    Ok(_) => info!("Connection closed"),
+                    Err(e) => warn!(error = ?e, "Connection closed on error"),
+                }
+            }
+            .instrument(span),
+        );
+    }
+
+    if supervised {
+        if let Err(e) = systemd::notify("STOPPING=1") {
+            warn!("Can't notify systemd: {}", e);
+        }
+    }
+    drop(listeners);
+    connections.close();
+    let timeout = Duration::from_secs(backend.config.shutdown_timeout());
+    if time::timeout(timeout, connections.wait()).await.is_err() {
+        warn!(
+            "{} connections still open after {:?}",
+            connections.len(),
+            timeout
+        );
+    }
+    backend.aof.stop();
+    backend.stopped.cancel();
+    Ok(())
+}
+
+impl Default for ClientSlots {
+    fn default() -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(0)),
+            total: 0,
+        }
+    }
+}
+
+impl ClientSlots {
+    // A slot for a new connection, released when dropped. None if maxclients are connected.
+    fn acquire(&mut self, maxclients: u64) -> Option<OwnedSemaphorePermit> {
+        let max = maxclients.min(Semaphore::MAX_PERMITS as u64) as usize;
+        if max > self.total {
+            self.semaphore.add_permits(max - self.total);
+            self.total = max;
+        } else if max < self.total {
+            // the permits taken are forgotten on the next calls, once released
+            self.total -= self.semaphore.forget_permits(self.total - max);
+        }
+        self.semaphore.clone().try_acquire_owned().ok()
+    }
+}
+
+impl Connection {
+    // Applies tcp-nodelay and tcp-keepalive to a TCP connection.
+    fn tune(&self, nodelay: bool, keepalive: u64) -> io::Result<()> {
+        match &self.stream {
+            Stream::Tcp(stream) | Stream::Tls(stream, _) => tune(stream, nodelay, keepalive),
+            Stream::Unix(_) => Ok(()),
+        }
+    }
+
+    // Serves the client until the connection is closed.
+    pub async fn serve(self, backend: Backend) -> Result<()> {
+        handle_stream(self.open().await?, backend).await
+    }
+
+    // Replies an error to the client and closes the connection.
+    pub async fn reject(self, error: RespFrame) -> Result<()> {
+        Ok(Framed::new(self.open().await?, RespCodec::default())
+            .send(error)
+            .await?)
+    }
+
+    // The TLS handshake runs here, in the task of the connection, a slow client doesn't hold the
+    // others back.
+    async fn open(self) -> Result<Box<dyn ClientStream>> {
+        Ok(match self.stream {
+            Stream::Tcp(stream) => Box::new(stream),
+            Stream::Tls(stream, acceptor) => {
+                Box::new(handshake(acceptor, stream, TLS_HANDSHAKE_TIMEOUT).await?)
+            }
+            Stream::Unix(stream) => Box::new(stream),
+        })
+    }
+}
+
+impl ClientStream for UnixStream {
+    fn addrs(&self) -> io::Result<(ClientAddr, ClientAddr)> {
+        let path = self
+            .local_addr()?
+            .as_pathname()
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        Ok((ClientAddr::Unix(path.clone()), ClientAddr::Unix(path)))
+    }
+}
+
+async fn handshake(
+    acceptor: TlsAcceptor,
+    stream: TcpStream,
+    timeout: Duration,
+) -> Result<TlsStream<TcpStream>> {
+    match time::timeout(timeout, acceptor.accept(stream)).await {
+        Ok(stream) => Ok(stream?),
+        Err(_) => bail!("TLS handshake timed out after {:?}", timeout),
+    }
+}
+
+// The listeners of tls-port, the certificate is read once, before accepting any connection.
+async fn listen_tls(config: &Config) -> Result<Option<(TlsAcceptor, Vec<TcpListener>)>> {
+    Ok(match config.tls_port {
+        0 => None,
+        port => Some((
+            tls_acceptor(config)?,
+            listen_all(&config.bind, port, config.tcp_backlog, "TLS ").await?,
+        )),
+    })
+}
+
+// Listens on each address of `bind`, `*` is any IPv4 address and `::*` any IPv6 one. An address
+// prefixed with `-` is skipped if it can't be bound. With port 0 the OS picks the port of the
+// first address, the others listen on the same one.
+async fn listen_all(
+    bind: &str,
+    mut port: u16,
+    backlog: u32,
+    kind: &str,
+) -> Result<Vec<TcpListener>> {
+    let mut listeners = vec![];
+    for addr in bind.split_whitespace() {
+        let (addr, optional) = match addr.strip_prefix('-') {
+            Some(addr) => (addr, true),
+            None => (addr, false),
+        };
+        let host = match addr {
+            "*" => "0.0.0.0",
+            "::*" => "::",
+            host => host,
+        };
+        match listen(host, port, backlog).await {
+            Ok(listener) => {
+                let local = listener.local_addr()?;
+                info!(
+                    "Simple-Redis-Server is listening for {}connections on {}",
+                    kind, local
+                );
+                port = local.port();
+                listeners.push(listener);
+            }
+            Err(e) if optional => warn!("Can't listen on {}: {}", addr, e),
+            Err(e) => return Err(e.context(format!("can't listen on {}", addr))),
+        }
+    }
+    Ok(listeners)
+}
+
+// The listen backlog is tcp-backlog, like redis the kernel may cap it at somaxconn.
+async fn listen(host: &str, port: u16, backlog: u32) -> Result<TcpListener> {
+    let Some(addr) = lookup_host((host, port)).await?.next() else {
+        bail!("can't resolve {}", host);
+    };
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => {
+            let socket = TcpSocket::new_v6()?;
+            // "::" doesn't take the port of "0.0.0.0" as well
+            SockRef::from(&socket).set_only_v6(true)?;
+            socket
+        }
+    };
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+    Ok(socket.listen(backlog)?)
+}
+
+// Like redis, the probes are sent every third of the idle time once it's elapsed.
+fn tune(stream: &TcpStream, nodelay: bool, keepalive: u64) -> io::Result<()> {
+    stream.set_nodelay(nodelay)?;
+    let socket = SockRef::from(stream);
+    if keepalive == 0 {
+        return socket.set_keepalive(false);
+    }
+    let idle = Duration::from_secs(keepalive);
+    let probes = TcpKeepalive::new()
+        .with_time(idle)
+        .with_interval((idle / 3).max(Duration::from_secs(1)));
+    socket.set_tcp_keepalive(&probes)
+}
+
+// A socket file left by a previous run is replaced. The permissions are octal like chmod, 0 to
+// keep the default ones.
+fn listen_unix(path: &str, perm: u32) -> Result<UnixListener> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    let listener = UnixListener::bind(path)?;
+    if perm != 0 {
+        fs::set_permissions(path, Permissions::from_mode(perm))?;
+    }
+    info!(
+        "Simple-Redis-Server is listening for connections on {}",
+        path
+    );
+    Ok(listener)
+}
+
+// The next connection to the listener, never if there is none.
+fn local_addrs(listeners: &[TcpListener]) -> Vec<SocketAddr> {
+    listeners
+        .iter()
+        .filter_map(|listener| listener.local_addr().ok())
+        .collect()
+}
+
+// The next connection on any of the listeners, never without one.
+async fn accept(listeners: &[TcpListener]) -> io::Result<(TcpStream, SocketAddr)> {
+    future::poll_fn(|cx| {
+        listeners
+            .iter()
+            .find_map(|listener| match listener.poll_accept(cx) {
+                Poll::Ready(accepted) => Some(Poll::Ready(accepted)),
+                Poll::Pending => None,
+            })
+            .unwrap_or(Poll::Pending)
+    })
+    .await
+}
+
+async fn accept_unix(
+    listener: Option<&(UnixListener, PathBuf)>,
+) -> io::Result<(UnixStream, PathBuf)> {
+    match listener {
+        Some((listener, path)) => Ok((listener.accept().await?.0, path.clone())),
+        None => future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespArray, RespCodec, RespFrame, ServerConfig};
+    use futures::SinkExt;
+    use std::os::unix::fs::FileTypeExt;
+    use tokio_stream::StreamExt;
+    use tokio_util::codec::Framed;
+
+    #[tokio::test]
+    async fn test_unix_socket() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("simple-redis-{}.sock", std::process::id()));
+        let config = Config {
+            bind: String::new(),
+            unixsocket: path.display().to_string(),
+            unixsocketperm: 0o700,
+            ..Default::default()
+        };
+        let listeners = Listeners::bind(&config).await?;
+        let metadata = fs::metadata(&path)?;
+        assert!(metadata.file_type().is_socket());
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o700);
+
+        let backend = Backend::new();
+        tokio::spawn(serve(listeners, backend.clone()));
+        let mut framed = Framed::new(
+            UnixStream::connect(&config.unixsocket).await?,
+            RespCodec::default(),
+        );
+        let args = ["CLIENT", "INFO"].map(|arg| BulkString::from(arg).into());
+        framed.send(RespArray::new(args.to_vec()).into()).await?;
+        let Some(Ok(RespFrame::BulkString(info))) = framed.next().await else {
+            panic!("CLIENT INFO replies a bulk string");
+        };
+        let expected = format!("addr={}:0 ", config.unixsocket);
+        assert!(String::from_utf8_lossy(&info).contains(&expected));
+
+        // the connections are closed and the socket file removed on shutdown
+        let handle = backend.shutdown_handle();
+        handle.shutdown(Some(false))?;
+        time::timeout(Duration::from_secs(5), handle.stopped()).await?;
+        assert!(framed.next().await.is_none());
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bind_ephemeral_port() -> Result<()> {
+        let config = Config {
+            bind: "127.0.0.1 -::1".to_string(),
+            port: 0,
+            ..Default::default()
+        };
+        let listeners = Listeners::bind(&config).await?;
+        let addrs = listeners.local_addrs();
+        assert!(!addrs.is_empty());
+        let port = addrs[0].port();
+        assert_ne!(port, 0);
+        // ::1 is skipped if the host has no IPv6
+        assert!(addrs.iter().all(|addr| addr.port() == port));
+
+        let backend = Backend::with_config(ServerConfig::new(config));
+        tokio::spawn(serve(listeners, backend.clone()));
+        let echo = RespArray::new(["ECHO", "hi"].map(|arg| BulkString::from(arg).into()));
+        for addr in addrs {
+            let mut framed = Framed::new(TcpStream::connect(addr).await?, RespCodec::default());
+            framed.send(echo.clone().into()).await?;
+            assert_eq!(
+                framed.next().await.transpose()?,
+                Some(BulkString::from("hi").into())
+            );
+        }
+        // CONFIG GET port tells the port picked
+        assert_eq!(backend.config.snapshot().port, port);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tcp_options() -> Result<()> {
+        let listener = listen("127.0.0.1", 0, 16).await?;
+        let client = TcpStream::connect(listener.local_addr()?).await?;
+        let (stream, _) = listener.accept().await?;
+        tune(&stream, true, 60)?;
+        assert!(stream.nodelay()?);
+        let socket = SockRef::from(&stream);
+        assert!(socket.keepalive()?);
+        assert_eq!(socket.keepalive_time()?, Duration::from_secs(60));
+        assert_eq!(socket.keepalive_interval()?, Duration::from_secs(20));
+        tune(&stream, false, 0)?;
+        assert!(!stream.nodelay()?);
+        assert!(!socket.keepalive()?);
+        drop(client);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_maxclients() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("simple-redis-max-{}.sock", std::process::id()));
+        let config = Config {
+            bind: String::new(),
+            unixsocket: path.display().to_string(),
+            ..Default::default()
+        };
+        let listeners = Listeners::bind(&config).await?;
+        let backend = Backend::new();
+        backend
+            .config
+            .set(&[("maxclients".to_string(), "1".to_string())])?;
+        tokio::spawn(serve(listeners, backend.clone()));
+
+        let echo = RespArray::new(
+            ["ECHO", "hi"]
+                .map(|arg| BulkString::from(arg).into())
+                .to_vec(),
+        );
+        let mut first = Framed::new(UnixStream::connect(&path).await?, RespCodec::default());
+        first.send(echo.clone().into()).await?;
+        assert_eq!(
+            first.next().await.transpose()?,
+            Some(BulkString::from("hi").into())
+        );
+
+        // the second client is told why it's closed
+        let mut second = Framed::new(UnixStream::connect(&path).await?, RespCodec::default());
+        assert_eq!(
+            second.next().await.transpose()?,
+            Some(SimpleError::new(MAXCLIENTS).into())
+        );
+        assert!(second.next().await.is_none());
+        assert_eq!(backend.stats.rejected_connections(), 1);
+
+        // a slot is free again once the first client leaves
+        drop(first);
+        time::sleep(Duration::from_millis(100)).await;
+        let mut third = Framed::new(UnixStream::connect(&path).await?, RespCodec::default());
+        third.send(echo.into()).await?;
+        assert_eq!(
+            third.next().await.transpose()?,
+            Some(BulkString::from("hi").into())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tls_handshake_timeout() -> Result<()> {
+        let server = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+        let dir = std::env::temp_dir().join(format!("simple-redis-tls-hs-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let config = Config {
+            tls_cert_file: dir.join("redis.crt").display().to_string(),
+            tls_key_file: dir.join("redis.key").display().to_string(),
+            tls_auth_clients: "no".to_string(),
+            ..Default::default()
+        };
+        fs::write(&config.tls_cert_file, server.cert.pem())?;
+        fs::write(&config.tls_key_file, server.key_pair.serialize_pem())?;
+        let acceptor = tls_acceptor(&config)?;
+        fs::remove_dir_all(&dir)?;
+
+        // the client connects and never starts the handshake
+        let listener = listen("127.0.0.1", 0, 16).await?;
+        let _client = TcpStream::connect(listener.local_addr()?).await?;
+        let (stream, _) = listener.accept().await?;
+        let timeout = Duration::from_millis(50);
+        let handshake = handshake(acceptor, stream, timeout);
+        assert!(time::timeout(Duration::from_secs(5), handshake)
+            .await?
+            .is_err());
+        Ok(())
+    }
+}
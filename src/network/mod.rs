@@ -0,0 +1,865 @@
+mod client;
+mod listener;
+mod monitor;
+mod registry;
+mod session;
+mod tls;
+
+use crate::{
+    backend,
+    cmd::{
+        command_keys, command_name, is_write, lookup, may_write, request_keys, request_static_name,
+        Command, CommandError, CommandExecutor, DebugCmd, ExecContext, Unrecognized, RESP_OK,
+    },
+    replication, Backend, BulkString, CodecError, CommandRename, RespArray, RespCodec, RespEncoder,
+    RespError, RespFrame, SimpleError, SimpleString,
+};
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{FutureExt, SinkExt};
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+    sync::mpsc,
+    time,
+};
+use tokio_stream::StreamExt;
+use tokio_util::codec::Framed;
+use tracing::{debug, debug_span, field, info, trace, trace_span, warn, Instrument, Span};
+
+pub use client::{ClientError, RedisClient};
+pub use listener::{serve, Connection, Listeners};
+pub use monitor::Monitors;
+pub use registry::{ClientAddr, ClientInfo, ClientRegistry};
+pub(crate) use session::{Queued, Session};
+pub use tls::tls_acceptor;
+
+// A connection clients talk RESP over, plain TCP or TLS.
+pub trait ClientStream: AsyncRead + AsyncWrite + Unpin + Send {
+    // (peer address, local address)
+    fn addrs(&self) -> io::Result<(ClientAddr, ClientAddr)>;
+
+    // the common name of the certificate the client authenticated with, if any
+    fn peer_cn(&self) -> Option<String> {
+        None
+    }
+}
+
+#[derive(Debug)]
+struct RedisRequest {
+    frame: RespFrame,
+    backend: Backend,
+}
+
+#[derive(Debug)]
+struct RedisResponse {
+    frame: RespFrame,
+}
+
+pub async fn handle_stream(stream: impl ClientStream, backend: Backend) -> Result<()> {
+    let (addr, laddr) = stream.addrs()?;
+    let (sender, mut pushes) = mpsc::unbounded_channel();
+    let mut session = Session::new(sender);
+    Span::current().record("client", session.id);
+    session.authenticated = backend.acl.default_nopass(&backend.config.requirepass());
+    let mut client = ClientInfo::new(session.id, addr, laddr);
+    client.cn = stream.peer_cn();
+    // how to get a frame from the stream?
+    let codec = RespCodec::new(session.resp).with_limits(backend.config.decode_limits());
+    let mut framed = Framed::new(stream, codec);
+    let killed = client.killed.clone();
+    backend.clients.register(client);
+    backend.stats.client_connected();
+    let ret = loop {
+        // the requests of a pipeline already read are all executed before their replies are
+        // flushed at once, not one write per reply
+        let frame = match framed.next().now_or_never() {
+            Some(_) if killed.is_cancelled() || backend.shutdown.is_cancelled() => break Ok(()),
+            Some(frame) => frame,
+            None => {
+                if let Err(e) = framed.flush().await {
+                    break Err(e.into());
+                }
+                let timeout = backend.config.timeout();
+                tokio::select! {
+                    frame = framed.next() => frame,
+                    Some(frame) = pushes.recv() => {
+                        trace!(%frame, "pushing frame");
+                        if over_output_limit(&framed, &frame, &session, &backend) {
+                            break Ok(());
+                        }
+                        if let Err(e) = framed.send(frame).await {
+                            break Err(e.into());
+                        }
+                        continue;
+                    }
+                    _ = backend.shutdown_requested() => {
+                        info!("Closing connection {} on shutdown", session.id);
+                        break Ok(());
+                    }
+                    _ = killed.cancelled() => {
+                        info!("Connection {} killed by CLIENT KILL", session.id);
+                        break Ok(());
+                    }
+                    // replicas may not hear from us for a long time when nothing is written
+                    _ = time::sleep(Duration::from_secs(timeout)), if timeout > 0 && !session.replica => {
+                        info!("Closing idle connection {}", session.id);
+                        break Ok(());
+                    }
+                }
+            }
+        };
+        match frame {
+            Some(Ok(frame)) => {
+                trace!(%frame, "received frame");
+                let span = command_span(&frame);
+                let request = RedisRequest {
+                    frame,
+                    backend: backend.clone(),
+                };
+                let start = Instant::now();
+                let response = handle_request(request, &mut session)
+                    .instrument(span.clone())
+                    .await;
+                span.record("duration_us", start.elapsed().as_micros() as u64);
+                let response = match response {
+                    Ok(response) => response,
+                    Err(e) => break Err(e),
+                };
+                // HELLO switches the protocol of its own reply already, CONFIG SET
+                // proto-max-bulk-len applies to the next request
+                framed.codec_mut().set_protover(session.resp);
+                framed
+                    .codec_mut()
+                    .set_limits(backend.config.decode_limits());
+                if !std::mem::take(&mut session.skip_reply) {
+                    trace!(frame = %response.frame, "sending response");
+                    if over_output_limit(&framed, &response.frame, &session, &backend) {
+                        break Ok(());
+                    }
+                    if let Err(e) = framed.feed(response.frame).await {
+                        break Err(e.into());
+                    }
+                }
+                if session.closing {
+                    break framed.flush().await.map_err(Into::into);
+                }
+            }
+            // the rest of the stream can't be made sense of, the client is told why before the
+            // connection is closed
+            Some(Err(CodecError::Protocol(e))) => {
+                info!("Protocol error from connection {}: {}", session.id, e);
+                let reply = SimpleError::new(format!("ERR Protocol error: {}", protocol_error(e)));
+                break framed.send(reply.into()).await.map_err(Into::into);
+            }
+            // the replies of the requests before are still sent
+            Some(Err(e)) => break framed.flush().await.and(Err(e)).map_err(Into::into),
+            None => break framed.flush().await.map_err(Into::into),
+        }
+    };
+    session.unwatch(&backend);
+    backend.tracking.disable(session.id);
+    backend.clients.unregister(session.id);
+    backend.monitors.remove(session.id);
+    backend.replication.remove_replica(session.id);
+    backend.stats.client_disconnected();
+    ret
+}
+
+// Whether writing the frame would take the replies waiting to be flushed past the hard
+// client-output-buffer-limit of the connection, which is then closed like in redis.
+fn over_output_limit<S>(
+    framed: &Framed<S, RespCodec>,
+    frame: &RespFrame,
+    session: &Session,
+    backend: &Backend,
+) -> bool {
+    let limit = backend.config.output_buffer_limit(session.output_class());
+    let pending = (framed.write_buffer().len() + frame.encoded_len()) as u64;
+    if limit == 0 || pending <= limit {
+        return false;
+    }
+    info!(
+        "Closing connection {} for overcoming of output buffer limits: {} bytes",
+        session.id, pending
+    );
+    true
+}
+
+async fn handle_request(request: RedisRequest, session: &mut Session) -> Result<RedisResponse> {
+    let (frame, backend) = (request.frame, request.backend);
+    let frame = match renamed(frame, &backend) {
+        Ok(frame) => frame,
+        Err(unknown) => {
+            session.fail_multi();
+            let frame = unknown
+                .execute(&mut ExecContext::new(&backend))
+                .unwrap_or_else(error_reply);
+            return Ok(RedisResponse { frame });
+        }
+    };
+    let name = request_static_name(&frame);
+    backend.clients.update(session.id, |client| {
+        client.last_cmd = name.clone();
+        client.last_interaction = Instant::now();
+    });
+    let no_auth = matches!(name.as_ref(), "auth" | "hello" | "quit");
+    if !session.authenticated && !no_auth {
+        return rejected(
+            &backend,
+            &name,
+            SimpleError::new("NOAUTH Authentication required."),
+        );
+    }
+    if let Err(e) = session.check_context(&name) {
+        return rejected(&backend, &name, SimpleError::new(e));
+    }
+    let spec = lookup(name.split('|').next().unwrap_or_default());
+    if !no_auth {
+        let custom = spec.is_none() && backend.commands.contains(&name);
+        if let Err(e) = backend
+            .acl
+            .check(&session.user, &name, custom, || command_keys(&frame))
+        {
+            // like a bad command, a forbidden one aborts the transaction
+            session.fail_multi();
+            return rejected(&backend, &name, SimpleError::new(e));
+        }
+    }
+    // the keys at the key positions of the spec, looked for only when the cluster checks them or
+    // client side caching may track them
+    let cluster = backend.config.cluster_enabled();
+    let keys = match &frame {
+        RespFrame::Array(args)
+            if cluster || backend.tracking.is_enabled(session.id) || session.queued.is_some() =>
+        {
+            request_keys(args)
+        }
+        _ => vec![],
+    };
+    // the command consumes the frame, keep the arguments for the slow log
+    let (slower_than, slowlog_max_len) = backend.config.slowlog();
+    // AUTH and HELLO are not logged, the password would leak
+    let args = (!matches!(name.as_ref(), "auth" | "hello")
+        && (slower_than >= 0 || !backend.monitors.is_empty()))
+    .then(|| frame.clone());
+    let cmd = match Command::try_from(frame) {
+        Ok(cmd) => backend.commands.resolve(cmd),
+        Err(e) => {
+            // the connection stays open, a bad command poisons the whole transaction though
+            session.fail_multi();
+            return rejected(&backend, &name, error_reply(e));
+        }
+    };
+    // in cluster mode the keys must be served by the node, MIGRATE moves the keys of the node it
+    // runs on
+    if cluster && name != "migrate" {
+        let asking = std::mem::take(&mut session.asking) || name == "restore-asking";
+        if let Err(redirect) = backend
+            .cluster
+            .check(&keys, asking, |key| backend.exists(key))
+        {
+            session.fail_multi();
+            return rejected(&backend, &name, SimpleError::new(redirect.to_string()));
+        }
+    }
+    // a replica only takes writes from its master, scripts are checked when they call a write
+    if is_write(&name) && backend.replication.read_only(&backend.config) {
+        session.fail_multi();
+        return rejected(&backend, &name, SimpleError::new(replication::READONLY));
+    }
+    // keys whose values are sent back to the client, for client side caching
+    let read_keys = match spec.is_some_and(|spec| spec.is_readonly()) {
+        true => keys,
+        false => vec![],
+    };
+    // commands that may need more memory evict keys first, or are refused
+    let denyoom = spec.is_some_and(|spec| spec.flags.contains(&"denyoom"));
+    if denyoom && !backend.free_memory() {
+        session.fail_multi();
+        return rejected(&backend, &name, SimpleError::new(backend::OOM));
+    }
+    debug!(?cmd, "executing command");
+    // wait while CLIENT PAUSE is in effect, queuing inside MULTI is not held back
+    let write = match (&cmd, &session.queued) {
+        (Command::Client(_), _) => None,
+        (Command::Exec(_), Some(queued)) => Some(queued.iter().any(|queued| queued.write)),
+        (_, Some(_)) => None,
+        (_, None) => Some(may_write(&name)),
+    };
+    if let Some(write) = write {
+        backend.pause.wait(write).await;
+    }
+    if let Some(args) = &args {
+        // like redis, administrative commands are not shown
+        let admin = spec.is_some_and(|spec| spec.flags.contains(&"admin"));
+        if !backend.monitors.is_empty() && !admin {
+            let addr = backend.clients.get(session.id).map(|c| c.addr);
+            backend
+                .monitors
+                .feed(session.id, addr, session.name.as_deref(), args);
+        }
+    }
+    // the commands queued by MULTI are counted when EXEC runs, as one call of EXEC
+    let queuing = session.queued.is_some();
+    let appended = backend.aof.appended();
+    let start = Instant::now();
+    let frame = match (cmd, session.queued.as_mut()) {
+        (Command::Multi(_), Some(_)) => {
+            SimpleError::new("ERR MULTI calls can not be nested").into()
+        }
+        (Command::Multi(_), None) => {
+            session.queued = Some(vec![]);
+            RESP_OK.clone()
+        }
+        (Command::Watch(_), Some(_)) => {
+            SimpleError::new("ERR WATCH inside MULTI is not allowed").into()
+        }
+        (Command::Watch(cmd), None) => cmd
+            .execute(&mut ExecContext::with_session(&backend, session))
+            .unwrap_or_else(error_reply),
+        (Command::Exec(_), Some(_)) => {
+            let queued = session.queued.take().unwrap_or_default();
+            if std::mem::take(&mut session.multi_error) {
+                session.unwatch(&backend);
+                SimpleError::new("EXECABORT Transaction discarded because of previous errors.")
+                    .into()
+            } else {
+                let _guard = backend.exec_lock.write().unwrap_or_else(|e| e.into_inner());
+                let modified = session
+                    .watched
+                    .iter()
+                    .any(|(key, version)| backend.version(key) != *version);
+                session.unwatch(&backend);
+                if modified {
+                    return Ok(RedisResponse {
+                        frame: RespFrame::null_array(),
+                    });
+                }
+                let frames = queued
+                    .into_iter()
+                    .map(|queued| execute_command(queued.cmd, queued.read_keys, session, &backend))
+                    .collect::<Vec<_>>();
+                RespArray::new(frames).into()
+            }
+        }
+        (Command::Discard(_), Some(_)) => {
+            session.queued = None;
+            session.multi_error = false;
+            session.unwatch(&backend);
+            RESP_OK.clone()
+        }
+        (Command::Unrecognized(cmd), Some(_)) => {
+            // like a bad command, an unknown one isn't queued and fails the transaction
+            session.fail_multi();
+            cmd.execute(&mut ExecContext::new(&backend))
+                .unwrap_or_else(error_reply)
+        }
+        (cmd, Some(queued)) => {
+            queued.push(Queued {
+                cmd,
+                write: may_write(&name),
+                read_keys,
+            });
+            SimpleString::new("QUEUED").into()
+        }
+        (cmd, None) if cmd.is_async() => {
+            // blocks the connection without holding the exec lock
+            backend.stats.command_processed();
+            cmd.execute_async(&mut ExecContext::with_session(&backend, session))
+                .await
+                .unwrap_or_else(error_reply)
+        }
+        (
+            cmd @ (Command::Eval(_)
+            | Command::EvalSha(_)
+            | Command::FCall(_)
+            | Command::Save(_)
+            | Command::BgRewriteAof(_)
+            | Command::Debug(DebugCmd::Reload)
+            | Command::Shutdown(_)
+            | Command::PSync(_)),
+            None,
+        ) => {
+            // scripts run atomically, SAVE and DEBUG RELOAD block the server like in redis, the AOF
+            // rewrite and the full resync start from a view of the dataset in step with the writes
+            let _guard = backend.exec_lock.write().unwrap_or_else(|e| e.into_inner());
+            execute_command(cmd, read_keys, session, &backend)
+        }
+        (cmd, None) => {
+            let _guard = backend.exec_lock.read().unwrap_or_else(|e| e.into_inner());
+            execute_command(cmd, read_keys, session, &backend)
+        }
+    };
+    // with appendfsync always, the writes are on disk before they're acknowledged
+    if let Some(sync) = backend.aof.sync_since(&backend, appended) {
+        sync.wait().await;
+    }
+    let elapsed = start.elapsed();
+    if !(queuing && session.queued.is_some()) {
+        let failed = matches!(frame, RespFrame::Error(_));
+        backend.stats.command_called(&name, elapsed, failed);
+    }
+    if let Some(args) = args.filter(|_| elapsed.as_micros() >= slower_than as u128) {
+        warn!(
+            command = %name,
+            duration_us = elapsed.as_micros() as u64,
+            "Slow command"
+        );
+        let client = backend.clients.get(session.id);
+        backend.slowlog.record(
+            &args,
+            elapsed,
+            client.map(|c| c.addr.to_string()).unwrap_or_default(),
+            session.name.clone().unwrap_or_default(),
+            slowlog_max_len as usize,
+        );
+    }
+    session.publish(&backend);
+    Ok(RedisResponse { frame })
+}
+
+fn execute_command(
+    cmd: Command,
+    read_keys: Vec<Bytes>,
+    session: &mut Session,
+    backend: &Backend,
+) -> RespFrame {
+    // the commands queued by MULTI each get their own span in the one of EXEC
+    let _span = trace_span!("execute").entered();
+    backend.stats.command_processed();
+    // the commands about the connection itself don't consume CLIENT CACHING
+    let connection = matches!(
+        cmd,
+        Command::Client(_)
+            | Command::Unwatch(_)
+            | Command::Monitor(_)
+            | Command::Auth(_)
+            | Command::Quit(_)
+            | Command::Hello(_)
+            | Command::Acl(_)
+            | Command::ReplConf(_)
+            | Command::PSync(_)
+            | Command::Cluster(_)
+            | Command::Asking(_)
+    );
+    let tracked_keys = match !connection && session.should_track(backend) {
+        true => read_keys,
+        false => vec![],
+    };
+    let frame = cmd.execute(&mut ExecContext::with_session(backend, session));
+    if !connection {
+        for key in tracked_keys {
+            backend.tracking.track(session.id, key);
+        }
+        session.caching = None;
+    }
+    frame.unwrap_or_else(error_reply)
+}
+
+// The request under the original name of the command once rename-command is applied, a command
+// renamed or disabled is unknown under its own name.
+fn renamed(frame: RespFrame, backend: &Backend) -> Result<RespFrame, Unrecognized> {
+    let RespFrame::Array(mut args) = frame else {
+        return Ok(frame);
+    };
+    let rename = match args.first() {
+        Some(RespFrame::BulkString(name)) => backend
+            .config
+            .command_rename(std::str::from_utf8(name).unwrap_or_default()),
+        _ => None,
+    };
+    match rename {
+        None => Ok(args.into()),
+        Some(CommandRename::Alias(name)) => {
+            args.0[0] = BulkString::from(name).into();
+            Ok(args.into())
+        }
+        Some(CommandRename::Hidden) => Err(Unrecognized::from(args)),
+    }
+}
+
+// the reply to a command refused before it ran
+fn rejected(backend: &Backend, name: &str, frame: impl Into<RespFrame>) -> Result<RedisResponse> {
+    backend.stats.command_rejected(name);
+    Ok(RedisResponse {
+        frame: frame.into(),
+    })
+}
+
+// the reply of a command that couldn't run
+fn error_reply(e: CommandError) -> RespFrame {
+    SimpleError::new(format!("ERR {}", e)).into()
+}
+
+// The span of a request, the command name and its first key are only looked up when a subscriber
+// listens to it.
+fn command_span(frame: &RespFrame) -> Span {
+    let span = debug_span!(
+        "command",
+        name = field::Empty,
+        key = field::Empty,
+        duration_us = field::Empty
+    );
+    if !span.is_disabled() {
+        span.record("name", command_name(frame));
+        if let Some(key) = command_keys(frame).first() {
+            span.record("key", String::from_utf8_lossy(key).as_ref());
+        }
+    }
+    span
+}
+
+// the detail of a protocol error, like "unbalanced quotes in request"
+fn protocol_error(e: RespError) -> String {
+    match e {
+        RespError::InvalidFrame(detail) | RespError::InvalidFrameType(detail) => detail,
+        e => e.to_string(),
+    }
+}
+
+impl<S: ClientStream + ?Sized> ClientStream for Box<S> {
+    fn addrs(&self) -> io::Result<(ClientAddr, ClientAddr)> {
+        (**self).addrs()
+    }
+
+    fn peer_cn(&self) -> Option<String> {
+        (**self).peer_cn()
+    }
+}
+
+impl ClientStream for TcpStream {
+    fn addrs(&self) -> io::Result<(ClientAddr, ClientAddr)> {
+        Ok((
+            ClientAddr::Tcp(self.peer_addr()?),
+            ClientAddr::Tcp(self.local_addr()?),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, RespPush, ServerConfig};
+    use std::sync::{Arc, Mutex};
+    use tokio::net::UnixStream;
+    use tracing_subscriber::fmt::format::FmtSpan;
+
+    // a request with the arguments as bulk strings
+    fn command(args: &[&str]) -> RespFrame {
+        RespArray::new(
+            args.iter()
+                .map(|arg| BulkString::from(*arg).into())
+                .collect::<Vec<_>>(),
+        )
+        .into()
+    }
+
+    #[tokio::test]
+    async fn test_protocol_error_reply() -> Result<()> {
+        let (client, server) = UnixStream::pair()?;
+        let backend = Backend::new();
+        let connection = tokio::spawn(handle_stream(server, backend));
+        let mut framed = Framed::new(client, RespCodec::default());
+        let echo = ["ECHO", "hi"].map(|arg| BulkString::from(arg).into());
+        framed.send(RespArray::new(echo.to_vec()).into()).await?;
+        assert_eq!(
+            framed.next().await.transpose()?,
+            Some(BulkString::from("hi").into())
+        );
+
+        framed.get_mut().try_write(b"SET \"foo\"bar\r\n")?;
+        assert_eq!(
+            framed.next().await.transpose()?,
+            Some(SimpleError::new("ERR Protocol error: unbalanced quotes in request").into())
+        );
+        assert!(framed.next().await.is_none());
+        // not an error of the server
+        assert!(connection.await?.is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replies_follow_the_protocol() -> Result<()> {
+        let (client, server) = UnixStream::pair()?;
+        tokio::spawn(handle_stream(server, Backend::new()));
+        let mut framed = Framed::new(client, RespCodec::default());
+
+        framed.send(command(&["GET", "foo"])).await?;
+        let reply = framed.next().await.transpose()?;
+        assert_eq!(
+            reply.map(|frame| frame.encode_to_vec()),
+            Some(b"$-1\r\n".to_vec())
+        );
+        // the reply of HELLO 3 is a map already
+        framed.send(command(&["HELLO", "3"])).await?;
+        assert!(matches!(
+            framed.next().await.transpose()?,
+            Some(RespFrame::Map(_))
+        ));
+        framed.send(command(&["GET", "foo"])).await?;
+        assert_eq!(framed.next().await.transpose()?, Some(RespFrame::null()));
+        framed.send(command(&["HELLO", "2"])).await?;
+        assert!(matches!(
+            framed.next().await.transpose()?,
+            Some(RespFrame::Array(_))
+        ));
+
+        // an EXEC aborted by WATCH is the null array of RESP2
+        for args in [
+            &["WATCH", "foo"][..],
+            &["SET", "foo", "bar"],
+            &["MULTI"],
+            &["GET", "foo"],
+        ] {
+            framed.send(command(args)).await?;
+            framed.next().await.transpose()?;
+        }
+        framed.send(command(&["EXEC"])).await?;
+        let reply = framed.next().await.transpose()?;
+        assert_eq!(
+            reply.map(|frame| frame.encode_to_vec()),
+            Some(b"*-1\r\n".to_vec())
+        );
+
+        // an empty transaction, or an empty string, isn't a null
+        for (args, expected) in [
+            (&["MULTI"][..], &b"+OK\r\n"[..]),
+            (&["EXEC"], b"*0\r\n"),
+            (&["SET", "foo", ""], b"+OK\r\n"),
+            (&["GET", "foo"], b"$0\r\n\r\n"),
+            (&["HGETALL", "missing"], b"*0\r\n"),
+            (&["SLOWLOG", "GET"], b"*0\r\n"),
+        ] {
+            framed.send(command(args)).await?;
+            let reply = framed.next().await.transpose()?;
+            assert_eq!(
+                reply.map(|frame| frame.encode_to_vec()),
+                Some(expected.to_vec())
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unknown_commands_are_errors() -> Result<()> {
+        let (client, server) = UnixStream::pair()?;
+        tokio::spawn(handle_stream(server, Backend::new()));
+        let mut framed = Framed::new(client, RespCodec::default());
+
+        framed.send(command(&["SETT", "k", "a\r\nb"])).await?;
+        assert_eq!(
+            framed.next().await.transpose()?,
+            Some(
+                SimpleError::new(
+                    "ERR unknown command 'SETT', with args beginning with: 'k' 'a  b' "
+                )
+                .into()
+            )
+        );
+
+        // and the transaction they were sent in is aborted
+        for args in [&["MULTI"][..], &["NOPE"], &["GET", "k"]] {
+            framed.send(command(args)).await?;
+            framed.next().await.transpose()?;
+        }
+        framed.send(command(&["EXEC"])).await?;
+        assert!(matches!(
+            framed.next().await.transpose()?,
+            Some(RespFrame::Error(e)) if e.starts_with("EXECABORT")
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wrong_arity_keeps_the_connection() -> Result<()> {
+        let (client, server) = UnixStream::pair()?;
+        tokio::spawn(handle_stream(server, Backend::new()));
+        let mut framed = Framed::new(client, RespCodec::default());
+
+        framed.send(command(&["GET"])).await?;
+        assert_eq!(
+            framed.next().await.transpose()?,
+            Some(SimpleError::new("ERR wrong number of arguments for 'get' command").into())
+        );
+        framed.send(command(&["CLIENT", "SETNAME"])).await?;
+        assert_eq!(
+            framed.next().await.transpose()?,
+            Some(
+                SimpleError::new("ERR wrong number of arguments for 'client|setname' command")
+                    .into()
+            )
+        );
+        framed.send(command(&["ECHO", "hi"])).await?;
+        assert_eq!(
+            framed.next().await.transpose()?,
+            Some(BulkString::from("hi").into())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tracking_follows_the_read_commands() -> Result<()> {
+        let (client, server) = UnixStream::pair()?;
+        let backend = Backend::new();
+        tokio::spawn(handle_stream(server, backend.clone()));
+        let mut framed = Framed::new(client, RespCodec::default());
+
+        for args in [
+            &["HELLO", "3"][..],
+            &["CLIENT", "TRACKING", "ON"],
+            &["GET", "a"],
+            &["SET", "b", "1"],
+            &["MULTI"],
+            &["HGET", "c", "f"],
+            &["EXEC"],
+        ] {
+            framed.send(command(args)).await?;
+            framed.next().await.transpose()?;
+        }
+        // GET and HGET read their key, SET doesn't
+        for key in ["a", "b", "c"] {
+            backend.set(key.into(), BulkString::from("2").into());
+        }
+        framed.send(command(&["ECHO", "hi"])).await?;
+        let invalidated = |key: &str| -> RespFrame {
+            RespPush::new([
+                BulkString::from("invalidate").into(),
+                RespArray::new([BulkString::from(key).into()]).into(),
+            ])
+            .into()
+        };
+        assert_eq!(framed.next().await.transpose()?, Some(invalidated("a")));
+        assert_eq!(framed.next().await.transpose()?, Some(invalidated("c")));
+        assert_eq!(
+            framed.next().await.transpose()?,
+            Some(BulkString::from("hi").into())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_renamed_commands() -> Result<()> {
+        let config = Config {
+            rename_command: vec![
+                ("echo".to_string(), "say".to_string()),
+                ("shutdown".to_string(), String::new()),
+            ],
+            ..Default::default()
+        };
+        let (client, server) = UnixStream::pair()?;
+        tokio::spawn(handle_stream(
+            server,
+            Backend::with_config(ServerConfig::new(config)),
+        ));
+        let mut framed = Framed::new(client, RespCodec::default());
+
+        framed.send(command(&["SAY", "hi"])).await?;
+        assert_eq!(
+            framed.next().await.transpose()?,
+            Some(BulkString::from("hi").into())
+        );
+        framed.send(command(&["ECHO", "hi"])).await?;
+        assert_eq!(
+            framed.next().await.transpose()?,
+            Some(
+                SimpleError::new("ERR unknown command 'ECHO', with args beginning with: 'hi' ")
+                    .into()
+            )
+        );
+        framed.send(command(&["shutdown"])).await?;
+        assert_eq!(
+            framed.next().await.transpose()?,
+            Some(
+                SimpleError::new("ERR unknown command 'shutdown', with args beginning with: ")
+                    .into()
+            )
+        );
+        Ok(())
+    }
+
+    // where the subscriber of a test writes its lines
+    #[derive(Clone, Default)]
+    struct Lines(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Lines {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_command_spans() -> Result<()> {
+        let lines = Lines::default();
+        let writer = lines.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let (client, server) = UnixStream::pair()?;
+        let span = tracing::info_span!("connection", client = field::Empty);
+        let connection = tokio::spawn(handle_stream(server, Backend::new()).instrument(span));
+        let mut framed = Framed::new(client, RespCodec::default());
+        let get = ["GET", "foo"].map(|arg| BulkString::from(arg).into());
+        framed.send(RespArray::new(get.to_vec()).into()).await?;
+        // the null bulk string of RESP2
+        assert_eq!(framed.next().await.transpose()?, Some(RespFrame::null()));
+        drop(framed);
+        connection.await??;
+
+        let output = String::from_utf8(lines.0.lock().unwrap().clone())?;
+        // the command span closes in the one of the connection, with the time it took
+        let closed = output
+            .lines()
+            .find(|line| line.contains(r#":command{name="get" key="foo" duration_us="#))
+            .expect("the command span is closed");
+        assert!(closed.contains("connection{client="));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_slow_commands_are_warned() -> Result<()> {
+        let lines = Lines::default();
+        let writer = lines.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::WARN)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let backend = Backend::new();
+        backend
+            .config
+            .set(&[("slowlog-log-slower-than".to_string(), "0".to_string())])?;
+        let (client, server) = UnixStream::pair()?;
+        let connection = tokio::spawn(handle_stream(server, backend));
+        let mut framed = Framed::new(client, RespCodec::default());
+        let echo = ["ECHO", "hi"].map(|arg| BulkString::from(arg).into());
+        framed.send(RespArray::new(echo.to_vec()).into()).await?;
+        framed.next().await.transpose()?;
+        drop(framed);
+        connection.await??;
+
+        let output = String::from_utf8(lines.0.lock().unwrap().clone())?;
+        let warned = output
+            .lines()
+            .find(|line| line.contains("Slow command"))
+            .expect("the slow command is warned");
+        assert!(warned.contains("WARN") && warned.contains("command=echo duration_us="));
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use super::ClientAddr;
+use crate::{resp::write_quoted, RespFrame, SimpleString};
+use dashmap::DashMap;
+use std::{
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+// Connections in MONITOR mode, every command processed by the server is fed to them.
+#[derive(Debug, Default)]
+pub struct Monitors(DashMap<u64, UnboundedSender<RespFrame>>);
+
+impl Monitors {
+    pub fn add(&self, id: u64, sender: UnboundedSender<RespFrame>) {
+        self.0.insert(id, sender);
+    }
+
+    pub fn remove(&self, id: u64) {
+        self.0.remove(&id);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // Sends the command of a client to all the monitors but itself.
+    pub fn feed(&self, from: u64, addr: Option<ClientAddr>, name: Option<&str>, args: &RespFrame) {
+        let line = format_line(addr, name, args);
+        for monitor in self.0.iter().filter(|m| *m.key() != from) {
+            let _ = monitor.send(SimpleString::new(line.clone()).into());
+        }
+    }
+}
+
+// 1339518083.107412 [0 127.0.0.1:60866] "set" "foo" "bar"
+// 1339518083.107412 [0 unix:/tmp/redis.sock] "get" "foo"
+fn format_line(addr: Option<ClientAddr>, name: Option<&str>, args: &RespFrame) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut line = format!("{}.{:06} [0", now.as_secs(), now.subsec_micros());
+    match addr {
+        Some(ClientAddr::Unix(path)) => {
+            let _ = write!(line, " unix:{}", path.display());
+        }
+        Some(addr) => {
+            let _ = write!(line, " {}", addr);
+        }
+        None => {}
+    }
+    if let Some(name) = name {
+        let _ = write!(line, " {}", name);
+    }
+    line.push(']');
+    if let RespFrame::Array(args) = args {
+        for arg in args.iter() {
+            if let RespFrame::BulkString(arg) = arg {
+                line.push(' ');
+                let _ = write_quoted(&mut line, arg);
+            }
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespArray;
+
+    #[test]
+    fn test_format_line() {
+        let args = RespArray::new([b"set".into(), b"k\"1".into(), b"a\r\n\x01".into()]).into();
+        let line = format_line(
+            "127.0.0.1:5000".parse().ok().map(ClientAddr::Tcp),
+            Some("web"),
+            &args,
+        );
+        let (_, rest) = line.split_once(' ').unwrap();
+        assert_eq!(rest, r#"[0 127.0.0.1:5000 web] "set" "k\"1" "a\r\n\x01""#);
+    }
+
+    #[tokio::test]
+    async fn test_feed_skips_the_sender() {
+        let monitors = Monitors::default();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        monitors.add(1, tx);
+        let args = RespArray::new([b"get".into(), b"k".into()]).into();
+        monitors.feed(1, None, None, &args);
+        monitors.feed(2, None, None, &args);
+        let frame = rx.recv().await.unwrap();
+        assert!(matches!(frame, RespFrame::SimpleString(s) if s.ends_with("[0] \"get\" \"k\"")));
+        assert!(rx.try_recv().is_err());
+    }
+}
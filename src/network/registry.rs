@@ -0,0 +1,198 @@
+use dashmap::DashMap;
+use std::{
+    borrow::Cow,
+    fmt::{self, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+
+// The address of a client, or of the server end of its connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientAddr {
+    Tcp(SocketAddr),
+    // the path of the unix socket, its clients have no address of their own
+    Unix(PathBuf),
+}
+
+// What CLIENT LIST knows about a connection.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub id: u64,
+    pub addr: ClientAddr,
+    pub laddr: ClientAddr,
+    pub name: String,
+    pub created_at: Instant,
+    pub last_interaction: Instant,
+    pub last_cmd: Cow<'static, str>,
+    // number of queued commands in MULTI, None if not in a transaction
+    pub multi: Option<usize>,
+    // the selected database and the number of channels and patterns subscribed to
+    pub db: usize,
+    pub sub: usize,
+    pub psub: usize,
+    pub resp: u8,
+    // a replica connection, after PSYNC
+    pub replica: bool,
+    // the user the connection is authenticated as
+    pub user: String,
+    // the common name of the TLS client certificate, for audit
+    pub cn: Option<String>,
+    // cancelled by CLIENT KILL, the connection task closes the connection
+    pub killed: CancellationToken,
+}
+
+// Registry of the connected clients, maintained by the connection tasks.
+#[derive(Debug, Default)]
+pub struct ClientRegistry(DashMap<u64, ClientInfo>);
+
+impl ClientInfo {
+    pub fn new(id: u64, addr: ClientAddr, laddr: ClientAddr) -> Self {
+        let now = Instant::now();
+        Self {
+            id,
+            addr,
+            laddr,
+            name: String::new(),
+            created_at: now,
+            last_interaction: now,
+            last_cmd: "NULL".into(),
+            multi: None,
+            db: 0,
+            sub: 0,
+            psub: 0,
+            resp: 2,
+            replica: false,
+            user: "default".to_string(),
+            cn: None,
+            killed: CancellationToken::new(),
+        }
+    }
+
+    // One line of CLIENT LIST, also the reply of CLIENT INFO. The clients authenticated by a TLS
+    // certificate end with its common name, cn=alice.
+    // id=3 addr=127.0.0.1:52555 laddr=127.0.0.1:6379 name= age=1 idle=0 flags=N db=0 sub=0 psub=0 multi=-1 cmd=client|info user=default resp=2
+    pub fn to_line(&self) -> String {
+        let mut line = String::new();
+        let _ = write!(
+            line,
+            "id={} addr={} laddr={} name={} age={} idle={} flags={} db={} sub={} psub={} multi={} cmd={} user={} resp={}",
+            self.id,
+            self.addr,
+            self.laddr,
+            self.name,
+            self.created_at.elapsed().as_secs(),
+            self.last_interaction.elapsed().as_secs(),
+            match (self.replica, self.multi.is_some(), self.sub + self.psub > 0) {
+                (true, _, _) => "S",
+                (_, true, _) => "x",
+                (_, _, true) => "P",
+                _ => "N",
+            },
+            self.db,
+            self.sub,
+            self.psub,
+            self.multi.map_or(-1, |n| n as i64),
+            self.last_cmd,
+            self.user,
+            self.resp,
+        );
+        if let Some(cn) = &self.cn {
+            let _ = write!(line, " cn={}", cn);
+        }
+        line
+    }
+}
+
+impl ClientAddr {
+    // the IP address, localhost for a unix socket
+    pub fn ip(&self) -> IpAddr {
+        match self {
+            ClientAddr::Tcp(addr) => addr.ip(),
+            ClientAddr::Unix(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        }
+    }
+}
+
+// Like redis, the clients of a unix socket are shown as /path/to/redis.sock:0
+impl fmt::Display for ClientAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientAddr::Tcp(addr) => write!(f, "{}", addr),
+            ClientAddr::Unix(path) => write!(f, "{}:0", path.display()),
+        }
+    }
+}
+
+impl ClientRegistry {
+    pub fn register(&self, info: ClientInfo) {
+        self.0.insert(info.id, info);
+    }
+
+    pub fn unregister(&self, id: u64) {
+        self.0.remove(&id);
+    }
+
+    pub fn get(&self, id: u64) -> Option<ClientInfo> {
+        self.0.get(&id).map(|c| c.clone())
+    }
+
+    pub fn update(&self, id: u64, f: impl FnOnce(&mut ClientInfo)) {
+        if let Some(mut info) = self.0.get_mut(&id) {
+            f(&mut info);
+        }
+    }
+
+    // All the clients ordered by id.
+    pub fn list(&self) -> Vec<ClientInfo> {
+        let mut clients = self.0.iter().map(|c| c.clone()).collect::<Vec<_>>();
+        clients.sort_by_key(|c| c.id);
+        clients
+    }
+
+    // Close the connections matching the filter, returns how many were killed.
+    pub fn kill(&self, filter: impl Fn(&ClientInfo) -> bool) -> usize {
+        self.0
+            .iter()
+            .filter(|c| filter(c))
+            .map(|c| c.killed.cancel())
+            .count()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_registry() {
+        let registry = ClientRegistry::default();
+        let addr = ClientAddr::Tcp("127.0.0.1:50000".parse().unwrap());
+        let laddr = ClientAddr::Tcp("127.0.0.1:6379".parse().unwrap());
+        registry.register(ClientInfo::new(2, addr.clone(), laddr.clone()));
+        registry.register(ClientInfo::new(1, addr, laddr));
+        registry.update(1, |c| c.last_cmd = "get".into());
+
+        let clients = registry.list();
+        assert_eq!(clients.iter().map(|c| c.id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(
+            clients[0].to_line(),
+            "id=1 addr=127.0.0.1:50000 laddr=127.0.0.1:6379 name= age=0 idle=0 flags=N db=0 sub=0 psub=0 multi=-1 cmd=get user=default resp=2"
+        );
+
+        assert_eq!(registry.kill(|c| c.id == 2), 1);
+        assert!(registry.get(2).unwrap().killed.is_cancelled());
+
+        registry.unregister(1);
+        assert_eq!(registry.len(), 1);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{cmd::Command, Backend, RespFrame};
+use bytes::Bytes;
+use std::{
+    collections::HashSet,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+// commands a RESP2 client may still send once it subscribed to a channel
+const SUBSCRIBED_COMMANDS: &[&str] = &[
+    "subscribe",
+    "psubscribe",
+    "ssubscribe",
+    "unsubscribe",
+    "punsubscribe",
+    "sunsubscribe",
+    "ping",
+    "quit",
+    "reset",
+];
+
+// A command queued after MULTI, with what EXEC needs to know of its request.
+#[derive(Debug)]
+pub(crate) struct Queued {
+    pub(crate) cmd: Command,
+    // held back by CLIENT PAUSE WRITE, see may_write
+    pub(crate) write: bool,
+    // the keys tracked for client side caching once it runs
+    pub(crate) read_keys: Vec<Bytes>,
+}
+
+// State owned by a single client connection, handed to the commands which need it.
+#[derive(Debug)]
+pub(crate) struct Session {
+    pub(crate) id: u64,
+    // set by CLIENT SETNAME
+    pub(crate) name: Option<String>,
+    // the database selected by SELECT, always 0 while there is a single one
+    pub(crate) db: usize,
+    // false until AUTH succeeds when the default user needs a password
+    pub(crate) authenticated: bool,
+    // the ACL user the connection is authenticated as
+    pub(crate) user: String,
+    // protocol version negotiated by HELLO, 2 or 3
+    pub(crate) resp: u8,
+    // set by QUIT, the connection is closed after the reply
+    pub(crate) closing: bool,
+    // out-of-band frames (e.g. invalidation messages) to be pushed to the client
+    pub(crate) sender: UnboundedSender<RespFrame>,
+    pub(crate) tracking_optin: bool,
+    pub(crate) tracking_optout: bool,
+    // set by CLIENT CACHING, only affects the command right after it
+    pub(crate) caching: Option<bool>,
+    // commands queued after MULTI, None when not in a transaction
+    pub(crate) queued: Option<Vec<Queued>>,
+    // set when a command failed to parse inside MULTI, EXEC will abort
+    pub(crate) multi_error: bool,
+    // keys watched by WATCH with the version seen at that time
+    pub(crate) watched: Vec<(Bytes, u64)>,
+    // channels and patterns subscribed to by SUBSCRIBE and PSUBSCRIBE
+    pub(crate) channels: HashSet<Bytes>,
+    pub(crate) patterns: HashSet<Bytes>,
+    // announced by REPLCONF listening-port
+    pub(crate) replica_port: Option<u16>,
+    // set by PSYNC, the connection receives the replication stream from now on
+    pub(crate) replica: bool,
+    // set by commands that are not replied, e.g. REPLCONF ACK
+    pub(crate) skip_reply: bool,
+    // set by ASKING, only affects the command right after it
+    pub(crate) asking: bool,
+}
+
+impl Session {
+    pub(crate) fn new(sender: UnboundedSender<RespFrame>) -> Self {
+        Self {
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
+            name: None,
+            db: 0,
+            authenticated: false,
+            user: "default".to_string(),
+            resp: 2,
+            closing: false,
+            sender,
+            tracking_optin: false,
+            tracking_optout: false,
+            caching: None,
+            queued: None,
+            multi_error: false,
+            watched: vec![],
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+            replica_port: None,
+            replica: false,
+            skip_reply: false,
+            asking: false,
+        }
+    }
+
+    // Marks the connection as authenticated as the given user.
+    pub(crate) fn login(&mut self, user: String, backend: &Backend) {
+        self.authenticated = true;
+        backend
+            .clients
+            .update(self.id, |client| client.user = user.clone());
+        self.user = user;
+    }
+
+    // Forgets the keys watched by WATCH.
+    pub(crate) fn unwatch(&mut self, backend: &Backend) {
+        for (key, _) in self.watched.drain(..) {
+            backend.unwatch(&key);
+        }
+    }
+
+    // A command refused inside MULTI makes EXEC abort the transaction.
+    pub(crate) fn fail_multi(&mut self) {
+        if self.queued.is_some() {
+            self.multi_error = true;
+        }
+    }
+
+    // whether the connection is subscribed to a channel or a pattern
+    pub(crate) fn subscribed(&self) -> bool {
+        !self.channels.is_empty() || !self.patterns.is_empty()
+    }
+
+    // the class of client-output-buffer-limit the connection falls in
+    pub(crate) fn output_class(&self) -> &'static str {
+        if self.replica {
+            "replica"
+        } else if !self.channels.is_empty() || !self.patterns.is_empty() {
+            "pubsub"
+        } else {
+            "normal"
+        }
+    }
+
+    // The error replied to a command not allowed in the current state of the connection. A RESP2
+    // client which subscribed only receives messages, RESP3 tells them apart with push frames.
+    pub(crate) fn check_context(&self, name: &str) -> Result<(), String> {
+        if self.resp == 2 && self.subscribed() && !SUBSCRIBED_COMMANDS.contains(&name) {
+            return Err(format!(
+                "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                name
+            ));
+        }
+        Ok(())
+    }
+
+    // Copies what CLIENT LIST shows of the session to the registry.
+    pub(crate) fn publish(&self, backend: &Backend) {
+        let multi = self.queued.as_ref().map(|queued| queued.len());
+        backend.clients.update(self.id, |client| {
+            client.multi = multi;
+            client.db = self.db;
+            client.sub = self.channels.len();
+            client.psub = self.patterns.len();
+        });
+    }
+
+    // whether keys read by the next command should be remembered for client side caching
+    pub(crate) fn should_track(&self, backend: &Backend) -> bool {
+        if !backend.tracking.is_enabled(self.id) {
+            return false;
+        }
+        match (self.tracking_optin, self.tracking_optout) {
+            (true, _) => self.caching == Some(true),
+            (_, true) => self.caching != Some(false),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_subscribed_context() {
+        let (sender, _) = mpsc::unbounded_channel();
+        let mut session = Session::new(sender);
+        assert!(session.check_context("get").is_ok());
+        session.channels.insert("news".into());
+        assert!(session.check_context("ping").is_ok());
+        assert_eq!(
+            session.check_context("get"),
+            Err("ERR Can't execute 'get': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context".to_string())
+        );
+        session.resp = 3;
+        assert!(session.check_context("get").is_ok());
+    }
+}
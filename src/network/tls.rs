@@ -0,0 +1,209 @@
+use super::{ClientAddr, ClientStream};
+use crate::Config;
+use anyhow::{bail, Context, Result};
+use std::{io, sync::Arc};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    rustls::{
+        self,
+        pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+        server::WebPkiClientVerifier,
+        RootCertStore, ServerConfig as TlsConfig,
+    },
+    server::TlsStream,
+    TlsAcceptor,
+};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+// The acceptor of the connections to tls-port, with the certificate chain of tls-cert-file and
+// the private key of tls-key-file. Unless tls-auth-clients is no, the clients present a
+// certificate signed by one of tls-ca-cert-file, or none at all with optional.
+pub fn tls_acceptor(config: &Config) -> Result<TlsAcceptor> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let certs = CertificateDer::pem_file_iter(&config.tls_cert_file)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("can't read the certificates of {}", config.tls_cert_file))?;
+    let key = PrivateKeyDer::from_pem_file(&config.tls_key_file)
+        .with_context(|| format!("can't read the private key of {}", config.tls_key_file))?;
+    let builder =
+        TlsConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+    let builder = match config.tls_auth_clients.as_str() {
+        "no" => builder.with_no_client_auth(),
+        auth => {
+            if config.tls_ca_cert_file.is_empty() {
+                bail!("tls-auth-clients {} needs tls-ca-cert-file", auth);
+            }
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(&config.tls_ca_cert_file)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .with_context(|| {
+                    format!("can't read the certificates of {}", config.tls_ca_cert_file)
+                })?
+            {
+                roots.add(cert)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = match auth {
+                "optional" => verifier.allow_unauthenticated(),
+                _ => verifier,
+            };
+            builder.with_client_cert_verifier(verifier.build()?)
+        }
+    };
+    let tls = builder
+        .with_single_cert(certs, key)
+        .context("invalid TLS certificate or key")?;
+    Ok(TlsAcceptor::from(Arc::new(tls)))
+}
+
+impl ClientStream for TlsStream<TcpStream> {
+    fn addrs(&self) -> io::Result<(ClientAddr, ClientAddr)> {
+        self.get_ref().0.addrs()
+    }
+
+    // the common name of the subject of the client certificate
+    fn peer_cn(&self) -> Option<String> {
+        let cert = self.get_ref().1.peer_certificates()?.first()?;
+        let (_, cert) = X509Certificate::from_der(cert).ok()?;
+        let cn = cert.subject().iter_common_name().next()?;
+        cn.as_str().ok().map(str::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handle_stream, Backend, BulkString, RespArray, RespCodec, RespFrame};
+    use futures::SinkExt;
+    use rcgen::{
+        BasicConstraints, CertificateParams, CertifiedKey, DnType, ExtendedKeyUsagePurpose, IsCa,
+        KeyPair,
+    };
+    use std::{fs, net::SocketAddr, path::Path};
+    use tokio::net::TcpListener;
+    use tokio_rustls::{
+        client::TlsStream,
+        rustls::{pki_types::ServerName, ClientConfig},
+        TlsConnector,
+    };
+    use tokio_stream::StreamExt;
+    use tokio_util::codec::Framed;
+
+    // writes the certificate and key of the server, returns their config
+    fn server_files(dir: &Path, server: &CertifiedKey) -> Result<Config> {
+        fs::create_dir_all(dir)?;
+        let config = Config {
+            tls_cert_file: dir.join("redis.crt").display().to_string(),
+            tls_key_file: dir.join("redis.key").display().to_string(),
+            tls_auth_clients: "no".to_string(),
+            ..Default::default()
+        };
+        fs::write(&config.tls_cert_file, server.cert.pem())?;
+        fs::write(&config.tls_key_file, server.key_pair.serialize_pem())?;
+        Ok(config)
+    }
+
+    async fn serve(acceptor: TlsAcceptor) -> Result<SocketAddr> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                if let Ok(stream) = acceptor.accept(stream).await {
+                    tokio::spawn(handle_stream(stream, Backend::new()));
+                }
+            }
+        });
+        Ok(addr)
+    }
+
+    async fn call(
+        addr: SocketAddr,
+        server: &CertifiedKey,
+        client: Option<&CertifiedKey>,
+        args: &[&str],
+    ) -> Result<RespFrame> {
+        let mut roots = RootCertStore::empty();
+        roots.add(server.cert.der().clone())?;
+        let config =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()?
+                .with_root_certificates(roots);
+        let config = match client {
+            Some(client) => config.with_client_auth_cert(
+                vec![client.cert.der().clone()],
+                PrivateKeyDer::try_from(client.key_pair.serialize_der())
+                    .map_err(io::Error::other)?,
+            )?,
+            None => config.with_no_client_auth(),
+        };
+        let stream = TcpStream::connect(addr).await?;
+        let stream: TlsStream<TcpStream> = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost")?, stream)
+            .await?;
+        let mut framed = Framed::new(stream, RespCodec::default());
+        let args = args.iter().map(|arg| BulkString::from(*arg).into());
+        framed
+            .send(RespArray::new(args.collect::<Vec<_>>()).into())
+            .await?;
+        Ok(framed.next().await.context("connection closed")??)
+    }
+
+    #[tokio::test]
+    async fn test_tls_echo() -> Result<()> {
+        let server = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+        let dir = std::env::temp_dir().join(format!("simple-redis-tls-{}", std::process::id()));
+        let acceptor = tls_acceptor(&server_files(&dir, &server)?)?;
+        fs::remove_dir_all(&dir)?;
+
+        let addr = serve(acceptor).await?;
+        let reply = call(addr, &server, None, &["ECHO", "hello"]).await?;
+        assert_eq!(reply, RespFrame::from(BulkString::from("hello")));
+
+        let missing = Config {
+            tls_cert_file: "/nonexistent/redis.crt".to_string(),
+            ..Default::default()
+        };
+        assert!(tls_acceptor(&missing).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tls_auth_clients() -> Result<()> {
+        let ca_key = KeyPair::generate()?;
+        let mut params = CertificateParams::new(vec![])?;
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "test ca");
+        let ca = params.self_signed(&ca_key)?;
+        let key_pair = KeyPair::generate()?;
+        let mut params = CertificateParams::new(vec![])?;
+        params.distinguished_name.push(DnType::CommonName, "alice");
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let cert = params.signed_by(&key_pair, &ca, &ca_key)?;
+        let client = CertifiedKey { cert, key_pair };
+
+        let server = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+        let dir = std::env::temp_dir().join(format!("simple-redis-mtls-{}", std::process::id()));
+        let mut config = server_files(&dir, &server)?;
+        config.tls_auth_clients = "yes".to_string();
+        assert!(tls_acceptor(&config).is_err());
+        config.tls_ca_cert_file = dir.join("ca.crt").display().to_string();
+        fs::write(&config.tls_ca_cert_file, ca.pem())?;
+        let required = serve(tls_acceptor(&config)?).await?;
+        config.tls_auth_clients = "optional".to_string();
+        let optional = serve(tls_acceptor(&config)?).await?;
+        fs::remove_dir_all(&dir)?;
+
+        let RespFrame::BulkString(info) =
+            call(required, &server, Some(&client), &["CLIENT", "INFO"]).await?
+        else {
+            panic!("CLIENT INFO replies a bulk string");
+        };
+        assert!(String::from_utf8_lossy(&info).contains(" cn=alice"));
+        assert!(call(required, &server, None, &["ECHO", "a"]).await.is_err());
+        let reply = call(optional, &server, None, &["ECHO", "a"]).await?;
+        assert_eq!(reply, RespFrame::from(BulkString::from("a")));
+        Ok(())
+    }
+}
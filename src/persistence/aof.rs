@@ -0,0 +1,552 @@
+use super::Snapshot;
+use crate::{
+    cmd::{Command, CommandExecutor, ExecContext},
+    Backend, BulkString, RespArray, RespEncoder, RespError, RespFrame, RespFrameRef,
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+const FSYNC_PERIOD: Duration = Duration::from_secs(1);
+
+// The append only file, every write is logged as a RESP command and replayed at startup. The
+// writes are sent to a dedicated thread, the commands don't wait for the disk, except with
+// appendfsync always where the reply waits for the fsync of the write.
+#[derive(Debug)]
+pub struct Aof {
+    enabled: AtomicBool,
+    state: Mutex<AofState>,
+    last_write_ok: AtomicBool,
+    rewrite_in_progress: AtomicBool,
+    last_rewrite_ok: AtomicBool,
+    // size of the file after the last rewrite and now, for auto-aof-rewrite
+    base_size: AtomicU64,
+    size: AtomicU64,
+    // the number of writes appended, and of those on disk, each write is numbered in turn
+    appended: AtomicU64,
+    synced: watch::Sender<u64>,
+}
+
+// Completes once the writes appended up to a point are on disk.
+#[derive(Debug)]
+pub(crate) struct AofSync {
+    seq: u64,
+    synced: watch::Receiver<u64>,
+}
+
+#[derive(Debug, Default)]
+struct AofState {
+    writer: Option<AofWriter>,
+    // the writes made while a rewrite runs, appended to the new file
+    rewrite_buffer: Option<Vec<Vec<u8>>>,
+}
+
+#[derive(Debug)]
+struct AofWriter {
+    sender: mpsc::Sender<(u64, Vec<u8>)>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl Default for Aof {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            state: Mutex::default(),
+            last_write_ok: AtomicBool::new(true),
+            rewrite_in_progress: AtomicBool::new(false),
+            last_rewrite_ok: AtomicBool::new(true),
+            base_size: AtomicU64::new(0),
+            size: AtomicU64::new(0),
+            appended: AtomicU64::new(0),
+            synced: watch::Sender::new(0),
+        }
+    }
+}
+
+// Where the AOF is written, from the `dir` and `appendfilename` config.
+pub fn aof_path(backend: &Backend) -> PathBuf {
+    let config = backend.config.snapshot();
+    PathBuf::from(config.dir).join(config.appendfilename)
+}
+
+impl Aof {
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn last_write_ok(&self) -> bool {
+        self.last_write_ok.load(Ordering::Relaxed)
+    }
+
+    pub fn rewrite_in_progress(&self) -> bool {
+        self.rewrite_in_progress.load(Ordering::Relaxed)
+    }
+
+    pub fn last_rewrite_ok(&self) -> bool {
+        self.last_rewrite_ok.load(Ordering::Relaxed)
+    }
+
+    // (current size, size after the last rewrite)
+    pub fn sizes(&self) -> (u64, u64) {
+        (
+            self.size.load(Ordering::Relaxed),
+            self.base_size.load(Ordering::Relaxed),
+        )
+    }
+
+    // Starts logging the writes. With `from_dataset` the file is created again with the current
+    // dataset first, otherwise the writes are appended to it.
+    pub fn start(&self, backend: &Backend, from_dataset: bool) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.writer.is_some() {
+            return Ok(());
+        }
+        let path = aof_path(backend);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(!from_dataset)
+            .write(true)
+            .truncate(from_dataset)
+            .open(&path)?;
+        let len = file.metadata()?.len();
+        self.size.store(len, Ordering::Relaxed);
+        self.base_size.store(len, Ordering::Relaxed);
+        self.enabled.store(true, Ordering::Relaxed);
+        self.last_write_ok.store(true, Ordering::Relaxed);
+        // the writes that run meanwhile are queued after the dataset, replaying them again is
+        // harmless
+        let dataset = from_dataset.then(|| Snapshot::capture(backend));
+        state.writer = Some(AofWriter::spawn(backend, file, dataset));
+        info!("Appending the writes to {}", path.display());
+        Ok(())
+    }
+
+    // Stops logging, once what was queued is on disk.
+    pub fn stop(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.enabled.store(false, Ordering::Relaxed);
+        // a running rewrite would miss the writes from now on
+        state.rewrite_buffer = None;
+        if let Some(writer) = state.writer.take() {
+            writer.close();
+        }
+    }
+
+    // Queues a write, the handle completes once it's on disk. Only appendfsync always waits for
+    // it, see `sync_since`.
+    pub(crate) fn append(&self, frame: RespFrame) -> Option<AofSync> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let bytes = frame.encode_to_vec();
+        if let Some(buffer) = state.rewrite_buffer.as_mut() {
+            buffer.push(bytes.clone());
+        }
+        let writer = state.writer.as_ref()?;
+        let seq = self.appended.fetch_add(1, Ordering::Relaxed) + 1;
+        writer.sender.send((seq, bytes)).ok()?;
+        Some(AofSync {
+            seq,
+            synced: self.synced.subscribe(),
+        })
+    }
+
+    // the number of writes appended so far, to pass to `sync_since` after a command
+    pub(crate) fn appended(&self) -> u64 {
+        self.appended.load(Ordering::Relaxed)
+    }
+
+    // With appendfsync always, the handle of the writes appended since `seq`, which the reply of
+    // the command that made them waits for. The writes of other connections meanwhile are
+    // waited for too, they are synced in the same batch anyway.
+    pub(crate) fn sync_since(&self, backend: &Backend, seq: u64) -> Option<AofSync> {
+        let appended = self.appended();
+        if appended <= seq || backend.config.snapshot().appendfsync != "always" {
+            return None;
+        }
+        Some(AofSync {
+            seq: appended,
+            synced: self.synced.subscribe(),
+        })
+    }
+
+    // How much the file grew since the last rewrite in percent, when auto-aof-rewrite says it
+    // is time for another one.
+    pub(crate) fn rewrite_due(&self, (percentage, min_size): (u64, u64)) -> Option<u64> {
+        let (size, base_size) = self.sizes();
+        if !self.enabled() || percentage == 0 || size < min_size {
+            return None;
+        }
+        let growth = size.saturating_sub(base_size) * 100 / base_size.max(1);
+        (growth >= percentage).then_some(growth)
+    }
+}
+
+impl AofSync {
+    pub(crate) async fn wait(mut self) {
+        // the sender lives as long as the Aof, the writer always marks the writes it's done with
+        let _ = self.synced.wait_for(|&synced| synced >= self.seq).await;
+    }
+}
+
+impl AofWriter {
+    fn spawn(backend: &Backend, file: File, dataset: Option<Snapshot>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let backend = backend.clone();
+        let thread = thread::spawn(move || {
+            let mut file = BufWriter::new(file);
+            if let Some(dataset) = dataset {
+                match write_dataset(&mut file, dataset) {
+                    Ok(len) => {
+                        backend.aof.size.fetch_add(len, Ordering::Relaxed);
+                        backend.aof.base_size.store(len, Ordering::Relaxed);
+                    }
+                    Err(e) => warn!("Error writing the dataset to the AOF: {}", e),
+                }
+            }
+            write_loop(&backend, file, receiver);
+        });
+        Self { sender, thread }
+    }
+
+    fn close(self) {
+        drop(self.sender);
+        let _ = self.thread.join();
+    }
+}
+
+// Writes what is queued and syncs the file as appendfsync says: after each batch of writes with
+// always, every second with everysec, never with no. The writes of a batch are then marked as
+// synced, the replies waiting for them are sent.
+fn write_loop(
+    backend: &Backend,
+    mut file: BufWriter<File>,
+    receiver: mpsc::Receiver<(u64, Vec<u8>)>,
+) {
+    let aof = &backend.aof;
+    let mut last_fsync = Instant::now();
+    loop {
+        let (batch, closed) = match receiver.recv_timeout(FSYNC_PERIOD) {
+            Ok(write) => (Some(write), false),
+            Err(RecvTimeoutError::Timeout) => (None, false),
+            Err(RecvTimeoutError::Disconnected) => (None, true),
+        };
+        let mut last_seq = None;
+        let written = batch
+            .into_iter()
+            .chain(receiver.try_iter())
+            .try_for_each(|(seq, bytes)| {
+                last_seq = Some(seq);
+                file.write_all(&bytes)?;
+                aof.size.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+                Ok(())
+            })
+            .and_then(|()| file.flush());
+        let fsync = match backend.config.snapshot().appendfsync.as_str() {
+            _ if closed => true,
+            "always" => true,
+            "everysec" => last_fsync.elapsed() >= FSYNC_PERIOD,
+            _ => false,
+        };
+        let synced = match fsync {
+            true => {
+                last_fsync = Instant::now();
+                file.get_ref().sync_data()
+            }
+            false => Ok(()),
+        };
+        let result = written.and(synced);
+        if let Err(e) = &result {
+            warn!("Error writing to the AOF: {}", e);
+        }
+        aof.last_write_ok.store(result.is_ok(), Ordering::Relaxed);
+        // a failed write is marked too, the reply isn't held forever, INFO reports the error
+        if let Some(seq) = last_seq {
+            aof.synced.send_if_modified(|synced| {
+                let newer = seq > *synced;
+                *synced = (*synced).max(seq);
+                newer
+            });
+        }
+        if closed {
+            break;
+        }
+    }
+}
+
+// Rewrites the AOF from the dataset in the background, the file then only holds the commands
+// needed to create the keys again. Must be called with the exec lock held for writing, the
+// writes after the view of the dataset go to the rewrite buffer and none before. Returns false if
+// a rewrite is already running.
+pub(crate) fn bgrewrite(backend: &Backend) -> bool {
+    let aof = &backend.aof;
+    if aof.rewrite_in_progress.swap(true, Ordering::Relaxed) {
+        return false;
+    }
+    let view = backend.snapshot();
+    let buffered = {
+        let mut state = aof.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.rewrite_buffer = state.writer.as_ref().map(|_| vec![]);
+        state.rewrite_buffer.is_some()
+    };
+    let backend = backend.clone();
+    tokio::task::spawn_blocking(move || {
+        let aof = &backend.aof;
+        match rewrite(&backend, Snapshot::from(&view), buffered) {
+            Ok(()) => {
+                aof.last_rewrite_ok.store(true, Ordering::Relaxed);
+                info!("Background AOF rewrite finished successfully");
+            }
+            Err(e) => {
+                aof.last_rewrite_ok.store(false, Ordering::Relaxed);
+                warn!("Background AOF rewrite error: {}", e);
+            }
+        }
+        aof.rewrite_in_progress.store(false, Ordering::Relaxed);
+    });
+    true
+}
+
+// Writes the dataset to a temporary file, then the writes buffered meanwhile with the new ones
+// on hold, and renames it over the AOF. A crash at any point leaves a complete AOF.
+fn rewrite(backend: &Backend, snapshot: Snapshot, buffered: bool) -> io::Result<()> {
+    let aof = &backend.aof;
+    let path = aof_path(backend);
+    let temp = path.with_file_name(format!("temp-rewriteaof-bg-{}.aof", std::process::id()));
+    let finish = |len: u64| -> io::Result<()> {
+        let mut file = BufWriter::new(OpenOptions::new().append(true).open(&temp)?);
+        let mut state = aof.state.lock().unwrap_or_else(|e| e.into_inner());
+        let buffer = state.rewrite_buffer.take();
+        // appendonly was switched meanwhile, the file would miss writes
+        if buffered != buffer.is_some() || buffered != state.writer.is_some() {
+            return Err(io::Error::other("appendonly changed during the rewrite"));
+        }
+        let mut len = len;
+        for bytes in buffer.iter().flatten() {
+            file.write_all(bytes)?;
+            len += bytes.len() as u64;
+        }
+        file.flush()?;
+        file.get_ref().sync_all()?;
+        drop(file);
+        // the old writer is done with the file before it is replaced
+        let Some(writer) = state.writer.take() else {
+            return fs::rename(&temp, &path);
+        };
+        writer.close();
+        let renamed = fs::rename(&temp, &path);
+        let file = OpenOptions::new().append(true).open(&path)?;
+        if renamed.is_ok() {
+            aof.size.store(len, Ordering::Relaxed);
+            aof.base_size.store(len, Ordering::Relaxed);
+        }
+        state.writer = Some(AofWriter::spawn(backend, file, None));
+        renamed
+    };
+    let result = File::create(&temp)
+        .and_then(|file| write_dataset(&mut BufWriter::new(file), snapshot))
+        .and_then(finish);
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+// Writes the commands that create the dataset again. Returns the number of bytes written.
+fn write_dataset(file: &mut BufWriter<File>, snapshot: Snapshot) -> io::Result<u64> {
+    let mut len = 0;
+    for frame in dataset_commands(snapshot) {
+        let bytes = frame.encode_to_vec();
+        file.write_all(&bytes)?;
+        len += bytes.len() as u64;
+    }
+    file.flush()?;
+    Ok(len)
+}
+
+// The commands that create the dataset again.
+pub(crate) fn dataset_commands(snapshot: Snapshot) -> Vec<RespFrame> {
+    let command = |args: Vec<RespFrame>| -> RespFrame { RespArray::new(args).into() };
+    let mut commands = vec![];
+    for (key, value) in snapshot.strings {
+        commands.push(command(vec![
+            BulkString::from("set").into(),
+            BulkString::from(key).into(),
+            value,
+        ]));
+    }
+    for (key, members) in snapshot.sets {
+        let mut args: Vec<RespFrame> = vec![
+            BulkString::from("sadd").into(),
+            BulkString::from(key).into(),
+        ];
+        args.extend(members.into_iter().map(|m| BulkString::from(m).into()));
+        commands.push(command(args));
+    }
+    for (key, fields) in snapshot.hashes {
+        for (field, value) in fields {
+            commands.push(command(vec![
+                BulkString::from("hset").into(),
+                BulkString::from(key.clone()).into(),
+                BulkString::from(field).into(),
+                value,
+            ]));
+        }
+    }
+    for (key, when) in snapshot.expires {
+        commands.push(command(vec![
+            BulkString::from("pexpireat").into(),
+            BulkString::from(key).into(),
+            BulkString::from(when.to_string()).into(),
+        ]));
+    }
+    commands
+}
+
+// Replays the AOF. A command cut short by a crash is dropped from the file, the commands before
+// it are kept. Returns the number of commands replayed.
+pub(crate) fn load_aof(backend: &Backend, path: &Path) -> io::Result<usize> {
+    let mut buf = vec![];
+    File::open(path)?.read_to_end(&mut buf)?;
+    // the commands are read in place, each is only copied to be executed
+    let mut at = 0;
+    let mut replayed = 0;
+    while at < buf.len() {
+        let frame = match RespFrameRef::decode(&buf[at..]) {
+            Ok((frame, len)) => {
+                at += len;
+                frame.to_frame()
+            }
+            Err(RespError::NotComplete) => {
+                warn!(
+                    "The AOF is truncated, dropping the last {} bytes",
+                    buf.len() - at
+                );
+                OpenOptions::new()
+                    .write(true)
+                    .open(path)?
+                    .set_len(at as u64)?;
+                break;
+            }
+            Err(e) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("bad AOF format at byte {}: {}", at, e),
+                ));
+            }
+        };
+        match Command::try_from(frame) {
+            Ok(cmd) => {
+                if let Err(e) = cmd.execute(&mut ExecContext::new(backend)) {
+                    warn!("Command of the AOF failed: {}", e);
+                }
+                replayed += 1;
+            }
+            Err(e) => warn!("Invalid command in the AOF: {}", e),
+        }
+    }
+    Ok(replayed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::now_ms, Config, ServerConfig};
+
+    fn test_backend(name: &str) -> io::Result<Backend> {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Backend::with_config(ServerConfig::new(Config {
+            dir: dir.display().to_string(),
+            appendfsync: "always".to_string(),
+            ..Default::default()
+        })))
+    }
+
+    #[test]
+    fn test_aof_append_and_load() -> io::Result<()> {
+        let backend = test_backend("aof-test")?;
+        backend.set("before".into(), BulkString::from("x").into());
+        backend.aof.start(&backend, true)?;
+        backend.set("k".into(), BulkString::from("v").into());
+        backend.sadd("s", "m").unwrap();
+        let when = now_ms() + 60_000;
+        backend.expire_at(b"s", when);
+        backend.del(b"before");
+        backend.aof.stop();
+
+        // a command cut short at the end is dropped
+        let path = aof_path(&backend);
+        OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(b"*3\r\n$3\r\nset\r\n")?;
+        let other = Backend::with_config(ServerConfig::new(backend.config.snapshot()));
+        assert_eq!(load_aof(&other, &path)?, 5);
+        assert_eq!(other.get(b"k"), Some(BulkString::from("v").into()));
+        assert!(other.sismember(b"s", b"m"));
+        assert_eq!(other.expire_time(b"s"), Some(when));
+        assert!(!other.exists(b"before"));
+        assert_eq!(load_aof(&Backend::new(), &path)?, 5);
+        std::fs::remove_dir_all(backend.config.snapshot().dir)
+    }
+
+    #[tokio::test]
+    async fn test_aof_always_waits_for_the_fsync() -> io::Result<()> {
+        let backend = test_backend("aof-always-test")?;
+        backend.aof.start(&backend, false)?;
+        let appended = backend.aof.appended();
+        assert!(backend.aof.sync_since(&backend, appended).is_none());
+        backend.set("k".into(), BulkString::from("v").into());
+        let sync = backend.aof.sync_since(&backend, appended);
+        sync.expect("a write to wait for").wait().await;
+        // on disk before the writer is stopped
+        let other = Backend::new();
+        assert_eq!(load_aof(&other, &aof_path(&backend))?, 1);
+        assert!(other.exists(b"k"));
+
+        backend
+            .config
+            .set(&[("appendfsync".to_string(), "everysec".to_string())])
+            .unwrap();
+        let appended = backend.aof.appended();
+        backend.set("k".into(), BulkString::from("w").into());
+        assert!(backend.aof.sync_since(&backend, appended).is_none());
+        backend.aof.stop();
+        std::fs::remove_dir_all(backend.config.snapshot().dir)
+    }
+
+    #[test]
+    fn test_aof_rewrite() -> io::Result<()> {
+        let backend = test_backend("aof-rewrite-test")?;
+        backend.aof.start(&backend, false)?;
+        for i in 0..10 {
+            backend.set("k".into(), RespFrame::Integer(i));
+        }
+        let snapshot = Snapshot::capture(&backend);
+        backend.aof.state.lock().unwrap().rewrite_buffer = Some(vec![]);
+        // written while the dataset is serialized, kept from the buffer
+        backend.sadd("s", "m").unwrap();
+        rewrite(&backend, snapshot, true)?;
+        backend.set("after".into(), RespFrame::Integer(0));
+        backend.aof.stop();
+
+        let other = Backend::new();
+        assert_eq!(load_aof(&other, &aof_path(&backend))?, 3);
+        assert_eq!(other.get(b"k"), Some(RespFrame::Integer(9)));
+        assert!(other.sismember(b"s", b"m") && other.exists(b"after"));
+        let (size, base_size) = backend.aof.sizes();
+        assert!(size > base_size);
+        assert_eq!(backend.aof.rewrite_due((100, 0)), None);
+        std::fs::remove_dir_all(backend.config.snapshot().dir)
+    }
+}
@@ -0,0 +1,239 @@
+mod aof;
+mod rdb;
+
+use crate::Backend;
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::PathBuf,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::time;
+use tracing::{info, warn};
+
+// how often the save rules are checked
+const SAVE_RULES_PERIOD: Duration = Duration::from_secs(1);
+// seconds to wait before trying again after a failed background save
+const BGSAVE_RETRY_DELAY: u64 = 5;
+
+pub(crate) use aof::bgrewrite;
+pub use aof::{aof_path, Aof};
+pub use rdb::Snapshot;
+pub(crate) use rdb::{dump, restore};
+
+// State of the snapshot persistence, reported by LASTSAVE and INFO persistence.
+#[derive(Debug)]
+pub struct Persistence {
+    // unix time of the last successful save, the start time until then
+    last_save: AtomicU64,
+    // number of modifications since the last save
+    dirty: AtomicU64,
+    bgsave_in_progress: AtomicBool,
+    last_bgsave_ok: AtomicBool,
+    // unix time the last background save started
+    last_bgsave_try: AtomicU64,
+}
+
+impl Default for Persistence {
+    fn default() -> Self {
+        Self {
+            last_save: AtomicU64::new(unix_time()),
+            dirty: AtomicU64::new(0),
+            bgsave_in_progress: AtomicBool::new(false),
+            last_bgsave_ok: AtomicBool::new(true),
+            last_bgsave_try: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Persistence {
+    pub fn last_save(&self) -> u64 {
+        self.last_save.load(Ordering::Relaxed)
+    }
+
+    pub fn dirty(&self) -> u64 {
+        self.dirty.load(Ordering::Relaxed)
+    }
+
+    pub fn bgsave_in_progress(&self) -> bool {
+        self.bgsave_in_progress.load(Ordering::Relaxed)
+    }
+
+    pub fn last_bgsave_ok(&self) -> bool {
+        self.last_bgsave_ok.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn mark_dirty(&self) {
+        self.dirty.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // the changes captured by a snapshot are no longer dirty once it is on disk
+    fn saved(&self, dirty_at_capture: u64) {
+        self.dirty.fetch_sub(dirty_at_capture, Ordering::Relaxed);
+        self.last_save.store(unix_time(), Ordering::Relaxed);
+    }
+}
+
+// Where snapshots are written, from the `dir` and `dbfilename` config.
+pub fn snapshot_path(backend: &Backend) -> PathBuf {
+    let config = backend.config.snapshot();
+    PathBuf::from(config.dir).join(config.dbfilename)
+}
+
+// Saves the dataset in the foreground. SAVE runs with the exec lock held for writing, like in
+// redis no other command runs until the snapshot is on disk.
+pub(crate) fn save(backend: &Backend) -> io::Result<()> {
+    let dirty = backend.persistence.dirty();
+    let snapshot = Snapshot::capture(backend);
+    snapshot.save(&snapshot_path(backend))?;
+    backend.persistence.saved(dirty);
+    info!("DB saved on disk");
+    Ok(())
+}
+
+// Saves the dataset if `save_first`, or if None when save points are configured, then asks the
+// server to stop. Must be called with the exec lock held for writing, no write comes after the
+// save.
+pub(crate) fn shutdown(backend: &Backend, save_first: Option<bool>) -> io::Result<()> {
+    let save_first = save_first.unwrap_or_else(|| !backend.config.snapshot().save.is_empty());
+    if save_first {
+        save(backend)?;
+    }
+    backend.shutdown();
+    Ok(())
+}
+
+// Takes a view of the dataset and writes it on a blocking task, the commands keep running
+// meanwhile. Returns false if a background save is already running.
+pub(crate) fn bgsave(backend: &Backend) -> bool {
+    let persistence = &backend.persistence;
+    if persistence.bgsave_in_progress.swap(true, Ordering::Relaxed) {
+        return false;
+    }
+    persistence
+        .last_bgsave_try
+        .store(unix_time(), Ordering::Relaxed);
+    let dirty = persistence.dirty();
+    let view = backend.snapshot();
+    let path = snapshot_path(backend);
+    let backend = backend.clone();
+    tokio::task::spawn_blocking(move || {
+        let persistence = &backend.persistence;
+        match Snapshot::from(&view).save(&path) {
+            Ok(()) => {
+                persistence.saved(dirty);
+                persistence.last_bgsave_ok.store(true, Ordering::Relaxed);
+                info!("Background saving terminated with success");
+            }
+            Err(e) => {
+                persistence.last_bgsave_ok.store(false, Ordering::Relaxed);
+                warn!("Background saving error: {}", e);
+            }
+        }
+        persistence
+            .bgsave_in_progress
+            .store(false, Ordering::Relaxed);
+    });
+    true
+}
+
+// Loads the snapshot at startup, if there is one. Returns whether it was found.
+pub fn load_snapshot(backend: &Backend) -> io::Result<bool> {
+    let path = snapshot_path(backend);
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let snapshot = Snapshot::read_from(&mut BufReader::new(file))?;
+    snapshot.restore(backend);
+    // the dataset is what is on disk
+    let persistence = &backend.persistence;
+    persistence.saved(persistence.dirty());
+    info!("DB loaded from disk: {} keys", backend.dbsize());
+    Ok(true)
+}
+
+// Loads the dataset at startup. With appendonly the AOF is replayed and the writes appended to
+// it, when there is no AOF yet it is created from the snapshot.
+pub fn load_dataset(backend: &Backend) -> io::Result<()> {
+    if !backend.config.snapshot().appendonly {
+        load_snapshot(backend)?;
+        return Ok(());
+    }
+    let path = aof_path(backend);
+    match path.exists() {
+        true => {
+            let replayed = aof::load_aof(backend, &path)?;
+            info!("DB loaded from append only file: {} commands", replayed);
+            backend.aof.start(backend, false)
+        }
+        false => {
+            load_snapshot(backend)?;
+            backend.aof.start(backend, true)
+        }
+    }
+}
+
+// Runs a background save whenever one of the `save <seconds> <changes>` rules is met, and rewrites
+// the AOF once it grew as much as auto-aof-rewrite-percentage says, until the server shuts down.
+pub async fn apply_save_rules(backend: Backend) {
+    let mut interval = time::interval(SAVE_RULES_PERIOD);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = backend.shutdown_requested() => break,
+        }
+        let aof = &backend.aof;
+        if !aof.rewrite_in_progress() {
+            if let Some(growth) = aof.rewrite_due(backend.config.auto_aof_rewrite()) {
+                info!("Starting automatic rewriting of AOF on {}% growth", growth);
+                let _guard = backend.exec_lock.write().unwrap_or_else(|e| e.into_inner());
+                bgrewrite(&backend);
+            }
+        }
+        let persistence = &backend.persistence;
+        let now = unix_time();
+        let retry_at = persistence.last_bgsave_try.load(Ordering::Relaxed) + BGSAVE_RETRY_DELAY;
+        if persistence.bgsave_in_progress() || (!persistence.last_bgsave_ok() && now < retry_at) {
+            continue;
+        }
+        let rules = backend.config.snapshot().save;
+        let elapsed = now.saturating_sub(persistence.last_save());
+        if let Some((seconds, changes)) = due_rule(&rules, persistence.dirty(), elapsed) {
+            info!("{} changes in {} seconds. Saving...", changes, seconds);
+            bgsave(&backend);
+        }
+    }
+}
+
+// The first rule met with `dirty` changes `elapsed` seconds after the last save.
+fn due_rule(rules: &[(u64, u64)], dirty: u64, elapsed: u64) -> Option<(u64, u64)> {
+    rules
+        .iter()
+        .copied()
+        .find(|&(seconds, changes)| dirty > 0 && dirty >= changes && elapsed >= seconds)
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_due_rule() {
+        let rules = [(3600, 1), (300, 100), (60, 10000)];
+        assert_eq!(due_rule(&rules, 0, 7200), None);
+        assert_eq!(due_rule(&rules, 1, 3600), Some((3600, 1)));
+        assert_eq!(due_rule(&rules, 100, 299), None);
+        assert_eq!(due_rule(&rules, 100, 300), Some((300, 100)));
+        assert_eq!(due_rule(&rules, 10000, 60), Some((60, 10000)));
+        assert_eq!(due_rule(&[], 10000, 60), None);
+    }
+}
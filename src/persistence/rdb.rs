@@ -0,0 +1,469 @@
+use crate::{
+    backend::{Hash, Set},
+    Backend, DatasetView, Entry, KeyEvent, RespDecoder, RespEncoder, RespFrame, Value,
+};
+use bytes::{Bytes, BytesMut};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+};
+
+// file layout: MAGIC VERSION (TYPE key value)* (EXPIRE_MS key time)* EOF
+// strings are a u64 little endian length followed by the bytes, values are RESP encoded frames
+const MAGIC: &[u8] = b"SRDB";
+// version 1 had no expiration times
+const VERSION: u8 = 2;
+const TYPE_STRING: u8 = 0;
+const TYPE_SET: u8 = 2;
+const TYPE_HASH: u8 = 4;
+// the expiration time of a key in unix milliseconds, as a u64 little endian
+const EXPIRE_MS: u8 = 0xfc;
+const EOF: u8 = 0xff;
+
+// A point-in-time copy of the dataset.
+#[derive(Debug, Default, PartialEq)]
+pub struct Snapshot {
+    pub(crate) strings: Vec<(Bytes, RespFrame)>,
+    pub(crate) sets: Vec<(Bytes, Vec<Bytes>)>,
+    pub(crate) hashes: Vec<(Bytes, Vec<(Bytes, RespFrame)>)>,
+    pub(crate) expires: Vec<(Bytes, u64)>,
+}
+
+impl Snapshot {
+    pub fn capture(backend: &Backend) -> Self {
+        Self::from(&backend.snapshot())
+    }
+}
+
+// The keys of the snapshot with their values, see `Backend::import`.
+impl IntoIterator for Snapshot {
+    type Item = Entry;
+    type IntoIter = std::vec::IntoIter<Entry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let expires: HashMap<Bytes, u64> = self.expires.into_iter().collect();
+        let strings = self
+            .strings
+            .into_iter()
+            .map(|(key, value)| (key, Value::String(value)));
+        let sets = self
+            .sets
+            .into_iter()
+            .map(|(key, members)| (key, Value::Set(members)));
+        let hashes = self
+            .hashes
+            .into_iter()
+            .map(|(key, fields)| (key, Value::Hash(fields)));
+        strings
+            .chain(sets)
+            .chain(hashes)
+            .map(|(key, value)| Entry {
+                expire_at: expires.get(&key).copied(),
+                key,
+                value,
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+// A snapshot of the entries, e.g. from `Backend::iter`, to save them.
+impl FromIterator<Entry> for Snapshot {
+    fn from_iter<I: IntoIterator<Item = Entry>>(entries: I) -> Self {
+        let mut snapshot = Snapshot::default();
+        for entry in entries {
+            match entry.value {
+                Value::String(value) => snapshot.strings.push((entry.key.clone(), value)),
+                Value::Set(members) => snapshot.sets.push((entry.key.clone(), members)),
+                Value::Hash(fields) => snapshot.hashes.push((entry.key.clone(), fields)),
+            }
+            if let Some(when) = entry.expire_at {
+                snapshot.expires.push((entry.key, when));
+            }
+        }
+        snapshot
+    }
+}
+
+// Copies the values out of a view, which may be done while the commands run.
+impl From<&DatasetView> for Snapshot {
+    fn from(view: &DatasetView) -> Self {
+        let mut snapshot = Self {
+            expires: view.expires.clone(),
+            ..Default::default()
+        };
+        for shard in &view.shards {
+            snapshot.strings.extend(
+                shard
+                    .map
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone())),
+            );
+            snapshot.sets.extend(
+                shard
+                    .hset
+                    .iter()
+                    .map(|(key, members)| (key.clone(), members.iter().collect())),
+            );
+            snapshot
+                .hashes
+                .extend(shard.hmap.iter().map(|(key, fields)| {
+                    let fields = fields
+                        .iter()
+                        .map(|(field, value)| (field.clone(), value.clone()))
+                        .collect();
+                    (key.clone(), fields)
+                }));
+        }
+        snapshot
+    }
+}
+impl Snapshot {
+    // Writes to a temp file then renames it, the previous snapshot stays intact on failure.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension(format!("tmp-{}", std::process::id()));
+        let result = File::create(&tmp).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            self.write_to(&mut writer)?;
+            writer.into_inner()?.sync_all()
+        });
+        match result {
+            Ok(()) => fs::rename(&tmp, path),
+            Err(e) => {
+                let _ = fs::remove_file(&tmp);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        for (key, value) in &self.strings {
+            w.write_all(&[TYPE_STRING])?;
+            write_bytes(w, key)?;
+            write_frame(w, value)?;
+        }
+        for (key, members) in &self.sets {
+            w.write_all(&[TYPE_SET])?;
+            write_bytes(w, key)?;
+            write_set(w, members)?;
+        }
+        for (key, fields) in &self.hashes {
+            w.write_all(&[TYPE_HASH])?;
+            write_bytes(w, key)?;
+            write_hash(w, fields)?;
+        }
+        for (key, when) in &self.expires {
+            w.write_all(&[EXPIRE_MS])?;
+            write_bytes(w, key)?;
+            w.write_all(&when.to_le_bytes())?;
+        }
+        w.write_all(&[EOF])
+    }
+
+    pub fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        read_header(r)?;
+        let mut snapshot = Snapshot::default();
+        loop {
+            match read_u8(r)? {
+                TYPE_STRING => {
+                    let key = read_key(r)?;
+                    snapshot.strings.push((key, read_frame(r)?));
+                }
+                TYPE_SET => {
+                    let key = read_key(r)?;
+                    snapshot.sets.push((key, read_set(r)?));
+                }
+                TYPE_HASH => {
+                    let key = read_key(r)?;
+                    snapshot.hashes.push((key, read_hash(r)?));
+                }
+                EXPIRE_MS => {
+                    let key = read_key(r)?;
+                    snapshot.expires.push((key, read_len(r)?));
+                }
+                EOF => return Ok(snapshot),
+                kind => return Err(invalid(format!("unknown value type {}", kind))),
+            }
+        }
+    }
+
+    // Replaces the dataset. The caller makes sure no command runs meanwhile.
+    pub fn restore(self, backend: &Backend) {
+        let old_keys = backend.keyspace.keys();
+        backend.keyspace.clear();
+        backend.expires.clear();
+        for key in old_keys {
+            backend.publish(&key, KeyEvent::Loaded);
+        }
+        let keyspace = &backend.keyspace;
+        let limits = backend.config.encoding_limits();
+        for (key, value) in self.strings {
+            keyspace.write(&key).map.insert(key.clone(), value);
+            backend.publish(&key, KeyEvent::Loaded);
+        }
+        for (key, members) in self.sets {
+            let members = Set::from_members(members, &limits);
+            keyspace.write(&key).hset.insert(key.clone(), members);
+            backend.publish(&key, KeyEvent::Loaded);
+        }
+        for (key, fields) in self.hashes {
+            let fields = Hash::from_fields(fields, &limits);
+            keyspace.write(&key).hmap.insert(key.clone(), fields);
+            backend.publish(&key, KeyEvent::Loaded);
+        }
+        // the keys that expired meanwhile are deleted by the expire cycle
+        for (key, when) in self.expires {
+            backend.expires.insert(key, when);
+        }
+        backend.recount_memory();
+    }
+}
+
+// DUMP payload: MAGIC VERSION TYPE value, the value laid out as in a snapshot. None if the key
+// doesn't exist.
+pub(crate) fn dump(backend: &Backend, key: &[u8]) -> Option<Vec<u8>> {
+    backend.expire_if_needed(key);
+    let mut payload = MAGIC.to_vec();
+    payload.push(VERSION);
+    let shard = backend.keyspace.read(key);
+    // writing into a Vec never fails
+    if let Some(value) = shard.map.get(key) {
+        payload.push(TYPE_STRING);
+        let _ = write_frame(&mut payload, value);
+    } else if let Some(set) = shard.hset.get(key) {
+        payload.push(TYPE_SET);
+        let members: Vec<Bytes> = set.iter().collect();
+        let _ = write_set(&mut payload, &members);
+    } else if let Some(hash) = shard.hmap.get(key) {
+        payload.push(TYPE_HASH);
+        let fields: Vec<(Bytes, RespFrame)> = hash
+            .iter()
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect();
+        let _ = write_hash(&mut payload, &fields);
+    } else {
+        return None;
+    }
+    Some(payload)
+}
+
+// Stores the value of a DUMP payload under `key`, replacing the current value if any, with the
+// expiration time in unix milliseconds if any. The payload is propagated as is to the replicas.
+pub(crate) fn restore(
+    backend: &Backend,
+    key: &[u8],
+    payload: &[u8],
+    expire_at: Option<u64>,
+) -> io::Result<()> {
+    let r = &mut &payload[..];
+    read_header(r)?;
+    let kind = read_u8(r)?;
+    let (string, set, hash) = match kind {
+        TYPE_STRING => (Some(read_frame(r)?), None, None),
+        TYPE_SET => (None, Some(read_set(r)?), None),
+        TYPE_HASH => (None, None, Some(read_hash(r)?)),
+        kind => return Err(invalid(format!("unknown value type {}", kind))),
+    };
+    if !r.is_empty() {
+        return Err(invalid("trailing bytes".to_string()));
+    }
+    let limits = backend.config.encoding_limits();
+    let mut shard = backend.keyspace.write(key);
+    let before = shard.memory(key);
+    shard.remove(key);
+    backend.expires.remove(key);
+    if let Some(value) = string {
+        shard.map.insert(Bytes::copy_from_slice(key), value);
+    }
+    if let Some(members) = set {
+        shard.hset.insert(
+            Bytes::copy_from_slice(key),
+            Set::from_members(members, &limits),
+        );
+    }
+    if let Some(fields) = hash {
+        shard.hmap.insert(
+            Bytes::copy_from_slice(key),
+            Hash::from_fields(fields, &limits),
+        );
+    }
+    if let Some(when) = expire_at {
+        backend.expires.insert(Bytes::copy_from_slice(key), when);
+    }
+    let after = shard.memory(key);
+    backend.publish(key, KeyEvent::Restore(payload, expire_at));
+    drop(shard);
+    backend.resize(before, after);
+    backend.accessed(key);
+    Ok(())
+}
+
+fn read_header(r: &mut impl Read) -> io::Result<()> {
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid("not a snapshot".to_string()));
+    }
+    let version = read_u8(r)?;
+    if version == 0 || version > VERSION {
+        return Err(invalid(format!("unsupported snapshot version {}", version)));
+    }
+    Ok(())
+}
+
+fn write_set(w: &mut impl Write, members: &[Bytes]) -> io::Result<()> {
+    write_len(w, members.len())?;
+    for member in members {
+        write_bytes(w, member)?;
+    }
+    Ok(())
+}
+
+fn write_hash(w: &mut impl Write, fields: &[(Bytes, RespFrame)]) -> io::Result<()> {
+    write_len(w, fields.len())?;
+    for (field, value) in fields {
+        write_bytes(w, field)?;
+        write_frame(w, value)?;
+    }
+    Ok(())
+}
+
+fn read_set(r: &mut impl Read) -> io::Result<Vec<Bytes>> {
+    (0..read_len(r)?).map(|_| read_key(r)).collect()
+}
+
+fn read_hash(r: &mut impl Read) -> io::Result<Vec<(Bytes, RespFrame)>> {
+    (0..read_len(r)?)
+        .map(|_| Ok((read_key(r)?, read_frame(r)?)))
+        .collect()
+}
+
+fn write_len(w: &mut impl Write, len: usize) -> io::Result<()> {
+    w.write_all(&(len as u64).to_le_bytes())
+}
+
+fn write_bytes(w: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    write_len(w, bytes.len())?;
+    w.write_all(bytes)
+}
+
+fn write_frame(w: &mut impl Write, frame: &RespFrame) -> io::Result<()> {
+    write_bytes(w, &frame.encode_to_vec())
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0];
+    r.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_len(r: &mut impl Read) -> io::Result<u64> {
+    let mut len = [0; 8];
+    r.read_exact(&mut len)?;
+    Ok(u64::from_le_bytes(len))
+}
+
+fn read_bytes(r: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_len(r)?;
+    // the length may be corrupted, don't trust it for the allocation
+    let mut bytes = vec![];
+    r.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+fn read_key(r: &mut impl Read) -> io::Result<Bytes> {
+    read_bytes(r).map(Bytes::from)
+}
+
+fn read_frame(r: &mut impl Read) -> io::Result<RespFrame> {
+    let mut buf = BytesMut::from(read_bytes(r)?.as_slice());
+    RespFrame::decode(&mut buf).map_err(|e| invalid(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+
+    #[test]
+    fn test_snapshot_write() -> io::Result<()> {
+        let backend = Backend::new();
+        backend.set("k".into(), BulkString::new("v").into());
+        let snapshot = Snapshot::capture(&backend);
+        assert_eq!(snapshot.strings.len(), 1);
+
+        let mut buf = vec![];
+        snapshot.write_to(&mut buf)?;
+        let mut expected = b"SRDB\x02\x00".to_vec();
+        expected.extend_from_slice(&1u64.to_le_bytes());
+        expected.push(b'k');
+        expected.extend_from_slice(&7u64.to_le_bytes());
+        expected.extend_from_slice(b"$1\r\nv\r\n");
+        expected.push(0xff);
+        assert_eq!(buf, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_read_restore() -> io::Result<()> {
+        let backend = Backend::new();
+        backend.set("k".into(), BulkString::new("v").into());
+        backend.sadd("s", "m").unwrap();
+        backend
+            .hset("h".into(), "f".into(), BulkString::new("v").into())
+            .unwrap();
+        backend.expire_at(b"s", u64::MAX);
+        let mut buf = vec![];
+        Snapshot::capture(&backend).write_to(&mut buf)?;
+        let snapshot = Snapshot::read_from(&mut buf.as_slice())?;
+        assert_eq!(snapshot, Snapshot::capture(&backend));
+
+        let other = Backend::new();
+        other.set("old".into(), BulkString::new("x").into());
+        snapshot.restore(&other);
+        assert_eq!(other.get(b"old"), None);
+        assert_eq!(other.get(b"k"), Some(BulkString::new("v").into()));
+        assert!(other.sismember(b"s", b"m"));
+        assert_eq!(other.expire_time(b"s"), Some(u64::MAX));
+        assert!(Snapshot::read_from(&mut &buf[..buf.len() - 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_restore() -> io::Result<()> {
+        let backend = Backend::new();
+        backend
+            .hset("h".into(), "f".into(), BulkString::new("v").into())
+            .unwrap();
+        assert_eq!(dump(&backend, b"missing"), None);
+        let payload = dump(&backend, b"h").unwrap();
+
+        // a value of another type is replaced
+        backend.set("copy".into(), BulkString::new("x").into());
+        restore(&backend, b"copy", &payload, None)?;
+        assert_eq!(backend.get(b"copy"), None);
+        assert_eq!(
+            backend.hget(b"copy", b"f"),
+            Some(BulkString::new("v").into())
+        );
+        assert!(restore(&backend, b"bad", &payload[..payload.len() - 1], None).is_err());
+        assert!(!backend.exists(b"bad"));
+        restore(&backend, b"volatile", &payload, Some(u64::MAX))?;
+        assert_eq!(backend.expire_time(b"volatile"), Some(u64::MAX));
+        // restored again without a TTL, it's persistent
+        restore(&backend, b"volatile", &payload, None)?;
+        assert_eq!(backend.expire_time(b"volatile"), None);
+        Ok(())
+    }
+}
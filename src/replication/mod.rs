@@ -0,0 +1,457 @@
+mod replica;
+
+use crate::{
+    Backend, Blocked, BulkString, RespArray, RespEncoder, RespFrame, ServerConfig, Wakeup,
+};
+use dashmap::DashMap;
+use sha1::{Digest, Sha1};
+use std::{
+    collections::VecDeque,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{mpsc::UnboundedSender, Notify};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+const NO_REPLID: &str = "0000000000000000000000000000000000000000";
+
+pub(crate) const READONLY: &str = "READONLY You can't write against a read only replica.";
+
+// State of the replication. As a master, writes are fed to the connected replicas. As a
+// replica, the dataset follows the master set by REPLICAOF.
+#[derive(Debug)]
+pub struct Replication {
+    ids: RwLock<ReplIds>,
+    // bytes of the replication stream produced, or received from the master
+    offset: AtomicU64,
+    // created when the first replica connects, then fed with every write
+    backlog: Mutex<Option<Backlog>>,
+    has_backlog: AtomicBool,
+    replicas: DashMap<u64, Replica>,
+    // notified when a replica acknowledges an offset
+    acked: Notify,
+    master: Mutex<Option<MasterLink>>,
+    // whether the initial synchronization with the master is done and the link is still up
+    link_up: AtomicBool,
+}
+
+// The offset of the replication stream only makes sense along with the id of its history.
+#[derive(Debug)]
+struct ReplIds {
+    replid: String,
+    // the previous history, which ours continues up to second_replid_offset
+    replid2: String,
+    second_replid_offset: i64,
+}
+
+// The tail of the replication stream, for replicas resuming after a short disconnection.
+#[derive(Debug, Default)]
+struct Backlog {
+    // frames with the offset of their first byte
+    frames: VecDeque<(u64, RespFrame)>,
+    histlen: u64,
+}
+
+// A replica connected to this server.
+#[derive(Debug, Clone)]
+pub struct Replica {
+    pub ip: IpAddr,
+    // announced by REPLCONF listening-port
+    pub port: u16,
+    // the last offset acknowledged by REPLCONF ACK
+    pub ack_offset: u64,
+    pub ack_time: Instant,
+    sender: UnboundedSender<RespFrame>,
+}
+
+#[derive(Debug)]
+struct MasterLink {
+    host: String,
+    port: u16,
+    // stops the task following the master
+    cancel: CancellationToken,
+}
+
+impl Default for Replication {
+    fn default() -> Self {
+        Self {
+            ids: RwLock::new(ReplIds {
+                replid: random_id(),
+                replid2: NO_REPLID.to_string(),
+                second_replid_offset: -1,
+            }),
+            offset: AtomicU64::new(0),
+            backlog: Mutex::new(None),
+            has_backlog: AtomicBool::new(false),
+            replicas: DashMap::new(),
+            acked: Notify::new(),
+            master: Mutex::new(None),
+            link_up: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Backlog {
+    fn push(&mut self, start: u64, frame: RespFrame, len: u64, size: u64) {
+        self.frames.push_back((start, frame));
+        self.histlen += len;
+        while self.histlen > size && self.frames.len() > 1 {
+            if let Some((start, _)) = self.frames.pop_front() {
+                let next = self.frames.front().map_or(start, |(next, _)| *next);
+                self.histlen -= next - start;
+            }
+        }
+    }
+}
+
+impl Replica {
+    pub fn new(ip: IpAddr, port: u16, sender: UnboundedSender<RespFrame>) -> Self {
+        Self {
+            ip,
+            port,
+            ack_offset: 0,
+            ack_time: Instant::now(),
+            sender,
+        }
+    }
+}
+
+impl Replication {
+    pub fn replid(&self) -> String {
+        self.ids
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .replid
+            .clone()
+    }
+
+    // (replid2, second_replid_offset)
+    pub fn replid2(&self) -> (String, i64) {
+        let ids = self.ids.read().unwrap_or_else(|e| e.into_inner());
+        (ids.replid2.clone(), ids.second_replid_offset)
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset.load(Ordering::Relaxed)
+    }
+
+    // The master followed by this server, None if it is a master itself.
+    pub fn master(&self) -> Option<(String, u16)> {
+        let master = self.master.lock().unwrap_or_else(|e| e.into_inner());
+        master.as_ref().map(|link| (link.host.clone(), link.port))
+    }
+
+    pub fn link_up(&self) -> bool {
+        self.link_up.load(Ordering::Relaxed)
+    }
+
+    // Whether writes from clients are refused, see replica-read-only.
+    pub fn read_only(&self, config: &ServerConfig) -> bool {
+        self.master().is_some() && config.replica_read_only()
+    }
+
+    // The replicas ordered by connection id.
+    pub fn replicas(&self) -> Vec<Replica> {
+        let mut replicas: Vec<(u64, Replica)> = self
+            .replicas
+            .iter()
+            .map(|r| (*r.key(), r.value().clone()))
+            .collect();
+        replicas.sort_by_key(|(id, _)| *id);
+        replicas.into_iter().map(|(_, replica)| replica).collect()
+    }
+
+    // (offset of the first byte, number of bytes) of the backlog, None until it is created
+    pub fn backlog(&self) -> Option<(u64, u64)> {
+        let backlog = self.backlog.lock().unwrap_or_else(|e| e.into_inner());
+        backlog.as_ref().map(|backlog| {
+            let first = backlog
+                .frames
+                .front()
+                .map_or(self.offset() + 1, |(start, _)| *start);
+            (first, backlog.histlen)
+        })
+    }
+
+    pub(crate) fn add_replica(&self, id: u64, replica: Replica) {
+        self.create_backlog(false);
+        self.replicas.insert(id, replica);
+    }
+
+    pub(crate) fn remove_replica(&self, id: u64) {
+        self.replicas.remove(&id);
+    }
+
+    pub(crate) fn ack(&self, id: u64, offset: u64) {
+        if let Some(mut replica) = self.replicas.get_mut(&id) {
+            replica.ack_offset = replica.ack_offset.max(offset);
+            replica.ack_time = Instant::now();
+        }
+        self.acked.notify_waiters();
+    }
+
+    // The number of replicas that acknowledged the stream up to `offset`.
+    pub fn acked(&self, offset: u64) -> usize {
+        self.replicas
+            .iter()
+            .filter(|r| r.ack_offset >= offset)
+            .count()
+    }
+
+    // Waits until `numreplicas` replicas acknowledged everything written so far, or the client is
+    // woken up by its timeout. Returns the number of replicas that did, None if CLIENT UNBLOCK
+    // ended the wait with an error.
+    pub(crate) async fn wait(
+        &self,
+        config: &ServerConfig,
+        numreplicas: usize,
+        blocked: &mut Blocked<'_>,
+    ) -> Option<usize> {
+        let offset = self.offset();
+        let mut getack = false;
+        loop {
+            // subscribe before counting so an ACK in between is not missed
+            let acked = self.acked.notified();
+            let count = self.acked(offset);
+            if count >= numreplicas {
+                return Some(count);
+            }
+            // replicas only acknowledge once per second on their own, ask them right away
+            if !getack && !self.replicas.is_empty() {
+                getack = true;
+                let frames: Vec<RespFrame> = ["REPLCONF", "GETACK", "*"]
+                    .iter()
+                    .map(|arg| BulkString::from(*arg).into())
+                    .collect();
+                self.propagate(config, RespArray::new(frames).into());
+            }
+            tokio::select! {
+                _ = acked => {}
+                wakeup = blocked.wakeup() => {
+                    return (wakeup != Wakeup::Unblocked).then(|| self.acked(offset));
+                }
+            }
+        }
+    }
+
+    // Sends a write to the backlog and the replicas. The command is only built when there is a
+    // backlog. A replica doesn't feed its own writes, it proxies the stream of its master instead.
+    pub(crate) fn feed(&self, config: &ServerConfig, command: impl FnOnce() -> Vec<RespFrame>) {
+        if !self.has_backlog.load(Ordering::Relaxed) || self.master().is_some() {
+            return;
+        }
+        self.propagate(config, RespArray::new(command()).into());
+    }
+
+    // Appends a frame to the replication stream. The backlog lock keeps the offsets, the backlog
+    // and what the replicas receive in the same order.
+    fn propagate(&self, config: &ServerConfig, frame: RespFrame) {
+        let mut backlog = self.backlog.lock().unwrap_or_else(|e| e.into_inner());
+        let len = frame.encoded_len() as u64;
+        let start = self.offset.fetch_add(len, Ordering::Relaxed) + 1;
+        for replica in self.replicas.iter() {
+            let _ = replica.sender.send(frame.clone());
+        }
+        if let Some(backlog) = backlog.as_mut() {
+            backlog.push(start, frame, len, config.repl_backlog_size());
+        }
+    }
+
+    // The frames a replica missed since `offset` if it can resume from there: it must follow the
+    // same history and the backlog must still hold what comes next.
+    pub(crate) fn continue_from(&self, replid: &str, offset: i64) -> Option<Vec<RespFrame>> {
+        let ids = self.ids.read().unwrap_or_else(|e| e.into_inner());
+        if replid != ids.replid && (replid != ids.replid2 || offset > ids.second_replid_offset) {
+            return None;
+        }
+        let backlog = self.backlog.lock().unwrap_or_else(|e| e.into_inner());
+        if offset == self.offset() as i64 + 1 {
+            return Some(vec![]);
+        }
+        let frames = &backlog.as_ref()?.frames;
+        let from = frames
+            .iter()
+            .position(|(start, _)| *start as i64 == offset)?;
+        Some(
+            frames
+                .iter()
+                .skip(from)
+                .map(|(_, frame)| frame.clone())
+                .collect(),
+        )
+    }
+
+    // Starts a new history, the current one stays valid for PSYNC up to the current offset.
+    fn shift_replid(&self, replid: String) {
+        let mut ids = self.ids.write().unwrap_or_else(|e| e.into_inner());
+        ids.replid2 = std::mem::replace(&mut ids.replid, replid);
+        ids.second_replid_offset = self.offset() as i64 + 1;
+    }
+
+    // Continues the history of the master after a full synchronization.
+    fn synced(&self, replid: String, offset: u64) {
+        *self.ids.write().unwrap_or_else(|e| e.into_inner()) = ReplIds {
+            replid,
+            replid2: NO_REPLID.to_string(),
+            second_replid_offset: -1,
+        };
+        self.offset.store(offset, Ordering::Relaxed);
+        // what the backlog holds belongs to the previous history
+        self.create_backlog(true);
+        self.link_up.store(true, Ordering::Relaxed);
+    }
+
+    fn create_backlog(&self, reset: bool) {
+        let mut backlog = self.backlog.lock().unwrap_or_else(|e| e.into_inner());
+        if reset || backlog.is_none() {
+            *backlog = Some(Backlog::default());
+        }
+        self.has_backlog.store(true, Ordering::Relaxed);
+    }
+}
+
+// REPLICAOF host port starts following a master, REPLICAOF NO ONE turns the server into a master.
+// Returns false if the server already follows the given master.
+pub(crate) fn replicaof(backend: &Backend, master: Option<(String, u16)>) -> bool {
+    let replication = &backend.replication;
+    let mut link = replication.master.lock().unwrap_or_else(|e| e.into_inner());
+    if master.is_none() && link.is_none() {
+        return true;
+    }
+    if let (Some(current), Some((host, port))) = (link.as_ref(), master.as_ref()) {
+        if current.host == *host && current.port == *port {
+            return false;
+        }
+    }
+    if let Some(current) = link.take() {
+        current.cancel.cancel();
+    }
+    replication.link_up.store(false, Ordering::Relaxed);
+    match master {
+        Some((host, port)) => {
+            info!("Connecting to MASTER {}:{}", host, port);
+            // the link is closed on shutdown as well
+            let cancel = backend.shutdown.child_token();
+            tokio::spawn(replica::follow(
+                backend.clone(),
+                host.clone(),
+                port,
+                cancel.clone(),
+            ));
+            *link = Some(MasterLink { host, port, cancel });
+        }
+        None => {
+            // the dataset may diverge from the master from now on, the replicas of our former
+            // master can still resume from where it stopped
+            replication.shift_replid(random_id());
+            info!("MASTER MODE enabled");
+        }
+    }
+    true
+}
+
+// 40 random hex characters, for replication and cluster node ids
+pub(crate) fn random_id() -> String {
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    Sha1::digest(format!("{}:{}", seed, std::process::id()).as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockedClients;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    fn set(key: &str) -> Vec<RespFrame> {
+        vec![BulkString::from("set").into(), BulkString::from(key).into()]
+    }
+
+    #[test]
+    fn test_feed_replicas() {
+        let replication = Replication::default();
+        let config = ServerConfig::default();
+        // nothing is built nor counted before a replica connects
+        replication.feed(&config, || unreachable!());
+        assert_eq!(replication.offset(), 0);
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let ip = "127.0.0.1".parse().unwrap();
+        replication.add_replica(1, Replica::new(ip, 6380, sender));
+        replication.feed(&config, || set("k"));
+        let frame = receiver.try_recv().unwrap();
+        assert_eq!(replication.offset(), frame.encode_to_vec().len() as u64);
+        assert_eq!(replication.replid().len(), 40);
+    }
+
+    #[test]
+    fn test_continue_from_backlog() {
+        let replication = Replication::default();
+        let config = ServerConfig::default();
+        let (sender, _receiver) = mpsc::unbounded_channel();
+        let ip = "127.0.0.1".parse().unwrap();
+        replication.add_replica(1, Replica::new(ip, 6380, sender));
+        replication.remove_replica(1);
+        // "*2\r\n$3\r\nset\r\n$1\r\na\r\n" is 20 bytes
+        for key in ["a", "b", "c"] {
+            replication.feed(&config, || set(key));
+        }
+        let replid = replication.replid();
+        assert_eq!(replication.backlog(), Some((1, 60)));
+        assert_eq!(
+            replication.continue_from(&replid, 21).map(|f| f.len()),
+            Some(2)
+        );
+        assert_eq!(replication.continue_from(&replid, 61), Some(vec![]));
+        assert_eq!(replication.continue_from(&replid, 22), None);
+        assert_eq!(replication.continue_from("?", 1), None);
+
+        // a promoted replica accepts the replicas of its former master
+        replication.shift_replid(random_id());
+        assert!(replication.continue_from(&replid, 41).is_some());
+        assert_eq!(replication.continue_from(&replid, 62), None);
+
+        // the oldest frames are dropped beyond repl-backlog-size
+        config
+            .set(&[("repl-backlog-size".to_string(), "50".to_string())])
+            .unwrap();
+        replication.feed(&config, || set("d"));
+        assert_eq!(replication.backlog(), Some((41, 40)));
+    }
+
+    #[tokio::test]
+    async fn test_wait_acks() {
+        let replication = Replication::default();
+        let config = ServerConfig::default();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let ip = "127.0.0.1".parse().unwrap();
+        replication.add_replica(1, Replica::new(ip, 6380, sender));
+        let clients = BlockedClients::default();
+        let wait = |timeout| clients.block(1, vec![], timeout);
+        replication.feed(&config, || set("a"));
+        assert_eq!(replication.wait(&config, 0, &mut wait(None)).await, Some(0));
+        let mut blocked = wait(Some(Duration::from_millis(10)));
+        assert_eq!(replication.wait(&config, 1, &mut blocked).await, Some(0));
+        // the write, then the GETACK sent by the first WAIT that had to block
+        receiver.try_recv().unwrap();
+        let getack = receiver.try_recv().unwrap();
+        assert_eq!(
+            getack.encode_to_vec(),
+            b"*3\r\n$8\r\nREPLCONF\r\n$6\r\nGETACK\r\n$1\r\n*\r\n"
+        );
+
+        replication.ack(1, replication.offset());
+        assert_eq!(replication.wait(&config, 1, &mut wait(None)).await, Some(1));
+    }
+}
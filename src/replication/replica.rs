@@ -0,0 +1,175 @@
+use crate::{
+    cmd::{Command, CommandExecutor, ExecContext},
+    Backend, BulkString, DecodeLimits, RespArray, RespCodec, RespFrame, Snapshot,
+};
+use anyhow::{anyhow, bail, Result};
+use futures::SinkExt;
+use std::{sync::atomic::Ordering, time::Duration};
+use tokio::{net::TcpStream, time};
+use tokio_stream::StreamExt;
+use tokio_util::{codec::Framed, sync::CancellationToken};
+use tracing::{info, warn};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const ACK_PERIOD: Duration = Duration::from_secs(1);
+
+// Follows the master until cancelled, reconnecting when the link breaks.
+pub(super) async fn follow(backend: Backend, host: String, port: u16, cancel: CancellationToken) {
+    loop {
+        tokio::select! {
+            ret = sync_with(&backend, &host, port) => {
+                backend.replication.link_up.store(false, Ordering::Relaxed);
+                match ret {
+                    Ok(()) => warn!("Connection with MASTER {}:{} lost", host, port),
+                    Err(e) => warn!("Error with MASTER {}:{}: {}", host, port, e),
+                }
+            }
+            _ = cancel.cancelled() => break,
+        }
+        tokio::select! {
+            _ = time::sleep(RECONNECT_DELAY) => {}
+            _ = cancel.cancelled() => break,
+        }
+    }
+    info!("Stopped following MASTER {}:{}", host, port);
+}
+
+// Does the handshake and the synchronization, then applies the writes streamed by the
+// master. Returns when the master closes the connection.
+async fn sync_with(backend: &Backend, host: &str, port: u16) -> Result<()> {
+    let stream = TcpStream::connect((host, port)).await?;
+    // the snapshot of the master comes as a single bulk string, as big as the dataset
+    let limits = DecodeLimits {
+        max_bulk_len: usize::MAX,
+        ..Default::default()
+    };
+    let mut framed = Framed::new(stream, RespCodec::default().with_limits(limits));
+    info!("MASTER <-> REPLICA sync started");
+    request(&mut framed, &["PING"]).await?;
+    let listening_port = backend.config.snapshot().port.to_string();
+    request(
+        &mut framed,
+        &["REPLCONF", "listening-port", &listening_port],
+    )
+    .await?;
+
+    // try to resume from where we stopped, the master may still have what we missed
+    let replication = &backend.replication;
+    let next = (replication.offset() + 1).to_string();
+    let reply = match request(&mut framed, &["PSYNC", &replication.replid(), &next]).await? {
+        RespFrame::SimpleString(reply) => reply.0,
+        reply => bail!("unexpected reply to PSYNC: {:?}", reply),
+    };
+    let mut parts = reply.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        // +FULLRESYNC <replid> <offset> then the snapshot as a bulk string
+        (Some("FULLRESYNC"), Some(replid), Some(offset)) => {
+            let offset = offset.parse()?;
+            full_sync(backend, &mut framed, replid.to_string(), offset).await?;
+        }
+        // +CONTINUE [<replid>] then the writes we missed, the master may have a new history
+        (Some("CONTINUE"), replid, None) => {
+            if let Some(replid) = replid.filter(|id| *id != replication.replid()) {
+                replication.shift_replid(replid.to_string());
+                disconnect_replicas(backend);
+            }
+            replication.link_up.store(true, Ordering::Relaxed);
+            info!("Successful partial resynchronization with master.");
+        }
+        _ => bail!("unexpected reply to PSYNC: {}", reply),
+    }
+
+    let mut acks = time::interval(ACK_PERIOD);
+    loop {
+        let frame = tokio::select! {
+            frame = framed.next() => match frame {
+                Some(frame) => frame?,
+                None => return Ok(()),
+            },
+            _ = acks.tick() => {
+                ack(&mut framed, replication.offset()).await?;
+                continue;
+            }
+        };
+        let getack = {
+            let _guard = backend.exec_lock.read().unwrap_or_else(|e| e.into_inner());
+            // the stream is proxied as is to our replicas, which keeps the offsets in line
+            replication.propagate(&backend.config, frame.clone());
+            match Command::try_from(frame) {
+                Ok(Command::ReplConf(cmd)) => cmd.is_getack(),
+                Ok(cmd) => {
+                    if let Err(e) = cmd.execute(&mut ExecContext::new(backend)) {
+                        warn!("Command from MASTER failed: {}", e);
+                    }
+                    false
+                }
+                Err(e) => {
+                    warn!("Invalid command from MASTER: {}", e);
+                    false
+                }
+            }
+        };
+        // the acknowledged offset includes the GETACK itself
+        if getack {
+            ack(&mut framed, replication.offset()).await?;
+        }
+    }
+}
+
+// REPLCONF ACK <offset>, which the master doesn't reply to.
+async fn ack(framed: &mut Framed<TcpStream, RespCodec>, offset: u64) -> Result<()> {
+    let frames: Vec<RespFrame> = ["REPLCONF", "ACK", &offset.to_string()]
+        .iter()
+        .map(|arg| BulkString::from(*arg).into())
+        .collect();
+    Ok(framed.send(RespArray::new(frames).into()).await?)
+}
+
+async fn full_sync(
+    backend: &Backend,
+    framed: &mut Framed<TcpStream, RespCodec>,
+    replid: String,
+    offset: u64,
+) -> Result<()> {
+    let payload = match framed.next().await {
+        Some(Ok(RespFrame::BulkString(payload))) => payload,
+        Some(Ok(frame)) => bail!("expected the snapshot, got {:?}", frame),
+        Some(Err(e)) => return Err(e.into()),
+        None => bail!("connection closed during the synchronization"),
+    };
+    let snapshot = Snapshot::read_from(&mut &payload[..])?;
+    {
+        let _guard = backend.exec_lock.write().unwrap_or_else(|e| e.into_inner());
+        snapshot.restore(backend);
+    }
+    // our own replicas have an outdated history now, they have to synchronize again
+    disconnect_replicas(backend);
+    backend.replication.synced(replid, offset);
+    info!("MASTER <-> REPLICA sync: Finished with success");
+    Ok(())
+}
+
+fn disconnect_replicas(backend: &Backend) {
+    let replicas: Vec<u64> = backend
+        .replication
+        .replicas
+        .iter()
+        .map(|r| *r.key())
+        .collect();
+    backend.clients.kill(|client| replicas.contains(&client.id));
+}
+
+// Sends a command to the master and waits for the reply, which must not be an error.
+async fn request(framed: &mut Framed<TcpStream, RespCodec>, args: &[&str]) -> Result<RespFrame> {
+    let frames: Vec<RespFrame> = args
+        .iter()
+        .map(|arg| BulkString::from(*arg).into())
+        .collect();
+    framed.send(RespArray::new(frames).into()).await?;
+    match framed.next().await {
+        Some(Ok(RespFrame::Error(e))) => Err(anyhow!("{} failed: {}", args[0], e.0)),
+        Some(Ok(reply)) => Ok(reply),
+        Some(Err(e)) => Err(e.into()),
+        None => bail!("connection closed by MASTER"),
+    }
+}
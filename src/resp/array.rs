@@ -2,12 +2,10 @@ use bytes::{Buf, BytesMut};
 use std::ops::Deref;
 
 use super::{
-    calc_total_length, parse_length, RespDecoder, RespEncoder, RespError, RespFrame, BUFFER_CAP,
-    CRLF_LEN,
+    calc_total_length, header_len, parse_length, put_fmt, RespDecoder, RespEncoder, RespError,
+    RespFrame, CRLF_LEN,
 };
 
-const NULL_ARRAY: &[u8] = b"*-1\r\n";
-
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct RespArray(pub(crate) Vec<RespFrame>);
 
@@ -18,35 +16,26 @@ impl RespArray {
 }
 
 // - array: "*<number-of-elements>\r\n<element-1>...<element-n>"
-// - null array: "*-1\r\n"
+// - empty array: "*0\r\n", the null array "*-1\r\n" is a RespNull
 // - "*2\r\n$3\r\nget\r\n$5\r\nhello\r\n"
 impl RespEncoder for RespArray {
-    fn encode(self) -> Vec<u8> {
-        if self.is_empty() {
-            return NULL_ARRAY.to_vec();
+    fn encode(&self, buf: &mut BytesMut) {
+        put_fmt(buf, format_args!("*{}\r\n", self.0.len()));
+        for frame in &self.0 {
+            frame.encode(buf);
         }
+    }
 
-        let mut buf = Vec::with_capacity(BUFFER_CAP);
-        buf.extend_from_slice(&format!("*{}\r\n", self.0.len()).into_bytes());
-        for frame in self.0 {
-            buf.extend_from_slice(&frame.encode());
-        }
-        buf
+    fn encoded_len(&self) -> usize {
+        header_len(self.0.len()) + self.0.iter().map(RespEncoder::encoded_len).sum::<usize>()
     }
 }
 
 // - array: "*<number-of-elements>\r\n<element-1>...<element-n>"
-// - null array: "*-1\r\n"
 // - "*2\r\n$3\r\nget\r\n$5\r\nhello\r\n"
-// FIXME: need to handle incomplete
 impl RespDecoder for RespArray {
     const PREFIX: &'static str = "*";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        if buf.starts_with(NULL_ARRAY) {
-            buf.advance(NULL_ARRAY.len());
-            return Ok(RespArray::new(vec![]));
-        }
-
         let (end, len) = parse_length(buf, Self::PREFIX)?;
         let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
 
@@ -85,17 +74,20 @@ mod tests {
     use anyhow::Result;
 
     #[test]
-    fn test_encode_null_array_encode() {
+    fn test_encode_empty_array() {
         let frame: RespFrame = RespArray::new(vec![]).into();
-        assert_eq!(frame.encode(), NULL_ARRAY);
+        assert_eq!(frame.encode_to_vec(), b"*0\r\n");
     }
 
     #[test]
-    fn test_decode_null_array() -> Result<()> {
-        let mut buf = BytesMut::new();
-        buf.extend_from_slice(NULL_ARRAY);
+    fn test_decode_empty_array() -> Result<()> {
+        let mut buf = BytesMut::from("*0\r\n");
         let frame = RespArray::decode(&mut buf)?;
         assert_eq!(frame, RespArray::new(vec![]));
+
+        // the null array is a null, not an empty array
+        let mut buf = BytesMut::from("*-1\r\n");
+        assert_eq!(RespFrame::decode(&mut buf)?, RespFrame::null());
         Ok(())
     }
 
@@ -106,7 +98,7 @@ mod tests {
             BulkString::new("hello".to_string()).into(),
         ])
         .into();
-        assert_eq!(frame.encode(), b"*2\r\n$3\r\nget\r\n$5\r\nhello\r\n")
+        assert_eq!(frame.encode_to_vec(), b"*2\r\n$3\r\nget\r\n$5\r\nhello\r\n")
     }
 
     #[test]
@@ -118,7 +110,7 @@ mod tests {
         ])
         .into();
         assert_eq!(
-            &frame.encode(),
+            &frame.encode_to_vec(),
             b"*3\r\n$3\r\nset\r\n$5\r\nhello\r\n$5\r\nworld\r\n"
         );
     }
@@ -139,6 +131,11 @@ mod tests {
         let frame = RespArray::decode(&mut buf)?;
         assert_eq!(frame, RespArray::new([b"set".into(), b"hello".into()]));
 
+        // a pipeline read up to the middle of a bulk string
+        buf.extend_from_slice(b"*2\r\n$3\r\nset\r\n$5\r\nhe");
+        let ret = RespArray::decode(&mut buf);
+        assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+
         Ok(())
     }
 
@@ -0,0 +1,119 @@
+use bytes::{BufMut, BytesMut};
+use std::ops::Deref;
+
+use super::{extract_simple_frame_data, RespDecoder, RespEncoder, RespError, CRLF, CRLF_LEN};
+
+// An integer of any size, kept as its decimal digits with an optional sign.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+pub struct BigNumber(pub(crate) String);
+
+impl BigNumber {
+    pub fn new(value: impl Into<String>) -> Result<Self, RespError> {
+        let value = value.into();
+        check(&value)?;
+        Ok(BigNumber(value))
+    }
+}
+
+pub(super) fn check(value: &str) -> Result<(), RespError> {
+    let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(RespError::InvalidFrame(format!(
+            "invalid big number: {}",
+            value
+        )));
+    }
+    Ok(())
+}
+
+// - big number: "([+|-]<number>\r\n"
+impl RespEncoder for BigNumber {
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.put_u8(b'(');
+        buf.put_slice(self.0.as_bytes());
+        buf.put_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        1 + self.0.len() + CRLF_LEN
+    }
+}
+
+impl RespDecoder for BigNumber {
+    const PREFIX: &'static str = "(";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
+        let data = buf.split_to(end + CRLF_LEN);
+        let s = std::str::from_utf8(&data[Self::PREFIX.len()..end])?;
+        BigNumber::new(s)
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
+        Ok(end + CRLF_LEN)
+    }
+}
+
+impl From<i128> for BigNumber {
+    fn from(n: i128) -> Self {
+        BigNumber(n.to_string())
+    }
+}
+
+impl From<u128> for BigNumber {
+    fn from(n: u128) -> Self {
+        BigNumber(n.to_string())
+    }
+}
+
+// fails when the number doesn't fit
+impl TryFrom<&BigNumber> for i128 {
+    type Error = std::num::ParseIntError;
+    fn try_from(n: &BigNumber) -> Result<Self, Self::Error> {
+        n.0.parse()
+    }
+}
+
+impl Deref for BigNumber {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+    use anyhow::Result;
+
+    #[test]
+    fn test_encode_big_number() {
+        let frame: RespFrame = BigNumber::from(u128::MAX).into();
+        assert_eq!(
+            frame.encode_to_vec(),
+            b"(340282366920938463463374607431768211455\r\n"
+        );
+    }
+
+    #[test]
+    fn test_big_number_decode() -> Result<()> {
+        let mut buf = BytesMut::from("(-3492890328409238509324850943850943825024385\r\n");
+        let frame = RespFrame::decode(&mut buf)?;
+        let RespFrame::BigNumber(n) = frame else {
+            panic!("expected a big number, got {:?}", frame);
+        };
+        assert_eq!(&*n, "-3492890328409238509324850943850943825024385");
+        assert!(i128::try_from(&n).is_err());
+        assert_eq!(i128::try_from(&BigNumber::new("+42")?)?, 42);
+
+        let mut buf = BytesMut::from("(12a\r\n");
+        assert!(matches!(
+            BigNumber::decode(&mut buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+        assert!(BigNumber::new("-").is_err());
+        Ok(())
+    }
+}
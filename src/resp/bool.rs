@@ -1,15 +1,19 @@
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 
-use super::{extract_fixed_data, RespDecoder, RespEncoder, RespError};
+use super::{extract_fixed_data, fixed_length, RespDecoder, RespEncoder, RespError};
 
 // - boolean: "#<t|f>\r\n"
 impl RespEncoder for bool {
-    fn encode(self) -> Vec<u8> {
+    fn encode(&self, buf: &mut BytesMut) {
         match self {
-            true => b"#t\r\n".to_vec(),
-            false => b"#f\r\n".to_vec(),
+            true => buf.put_slice(b"#t\r\n"),
+            false => buf.put_slice(b"#f\r\n"),
         }
     }
+
+    fn encoded_len(&self) -> usize {
+        4
+    }
 }
 
 impl RespDecoder for bool {
@@ -25,8 +29,8 @@ impl RespDecoder for bool {
         }
     }
 
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        Ok(4)
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        fixed_length(buf, 4)
     }
 }
 
@@ -40,13 +44,13 @@ mod tests {
     #[test]
     fn test_encode_true_boolean() {
         let frame: RespFrame = true.into();
-        assert_eq!(frame.encode(), b"#t\r\n");
+        assert_eq!(frame.encode_to_vec(), b"#t\r\n");
     }
 
     #[test]
     fn test_encode_false_boolean() {
         let frame: RespFrame = false.into();
-        assert_eq!(frame.encode(), b"#f\r\n");
+        assert_eq!(frame.encode_to_vec(), b"#f\r\n");
     }
 
     #[test]
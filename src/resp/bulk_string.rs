@@ -1,85 +1,108 @@
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::ops::Deref;
 
-use super::{parse_length, RespDecoder, RespEncoder, RespError, CRLF_LEN};
+use super::{
+    data_end, header_len, parse_length, put_fmt, RespDecoder, RespEncoder, RespError, CRLF,
+    CRLF_LEN,
+};
 
-const NULL_BULK_STRING: &[u8] = b"$-1\r\n";
+// Shorter strings are copied out of the read buffer: a slice of it would keep the whole buffer
+// alive for as long as the value is stored.
+const ZERO_COPY_MIN_LEN: usize = 4096;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
-pub struct BulkString(pub(crate) Vec<u8>);
+pub struct BulkString(pub(crate) Bytes);
 
 impl BulkString {
-    pub fn new(s: impl Into<Vec<u8>>) -> Self {
+    pub fn new(s: impl Into<Bytes>) -> Self {
         BulkString(s.into())
     }
 }
 
 // - bulk string: "$<length>\r\n<data>\r\n"
-// - null bulk string: "$-1\r\n"
+// - empty bulk string: "$0\r\n\r\n", the null bulk string "$-1\r\n" is a RespNull
 impl RespEncoder for BulkString {
-    fn encode(self) -> Vec<u8> {
-        if self.is_empty() {
-            return NULL_BULK_STRING.to_vec();
-        }
+    fn encode(&self, buf: &mut BytesMut) {
+        put_fmt(buf, format_args!("${}\r\n", self.len()));
+        buf.put_slice(&self.0);
+        buf.put_slice(CRLF);
+    }
 
-        let mut buf = Vec::with_capacity(self.len() + 16);
-        buf.extend_from_slice(&format!("${}\r\n", self.len()).into_bytes());
-        buf.extend_from_slice(&self.0);
-        buf.extend_from_slice(b"\r\n");
-        buf
+    fn encoded_len(&self) -> usize {
+        header_len(self.len()) + self.len() + CRLF_LEN
     }
 }
 
 impl RespDecoder for BulkString {
     const PREFIX: &'static str = "$";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        if buf.starts_with(NULL_BULK_STRING) {
-            buf.advance(NULL_BULK_STRING.len());
-            return Ok(BulkString::new(vec![]));
-        }
-
         let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let remained = &buf[end + CRLF_LEN..];
-        if remained.len() < len + CRLF_LEN {
+        if data_end(end, len)? > buf.len() {
             return Err(RespError::NotComplete);
         }
 
         buf.advance(end + CRLF_LEN);
 
-        let data = buf.split_to(len + CRLF_LEN);
-        Ok(BulkString::new(data[..len].to_vec()))
+        let data = if len < ZERO_COPY_MIN_LEN {
+            let data = Bytes::copy_from_slice(&buf[..len]);
+            buf.advance(len);
+            data
+        } else {
+            buf.split_to(len).freeze()
+        };
+        buf.advance(CRLF_LEN);
+        Ok(BulkString(data))
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
         let (end, len) = parse_length(buf, Self::PREFIX)?;
-        if len > buf.len() {
+        let total = data_end(end, len)?;
+        if total > buf.len() {
             return Err(RespError::NotComplete);
         }
-        Ok(end + CRLF_LEN + len + CRLF_LEN)
+        Ok(total)
     }
 }
 
 impl From<&str> for BulkString {
     fn from(s: &str) -> Self {
-        BulkString(s.as_bytes().to_vec())
+        BulkString(Bytes::copy_from_slice(s.as_bytes()))
     }
 }
 
 impl From<&[u8]> for BulkString {
     fn from(s: &[u8]) -> Self {
-        BulkString(s.to_vec())
+        BulkString(Bytes::copy_from_slice(s))
     }
 }
 
 impl From<String> for BulkString {
     fn from(s: String) -> Self {
-        BulkString(s.into_bytes())
+        BulkString(s.into())
+    }
+}
+
+impl From<Vec<u8>> for BulkString {
+    fn from(s: Vec<u8>) -> Self {
+        BulkString(s.into())
+    }
+}
+
+impl From<Bytes> for BulkString {
+    fn from(s: Bytes) -> Self {
+        BulkString(s)
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for BulkString {
     fn from(s: &[u8; N]) -> Self {
-        BulkString(s.to_vec())
+        BulkString(Bytes::copy_from_slice(s))
+    }
+}
+
+impl From<BulkString> for Bytes {
+    fn from(s: BulkString) -> Self {
+        s.0
     }
 }
 
@@ -90,7 +113,7 @@ impl AsRef<[u8]> for BulkString {
 }
 
 impl Deref for BulkString {
-    type Target = Vec<u8>;
+    type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -104,26 +127,28 @@ mod tests {
     use anyhow::Result;
 
     #[test]
-    fn test_encode_null_bulk_string() {
+    fn test_encode_empty_bulk_string() {
         let frame: RespFrame = b"".into();
-        assert_eq!(frame.encode(), NULL_BULK_STRING.to_vec());
+        assert_eq!(frame.encode_to_vec(), b"$0\r\n\r\n");
     }
 
     #[test]
-    fn test_decode_null_bulk_string() -> Result<()> {
-        let mut buf = BytesMut::new();
-        buf.extend_from_slice(NULL_BULK_STRING);
-
+    fn test_decode_empty_bulk_string() -> Result<()> {
+        let mut buf = BytesMut::from("$0\r\n\r\n");
         let frame = BulkString::decode(&mut buf)?;
-        assert_eq!(frame, BulkString(vec![]));
+        assert_eq!(frame, BulkString(Bytes::new()));
+        assert!(buf.is_empty());
 
+        // the null bulk string is a null, not an empty string
+        let mut buf = BytesMut::from("$-1\r\n");
+        assert_eq!(RespFrame::decode(&mut buf)?, RespFrame::null());
         Ok(())
     }
 
     #[test]
     fn test_encode_bulk_string() {
         let frame: RespFrame = BulkString::new("hello".to_string()).into();
-        assert_eq!(frame.encode(), b"$5\r\nhello\r\n".to_vec());
+        assert_eq!(frame.encode_to_vec(), b"$5\r\nhello\r\n".to_vec());
     }
 
     #[test]
@@ -132,7 +157,7 @@ mod tests {
         buf.extend_from_slice(b"$5\r\nhello\r\n");
 
         let frame = BulkString::decode(&mut buf)?;
-        assert_eq!(frame, BulkString::new(b"hello"));
+        assert_eq!(frame, BulkString::from(b"hello"));
 
         buf.extend_from_slice(b"$5\r\nhello");
         let ret = BulkString::decode(&mut buf);
@@ -140,8 +165,30 @@ mod tests {
 
         buf.extend_from_slice(b"\r\n");
         let frame = BulkString::decode(&mut buf)?;
-        assert_eq!(frame, BulkString::new(b"hello"));
+        assert_eq!(frame, BulkString::from(b"hello"));
+
+        Ok(())
+    }
 
+    #[test]
+    fn test_decode_large_bulk_string_shares_the_buffer() -> Result<()> {
+        let value = vec![b'x'; ZERO_COPY_MIN_LEN];
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(format!("${}\r\n", value.len()).as_bytes());
+        buf.extend_from_slice(&value);
+        buf.extend_from_slice(b"\r\n$5\r\nhello\r\n");
+        let start = buf.as_ptr() as usize;
+
+        let frame = BulkString::decode(&mut buf)?;
+        assert_eq!(*frame, value);
+        let offset = frame.as_ptr() as usize - start;
+        assert_eq!(offset, format!("${}\r\n", value.len()).len());
+
+        // a short one is copied out
+        let start = buf.as_ptr() as usize;
+        let frame = BulkString::decode(&mut buf)?;
+        assert_eq!(&*frame, b"hello");
+        assert_ne!(frame.as_ptr() as usize, start + b"$5\r\n".len());
         Ok(())
     }
 }
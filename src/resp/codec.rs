@@ -0,0 +1,195 @@
+use super::{
+    scanner::FrameScanner, DecodeLimits, RespArray, RespDecoder, RespEncoder, RespError, RespFrame,
+};
+use bytes::BytesMut;
+use std::{borrow::Cow, io};
+use thiserror::Error;
+use tokio_util::codec::{Decoder, Encoder};
+
+// Frames RESP over a byte stream with `Framed`, for the connections of the server and for the
+// clients talking to one. The commands typed in telnet are read too. The frames are written in the
+// protocol version negotiated with HELLO, RESP3 ones are turned into RESP2 for a RESP2 peer.
+#[derive(Debug, Clone)]
+pub struct RespCodec {
+    protover: u8,
+    limits: DecodeLimits,
+    // how far the frame being read was scanned, it's decoded once it was all read
+    scanner: FrameScanner,
+}
+
+impl RespCodec {
+    pub fn new(protover: u8) -> Self {
+        Self {
+            protover,
+            limits: DecodeLimits::default(),
+            scanner: FrameScanner::default(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn set_limits(&mut self, limits: DecodeLimits) {
+        self.limits = limits;
+    }
+
+    pub fn protover(&self) -> u8 {
+        self.protover
+    }
+
+    pub fn set_protover(&mut self, protover: u8) {
+        self.protover = protover;
+    }
+
+    // Writes a frame that is kept to be sent again, like the shared +OK, into a write buffer such
+    // as `Framed::write_buffer_mut`.
+    pub fn encode_ref(&self, item: &RespFrame, dst: &mut BytesMut) {
+        let item = match self.protover {
+            2 => item.to_resp2(),
+            _ => Cow::Borrowed(item),
+        };
+        dst.reserve(item.encoded_len());
+        item.encode(dst);
+    }
+}
+
+// writes the frames as they are
+impl Default for RespCodec {
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+// A stream can't be read past a protocol error, unlike an incomplete frame which waits for more.
+#[derive(Error, Debug)]
+pub enum CodecError {
+    #[error("Protocol error: {0}")]
+    Protocol(#[from] RespError),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl Encoder<RespFrame> for RespCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: RespFrame, dst: &mut BytesMut) -> Result<(), CodecError> {
+        let item = match self.protover {
+            2 => item.into_resp2(),
+            _ => item,
+        };
+        // the buffer grows once for a large reply, not each time a part of it is written
+        dst.reserve(item.encoded_len());
+        item.encode(dst);
+        Ok(())
+    }
+}
+
+impl Decoder for RespCodec {
+    type Item = RespFrame;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<RespFrame>, CodecError> {
+        // commands typed in telnet, the empty lines are skipped like in redis
+        while RespArray::is_inline(src) {
+            match RespArray::decode_inline(src) {
+                Ok(args) if args.is_empty() => continue,
+                Ok(args) => return Ok(Some(args.into())),
+                Err(RespError::NotComplete) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        // a frame over the limits is refused before it's all read, the bytes of a large one are
+        // scanned once however many reads it takes
+        if self.scanner.scan(src, &self.limits)?.is_none() {
+            return Ok(None);
+        }
+        match RespFrame::decode(src) {
+            Ok(frame) => Ok(Some(frame)),
+            Err(RespError::NotComplete) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, SimpleString};
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_codec_round_trip() -> anyhow::Result<()> {
+        let mut codec = RespCodec::default();
+        let mut buf = BytesMut::new();
+        let set = ["SET", "key", "value"].map(|arg| BulkString::from(arg).into());
+        codec.encode(RespArray::new(set.to_vec()).into(), &mut buf)?;
+        codec.encode(SimpleString::new("OK").into(), &mut buf)?;
+
+        // a frame split across reads waits for the rest
+        let mut partial = buf.split_to(10);
+        assert!(codec.decode(&mut partial)?.is_none());
+        partial.unsplit(buf);
+        assert_eq!(
+            codec.decode(&mut partial)?,
+            Some(RespArray::new(set.to_vec()).into())
+        );
+        assert_eq!(
+            codec.decode(&mut partial)?,
+            Some(SimpleString::new("OK").into())
+        );
+        assert!(partial.is_empty());
+
+        let mut bad = BytesMut::from("$abc\r\n");
+        assert!(matches!(
+            codec.decode(&mut bad),
+            Err(CodecError::Protocol(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_ref_leaves_the_frame() {
+        let reply: RespFrame =
+            RespArray::new(vec![RespFrame::null(), SimpleString::new("OK").into()]).into();
+        let mut buf = BytesMut::new();
+        RespCodec::new(2).encode_ref(&reply, &mut buf);
+        RespCodec::new(3).encode_ref(&reply, &mut buf);
+        assert_eq!(
+            buf,
+            BytesMut::from("*2\r\n$-1\r\n+OK\r\n*2\r\n_\r\n+OK\r\n")
+        );
+
+        let ok: RespFrame = SimpleString::new("OK").into();
+        assert!(matches!(ok.to_resp2(), Cow::Borrowed(_)));
+    }
+
+    // An empty value is empty in both versions, only a null is a null in either.
+    #[test]
+    fn test_encode_empty_and_null_by_version() -> anyhow::Result<()> {
+        let reply = || -> RespFrame {
+            RespArray::new([
+                BulkString::new("").into(),
+                RespArray::new([]).into(),
+                RespFrame::null(),
+                RespFrame::null_array(),
+            ])
+            .into()
+        };
+        for (protover, expected) in [
+            (2, "*4\r\n$0\r\n\r\n*0\r\n$-1\r\n*-1\r\n"),
+            (3, "*4\r\n$0\r\n\r\n*0\r\n_\r\n_\r\n"),
+        ] {
+            let mut codec = RespCodec::new(protover);
+            let mut buf = BytesMut::new();
+            codec.encode(reply(), &mut buf)?;
+            assert_eq!(buf, BytesMut::from(expected));
+            let mut shared = BytesMut::new();
+            codec.encode_ref(&reply(), &mut shared);
+            assert_eq!(shared, buf);
+            assert_eq!(codec.decode(&mut buf)?, Some(reply()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use thiserror::Error;
+
+use super::RespFrame;
+
+// The frame a reply was turned into a Rust value from, when it holds something else. It's kept so
+// a caller can still look at it.
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("expected {expected}, got {frame:?}")]
+pub struct FromFrameError {
+    pub expected: &'static str,
+    pub frame: RespFrame,
+}
+
+fn mismatch<T>(expected: &'static str, frame: RespFrame) -> Result<T, FromFrameError> {
+    Err(FromFrameError { expected, frame })
+}
+
+// Strings and bytes come from the string frames, numbers from the number frames or from strings
+// holding them, as RESP2 replies do. A RESP2 reply of a map is an array of its keys and values.
+
+impl TryFrom<RespFrame> for Vec<u8> {
+    type Error = FromFrameError;
+
+    fn try_from(frame: RespFrame) -> Result<Self, Self::Error> {
+        match frame {
+            RespFrame::BulkString(s) => Ok(s.0.into()),
+            RespFrame::SimpleString(s) => Ok(s.0.into_bytes()),
+            RespFrame::VerbatimString(s) => Ok(s.data),
+            frame => mismatch("a string", frame),
+        }
+    }
+}
+
+fn text(frame: &RespFrame) -> Option<String> {
+    let bytes: &[u8] = match frame {
+        RespFrame::SimpleString(s) => s.as_bytes(),
+        RespFrame::BigNumber(n) => n.as_bytes(),
+        RespFrame::BulkString(s) => s,
+        RespFrame::VerbatimString(s) => s.data(),
+        _ => return None,
+    };
+    std::str::from_utf8(bytes).ok().map(str::to_string)
+}
+
+impl TryFrom<RespFrame> for String {
+    type Error = FromFrameError;
+
+    fn try_from(frame: RespFrame) -> Result<Self, Self::Error> {
+        text(&frame).map_or_else(|| mismatch("a UTF-8 string", frame), Ok)
+    }
+}
+
+impl TryFrom<RespFrame> for i64 {
+    type Error = FromFrameError;
+
+    fn try_from(frame: RespFrame) -> Result<Self, Self::Error> {
+        let parsed = match &frame {
+            RespFrame::Integer(n) => return Ok(*n),
+            RespFrame::BulkString(s) => std::str::from_utf8(s).ok().and_then(|s| s.parse().ok()),
+            RespFrame::SimpleString(s) => s.parse().ok(),
+            RespFrame::BigNumber(n) => n.parse().ok(),
+            _ => None,
+        };
+        parsed.map_or_else(|| mismatch("an integer", frame), Ok)
+    }
+}
+
+impl TryFrom<RespFrame> for f64 {
+    type Error = FromFrameError;
+
+    fn try_from(frame: RespFrame) -> Result<Self, Self::Error> {
+        let parsed = match &frame {
+            RespFrame::Double(d) => return Ok(*d),
+            RespFrame::Integer(n) => return Ok(*n as f64),
+            RespFrame::BulkString(s) => std::str::from_utf8(s).ok().and_then(|s| s.parse().ok()),
+            RespFrame::SimpleString(s) => s.parse().ok(),
+            _ => None,
+        };
+        parsed.map_or_else(|| mismatch("a double", frame), Ok)
+    }
+}
+
+impl TryFrom<RespFrame> for bool {
+    type Error = FromFrameError;
+
+    fn try_from(frame: RespFrame) -> Result<Self, Self::Error> {
+        match frame {
+            RespFrame::Boolean(b) => Ok(b),
+            RespFrame::Integer(0) => Ok(false),
+            RespFrame::Integer(1) => Ok(true),
+            frame => mismatch("a boolean", frame),
+        }
+    }
+}
+
+impl TryFrom<RespFrame> for Vec<RespFrame> {
+    type Error = FromFrameError;
+
+    fn try_from(frame: RespFrame) -> Result<Self, Self::Error> {
+        match frame {
+            RespFrame::Array(frames) => Ok(frames.0),
+            RespFrame::Set(set) => Ok(set.into_vec()),
+            RespFrame::Push(frames) => Ok(frames.0),
+            frame => mismatch("an array", frame),
+        }
+    }
+}
+
+impl TryFrom<RespFrame> for HashMap<String, RespFrame> {
+    type Error = FromFrameError;
+
+    fn try_from(frame: RespFrame) -> Result<Self, Self::Error> {
+        // the keys are checked before the frame is taken apart
+        let keys: Option<Vec<String>> = match &frame {
+            RespFrame::Map(map) => map.iter().map(|(key, _)| text(key)).collect(),
+            RespFrame::Array(frames) if frames.len() % 2 == 0 => {
+                frames.iter().step_by(2).map(text).collect()
+            }
+            _ => None,
+        };
+        let Some(keys) = keys else {
+            return mismatch("a map with string keys", frame);
+        };
+        let values: Vec<RespFrame> = match frame {
+            RespFrame::Map(map) => map.0.into_iter().map(|(_, value)| value).collect(),
+            RespFrame::Array(frames) => frames.0.into_iter().skip(1).step_by(2).collect(),
+            _ => unreachable!("only maps and arrays have keys"),
+        };
+        Ok(keys.into_iter().zip(values).collect())
+    }
+}
+
+// A null, or the null bulk string or array of RESP2, is None.
+macro_rules! impl_option {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<RespFrame> for Option<$t> {
+                type Error = FromFrameError;
+
+                fn try_from(frame: RespFrame) -> Result<Self, Self::Error> {
+                    match frame {
+                        RespFrame::Null(_) => Ok(None),
+                        frame => frame.try_into().map(Some),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_option!(
+    Vec<u8>,
+    String,
+    i64,
+    f64,
+    bool,
+    Vec<RespFrame>,
+    HashMap<String, RespFrame>
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespArray, RespMap, SimpleString};
+    use anyhow::Result;
+
+    #[test]
+    fn test_frames_into_rust_values() -> Result<()> {
+        assert_eq!(String::try_from(RespFrame::from("OK"))?, "OK");
+        assert_eq!(i64::try_from(RespFrame::from(b"42"))?, 42);
+        assert_eq!(f64::try_from(RespFrame::from(b"inf"))?, f64::INFINITY);
+        assert!(bool::try_from(RespFrame::Integer(1))?);
+        assert_eq!(Option::<i64>::try_from(RespFrame::null())?, None);
+        assert_eq!(
+            Option::<Vec<u8>>::try_from(RespFrame::from(b"v"))?,
+            Some(b"v".to_vec())
+        );
+
+        // a map, or the flattened map of RESP2
+        let mut map = RespMap::new();
+        map.insert(BulkString::from("a"), RespFrame::Integer(1));
+        let flat = RespArray::new([BulkString::from("a").into(), RespFrame::Integer(1)]);
+        for frame in [RespFrame::from(map), flat.into()] {
+            let map = HashMap::<String, RespFrame>::try_from(frame)?;
+            assert_eq!(map.get("a"), Some(&RespFrame::Integer(1)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_mismatches_keep_the_frame() {
+        let frame: RespFrame = SimpleString::new("OK").into();
+        let err = i64::try_from(frame.clone()).unwrap_err();
+        assert_eq!(err.frame, frame);
+        assert_eq!(
+            err.to_string(),
+            "expected an integer, got SimpleString(SimpleString(\"OK\"))"
+        );
+        assert!(String::try_from(RespFrame::from(b"\xff")).is_err());
+        assert!(HashMap::<String, RespFrame>::try_from(RespFrame::Integer(1)).is_err());
+    }
+}
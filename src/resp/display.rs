@@ -0,0 +1,172 @@
+use std::fmt::{self, Write};
+
+use super::{double::Double, RespFrame};
+
+// Frames the way redis-cli shows them: strings quoted with the bytes that aren't printable escaped,
+// "(integer) 1", "(nil)", "(error) ERR ...". Display keeps a frame on one line, for the logs:
+// ["set", "k", (integer) 1], {"a" => (true)}. `to_pretty_string` numbers the elements of the
+// aggregates over several lines like redis-cli does:
+// 1) "set"
+// 2) 1) (integer) 1
+//    2) (nil)
+impl fmt::Display for RespFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RespFrame::Array(frames) => write_list(f, "[", frames, "]"),
+            RespFrame::Set(frames) => write_list(f, "{", frames, "}"),
+            RespFrame::Push(frames) => write_list(f, "[", frames, "]"),
+            RespFrame::Map(map) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{} => {}", key, value)?;
+                }
+                f.write_char('}')
+            }
+            frame => write_scalar(f, frame),
+        }
+    }
+}
+
+impl RespFrame {
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+        // writing to a String doesn't fail
+        let _ = write_pretty(&mut out, self, "");
+        out
+    }
+}
+
+fn write_list(f: &mut impl Write, open: &str, frames: &[RespFrame], close: &str) -> fmt::Result {
+    f.write_str(open)?;
+    for (i, frame) in frames.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", frame)?;
+    }
+    f.write_str(close)
+}
+
+fn write_scalar(f: &mut impl Write, frame: &RespFrame) -> fmt::Result {
+    match frame {
+        RespFrame::SimpleString(s) => f.write_str(s),
+        RespFrame::Error(e) => write!(f, "(error) {}", e.0),
+        RespFrame::Integer(n) => write!(f, "(integer) {}", n),
+        RespFrame::BulkString(s) => write_quoted(f, s),
+        RespFrame::Null(_) => f.write_str("(nil)"),
+        RespFrame::Boolean(b) => write!(f, "({})", b),
+        RespFrame::Double(d) => write!(f, "(double) {}", Double(*d)),
+        RespFrame::BigNumber(n) => write!(f, "(big number) {}", &**n),
+        RespFrame::VerbatimString(s) => write_quoted(f, s.data()),
+        frame => write!(f, "{}", frame),
+    }
+}
+
+// Each element after its number, the lines of a nested aggregate lined up under the first one.
+fn write_pretty(out: &mut String, frame: &RespFrame, indent: &str) -> fmt::Result {
+    let (frames, mark, empty) = match frame {
+        RespFrame::Array(frames) => (&frames[..], ')', "(empty array)"),
+        RespFrame::Push(frames) => (&frames[..], ')', "(empty array)"),
+        RespFrame::Set(frames) => (&frames[..], '~', "(empty set)"),
+        RespFrame::Map(map) if map.is_empty() => return out.write_str("(empty hash)"),
+        RespFrame::Map(map) => {
+            let width = map.len().to_string().len();
+            let nested = format!("{}{}", indent, " ".repeat(width + 2));
+            for (i, (key, value)) in map.iter().enumerate() {
+                if i > 0 {
+                    write!(out, "\n{}", indent)?;
+                }
+                write!(out, "{:>width$}# ", i + 1)?;
+                write_pretty(out, key, &nested)?;
+                out.write_str(" => ")?;
+                write_pretty(out, value, &nested)?;
+            }
+            return Ok(());
+        }
+        frame => return write_scalar(out, frame),
+    };
+    if frames.is_empty() {
+        return out.write_str(empty);
+    }
+    let width = frames.len().to_string().len();
+    let nested = format!("{}{}", indent, " ".repeat(width + 2));
+    for (i, frame) in frames.iter().enumerate() {
+        if i > 0 {
+            write!(out, "\n{}", indent)?;
+        }
+        write!(out, "{:>width$}{} ", i + 1, mark)?;
+        write_pretty(out, frame, &nested)?;
+    }
+    Ok(())
+}
+
+// Quotes a string the way redis-cli shows it, non printable bytes are escaped as \xNN.
+pub(crate) fn write_quoted(f: &mut impl Write, s: &[u8]) -> fmt::Result {
+    f.write_char('"')?;
+    for &b in s {
+        match b {
+            b'\\' => f.write_str("\\\\")?,
+            b'"' => f.write_str("\\\"")?,
+            b'\n' => f.write_str("\\n")?,
+            b'\r' => f.write_str("\\r")?,
+            b'\t' => f.write_str("\\t")?,
+            0x07 => f.write_str("\\a")?,
+            0x08 => f.write_str("\\b")?,
+            b if b.is_ascii_graphic() || b == b' ' => f.write_char(b as char)?,
+            b => write!(f, "\\x{:02x}", b)?,
+        }
+    }
+    f.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespArray, RespMap, SimpleError};
+
+    #[test]
+    fn test_display_frames() {
+        let frame: RespFrame = RespArray::new([
+            BulkString::from("set").into(),
+            BulkString::from(b"a\"\x01").into(),
+            RespFrame::Integer(1),
+            RespFrame::null(),
+        ])
+        .into();
+        assert_eq!(
+            frame.to_string(),
+            r#"["set", "a\"\x01", (integer) 1, (nil)]"#
+        );
+        let frame: RespFrame = SimpleError::new("ERR bad").into();
+        assert_eq!(frame.to_string(), "(error) ERR bad");
+    }
+
+    #[test]
+    fn test_pretty_print_like_redis_cli() {
+        let mut nested: Vec<RespFrame> = (0..10).map(RespFrame::Integer).collect();
+        nested[1] = RespArray::new([BulkString::from("x").into(), true.into()]).into();
+        let mut map = RespMap::new();
+        map.insert(BulkString::from("k"), RespArray::new([]));
+        let frame: RespFrame = RespArray::new([
+            BulkString::from("a").into(),
+            RespArray::new(nested).into(),
+            map.into(),
+        ])
+        .into();
+        let expected = [
+            r#"1) "a""#,
+            r#"2)  1) (integer) 0"#,
+            r#"    2) 1) "x""#,
+            r#"       2) (true)"#,
+            r#"    3) (integer) 2"#,
+        ];
+        let pretty = frame.to_pretty_string();
+        let lines: Vec<&str> = pretty.lines().collect();
+        assert_eq!(lines[..5], expected);
+        assert_eq!(lines[11], r#"   10) (integer) 9"#);
+        assert_eq!(lines[12], r#"3) 1# "k" => (empty array)"#);
+    }
+}
@@ -1,19 +1,43 @@
 use bytes::BytesMut;
+use std::fmt;
 
-use super::{extract_simple_frame_data, RespDecoder, RespEncoder, RespError, CRLF_LEN};
+use super::{
+    extract_simple_frame_data, fmt_len, put_fmt, RespDecoder, RespEncoder, RespError, CRLF_LEN,
+};
+
+// A double written like redis does with "%.17g", in the fewest digits that read back the same
+// number: "1.5", "1e+20", "1.5e-05", and "inf", "-inf" or "nan".
+pub(crate) struct Double(pub f64);
+
+impl fmt::Display for Double {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let d = self.0;
+        if d.is_nan() {
+            return f.write_str("nan");
+        }
+        if d.is_infinite() {
+            return f.write_str(if d > 0.0 { "inf" } else { "-inf" });
+        }
+        let scientific = format!("{:e}", d);
+        let (mantissa, exp) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+        let exp: i32 = exp.parse().unwrap_or_default();
+        match exp {
+            -4..=16 => write!(f, "{}", d),
+            exp if exp < 0 => write!(f, "{}e-{:02}", mantissa, -exp),
+            exp => write!(f, "{}e+{:02}", mantissa, exp),
+        }
+    }
+}
 
 // - double: ",[<+|->]<integral>[.<fractional>][<E|e>[sign]<exponent>]\r\n"
+// - ",inf\r\n", ",-inf\r\n" and ",nan\r\n"
 impl RespEncoder for f64 {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(32);
-        let ret = if self.abs() > 1e+8 || self.abs() < 1e-8 {
-            format!(",{:+e}\r\n", self)
-        } else {
-            format!(",{}\r\n", self)
-        };
-
-        buf.extend_from_slice(&ret.into_bytes());
-        buf
+    fn encode(&self, buf: &mut BytesMut) {
+        put_fmt(buf, format_args!(",{}\r\n", Double(*self)));
+    }
+
+    fn encoded_len(&self) -> usize {
+        fmt_len(format_args!(",{}\r\n", Double(*self)))
     }
 }
 
@@ -24,6 +48,7 @@ impl RespDecoder for f64 {
         let end = extract_simple_frame_data(buf, Self::PREFIX)?;
         let data = buf.split_to(end + CRLF_LEN);
         let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
+        // "inf", "-inf" and "nan" are read by parse too
         Ok(s.parse()?)
     }
 
@@ -42,19 +67,33 @@ mod tests {
     #[test]
     fn test_encode_positive_double() {
         let frame: RespFrame = 123.456.into();
-        assert_eq!(frame.encode(), b",123.456\r\n");
+        assert_eq!(frame.encode_to_vec(), b",123.456\r\n");
 
         let frame: RespFrame = 1.23456e+8.into();
-        assert_eq!(frame.encode(), b",+1.23456e8\r\n");
+        assert_eq!(frame.encode_to_vec(), b",123456000\r\n");
+
+        let frame: RespFrame = 1.5e+20.into();
+        assert_eq!(frame.encode_to_vec(), b",1.5e+20\r\n");
+    }
+
+    #[test]
+    fn test_encode_special_doubles() {
+        for (d, expected) in [
+            (f64::INFINITY, ",inf\r\n"),
+            (f64::NEG_INFINITY, ",-inf\r\n"),
+            (f64::NAN, ",nan\r\n"),
+        ] {
+            assert_eq!(RespFrame::Double(d).encode_to_vec(), expected.as_bytes());
+        }
     }
 
     #[test]
     fn test_encode_negative_double() {
         let frame: RespFrame = (-123.456).into();
-        assert_eq!(frame.encode(), b",-123.456\r\n");
+        assert_eq!(frame.encode_to_vec(), b",-123.456\r\n");
 
         let frame: RespFrame = (-1.23456e-9).into();
-        assert_eq!(&frame.encode(), b",-1.23456e-9\r\n");
+        assert_eq!(&frame.encode_to_vec(), b",-1.23456e-09\r\n");
     }
 
     #[test]
@@ -69,6 +108,14 @@ mod tests {
         let frame = f64::decode(&mut buf)?;
         assert_eq!(frame, 1.23456e-9);
 
+        buf.extend_from_slice(b",inf\r\n,-inf\r\n,nan\r\n,1.5e-05\r\n");
+        assert_eq!(f64::decode(&mut buf)?, f64::INFINITY);
+        assert_eq!(f64::decode(&mut buf)?, f64::NEG_INFINITY);
+        assert!(f64::decode(&mut buf)?.is_nan());
+        let d = f64::decode(&mut buf)?;
+        assert_eq!(d, 1.5e-5);
+        assert_eq!(Double(d).to_string(), "1.5e-05");
+
         Ok(())
     }
 }
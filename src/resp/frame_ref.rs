@@ -0,0 +1,335 @@
+use std::{borrow::Cow, fmt};
+
+use super::{
+    big_number, display::write_quoted, double::Double, parse_length, streamed, BigNumber,
+    BulkString, RespArray, RespDecoder, RespError, RespFrame, RespMap, RespPush, RespSet,
+    SimpleError, SimpleString, VerbatimString, CRLF_LEN,
+};
+
+// A frame read in place: its strings are slices of the buffer it was decoded from and its
+// aggregates are read element by element as they're iterated, nothing is allocated. It's for
+// looking at a frame, like the name of a command, before or instead of taking it apart. Only the
+// chunks of a streamed string are copied, to be put together. `to_frame` makes an owned frame of
+// it when one is needed after all.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RespFrameRef<'a> {
+    SimpleString(&'a [u8]),
+    Error(&'a [u8]),
+    Integer(i64),
+    BulkString(Cow<'a, [u8]>),
+    Array(FramesRef<'a>),
+    Null,
+    Boolean(bool),
+    Double(f64),
+    // the keys and the values one after the other
+    Map(FramesRef<'a>),
+    Set(FramesRef<'a>),
+    Push(FramesRef<'a>),
+    BigNumber(&'a str),
+    VerbatimString { format: &'a [u8], data: &'a [u8] },
+}
+
+// The elements of an aggregate, still in the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramesRef<'a> {
+    len: usize,
+    buf: &'a [u8],
+}
+
+impl<'a> RespFrameRef<'a> {
+    // The frame at the start of the buffer and its length. The whole frame is checked, so its
+    // elements can be iterated without errors.
+    pub fn decode(buf: &'a [u8]) -> Result<(Self, usize), RespError> {
+        let total = RespFrame::expect_length(buf)?;
+        let buf = &buf[..total];
+        if streamed::is_streamed(buf) {
+            return Ok((decode_streamed(buf)?, total));
+        }
+        // the line of a simple frame, without its prefix and CRLF
+        let line = &buf[1..total - CRLF_LEN];
+        let frame = match buf[0] {
+            b'+' => RespFrameRef::SimpleString(line),
+            b'-' => RespFrameRef::Error(line),
+            b':' => RespFrameRef::Integer(std::str::from_utf8(line)?.parse()?),
+            b',' => RespFrameRef::Double(std::str::from_utf8(line)?.parse()?),
+            b'_' | b'#' => match buf {
+                b"_\r\n" => RespFrameRef::Null,
+                b"#t\r\n" => RespFrameRef::Boolean(true),
+                b"#f\r\n" => RespFrameRef::Boolean(false),
+                _ => {
+                    return Err(RespError::InvalidFrameType(format!(
+                        "expect: Null or Bool, got: {:?}",
+                        buf
+                    )))
+                }
+            },
+            b'(' => {
+                let n = std::str::from_utf8(line)?;
+                big_number::check(n)?;
+                RespFrameRef::BigNumber(n)
+            }
+            b'$' | b'*' if buf == b"$-1\r\n" || buf == b"*-1\r\n" => RespFrameRef::Null,
+            b'$' => {
+                let (end, len) = parse_length(buf, "$")?;
+                let start = end + CRLF_LEN;
+                RespFrameRef::BulkString(Cow::Borrowed(&buf[start..start + len]))
+            }
+            b'=' => {
+                let (end, _) = parse_length(buf, "=")?;
+                let text = &buf[end + CRLF_LEN..total - CRLF_LEN];
+                // "<format>:<data>"
+                if text.len() < 4 || text[3] != b':' {
+                    return Err(RespError::InvalidFrame(format!(
+                        "verbatim string without a format: {:?}",
+                        String::from_utf8_lossy(text)
+                    )));
+                }
+                RespFrameRef::VerbatimString {
+                    format: &text[..3],
+                    data: &text[4..],
+                }
+            }
+            prefix @ (b'*' | b'~' | b'>' | b'%') => {
+                let (end, len) = parse_length(buf, std::str::from_utf8(&buf[..1])?)?;
+                let len = if prefix == b'%' { len * 2 } else { len };
+                let frames = FramesRef::check(&buf[end + CRLF_LEN..], len)?;
+                match prefix {
+                    b'*' => RespFrameRef::Array(frames),
+                    b'~' => RespFrameRef::Set(frames),
+                    b'>' => RespFrameRef::Push(frames),
+                    _ => RespFrameRef::Map(frames),
+                }
+            }
+            prefix => {
+                return Err(RespError::InvalidFrameType(format!(
+                    "expected a frame, got '{}'",
+                    prefix as char
+                )))
+            }
+        };
+        Ok((frame, total))
+    }
+
+    // the bytes of a string frame
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            RespFrameRef::SimpleString(s) | RespFrameRef::Error(s) => Some(s),
+            RespFrameRef::BulkString(s) => Some(s),
+            RespFrameRef::BigNumber(n) => Some(n.as_bytes()),
+            RespFrameRef::VerbatimString { data, .. } => Some(data),
+            _ => None,
+        }
+    }
+
+    // the elements of an aggregate
+    pub fn frames(&self) -> Option<FramesRef<'a>> {
+        match self {
+            RespFrameRef::Array(frames)
+            | RespFrameRef::Map(frames)
+            | RespFrameRef::Set(frames)
+            | RespFrameRef::Push(frames) => Some(*frames),
+            _ => None,
+        }
+    }
+
+    // the owned frame this one reads as
+    pub fn to_frame(&self) -> RespFrame {
+        let frames = |frames: &FramesRef| -> Vec<RespFrame> {
+            frames.iter().map(|frame| frame.to_frame()).collect()
+        };
+        match self {
+            RespFrameRef::SimpleString(s) => {
+                SimpleString::new(String::from_utf8_lossy(s).into_owned()).into()
+            }
+            RespFrameRef::Error(e) => {
+                SimpleError::new(String::from_utf8_lossy(e).into_owned()).into()
+            }
+            RespFrameRef::Integer(n) => RespFrame::Integer(*n),
+            RespFrameRef::BulkString(s) => BulkString::from(&s[..]).into(),
+            RespFrameRef::Array(elements) => RespArray::new(frames(elements)).into(),
+            RespFrameRef::Null => RespFrame::null(),
+            RespFrameRef::Boolean(b) => RespFrame::Boolean(*b),
+            RespFrameRef::Double(d) => RespFrame::Double(*d),
+            RespFrameRef::Map(elements) => {
+                let mut frames = frames(elements).into_iter();
+                let mut map = RespMap::new();
+                while let (Some(key), Some(value)) = (frames.next(), frames.next()) {
+                    map.0.push((key, value));
+                }
+                map.into()
+            }
+            RespFrameRef::Set(elements) => RespSet::new(frames(elements)).into(),
+            RespFrameRef::Push(elements) => RespPush::new(frames(elements)).into(),
+            RespFrameRef::BigNumber(n) => BigNumber(n.to_string()).into(),
+            RespFrameRef::VerbatimString { format, data } => {
+                VerbatimString::new([format[0], format[1], format[2]], *data).into()
+            }
+        }
+    }
+}
+
+// The same line as the owned frame shows, for the logs or MONITOR.
+impl fmt::Display for RespFrameRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter<'_>, open, frames: &FramesRef, close, sep| {
+            f.write_str(open)?;
+            for (i, frame) in frames.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(if i % 2 == 1 { sep } else { ", " })?;
+                }
+                write!(f, "{}", frame)?;
+            }
+            f.write_str(close)
+        };
+        match self {
+            RespFrameRef::SimpleString(s) => f.write_str(&String::from_utf8_lossy(s)),
+            RespFrameRef::Error(e) => write!(f, "(error) {}", String::from_utf8_lossy(e)),
+            RespFrameRef::Integer(n) => write!(f, "(integer) {}", n),
+            RespFrameRef::BulkString(s) => write_quoted(f, s),
+            RespFrameRef::Array(frames) | RespFrameRef::Push(frames) => {
+                list(f, "[", frames, "]", ", ")
+            }
+            RespFrameRef::Null => f.write_str("(nil)"),
+            RespFrameRef::Boolean(b) => write!(f, "({})", b),
+            RespFrameRef::Double(d) => write!(f, "(double) {}", Double(*d)),
+            RespFrameRef::Map(frames) => list(f, "{", frames, "}", " => "),
+            RespFrameRef::Set(frames) => list(f, "{", frames, "}", ", "),
+            RespFrameRef::BigNumber(n) => write!(f, "(big number) {}", n),
+            RespFrameRef::VerbatimString { data, .. } => write_quoted(f, data),
+        }
+    }
+}
+
+impl<'a> FramesRef<'a> {
+    // the elements at the start of the buffer, which holds them all
+    fn check(buf: &'a [u8], len: usize) -> Result<Self, RespError> {
+        let mut total = 0;
+        for _ in 0..len {
+            total += RespFrameRef::decode(&buf[total..])?.1;
+        }
+        Ok(FramesRef {
+            len,
+            buf: &buf[..total],
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> FramesIter<'a> {
+        FramesIter { buf: self.buf }
+    }
+}
+
+impl<'a> IntoIterator for FramesRef<'a> {
+    type Item = RespFrameRef<'a>;
+    type IntoIter = FramesIter<'a>;
+
+    fn into_iter(self) -> FramesIter<'a> {
+        self.iter()
+    }
+}
+
+pub struct FramesIter<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Iterator for FramesIter<'a> {
+    type Item = RespFrameRef<'a>;
+
+    fn next(&mut self) -> Option<RespFrameRef<'a>> {
+        // the elements were checked when the aggregate was decoded
+        let (frame, len) = RespFrameRef::decode(self.buf).ok()?;
+        self.buf = &self.buf[len..];
+        Some(frame)
+    }
+}
+
+// A streamed string is put together from its chunks, a streamed aggregate is read like the others
+// up to its end.
+fn decode_streamed(buf: &[u8]) -> Result<RespFrameRef<'_>, RespError> {
+    let body = &buf[streamed::HEADER_LEN..];
+    if buf[0] == b'$' {
+        let mut data = Vec::new();
+        let mut rest = body;
+        loop {
+            let (end, len) = parse_length(rest, ";")?;
+            rest = &rest[end + CRLF_LEN..];
+            if len == 0 {
+                return Ok(RespFrameRef::BulkString(Cow::Owned(data)));
+            }
+            data.extend_from_slice(&rest[..len]);
+            rest = &rest[len + CRLF_LEN..];
+        }
+    }
+    // the body ends with ".\r\n"
+    let elements = &body[..body.len() - 3];
+    let mut len = 0;
+    let mut total = 0;
+    while total < elements.len() {
+        total += RespFrameRef::decode(&elements[total..])?.1;
+        len += 1;
+    }
+    let frames = FramesRef { len, buf: elements };
+    Ok(match buf[0] {
+        b'*' => RespFrameRef::Array(frames),
+        b'~' => RespFrameRef::Set(frames),
+        _ => RespFrameRef::Map(frames),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_sniff_command_name_in_place() -> Result<()> {
+        let buf = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n:1\r\n";
+        let (frame, len) = RespFrameRef::decode(buf)?;
+        assert_eq!(len, buf.len() - 4);
+        let args = frame.frames().unwrap();
+        assert_eq!(args.len(), 3);
+        let name = args.iter().next().unwrap();
+        assert!(matches!(
+            name,
+            RespFrameRef::BulkString(Cow::Borrowed(b"SET"))
+        ));
+        assert_eq!(frame.to_string(), r#"["SET", "k", "v"]"#);
+
+        assert_eq!(
+            RespFrameRef::decode(&buf[..10]),
+            Err(RespError::NotComplete)
+        );
+        assert!(RespFrameRef::decode(b"*1\r\n#x\r\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_frame_matches_owned_decode() -> Result<()> {
+        let frames: [&[u8]; 8] = [
+            b"+OK\r\n",
+            b"$-1\r\n",
+            b"*2\r\n_\r\n,1.5\r\n",
+            b"%1\r\n+a\r\n~2\r\n#t\r\n(12345678901234567890\r\n",
+            b">2\r\n-ERR x\r\n=8\r\ntxt:some\r\n",
+            b"$?\r\n;2\r\nab\r\n;1\r\nc\r\n;0\r\n",
+            b"%?\r\n+k\r\n*?\r\n:1\r\n.\r\n.\r\n",
+            b"*-1\r\n",
+        ];
+        for buf in frames {
+            let (frame, len) = RespFrameRef::decode(buf)?;
+            assert_eq!(len, buf.len());
+            let owned = RespFrame::decode(&mut BytesMut::from(buf))?;
+            assert_eq!(frame.to_frame(), owned);
+            assert_eq!(frame.to_string(), owned.to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use bytes::{Buf, BytesMut};
+
+use super::{BulkString, RespArray, RespError};
+
+// the first bytes of the RESP frames, any other starts an inline command
+const FRAME_PREFIXES: &[u8] = b"+-:$*_#,%~>(=";
+// like redis, a line longer than this is refused before its end is seen
+const INLINE_MAX_SIZE: usize = 64 * 1024;
+
+// - inline command: "SET foo \"hello world\"\r\n", as typed in telnet
+impl RespArray {
+    // Whether the buffer starts with an inline command rather than a RESP frame.
+    pub fn is_inline(buf: &[u8]) -> bool {
+        buf.first()
+            .is_some_and(|prefix| !FRAME_PREFIXES.contains(prefix))
+    }
+
+    // Splits a line into its arguments like redis-cli does, quotes may enclose spaces. The line
+    // ends with "\r\n" or a bare "\n", an empty line decodes to an empty array.
+    pub fn decode_inline(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let Some(end) = buf.iter().position(|&b| b == b'\n') else {
+            if buf.len() > INLINE_MAX_SIZE {
+                return Err(RespError::InvalidFrame(
+                    "too big inline request".to_string(),
+                ));
+            }
+            return Err(RespError::NotComplete);
+        };
+        let line = buf.split_to(end + 1);
+        let line = line.strip_suffix(b"\r\n").unwrap_or(&line[..end]);
+        let args = split_args(line)?;
+        Ok(RespArray::new(
+            args.into_iter()
+                .map(|arg| BulkString::new(arg).into())
+                .collect::<Vec<_>>(),
+        ))
+    }
+}
+
+fn split_args(mut line: &[u8]) -> Result<Vec<Vec<u8>>, RespError> {
+    let unbalanced = || RespError::InvalidFrame("unbalanced quotes in request".to_string());
+    let mut args = vec![];
+    loop {
+        while line.first().is_some_and(u8::is_ascii_whitespace) {
+            line.advance(1);
+        }
+        let Some(&first) = line.first() else {
+            return Ok(args);
+        };
+        let mut arg = vec![];
+        match first {
+            b'"' => {
+                line.advance(1);
+                loop {
+                    match line {
+                        [b'\\', b'x', hi, lo, ..]
+                            if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() =>
+                        {
+                            let hex = std::str::from_utf8(&line[2..4]).unwrap_or_default();
+                            arg.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+                            line.advance(4);
+                        }
+                        [b'\\', c, ..] => {
+                            arg.push(match c {
+                                b'n' => b'\n',
+                                b'r' => b'\r',
+                                b't' => b'\t',
+                                b'b' => 0x08,
+                                b'a' => 0x07,
+                                c => *c,
+                            });
+                            line.advance(2);
+                        }
+                        [b'"', ..] => {
+                            line.advance(1);
+                            break;
+                        }
+                        [c, ..] => {
+                            arg.push(*c);
+                            line.advance(1);
+                        }
+                        [] => return Err(unbalanced()),
+                    }
+                }
+            }
+            b'\'' => {
+                line.advance(1);
+                loop {
+                    match line {
+                        [b'\\', b'\'', ..] => {
+                            arg.push(b'\'');
+                            line.advance(2);
+                        }
+                        [b'\'', ..] => {
+                            line.advance(1);
+                            break;
+                        }
+                        [c, ..] => {
+                            arg.push(*c);
+                            line.advance(1);
+                        }
+                        [] => return Err(unbalanced()),
+                    }
+                }
+            }
+            _ => {
+                while let Some(&c) = line.first().filter(|c| !c.is_ascii_whitespace()) {
+                    arg.push(c);
+                    line.advance(1);
+                }
+            }
+        }
+        // a closing quote must end the argument
+        if line.first().is_some_and(|c| !c.is_ascii_whitespace()) {
+            return Err(unbalanced());
+        }
+        args.push(arg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_decode_inline() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"SET foo \"hello \\\"world\\\"\\x21\"\r\nGET 'it\\'s'\nPI");
+        assert!(RespArray::is_inline(&buf));
+
+        let frame = RespArray::decode_inline(&mut buf)?;
+        assert_eq!(
+            frame,
+            RespArray::new([b"SET".into(), b"foo".into(), b"hello \"world\"!".into()])
+        );
+        let frame = RespArray::decode_inline(&mut buf)?;
+        assert_eq!(frame, RespArray::new([b"GET".into(), b"it's".into()]));
+
+        let ret = RespArray::decode_inline(&mut buf);
+        assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+        buf.extend_from_slice(b"NG\r\n\r\n");
+        let frame = RespArray::decode_inline(&mut buf)?;
+        assert_eq!(frame, RespArray::new([b"PING".into()]));
+        assert!(RespArray::decode_inline(&mut buf)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_inline_unbalanced_quotes() {
+        let mut buf = BytesMut::from(&b"SET \"foo\"bar x\r\n"[..]);
+        let ret = RespArray::decode_inline(&mut buf);
+        assert_eq!(
+            ret.unwrap_err(),
+            RespError::InvalidFrame("unbalanced quotes in request".to_string())
+        );
+        assert!(!RespArray::is_inline(b"*1\r\n$4\r\nPING\r\n"));
+    }
+}
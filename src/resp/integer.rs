@@ -1,11 +1,18 @@
 use bytes::BytesMut;
 
-use super::{extract_simple_frame_data, RespDecoder, RespEncoder, RespError, CRLF_LEN};
+use super::{
+    digits, extract_simple_frame_data, put_fmt, RespDecoder, RespEncoder, RespError, CRLF_LEN,
+};
 
 // - integer: ":[<+|->]<value>\r\n"
 impl RespEncoder for i64 {
-    fn encode(self) -> Vec<u8> {
-        format!(":{}\r\n", self).into_bytes()
+    fn encode(&self, buf: &mut BytesMut) {
+        put_fmt(buf, format_args!(":{}\r\n", self));
+    }
+
+    fn encoded_len(&self) -> usize {
+        let sign = usize::from(*self < 0);
+        1 + sign + digits(self.unsigned_abs()) + CRLF_LEN
     }
 }
 
@@ -34,13 +41,13 @@ mod tests {
     #[test]
     fn test_encode_positive_integer() {
         let frame: RespFrame = 123.into();
-        assert_eq!(frame.encode(), b":123\r\n");
+        assert_eq!(frame.encode_to_vec(), b":123\r\n");
     }
 
     #[test]
     fn test_encode_negative_integer() {
         let frame: RespFrame = (-123).into();
-        assert_eq!(frame.encode(), b":-123\r\n");
+        assert_eq!(frame.encode_to_vec(), b":-123\r\n");
     }
 
     #[test]
@@ -0,0 +1,115 @@
+use super::{find_crlf, scanner::FrameScanner, RespError, CRLF_LEN};
+
+// a length header without its CRLF past this size is not waited for, like in redis
+const HEADER_MAX_SIZE: usize = 64 * 1024;
+
+// Caps on what the headers of a frame may declare. They are checked as soon as a header is read,
+// before the rest of the frame is, so a huge length can't make the read buffer grow without
+// bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    // the length of a bulk or verbatim string
+    pub max_bulk_len: usize,
+    // the elements of an array, a set or a push, the entries of a map
+    pub max_aggregate_len: usize,
+    // how deep aggregates may be nested in each other
+    pub max_depth: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_aggregate_len: i32::MAX as usize,
+            max_depth: 128,
+        }
+    }
+}
+
+impl DecodeLimits {
+    // Checks the headers of the frame at the start of the buffer, as far as it was read.
+    pub fn check(&self, buf: &[u8]) -> Result<(), RespError> {
+        FrameScanner::default().scan(buf, self).map(|_| ())
+    }
+}
+
+// (the length of the header with its CRLF, the length it declares or None for a null)
+pub(super) fn header(buf: &[u8], kind: &str) -> Result<(usize, Option<usize>), RespError> {
+    let end = match find_crlf(buf) {
+        Ok(end) => end,
+        Err(RespError::NotComplete) if buf.len() > HEADER_MAX_SIZE => {
+            return Err(RespError::InvalidFrame(format!(
+                "too big {} count string",
+                kind
+            )));
+        }
+        Err(e) => return Err(e),
+    };
+    let len = buf
+        .get(1..end)
+        .and_then(|len| std::str::from_utf8(len).ok())
+        .and_then(|len| len.parse::<i64>().ok())
+        .ok_or_else(|| RespError::InvalidFrame(format!("invalid {} length", kind)))?;
+    match len {
+        -1 => Ok((end + CRLF_LEN, None)),
+        len if len < 0 => Err(RespError::InvalidFrame(format!("invalid {} length", kind))),
+        len => Ok((end + CRLF_LEN, Some(len as usize))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_huge_headers_are_refused() {
+        let limits = DecodeLimits::default();
+        // refused before the data they announce arrives
+        for (input, error) in [
+            ("*4294967295\r\n", "invalid multibulk length"),
+            ("$9999999999\r\n", "invalid bulk length"),
+            ("*1\r\n$9999999999\r\n", "invalid bulk length"),
+            ("%1\r\n+key\r\n=9999999999\r\n", "invalid bulk length"),
+        ] {
+            assert_eq!(
+                limits.check(input.as_bytes()),
+                Err(RespError::InvalidFrame(error.to_string())),
+                "{:?}",
+                input
+            );
+        }
+        let header = format!("*{}", "1".repeat(HEADER_MAX_SIZE));
+        assert!(limits.check(header.as_bytes()).is_err());
+
+        // a frame not read yet, or with nulls, is fine
+        for input in ["*2\r\n$3\r\nget\r\n$10\r\nab", "*2\r\n$-1\r\n*-1\r\n", "*"] {
+            assert_eq!(limits.check(input.as_bytes()), Ok(()), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_limits_are_configurable() {
+        let limits = DecodeLimits {
+            max_bulk_len: 4,
+            max_aggregate_len: 2,
+            max_depth: 2,
+        };
+        assert!(limits.check(b"*2\r\n$4\r\nabcd\r\n*1\r\n:1\r\n").is_ok());
+        assert!(limits.check(b"$5\r\n").is_err());
+        assert!(limits.check(b"*3\r\n").is_err());
+        assert_eq!(
+            limits.check(b"*1\r\n*1\r\n*1\r\n"),
+            Err(RespError::InvalidFrame(
+                "too deeply nested aggregate".to_string()
+            ))
+        );
+
+        // streamed ones are counted as they are read
+        assert!(limits
+            .check(b"*?\r\n$?\r\n;2\r\nab\r\n;2\r\ncd\r\n;0\r\n.\r\n")
+            .is_ok());
+        assert!(limits.check(b"$?\r\n;4\r\nabcd\r\n;1\r\n").is_err());
+        assert!(limits.check(b"~?\r\n:1\r\n:2\r\n:3\r\n").is_err());
+        assert!(limits.check(b"*?\r\n*?\r\n*?\r\n").is_err());
+    }
+}
@@ -1,31 +1,60 @@
 use bytes::{Buf, BytesMut};
-use std::collections::BTreeMap;
-use std::ops::{Deref, DerefMut};
+use std::mem;
+use std::ops::Deref;
 
 use super::{
-    calc_total_length, parse_length, RespDecoder, RespEncoder, RespError, RespFrame, SimpleString,
-    BUFFER_CAP, CRLF_LEN,
+    calc_total_length, header_len, parse_length, put_fmt, RespDecoder, RespEncoder, RespError,
+    RespFrame, CRLF_LEN,
 };
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub struct RespMap(pub(crate) BTreeMap<String, RespFrame>);
+// The entries in the order they were inserted, with keys of any type like in RESP3: CONFIG GET
+// replies bulk strings, a client may send integers. Inserting a key again replaces its value.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
+pub struct RespMap(pub(crate) Vec<(RespFrame, RespFrame)>);
 
 impl RespMap {
     pub fn new() -> Self {
-        RespMap(BTreeMap::new())
+        RespMap(Vec::new())
+    }
+
+    // the value the key had
+    pub fn insert(
+        &mut self,
+        key: impl Into<RespFrame>,
+        value: impl Into<RespFrame>,
+    ) -> Option<RespFrame> {
+        let (key, value) = (key.into(), value.into());
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => Some(mem::replace(v, value)),
+            None => {
+                self.0.push((key, value));
+                None
+            }
+        }
+    }
+
+    pub fn get(&self, key: &RespFrame) -> Option<&RespFrame> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v)
     }
 }
 
 // - map: "%<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>"
 impl RespEncoder for RespMap {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(BUFFER_CAP);
-        buf.extend_from_slice(&format!("%{}\r\n", self.len()).into_bytes());
-        for (key, value) in self.0 {
-            buf.extend_from_slice(&SimpleString::new(key).encode());
-            buf.extend_from_slice(&value.encode());
+    fn encode(&self, buf: &mut BytesMut) {
+        put_fmt(buf, format_args!("%{}\r\n", self.len()));
+        for (key, value) in &self.0 {
+            key.encode(buf);
+            value.encode(buf);
         }
-        buf
+    }
+
+    fn encoded_len(&self) -> usize {
+        let entries: usize = self
+            .0
+            .iter()
+            .map(|(key, value)| key.encoded_len() + value.encoded_len())
+            .sum();
+        header_len(self.len()) + entries
     }
 }
 
@@ -42,14 +71,15 @@ impl RespDecoder for RespMap {
 
         buf.advance(end + CRLF_LEN);
 
-        let mut frames = RespMap::new();
+        // the entries are kept as they were sent
+        let mut entries = Vec::with_capacity(len);
         for _ in 0..len {
-            let key = SimpleString::decode(buf)?;
+            let key = RespFrame::decode(buf)?;
             let value = RespFrame::decode(buf)?;
-            frames.insert(key.0, value);
+            entries.push((key, value));
         }
 
-        Ok(frames)
+        Ok(RespMap(entries))
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
@@ -59,22 +89,20 @@ impl RespDecoder for RespMap {
 }
 
 impl Deref for RespMap {
-    type Target = BTreeMap<String, RespFrame>;
+    type Target = [(RespFrame, RespFrame)];
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-impl DerefMut for RespMap {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-
-impl Default for RespMap {
-    fn default() -> Self {
-        Self::new()
+impl<K: Into<RespFrame>, V: Into<RespFrame>> FromIterator<(K, V)> for RespMap {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = RespMap::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
     }
 }
 
@@ -87,32 +115,36 @@ mod tests {
     #[test]
     fn test_encode_map() {
         let mut map = RespMap::new();
-        map.insert(
-            "hello".to_string(),
-            BulkString::new("world".to_string()).into(),
-        );
-        map.insert("foo".to_string(), (-123456.789).into());
+        map.insert(BulkString::from("hello"), BulkString::from("world"));
+        map.insert("foo", -123456.789);
+        map.insert(RespFrame::Integer(1), true);
 
         let frame: RespFrame = map.into();
         assert_eq!(
-            String::from_utf8_lossy(&frame.encode()),
-            "%2\r\n+foo\r\n,-123456.789\r\n+hello\r\n$5\r\nworld\r\n"
+            String::from_utf8_lossy(&frame.encode_to_vec()),
+            "%3\r\n$5\r\nhello\r\n$5\r\nworld\r\n+foo\r\n,-123456.789\r\n:1\r\n#t\r\n"
         );
     }
 
     #[test]
     fn test_map_decode() -> Result<()> {
         let mut buf = BytesMut::new();
-        buf.extend_from_slice(b"%2\r\n+hello\r\n$5\r\nworld\r\n+foo\r\n$3\r\nbar\r\n");
+        buf.extend_from_slice(b"%2\r\n$5\r\nhello\r\n$5\r\nworld\r\n:7\r\n$3\r\nbar\r\n");
 
         let frame = RespMap::decode(&mut buf)?;
         let mut map = RespMap::new();
-        map.insert(
-            "hello".to_string(),
-            BulkString::new(b"world".to_vec()).into(),
-        );
-        map.insert("foo".to_string(), BulkString::new(b"bar".to_vec()).into());
+        map.insert(BulkString::from("hello"), BulkString::from("world"));
+        map.insert(RespFrame::Integer(7), BulkString::from("bar"));
         assert_eq!(frame, map);
+        assert_eq!(
+            map.get(&RespFrame::Integer(7)),
+            Some(&BulkString::from("bar").into())
+        );
+
+        // a key inserted again keeps its place
+        assert!(map.insert(BulkString::from("hello"), "again").is_some());
+        assert_eq!(map[0].1, RespFrame::from("again"));
+        assert_eq!(map.len(), 2);
 
         Ok(())
     }
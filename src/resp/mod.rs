@@ -14,36 +14,81 @@
     - double: ",[<+|->]<integral>[.<fractional>][<E|e>[sign]<exponent>]\r\n"
     - map: "%<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>"
     - set: "~<number-of-elements>\r\n<element-1>...<element-n>"
+    - push: "><number-of-elements>\r\n<element-1>...<element-n>"
+    - big number: "([+|-]<number>\r\n"
+    - verbatim string: "=<length>\r\n<format>:<data>\r\n"
+    - streamed string: "$?\r\n;<length>\r\n<data>\r\n...;0\r\n"
+    - streamed array, set or map: "*?\r\n<element-1>...<element-n>.\r\n"
  */
 
 mod array;
+mod big_number;
 mod bool;
 mod bulk_string;
+mod codec;
+mod convert;
+mod display;
 mod double;
 mod frame;
+mod frame_ref;
+mod inline;
 mod integer;
+mod limits;
 mod map;
 mod null;
+mod push;
+mod scanner;
+mod serialize;
 mod set;
 mod simple_error;
 mod simple_string;
+mod streamed;
+mod verbatim_string;
 
 use bytes::{Buf, BytesMut};
-use enum_dispatch::enum_dispatch;
+use std::{
+    fmt::{self, Write},
+    num::{IntErrorKind, ParseIntError},
+};
 use thiserror::Error;
 
 pub use self::{
-    array::RespArray, bulk_string::BulkString, frame::RespFrame, map::RespMap, null::RespNull,
-    set::RespSet, simple_error::SimpleError, simple_string::SimpleString,
+    array::RespArray,
+    big_number::BigNumber,
+    bulk_string::BulkString,
+    codec::{CodecError, RespCodec},
+    convert::FromFrameError,
+    frame::RespFrame,
+    frame_ref::{FramesIter, FramesRef, RespFrameRef},
+    limits::DecodeLimits,
+    map::RespMap,
+    null::RespNull,
+    push::RespPush,
+    set::RespSet,
+    simple_error::SimpleError,
+    simple_string::SimpleString,
+    streamed::StreamedPart,
+    verbatim_string::VerbatimString,
 };
 
-const BUFFER_CAP: usize = 4096;
+pub(crate) use self::display::write_quoted;
+
 const CRLF: &[u8] = b"\r\n";
 const CRLF_LEN: usize = CRLF.len();
 
-#[enum_dispatch]
+// Frames are written at the end of a buffer, like the write buffer of a connection, and are left
+// as they were so a shared reply can be written again.
 pub trait RespEncoder {
-    fn encode(self) -> Vec<u8>;
+    fn encode(&self, buf: &mut BytesMut);
+
+    // the number of bytes `encode` writes, counted without writing them
+    fn encoded_len(&self) -> usize;
+
+    fn encode_to_vec(&self) -> Vec<u8> {
+        let mut buf = BytesMut::with_capacity(self.encoded_len());
+        self.encode(&mut buf);
+        buf.to_vec()
+    }
 }
 
 pub trait RespDecoder: Sized {
@@ -52,7 +97,7 @@ pub trait RespDecoder: Sized {
     fn expect_length(buf: &[u8]) -> Result<usize, RespError>;
 }
 
-#[derive(Error, Debug, PartialEq, Eq)]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
 pub enum RespError {
     #[error("Invalid frame: {0}")]
     InvalidFrame(String),
@@ -60,6 +105,10 @@ pub enum RespError {
     InvalidFrameType(String),
     #[error("Invalid frame length： {0}")]
     InvalidFrameLength(isize),
+    #[error("Frame length overflows")]
+    LengthOverflow,
+    #[error("CR not followed by LF")]
+    UnexpectedCr,
     #[error("Frame is not complete")]
     NotComplete,
     #[error("Parse error: {0}")]
@@ -70,6 +119,42 @@ pub enum RespError {
     ParseFloatError(#[from] std::num::ParseFloatError),
 }
 
+// formats a header or a number straight into the buffer, which grows as needed
+fn put_fmt(buf: &mut BytesMut, args: fmt::Arguments) {
+    buf.write_fmt(args)
+        .expect("writing to a BytesMut doesn't fail");
+}
+
+// the number of bytes `put_fmt` would write
+fn fmt_len(args: fmt::Arguments) -> usize {
+    struct Counter(usize);
+
+    impl Write for Counter {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    let _ = counter.write_fmt(args);
+    counter.0
+}
+
+fn digits(mut n: u64) -> usize {
+    let mut digits = 1;
+    while n >= 10 {
+        n /= 10;
+        digits += 1;
+    }
+    digits
+}
+
+// "<prefix><length>\r\n"
+fn header_len(len: usize) -> usize {
+    1 + digits(len as u64) + CRLF_LEN
+}
+
 fn extract_fixed_data(
     buf: &mut BytesMut,
     expect: &str,
@@ -102,38 +187,56 @@ fn extract_simple_frame_data(buf: &[u8], prefix: &str) -> Result<usize, RespErro
         )));
     }
 
-    let end = find_crlf(buf, 1).ok_or(RespError::NotComplete)?;
-
-    Ok(end)
+    find_crlf(buf)
 }
 
-// find nth CRLF in the buffer
-fn find_crlf(buf: &[u8], nth: usize) -> Option<usize> {
-    let mut count = 0;
-    for i in 1..buf.len() - 1 {
-        if buf[i] == b'\r' && buf[i + 1] == b'\n' {
-            count += 1;
-            if count == nth {
-                return Some(i);
-            }
-        }
+// The CRLF ending the line of a header or a simple frame, which can't hold a CR of its own.
+fn find_crlf(buf: &[u8]) -> Result<usize, RespError> {
+    match buf.iter().position(|&b| b == b'\r') {
+        Some(i) if i + 1 == buf.len() => Err(RespError::NotComplete),
+        Some(i) if buf[i + 1] == b'\n' => Ok(i),
+        Some(_) => Err(RespError::UnexpectedCr),
+        None => Err(RespError::NotComplete),
     }
-
-    None
 }
 
+// The length a header declares. A negative one is only the null of a bulk string or an array,
+// which their decoders check for before.
 fn parse_length(buf: &[u8], prefix: &str) -> Result<(usize, usize), RespError> {
     let end = extract_simple_frame_data(buf, prefix)?;
-    let s = String::from_utf8_lossy(&buf[prefix.len()..end]);
-    Ok((end, s.parse()?))
+    let s = std::str::from_utf8(&buf[prefix.len()..end])?;
+    let len: i64 = s.parse().map_err(|e: ParseIntError| match e.kind() {
+        IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => RespError::LengthOverflow,
+        _ => e.into(),
+    })?;
+    if len < 0 {
+        return Err(RespError::InvalidFrameLength(len as isize));
+    }
+    let len = usize::try_from(len).map_err(|_| RespError::LengthOverflow)?;
+    Ok((end, len))
+}
+
+// the end of `len` bytes of data and their CRLF after a header ending at `end`
+fn data_end(end: usize, len: usize) -> Result<usize, RespError> {
+    end.checked_add(CRLF_LEN + CRLF_LEN)
+        .and_then(|total| total.checked_add(len))
+        .ok_or(RespError::LengthOverflow)
+}
+
+// the length of a frame which is always the same, like "_\r\n", checked when it's decoded
+fn fixed_length(buf: &[u8], len: usize) -> Result<usize, RespError> {
+    match buf.len() < len {
+        true => Err(RespError::NotComplete),
+        false => Ok(len),
+    }
 }
 
 fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result<usize, RespError> {
     let mut total = end + CRLF_LEN;
     let mut data = &buf[total..];
     match prefix {
-        "*" | "~" => {
-            // find nth CRLF in the buffer, for array and set, we need to find 1 CRLF for each element
+        "*" | "~" | ">" => {
+            // find nth CRLF in the buffer, for array, set and push, we need to find 1 CRLF for each element
             for _ in 0..len {
                 let len = RespFrame::expect_length(data)?;
                 data = &data[len..];
@@ -144,8 +247,7 @@ fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result
         "%" => {
             // find nth CRLF in the buffer. For map, we need to find 2 CRLF for each key-value pair
             for _ in 0..len {
-                let len = SimpleString::expect_length(data)?;
-
+                let len = RespFrame::expect_length(data)?;
                 data = &data[len..];
                 total += len;
 
@@ -155,6 +257,6 @@ fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result
             }
             Ok(total)
         }
-        _ => Ok(len + CRLF_LEN),
+        _ => len.checked_add(CRLF_LEN).ok_or(RespError::LengthOverflow),
     }
 }
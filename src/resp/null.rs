@@ -1,26 +1,109 @@
-use bytes::BytesMut;
+use bytes::{Buf, BufMut, BytesMut};
 
-use super::{extract_fixed_data, RespDecoder, RespEncoder, RespError};
+use super::{extract_fixed_data, fixed_length, RespDecoder, RespEncoder, RespError};
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
-pub struct RespNull;
+const NULL: &[u8] = b"_\r\n";
+const NULL_BULK_STRING: &[u8] = b"$-1\r\n";
+const NULL_ARRAY: &[u8] = b"*-1\r\n";
+
+// The null of a reply, "_\r\n" in RESP3. A RESP2 client reads it as the null bulk string "$-1\r\n",
+// or as the null array "*-1\r\n" where the command replies with an array, like EXEC does when WATCH
+// aborts the transaction. The nulls are all equal, only their RESP2 form differs. An empty string
+// or array is never a null: only a RespNull is written as one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RespNull {
+    array: bool,
+    // written in its RESP2 form, set by `RespFrame::into_resp2` or read from a RESP2 peer
+    resp2: bool,
+}
+
+impl RespNull {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the null of a command replying with an array
+    pub fn array() -> Self {
+        Self {
+            array: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_array(&self) -> bool {
+        self.array
+    }
+
+    pub fn is_resp2(&self) -> bool {
+        self.resp2
+    }
+
+    pub fn into_resp2(self) -> Self {
+        Self {
+            resp2: true,
+            ..self
+        }
+    }
+
+    // whether the buffer starts with a null in any of its forms, "_" alone waiting for the rest
+    pub(crate) fn is_null(buf: &[u8]) -> bool {
+        buf.starts_with(b"_") || buf.starts_with(NULL_BULK_STRING) || buf.starts_with(NULL_ARRAY)
+    }
+
+    fn form(&self) -> &'static [u8] {
+        match (self.resp2, self.array) {
+            (false, _) => NULL,
+            (true, false) => NULL_BULK_STRING,
+            (true, true) => NULL_ARRAY,
+        }
+    }
+}
+
+impl PartialEq for RespNull {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for RespNull {}
+
+impl PartialOrd for RespNull {
+    fn partial_cmp(&self, _: &Self) -> Option<std::cmp::Ordering> {
+        Some(std::cmp::Ordering::Equal)
+    }
+}
 
 // - null: "_\r\n"
+// - null bulk string: "$-1\r\n"
+// - null array: "*-1\r\n"
 impl RespEncoder for RespNull {
-    fn encode(self) -> Vec<u8> {
-        b"_\r\n".to_vec()
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.put_slice(self.form());
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.form().len()
     }
 }
 
 impl RespDecoder for RespNull {
     const PREFIX: &'static str = "_";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        for null in [RespNull::new().into_resp2(), RespNull::array().into_resp2()] {
+            if buf.starts_with(null.form()) {
+                buf.advance(null.form().len());
+                return Ok(null);
+            }
+        }
         extract_fixed_data(buf, "_\r\n", "Null")?;
-        Ok(RespNull)
+        Ok(RespNull::new())
     }
 
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        Ok(3)
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        match buf.first() {
+            Some(b'$') | Some(b'*') => fixed_length(buf, NULL_ARRAY.len()),
+            _ => fixed_length(buf, NULL.len()),
+        }
     }
 }
 
@@ -32,8 +115,27 @@ mod tests {
 
     #[test]
     fn test_encode_null_encode() {
-        let frame: RespFrame = RespNull.into();
-        assert_eq!(frame.encode(), b"_\r\n");
+        let frame: RespFrame = RespNull::new().into();
+        assert_eq!(frame.encode_to_vec(), b"_\r\n");
+    }
+
+    #[test]
+    fn test_null_in_resp2() {
+        assert_eq!(RespFrame::null().into_resp2().encode_to_vec(), b"$-1\r\n");
+        let null = RespFrame::null_array();
+        assert_eq!(null, RespFrame::null());
+        assert_eq!(null.into_resp2().encode_to_vec(), b"*-1\r\n");
+    }
+
+    #[test]
+    fn test_decode_resp2_nulls() -> Result<()> {
+        let mut buf = BytesMut::from("$-1\r\n*-1\r\n");
+        let null = RespFrame::decode(&mut buf)?;
+        assert_eq!(null.encode_to_vec(), b"$-1\r\n");
+        let null = RespFrame::decode(&mut buf)?;
+        assert_eq!(null.encode_to_vec(), b"*-1\r\n");
+        assert!(buf.is_empty());
+        Ok(())
     }
 
     #[test]
@@ -42,7 +144,7 @@ mod tests {
         buf.extend_from_slice(b"_\r\n");
 
         let frame = RespNull::decode(&mut buf)?;
-        assert_eq!(frame, RespNull);
+        assert_eq!(frame, RespNull::new());
 
         Ok(())
     }
@@ -0,0 +1,237 @@
+use super::{limits::header, streamed, DecodeLimits, RespDecoder, RespError, RespFrame, CRLF_LEN};
+
+// the terminator of a streamed aggregate, ".\r\n"
+const END_LEN: usize = 3;
+
+// What is left of an aggregate the scanner is in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Pending {
+    // the elements of an array, a set or a push, the keys and the values of a map
+    Elements(usize),
+    // a streamed aggregate until its end, with the frames of it read so far
+    Streamed { frames: usize, map: bool },
+    // the chunks of a streamed string, with the length of those read so far
+    Chunks(usize),
+}
+
+// Finds where the frame at the start of a buffer ends while it's read piece by piece. What was
+// scanned of it is remembered, so each read only scans the bytes that arrived since the last one
+// instead of the whole frame again. The limits are checked as the headers are read.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FrameScanner {
+    // the bytes of the frame made of complete headers and elements
+    scanned: usize,
+    // the aggregates the scan is in, the innermost last
+    stack: Vec<Pending>,
+}
+
+impl FrameScanner {
+    // The length of the frame once it's all in the buffer, None while it isn't. The buffer must
+    // hold the bytes scanned before, the scanner starts again for the next frame.
+    pub(crate) fn scan(
+        &mut self,
+        buf: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<Option<usize>, RespError> {
+        loop {
+            let rest = &buf[self.scanned..];
+            let step = match self.stack.last() {
+                Some(Pending::Chunks(len)) => self.chunk(rest, *len, limits),
+                Some(Pending::Streamed { .. }) if rest.first() == Some(&b'.') => {
+                    match rest.len() < END_LEN {
+                        true => Err(RespError::NotComplete),
+                        // the terminator itself is checked when the aggregate is decoded
+                        false => {
+                            self.stack.pop();
+                            self.scanned += END_LEN;
+                            self.finish(limits)
+                        }
+                    }
+                }
+                _ => self.element(rest, limits),
+            };
+            match step {
+                Ok(true) => {
+                    let len = std::mem::take(&mut self.scanned);
+                    self.stack.clear();
+                    return Ok(Some(len));
+                }
+                Ok(false) => continue,
+                Err(RespError::NotComplete) => return Ok(None),
+                Err(e) => {
+                    self.reset();
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.scanned = 0;
+        self.stack.clear();
+    }
+
+    // Scans the frame starting at `buf`, true when the whole frame is done. Nothing is kept of an
+    // element which isn't all read, but its header.
+    fn element(&mut self, buf: &[u8], limits: &DecodeLimits) -> Result<bool, RespError> {
+        if streamed::is_streamed(buf) {
+            streamed::check_header(buf)?;
+            let pending = match buf[0] {
+                b'$' => Pending::Chunks(0),
+                prefix => {
+                    self.check_depth(limits)?;
+                    Pending::Streamed {
+                        frames: 0,
+                        map: prefix == b'%',
+                    }
+                }
+            };
+            self.stack.push(pending);
+            self.scanned += streamed::HEADER_LEN;
+            return Ok(false);
+        }
+        match buf.first() {
+            Some(b'$' | b'=') => {
+                let len = match header(buf, "bulk")? {
+                    (end, None) => end,
+                    (_, Some(len)) if len > limits.max_bulk_len => {
+                        return Err(RespError::InvalidFrame("invalid bulk length".to_string()));
+                    }
+                    (end, Some(len)) => end
+                        .checked_add(len + CRLF_LEN)
+                        .ok_or(RespError::LengthOverflow)?,
+                };
+                if len > buf.len() {
+                    return Err(RespError::NotComplete);
+                }
+                self.scanned += len;
+            }
+            Some(prefix @ (b'*' | b'~' | b'>' | b'%')) => {
+                let (end, len) = match header(buf, "multibulk")? {
+                    (end, None) => (end, 0),
+                    (end, Some(len)) => (end, len),
+                };
+                if len > limits.max_aggregate_len {
+                    return Err(RespError::InvalidFrame(
+                        "invalid multibulk length".to_string(),
+                    ));
+                }
+                if len > 0 {
+                    self.check_depth(limits)?;
+                }
+                self.scanned += end;
+                // the entries of a map are a key and a value
+                let elements = match *prefix == b'%' {
+                    true => len.checked_mul(2).ok_or(RespError::LengthOverflow)?,
+                    false => len,
+                };
+                if elements > 0 {
+                    self.stack.push(Pending::Elements(elements));
+                    return Ok(false);
+                }
+            }
+            _ => self.scanned += RespFrame::expect_length(buf)?,
+        }
+        self.finish(limits)
+    }
+
+    // Scans a chunk of a streamed string, `len` is the length of the chunks before it.
+    fn chunk(&mut self, buf: &[u8], len: usize, limits: &DecodeLimits) -> Result<bool, RespError> {
+        let (end, chunk) = match header(buf, "bulk")? {
+            (end, Some(chunk)) => (end, chunk),
+            (_, None) => return Err(RespError::InvalidFrame("invalid bulk length".to_string())),
+        };
+        let len = len.saturating_add(chunk);
+        if len > limits.max_bulk_len {
+            return Err(RespError::InvalidFrame("invalid bulk length".to_string()));
+        }
+        if chunk == 0 {
+            self.stack.pop();
+            self.scanned += end;
+            return self.finish(limits);
+        }
+        let total = end
+            .checked_add(chunk + CRLF_LEN)
+            .ok_or(RespError::LengthOverflow)?;
+        if total > buf.len() {
+            return Err(RespError::NotComplete);
+        }
+        self.scanned += total;
+        if let Some(pending) = self.stack.last_mut() {
+            *pending = Pending::Chunks(len);
+        }
+        Ok(false)
+    }
+
+    // Counts a frame just scanned in the aggregates it completes, true when it was the whole frame.
+    fn finish(&mut self, limits: &DecodeLimits) -> Result<bool, RespError> {
+        loop {
+            match self.stack.last_mut() {
+                None => return Ok(true),
+                Some(Pending::Elements(left)) => {
+                    *left -= 1;
+                    if *left > 0 {
+                        return Ok(false);
+                    }
+                    self.stack.pop();
+                }
+                Some(Pending::Streamed { frames, map }) => {
+                    *frames += 1;
+                    let elements = if *map { frames.div_ceil(2) } else { *frames };
+                    if elements > limits.max_aggregate_len {
+                        return Err(RespError::InvalidFrame(
+                            "invalid multibulk length".to_string(),
+                        ));
+                    }
+                    return Ok(false);
+                }
+                Some(Pending::Chunks(_)) => unreachable!("a streamed string holds no frames"),
+            }
+        }
+    }
+
+    fn check_depth(&self, limits: &DecodeLimits) -> Result<(), RespError> {
+        match self.stack.len() >= limits.max_depth {
+            true => Err(RespError::InvalidFrame(
+                "too deeply nested aggregate".to_string(),
+            )),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_resumes_where_it_stopped() {
+        let frame = b"*3\r\n$3\r\nget\r\n%1\r\n+k\r\n$?\r\n;2\r\nab\r\n;0\r\n:1\r\n";
+        let limits = DecodeLimits::default();
+        let mut scanner = FrameScanner::default();
+        for end in 0..frame.len() {
+            assert_eq!(scanner.scan(&frame[..end], &limits), Ok(None));
+        }
+        // all but the last integer was scanned already, and isn't again
+        assert_eq!(scanner.scanned, frame.len() - 4);
+        assert_eq!(scanner.scan(frame, &limits), Ok(Some(frame.len())));
+        assert_eq!(scanner.scanned, 0);
+
+        // the next frame starts from scratch
+        assert_eq!(scanner.scan(b"*-1\r\n", &limits), Ok(Some(5)));
+    }
+
+    #[test]
+    fn test_scan_checks_limits_as_it_goes() {
+        let limits = DecodeLimits {
+            max_bulk_len: 4,
+            max_aggregate_len: 2,
+            max_depth: 2,
+        };
+        let mut scanner = FrameScanner::default();
+        assert_eq!(scanner.scan(b"~?\r\n:1\r\n:2\r\n", &limits), Ok(None));
+        assert!(scanner.scan(b"~?\r\n:1\r\n:2\r\n:3\r\n", &limits).is_err());
+        // a frame after an error is scanned on its own
+        assert_eq!(scanner.scan(b"*1\r\n$4\r\nabcd\r\n", &limits), Ok(Some(14)));
+    }
+}
@@ -0,0 +1,172 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+
+use super::{
+    double::Double, BigNumber, BulkString, RespArray, RespFrame, RespMap, RespPush, RespSet,
+    SimpleError, SimpleString, VerbatimString,
+};
+
+// The serde form of a frame names its type, so every frame reads back as the same frame:
+// {"type":"bulk_string","value":"hello"}, {"type":"map","value":[[key,value],...]}, {"type":"null"}
+// Bulk strings are strings when they are valid UTF-8 and arrays of bytes otherwise, the doubles
+// that JSON can't hold are "inf", "-inf" and "nan".
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+enum Repr<'a> {
+    SimpleString(Cow<'a, str>),
+    Error(Cow<'a, str>),
+    Integer(i64),
+    BulkString(Data<'a>),
+    Array(Cow<'a, [RespFrame]>),
+    Null,
+    Boolean(bool),
+    Double(Number<'a>),
+    Map(Cow<'a, [(RespFrame, RespFrame)]>),
+    Set(Cow<'a, [RespFrame]>),
+    Push(Cow<'a, [RespFrame]>),
+    BigNumber(Cow<'a, str>),
+    VerbatimString {
+        format: Cow<'a, str>,
+        data: Data<'a>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Data<'a> {
+    Utf8(Cow<'a, str>),
+    Binary(Cow<'a, [u8]>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Number<'a> {
+    Finite(f64),
+    Special(Cow<'a, str>),
+}
+
+impl<'a> From<&'a [u8]> for Data<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Data::Utf8(s.into()),
+            Err(_) => Data::Binary(bytes.into()),
+        }
+    }
+}
+
+impl Data<'_> {
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Data::Utf8(s) => s.into_owned().into_bytes(),
+            Data::Binary(bytes) => bytes.into_owned(),
+        }
+    }
+}
+
+impl<'a> From<&'a RespFrame> for Repr<'a> {
+    fn from(frame: &'a RespFrame) -> Self {
+        match frame {
+            RespFrame::SimpleString(s) => Repr::SimpleString(s.0.as_str().into()),
+            RespFrame::Error(e) => Repr::Error(e.0.as_str().into()),
+            RespFrame::Integer(n) => Repr::Integer(*n),
+            RespFrame::BulkString(s) => Repr::BulkString(Data::from(&s[..])),
+            RespFrame::Array(frames) => Repr::Array(frames.0.as_slice().into()),
+            RespFrame::Null(_) => Repr::Null,
+            RespFrame::Boolean(b) => Repr::Boolean(*b),
+            RespFrame::Double(d) if d.is_finite() => Repr::Double(Number::Finite(*d)),
+            RespFrame::Double(d) => Repr::Double(Number::Special(Double(*d).to_string().into())),
+            RespFrame::Map(map) => Repr::Map(map.0.as_slice().into()),
+            RespFrame::Set(set) => Repr::Set(Cow::Borrowed(set)),
+            RespFrame::Push(frames) => Repr::Push(frames.0.as_slice().into()),
+            RespFrame::BigNumber(n) => Repr::BigNumber(n.0.as_str().into()),
+            RespFrame::VerbatimString(s) => Repr::VerbatimString {
+                format: s.format().into(),
+                data: Data::from(s.data()),
+            },
+        }
+    }
+}
+
+impl TryFrom<Repr<'_>> for RespFrame {
+    type Error = String;
+
+    fn try_from(repr: Repr<'_>) -> Result<Self, String> {
+        Ok(match repr {
+            Repr::SimpleString(s) => SimpleString::new(s).into(),
+            Repr::Error(e) => SimpleError::new(e).into(),
+            Repr::Integer(n) => RespFrame::Integer(n),
+            Repr::BulkString(data) => BulkString::new(data.into_bytes()).into(),
+            Repr::Array(frames) => RespArray::new(frames.into_owned()).into(),
+            Repr::Null => RespFrame::null(),
+            Repr::Boolean(b) => RespFrame::Boolean(b),
+            Repr::Double(Number::Finite(d)) => RespFrame::Double(d),
+            Repr::Double(Number::Special(s)) => {
+                RespFrame::Double(s.parse().map_err(|_| format!("invalid double: {:?}", s))?)
+            }
+            Repr::Map(entries) => RespMap(entries.into_owned()).into(),
+            Repr::Set(frames) => RespSet::new(frames.into_owned()).into(),
+            Repr::Push(frames) => RespPush::new(frames.into_owned()).into(),
+            Repr::BigNumber(n) => BigNumber::new(n).map_err(|e| e.to_string())?.into(),
+            Repr::VerbatimString { format, data } => {
+                let format: [u8; 3] = format
+                    .as_bytes()
+                    .try_into()
+                    .map_err(|_| format!("invalid verbatim string format: {:?}", format))?;
+                VerbatimString::new(format, data.into_bytes()).into()
+            }
+        })
+    }
+}
+
+impl Serialize for RespFrame {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        Repr::from(self).serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for RespFrame {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Repr::deserialize(d)?.try_into().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_frames_round_trip_through_json() -> Result<()> {
+        let mut map = RespMap::new();
+        map.insert(RespFrame::Integer(1), RespFrame::null());
+        map.insert(
+            BulkString::from(b"\xff\x00"),
+            RespFrame::Double(f64::NEG_INFINITY),
+        );
+        let frames: Vec<RespFrame> = vec![
+            SimpleString::new("OK").into(),
+            SimpleError::new("ERR bad").into(),
+            RespFrame::Integer(-42),
+            BulkString::from("hello").into(),
+            RespArray::new([true.into(), RespFrame::Double(1.5)]).into(),
+            map.into(),
+            RespSet::new([RespFrame::Integer(1)]).into(),
+            RespPush::new([SimpleString::new("message").into()]).into(),
+            BigNumber::from(u128::MAX).into(),
+            VerbatimString::markdown("# title").into(),
+        ];
+        for frame in frames {
+            let json = serde_json::to_string(&frame)?;
+            assert_eq!(serde_json::from_str::<RespFrame>(&json)?, frame, "{}", json);
+        }
+
+        let json = serde_json::to_string(&RespFrame::from(BulkString::from(b"\xff")))?;
+        assert_eq!(json, r#"{"type":"bulk_string","value":[255]}"#);
+        let nan: RespFrame = serde_json::from_str(r#"{"type":"double","value":"nan"}"#)?;
+        assert!(matches!(nan, RespFrame::Double(d) if d.is_nan()));
+        assert!(
+            serde_json::from_str::<RespFrame>(r#"{"type":"big_number","value":"1x"}"#).is_err()
+        );
+        Ok(())
+    }
+}
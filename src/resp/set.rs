@@ -1,29 +1,87 @@
 use bytes::{Buf, BytesMut};
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::BuildHasher;
 use std::ops::Deref;
 
 use super::{
-    calc_total_length, parse_length, RespDecoder, RespEncoder, RespError, RespFrame, BUFFER_CAP,
-    CRLF_LEN,
+    calc_total_length, header_len, parse_length, put_fmt, RespDecoder, RespEncoder, RespError,
+    RespFrame, CRLF_LEN,
 };
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub struct RespSet(pub(crate) Vec<RespFrame>);
+// Frames without duplicates, kept in the order they were inserted. Two frames are the same when
+// they are encoded the same, so 1 and 1.0 are not, and a frame is looked up by the hash of its
+// encoding.
+#[derive(Clone)]
+pub struct RespSet {
+    frames: Vec<RespFrame>,
+    // the positions of the frames by hash
+    index: HashMap<u64, Vec<usize>>,
+    hasher: RandomState,
+}
 
 impl RespSet {
+    // the duplicates are dropped
     pub fn new(s: impl Into<Vec<RespFrame>>) -> Self {
-        RespSet(s.into())
+        let mut set = RespSet {
+            frames: Vec::new(),
+            index: HashMap::new(),
+            hasher: RandomState::new(),
+        };
+        for frame in s.into() {
+            set.insert(frame);
+        }
+        set
+    }
+
+    // false when the set had the frame already
+    pub fn insert(&mut self, frame: RespFrame) -> bool {
+        let encoded = frame.encode_to_vec();
+        let hash = self.hasher.hash_one(&encoded);
+        if self.find(hash, &encoded) {
+            return false;
+        }
+        self.index.entry(hash).or_default().push(self.frames.len());
+        self.frames.push(frame);
+        true
+    }
+
+    pub fn contains(&self, frame: &RespFrame) -> bool {
+        let encoded = frame.encode_to_vec();
+        self.find(self.hasher.hash_one(&encoded), &encoded)
+    }
+
+    pub fn into_vec(self) -> Vec<RespFrame> {
+        self.frames
+    }
+
+    fn find(&self, hash: u64, encoded: &[u8]) -> bool {
+        self.index.get(&hash).is_some_and(|positions| {
+            positions
+                .iter()
+                .any(|&i| self.frames[i].encode_to_vec() == encoded)
+        })
     }
 }
 
 // - set: "~<number-of-elements>\r\n<element-1>...<element-n>"
 impl RespEncoder for RespSet {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(BUFFER_CAP);
-        buf.extend_from_slice(&format!("~{}\r\n", self.len()).into_bytes());
-        for frame in self.0 {
-            buf.extend_from_slice(&frame.encode());
+    fn encode(&self, buf: &mut BytesMut) {
+        put_fmt(buf, format_args!("~{}\r\n", self.len()));
+        for frame in &self.frames {
+            frame.encode(buf);
         }
-        buf
+    }
+
+    fn encoded_len(&self) -> usize {
+        header_len(self.len())
+            + self
+                .frames
+                .iter()
+                .map(RespEncoder::encoded_len)
+                .sum::<usize>()
     }
 }
 
@@ -41,12 +99,13 @@ impl RespDecoder for RespSet {
 
         buf.advance(end + CRLF_LEN);
 
-        let mut frames = Vec::new();
+        // the duplicates a peer sent are dropped
+        let mut set = RespSet::new([]);
         for _ in 0..len {
-            frames.push(RespFrame::decode(buf)?);
+            set.insert(RespFrame::decode(buf)?);
         }
 
-        Ok(RespSet::new(frames))
+        Ok(set)
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
@@ -56,10 +115,32 @@ impl RespDecoder for RespSet {
 }
 
 impl Deref for RespSet {
-    type Target = Vec<RespFrame>;
+    type Target = [RespFrame];
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.frames
+    }
+}
+
+// the same frames in any order
+impl PartialEq for RespSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|frame| other.contains(frame))
+    }
+}
+
+impl PartialOrd for RespSet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self == other {
+            true => Some(Ordering::Equal),
+            false => self.frames.partial_cmp(&other.frames),
+        }
+    }
+}
+
+impl fmt::Debug for RespSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RespSet").field(&self.frames).finish()
     }
 }
 
@@ -77,7 +158,7 @@ mod tests {
         ])
         .into();
         assert_eq!(
-            frame.encode(),
+            frame.encode_to_vec(),
             b"~2\r\n*2\r\n:1234\r\n#t\r\n$5\r\nworld\r\n"
         );
     }
@@ -98,4 +179,19 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_set_has_no_duplicates() -> Result<()> {
+        let mut buf = BytesMut::from("~4\r\n:1\r\n,1\r\n:1\r\n*1\r\n:1\r\n");
+        let mut set = RespSet::decode(&mut buf)?;
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&RespFrame::Double(1.0)));
+        assert!(!set.insert(RespArray::new([1.into()]).into()));
+        assert!(set.insert(RespFrame::Double(f64::NAN)));
+        assert!(!set.insert(RespFrame::Double(f64::NAN)));
+        // in any order
+        let reversed = RespSet::new(set.iter().rev().cloned().collect::<Vec<_>>());
+        assert_eq!(set, reversed);
+        Ok(())
+    }
 }
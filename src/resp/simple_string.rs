@@ -1,7 +1,7 @@
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 use std::ops::Deref;
 
-use super::{extract_simple_frame_data, RespDecoder, RespEncoder, RespError, CRLF_LEN};
+use super::{extract_simple_frame_data, RespDecoder, RespEncoder, RespError, CRLF, CRLF_LEN};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
 pub struct SimpleString(pub(crate) String);
@@ -14,8 +14,14 @@ impl SimpleString {
 
 // - simple string: "+OK\r\n"
 impl RespEncoder for SimpleString {
-    fn encode(self) -> Vec<u8> {
-        format!("+{}\r\n", self.0).into_bytes()
+    fn encode(&self, buf: &mut BytesMut) {
+        buf.put_u8(b'+');
+        buf.put_slice(self.0.as_bytes());
+        buf.put_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        1 + self.0.len() + CRLF_LEN
     }
 }
 
@@ -65,7 +71,7 @@ mod tests {
     #[test]
     fn test_encode_simple_string() {
         let frame: RespFrame = SimpleString::new("OK".to_string()).into();
-        assert_eq!(frame.encode(), b"+OK\r\n");
+        assert_eq!(frame.encode_to_vec(), b"+OK\r\n");
     }
 
     #[test]
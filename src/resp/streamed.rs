@@ -0,0 +1,212 @@
+use bytes::{Buf, BufMut, BytesMut};
+
+use super::{
+    data_end, header_len, parse_length, put_fmt, BulkString, RespArray, RespDecoder, RespEncoder,
+    RespError, RespFrame, RespMap, RespSet, CRLF, CRLF_LEN,
+};
+
+// "$?\r\n", "*?\r\n" and so on
+pub(super) const HEADER_LEN: usize = 4;
+const END: &[u8] = b".\r\n";
+const LAST_CHUNK: &[u8] = b";0\r\n";
+
+// - streamed string: "$?\r\n;<length>\r\n<data>\r\n...;0\r\n"
+// - streamed array, set or map: "*?\r\n<element-1>...<element-n>.\r\n", a map has a key and a
+//   value for each entry
+// They are read into the frame they stand for, a bulk string, an array, a set or a map.
+pub(crate) fn is_streamed(buf: &[u8]) -> bool {
+    matches!(buf, [b'$' | b'*' | b'~' | b'%', b'?', ..])
+}
+
+pub(crate) fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+    check_header(buf)?;
+    let mut total = HEADER_LEN;
+    if buf[0] == b'$' {
+        loop {
+            let (end, len) = parse_length(&buf[total..], ";")?;
+            if len == 0 {
+                return Ok(total + end + CRLF_LEN);
+            }
+            total = data_end(total + end, len)?;
+            if total > buf.len() {
+                return Err(RespError::NotComplete);
+            }
+        }
+    }
+    let elements = if buf[0] == b'%' { 2 } else { 1 };
+    loop {
+        if at_end(&buf[total..])? {
+            return Ok(total + END.len());
+        }
+        for _ in 0..elements {
+            total += RespFrame::expect_length(&buf[total..])?;
+        }
+    }
+}
+
+pub(crate) fn decode(buf: &mut BytesMut) -> Result<RespFrame, RespError> {
+    expect_length(buf)?;
+    let prefix = buf[0];
+    buf.advance(HEADER_LEN);
+    match prefix {
+        b'$' => {
+            let mut data = BytesMut::new();
+            loop {
+                let (end, len) = parse_length(buf, ";")?;
+                buf.advance(end + CRLF_LEN);
+                if len == 0 {
+                    break;
+                }
+                data.extend_from_slice(&buf[..len]);
+                buf.advance(len + CRLF_LEN);
+            }
+            Ok(BulkString(data.freeze()).into())
+        }
+        b'%' => {
+            let mut map = RespMap::new();
+            while !at_end(buf)? {
+                let key = RespFrame::decode(buf)?;
+                let value = RespFrame::decode(buf)?;
+                map.0.push((key, value));
+            }
+            buf.advance(END.len());
+            Ok(map.into())
+        }
+        prefix => {
+            let mut frames = Vec::new();
+            while !at_end(buf)? {
+                frames.push(RespFrame::decode(buf)?);
+            }
+            buf.advance(END.len());
+            match prefix {
+                b'*' => Ok(RespArray::new(frames).into()),
+                _ => Ok(RespSet::new(frames).into()),
+            }
+        }
+    }
+}
+
+pub(super) fn check_header(buf: &[u8]) -> Result<(), RespError> {
+    if buf.len() < HEADER_LEN {
+        return Err(RespError::NotComplete);
+    }
+    if &buf[2..HEADER_LEN] != CRLF {
+        return Err(RespError::InvalidFrame(format!(
+            "invalid streamed header: {:?}",
+            String::from_utf8_lossy(&buf[..HEADER_LEN])
+        )));
+    }
+    Ok(())
+}
+
+// whether a streamed aggregate ends here
+fn at_end(buf: &[u8]) -> Result<bool, RespError> {
+    match buf.first() {
+        Some(b'.') if buf.len() < END.len() => Err(RespError::NotComplete),
+        Some(b'.') if buf.starts_with(END) => Ok(true),
+        Some(b'.') => Err(RespError::InvalidFrame(
+            "invalid end of a streamed aggregate".to_string(),
+        )),
+        Some(_) => Ok(false),
+        None => Err(RespError::NotComplete),
+    }
+}
+
+// The parts of a reply too large to be built as a whole, written one after the other as they are
+// produced, like into `Framed::write_buffer_mut`. The streamed types are RESP3 only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamedPart<'a> {
+    String,
+    // a part of the string, an empty one is not written since it would end the string
+    Chunk(&'a [u8]),
+    StringEnd,
+    Array,
+    Set,
+    // followed by the keys and the values
+    Map,
+    // the end of an array, a set or a map
+    End,
+}
+
+impl RespEncoder for StreamedPart<'_> {
+    fn encode(&self, buf: &mut BytesMut) {
+        match self {
+            StreamedPart::String => buf.put_slice(b"$?\r\n"),
+            StreamedPart::Chunk([]) => {}
+            StreamedPart::Chunk(data) => {
+                put_fmt(buf, format_args!(";{}\r\n", data.len()));
+                buf.put_slice(data);
+                buf.put_slice(CRLF);
+            }
+            StreamedPart::StringEnd => buf.put_slice(LAST_CHUNK),
+            StreamedPart::Array => buf.put_slice(b"*?\r\n"),
+            StreamedPart::Set => buf.put_slice(b"~?\r\n"),
+            StreamedPart::Map => buf.put_slice(b"%?\r\n"),
+            StreamedPart::End => buf.put_slice(END),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            StreamedPart::Chunk([]) => 0,
+            StreamedPart::Chunk(data) => header_len(data.len()) + data.len() + CRLF_LEN,
+            StreamedPart::StringEnd => LAST_CHUNK.len(),
+            StreamedPart::End => END.len(),
+            _ => HEADER_LEN,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_streamed_frames_round_trip() -> Result<()> {
+        let mut buf = BytesMut::new();
+        StreamedPart::Array.encode(&mut buf);
+        StreamedPart::String.encode(&mut buf);
+        for chunk in [b"Hell".as_slice(), b"", b"o world"] {
+            StreamedPart::Chunk(chunk).encode(&mut buf);
+        }
+        StreamedPart::StringEnd.encode(&mut buf);
+        RespFrame::Integer(1).encode(&mut buf);
+        StreamedPart::End.encode(&mut buf);
+        assert_eq!(
+            buf,
+            BytesMut::from("*?\r\n$?\r\n;4\r\nHell\r\n;7\r\no world\r\n;0\r\n:1\r\n.\r\n")
+        );
+
+        // nothing is taken before the end arrives
+        let mut partial = buf.split_to(buf.len() - 2);
+        assert_eq!(RespFrame::decode(&mut partial), Err(RespError::NotComplete));
+        partial.unsplit(buf);
+        assert_eq!(
+            RespFrame::decode(&mut partial)?,
+            RespArray::new(vec![
+                BulkString::from("Hello world").into(),
+                RespFrame::Integer(1)
+            ])
+            .into()
+        );
+        assert!(partial.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_streamed_map_and_set() -> Result<()> {
+        let mut buf = BytesMut::from("%?\r\n+a\r\n:1\r\n+b\r\n~?\r\n#t\r\n.\r\n.\r\n");
+        let mut map = RespMap::new();
+        map.insert("a", RespFrame::Integer(1));
+        map.insert("b", RespSet::new(vec![true.into()]));
+        assert_eq!(RespFrame::decode(&mut buf)?, map.into());
+
+        let mut buf = BytesMut::from("*?\r\n:1\r\n.x\r\n");
+        assert!(matches!(
+            RespFrame::decode(&mut buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+        Ok(())
+    }
+}
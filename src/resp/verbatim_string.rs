@@ -0,0 +1,121 @@
+use bytes::{Buf, BufMut, BytesMut};
+
+use super::{
+    data_end, header_len, parse_length, put_fmt, BulkString, RespDecoder, RespEncoder, RespError,
+    CRLF, CRLF_LEN,
+};
+
+// the length of the format and the colon before the text
+const FORMAT_LEN: usize = 4;
+
+// A text with its format, "txt" for plain text or "mkd" for markdown, a RESP2 client gets the
+// text as a bulk string.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+pub struct VerbatimString {
+    pub(crate) format: [u8; 3],
+    pub(crate) data: Vec<u8>,
+}
+
+impl VerbatimString {
+    pub fn new(format: [u8; 3], data: impl Into<Vec<u8>>) -> Self {
+        VerbatimString {
+            format,
+            data: data.into(),
+        }
+    }
+
+    pub fn text(data: impl Into<Vec<u8>>) -> Self {
+        Self::new(*b"txt", data)
+    }
+
+    pub fn markdown(data: impl Into<Vec<u8>>) -> Self {
+        Self::new(*b"mkd", data)
+    }
+
+    pub fn format(&self) -> &str {
+        std::str::from_utf8(&self.format).unwrap_or_default()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+// - verbatim string: "=<length>\r\n<format>:<data>\r\n", the length counts the format
+impl RespEncoder for VerbatimString {
+    fn encode(&self, buf: &mut BytesMut) {
+        put_fmt(buf, format_args!("={}\r\n", FORMAT_LEN + self.data.len()));
+        buf.put_slice(&self.format);
+        buf.put_u8(b':');
+        buf.put_slice(&self.data);
+        buf.put_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        let len = FORMAT_LEN + self.data.len();
+        header_len(len) + len + CRLF_LEN
+    }
+}
+
+impl RespDecoder for VerbatimString {
+    const PREFIX: &'static str = "=";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let total = Self::expect_length(buf)?;
+        let (end, len) = parse_length(buf, Self::PREFIX)?;
+        let data = &buf[end + CRLF_LEN..total - CRLF_LEN];
+        if len < FORMAT_LEN || data[FORMAT_LEN - 1] != b':' {
+            return Err(RespError::InvalidFrame(format!(
+                "verbatim string without a format: {:?}",
+                String::from_utf8_lossy(data)
+            )));
+        }
+        let frame = VerbatimString::new([data[0], data[1], data[2]], data[FORMAT_LEN..].to_vec());
+        buf.advance(total);
+        Ok(frame)
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        let (end, len) = parse_length(buf, Self::PREFIX)?;
+        let total = data_end(end, len)?;
+        if total > buf.len() {
+            return Err(RespError::NotComplete);
+        }
+        Ok(total)
+    }
+}
+
+impl From<VerbatimString> for BulkString {
+    fn from(s: VerbatimString) -> Self {
+        BulkString(s.data.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespFrame;
+    use anyhow::Result;
+
+    #[test]
+    fn test_encode_verbatim_string() {
+        let frame: RespFrame = VerbatimString::text("Some string").into();
+        assert_eq!(frame.encode_to_vec(), b"=15\r\ntxt:Some string\r\n");
+    }
+
+    #[test]
+    fn test_verbatim_string_decode() -> Result<()> {
+        let mut buf = BytesMut::from("=15\r\nmkd:Some string\r\n=1");
+        let frame = VerbatimString::decode(&mut buf)?;
+        assert_eq!(frame.format(), "mkd");
+        assert_eq!(frame.data(), b"Some string");
+        assert_eq!(BulkString::from(frame), BulkString::from("Some string"));
+        assert_eq!(buf, BytesMut::from("=1"));
+
+        let mut buf = BytesMut::from("=3\r\ntxt\r\n");
+        assert!(matches!(
+            VerbatimString::decode(&mut buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use std::io;
+use tokio::runtime::{Builder, Runtime};
+
+// the flavor of the runtime, picked at build time
+pub const RUNTIME_FLAVOR: &str = if cfg!(feature = "current-thread") {
+    "current-thread"
+} else {
+    "multi-thread"
+};
+
+// The runtime the server runs on. By default the connections are spread over a worker thread per
+// core, with the current-thread feature a single reactor runs them all, without the wakeups across
+// threads and the work stealing, for the deployments where the latency matters more than the
+// throughput. The io_uring runtimes, tokio-uring or monoio, are not offered: their sockets are not
+// Send and read into owned buffers, the connections would need a path of their own besides
+// handle_stream and the Framed codec.
+pub fn runtime() -> io::Result<Runtime> {
+    let mut builder = if cfg!(feature = "current-thread") {
+        Builder::new_current_thread()
+    } else {
+        Builder::new_multi_thread()
+    };
+    builder.enable_all().build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serve, Backend, Config, Listeners, RedisClient, ServerConfig};
+    use anyhow::Result;
+
+    #[test]
+    fn test_runtime_serves() -> Result<()> {
+        runtime()?.block_on(async {
+            let config = Config {
+                bind: "127.0.0.1".to_string(),
+                port: 0,
+                ..Default::default()
+            };
+            let listeners = Listeners::bind(&config).await?;
+            let addr = listeners.local_addrs()[0];
+            let backend = Backend::with_config(ServerConfig::new(config));
+            let server = tokio::spawn(serve(listeners, backend.clone()));
+
+            let mut client = RedisClient::connect(addr).await?;
+            client.set("foo", "bar").await?;
+            assert_eq!(client.get("foo").await?, Some("bar".into()));
+            backend.shutdown_handle().shutdown(Some(false))?;
+            server.await?
+        })
+    }
+}
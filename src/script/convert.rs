@@ -0,0 +1,76 @@
+use crate::{BulkString, RespArray, RespFrame, SimpleError, SimpleString};
+use mlua::{Lua, Table, Value};
+
+// Converts a reply into a Lua value, following the Redis conventions:
+// integers become numbers, bulk strings become strings, nulls become false,
+// status and error replies become tables with a single `ok` or `err` field.
+pub(super) fn frame_to_lua(lua: &Lua, frame: RespFrame) -> mlua::Result<Value> {
+    let value = match frame {
+        RespFrame::SimpleString(s) => single_field_table(lua, "ok", &s.0)?,
+        RespFrame::Error(e) => single_field_table(lua, "err", &e.0)?,
+        RespFrame::Integer(i) => Value::Integer(i),
+        RespFrame::BulkString(s) => Value::String(lua.create_string(&s.0)?),
+        RespFrame::VerbatimString(s) => Value::String(lua.create_string(&s.data)?),
+        RespFrame::Null(_) => Value::Boolean(false),
+        RespFrame::Boolean(b) => Value::Boolean(b),
+        RespFrame::Double(d) => single_field_table(lua, "double", &d.to_string())?,
+        RespFrame::BigNumber(n) => single_field_table(lua, "big_number", &n.0)?,
+        RespFrame::Array(frames) => sequence(lua, frames.0)?,
+        RespFrame::Set(frames) => sequence(lua, frames.into_vec())?,
+        RespFrame::Push(frames) => sequence(lua, frames.0)?,
+        RespFrame::Map(map) => {
+            let t = lua.create_table()?;
+            for (k, v) in map.0 {
+                t.set(frame_to_lua(lua, k)?, frame_to_lua(lua, v)?)?;
+            }
+            Value::Table(t)
+        }
+    };
+    Ok(value)
+}
+
+// Converts the value returned by a script into a reply.
+pub(super) fn lua_to_frame(value: Value) -> mlua::Result<RespFrame> {
+    let frame = match value {
+        Value::Nil => RespFrame::null(),
+        Value::Boolean(true) => RespFrame::Integer(1),
+        Value::Boolean(false) => RespFrame::null(),
+        Value::Integer(i) => RespFrame::Integer(i),
+        Value::Number(n) => RespFrame::Integer(n as i64),
+        Value::String(s) => BulkString::new(s.as_bytes().to_vec()).into(),
+        Value::Table(t) => table_to_frame(t)?,
+        Value::Error(e) => SimpleError::new(format!("ERR {}", e)).into(),
+        _ => RespFrame::null(),
+    };
+    Ok(frame)
+}
+
+fn table_to_frame(t: Table) -> mlua::Result<RespFrame> {
+    if let Value::String(err) = t.raw_get::<Value>("err")? {
+        return Ok(SimpleError::new(err.to_string_lossy()).into());
+    }
+    if let Value::String(ok) = t.raw_get::<Value>("ok")? {
+        return Ok(SimpleString::new(ok.to_string_lossy()).into());
+    }
+
+    // like Redis, the array stops at the first nil
+    let mut frames = Vec::new();
+    for value in t.sequence_values::<Value>() {
+        frames.push(lua_to_frame(value?)?);
+    }
+    Ok(RespArray::new(frames).into())
+}
+
+pub(super) fn single_field_table(lua: &Lua, field: &str, value: &str) -> mlua::Result<Value> {
+    let t = lua.create_table()?;
+    t.set(field, value)?;
+    Ok(Value::Table(t))
+}
+
+fn sequence(lua: &Lua, frames: Vec<RespFrame>) -> mlua::Result<Value> {
+    let t = lua.create_table_with_capacity(frames.len(), 0)?;
+    for (i, frame) in frames.into_iter().enumerate() {
+        t.raw_set(i + 1, frame_to_lua(lua, frame)?)?;
+    }
+    Ok(Value::Table(t))
+}
@@ -0,0 +1,260 @@
+use super::{convert::lua_to_frame, lua_strings, new_lua, register_calls};
+use crate::{Backend, RespFrame, SimpleError};
+use bytes::Bytes;
+use mlua::{Function, Lua, MultiValue, Table, Value};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+const REGISTERED: &str = "__registered_functions";
+
+// A library loaded by FUNCTION LOAD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Library {
+    pub name: String,
+    pub code: String,
+    pub functions: Vec<String>,
+}
+
+// Libraries loaded by FUNCTION LOAD, keyed by library name.
+#[derive(Debug, Default)]
+pub struct FunctionRegistry(RwLock<BTreeMap<String, Library>>);
+
+impl FunctionRegistry {
+    // Loads a library, returning its name. Fails if the code doesn't compile, if it doesn't
+    // register any function or if a library or function with the same name already exists.
+    pub fn load(&self, code: impl Into<String>, replace: bool) -> Result<String, String> {
+        let code = code.into();
+        let name = parse_metadata(&code)?;
+        let functions = registered_functions(&code).map_err(|e| format!("ERR {}", e))?;
+        if functions.is_empty() {
+            return Err("ERR No functions registered".to_string());
+        }
+
+        let mut libraries = self.0.write().unwrap_or_else(|e| e.into_inner());
+        if libraries.contains_key(&name) && !replace {
+            return Err(format!("ERR Library '{}' already exists", name));
+        }
+        for library in libraries.values().filter(|l| l.name != name) {
+            if let Some(f) = functions.iter().find(|f| library.functions.contains(f)) {
+                return Err(format!("ERR Function {} already exists", f));
+            }
+        }
+
+        let library = Library {
+            name: name.clone(),
+            code,
+            functions,
+        };
+        libraries.insert(name.clone(), library);
+        Ok(name)
+    }
+
+    pub fn delete(&self, name: &str) -> bool {
+        let mut libraries = self.0.write().unwrap_or_else(|e| e.into_inner());
+        libraries.remove(name).is_some()
+    }
+
+    pub fn flush(&self) {
+        self.0.write().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    pub fn libraries(&self) -> Vec<Library> {
+        let libraries = self.0.read().unwrap_or_else(|e| e.into_inner());
+        libraries.values().cloned().collect()
+    }
+
+    // Returns the library which registered the function.
+    pub fn find(&self, function: &str) -> Option<Library> {
+        let libraries = self.0.read().unwrap_or_else(|e| e.into_inner());
+        libraries
+            .values()
+            .find(|l| l.functions.iter().any(|f| f == function))
+            .cloned()
+    }
+}
+
+// Runs a function registered by FUNCTION LOAD. The caller is responsible for holding the
+// exclusive backend lock so that the function executes atomically.
+pub(crate) fn fcall(
+    backend: &Backend,
+    user: Option<&str>,
+    function: &str,
+    keys: &[Bytes],
+    args: &[Bytes],
+) -> RespFrame {
+    let library = match backend.functions.find(function) {
+        Some(library) => library,
+        None => return SimpleError::new("ERR Function not found").into(),
+    };
+    match run(backend, user, &library, function, keys, args) {
+        Ok(frame) => frame,
+        Err(e) => SimpleError::new(format!("ERR Error running function: {}", e)).into(),
+    }
+}
+
+fn run(
+    backend: &Backend,
+    user: Option<&str>,
+    library: &Library,
+    function: &str,
+    keys: &[Bytes],
+    args: &[Bytes],
+) -> mlua::Result<RespFrame> {
+    let lua = load_library(&library.code)?;
+    let registered: Table = lua.globals().get(REGISTERED)?;
+    let callback: Function = registered.get(function)?;
+    let keys = lua_strings(&lua, keys)?;
+    let args = lua_strings(&lua, args)?;
+
+    lua.scope(|scope| {
+        register_calls(&lua, scope, backend, user)?;
+        let ret: Value = callback.call((keys, args))?;
+        lua_to_frame(ret)
+    })
+}
+
+// #!lua name=mylib
+fn parse_metadata(code: &str) -> Result<String, String> {
+    let shebang = code
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("#!"))
+        .ok_or("ERR Missing library metadata")?;
+
+    let mut parts = shebang.split_whitespace();
+    match parts.next() {
+        Some(engine) if engine.eq_ignore_ascii_case("lua") => {}
+        Some(engine) => return Err(format!("ERR Engine '{}' not found", engine)),
+        None => return Err("ERR Missing library metadata".to_string()),
+    }
+
+    let mut name = None;
+    for part in parts {
+        match part.split_once('=') {
+            Some(("name", value)) => name = Some(value.to_string()),
+            _ => return Err(format!("ERR Invalid metadata value given: {}", part)),
+        }
+    }
+    match name {
+        Some(name) if is_valid_name(&name) => Ok(name),
+        Some(_) => Err("ERR Library names can only contain letters, numbers, or underscores(_) and must be at least one character long".to_string()),
+        None => Err("ERR Library name was not given".to_string()),
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Runs the library code in a fresh state, collecting the functions it registers.
+fn load_library(code: &str) -> mlua::Result<Lua> {
+    let lua = new_lua()?;
+    lua.globals().set(REGISTERED, lua.create_table()?)?;
+    let redis: Table = lua.globals().get("redis")?;
+    redis.set(
+        "register_function",
+        lua.create_function(|lua, args: MultiValue| {
+            let (name, callback) = match (args.front(), args.get(1)) {
+                // redis.register_function{function_name='name', callback=function(keys, args) end}
+                (Some(Value::Table(t)), None) => (
+                    t.get::<String>("function_name")?,
+                    t.get::<Function>("callback")?,
+                ),
+                // redis.register_function('name', function(keys, args) end)
+                (Some(Value::String(name)), Some(Value::Function(callback))) => {
+                    (name.to_str()?.to_string(), callback.clone())
+                }
+                _ => {
+                    return Err(mlua::Error::runtime(
+                        "wrong arguments given to redis.register_function",
+                    ))
+                }
+            };
+            if !is_valid_name(&name) {
+                return Err(mlua::Error::runtime(
+                    "Function names can only contain letters, numbers, or underscores(_) and must be at least one character long",
+                ));
+            }
+            let registered: Table = lua.globals().get(REGISTERED)?;
+            if registered.contains_key(name.as_str())? {
+                return Err(mlua::Error::runtime("Function already exists in the library"));
+            }
+            registered.set(name, callback)
+        })?,
+    )?;
+
+    // blank out the shebang line, which is not valid Lua, keeping line numbers intact
+    let body = match code.find('\n') {
+        Some(pos) => &code[pos..],
+        None => "",
+    };
+    lua.load(body).set_name("@user_function").exec()?;
+    Ok(lua)
+}
+
+fn registered_functions(code: &str) -> mlua::Result<Vec<String>> {
+    let lua = load_library(code)?;
+    let registered: Table = lua.globals().get(REGISTERED)?;
+    let mut functions = registered
+        .pairs::<String, Function>()
+        .map(|pair| pair.map(|(name, _)| name))
+        .collect::<mlua::Result<Vec<_>>>()?;
+    functions.sort();
+    Ok(functions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+
+    const LIBRARY: &str = "#!lua name=mylib\n\
+        redis.register_function('echo_key', function(keys, args) return keys[1] end)\n\
+        redis.register_function{function_name='setget', callback=function(keys, args)\n\
+            redis.call('SET', keys[1], args[1])\n\
+            return redis.call('GET', keys[1])\n\
+        end}";
+
+    #[test]
+    fn test_parse_metadata() {
+        assert_eq!(
+            parse_metadata("#!lua name=mylib\n"),
+            Ok("mylib".to_string())
+        );
+        assert!(parse_metadata("return 1").is_err());
+        assert!(parse_metadata("#!js name=mylib\n").is_err());
+        assert!(parse_metadata("#!lua\n").is_err());
+    }
+
+    #[test]
+    fn test_load_library() {
+        let registry = FunctionRegistry::default();
+        assert_eq!(registry.load(LIBRARY, false), Ok("mylib".to_string()));
+        let library = registry.find("setget").unwrap();
+        assert_eq!(library.functions, ["echo_key", "setget"]);
+
+        assert!(registry.load(LIBRARY, false).is_err());
+        assert!(registry.load(LIBRARY, true).is_ok());
+
+        let other = LIBRARY.replace("mylib", "other");
+        assert_eq!(
+            registry.load(other, false),
+            Err("ERR Function echo_key already exists".to_string())
+        );
+
+        assert!(registry.delete("mylib"));
+        assert!(registry.find("setget").is_none());
+    }
+
+    #[test]
+    fn test_fcall() {
+        let backend = Backend::new();
+        backend.functions.load(LIBRARY, false).unwrap();
+        let ret = fcall(&backend, None, "setget", &["foo".into()], &["bar".into()]);
+        assert_eq!(ret, BulkString::from("bar").into());
+        assert_eq!(backend.get(b"foo"), Some(BulkString::from("bar").into()));
+
+        let ret = fcall(&backend, None, "nosuchfunction", &[], &[]);
+        assert_eq!(ret, SimpleError::new("ERR Function not found").into());
+    }
+}
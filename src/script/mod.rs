@@ -0,0 +1,308 @@
+mod convert;
+mod function;
+
+use crate::{
+    cmd::{
+        is_noscript, is_write, request_keys, request_name, Command, CommandExecutor, ExecContext,
+    },
+    replication, Backend, BulkString, RespArray, RespFrame, SimpleError,
+};
+use bytes::Bytes;
+use dashmap::DashMap;
+use mlua::{Lua, LuaOptions, MultiValue, Scope, StdLib, Table, Value};
+use sha1::{Digest, Sha1};
+
+use self::convert::{frame_to_lua, lua_to_frame, single_field_table};
+
+pub(crate) use self::function::fcall;
+pub use self::function::FunctionRegistry;
+
+const NOSCRIPT: &str = "ERR This Redis command is not allowed from script";
+
+// Scripts loaded by EVAL, keyed by the hex SHA1 of their body for EVALSHA.
+#[derive(Debug, Default)]
+pub struct ScriptCache(DashMap<String, String>);
+
+impl ScriptCache {
+    // Caches the script and returns its SHA1.
+    pub fn load(&self, body: impl Into<String>) -> String {
+        let body = body.into();
+        let sha = sha1_hex(&body);
+        self.0.insert(sha.clone(), body);
+        sha
+    }
+
+    pub fn get(&self, sha: &str) -> Option<String> {
+        self.0.get(&sha.to_ascii_lowercase()).map(|v| v.clone())
+    }
+
+    pub fn exists(&self, sha: &str) -> bool {
+        self.0.contains_key(&sha.to_ascii_lowercase())
+    }
+
+    pub fn flush(&self) {
+        self.0.clear();
+    }
+}
+
+pub fn sha1_hex(body: &str) -> String {
+    Sha1::digest(body.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// Runs a script against the backend. The caller is responsible for holding the exclusive
+// backend lock so that the script executes atomically. The commands it calls are checked against
+// the ACL of the user, when it runs for a connection.
+pub(crate) fn eval(
+    backend: &Backend,
+    user: Option<&str>,
+    body: &str,
+    keys: &[Bytes],
+    args: &[Bytes],
+) -> RespFrame {
+    match run(backend, user, body, keys, args) {
+        Ok(frame) => frame,
+        Err(e) => SimpleError::new(format!("ERR Error running script: {}", e)).into(),
+    }
+}
+
+fn run(
+    backend: &Backend,
+    user: Option<&str>,
+    body: &str,
+    keys: &[Bytes],
+    args: &[Bytes],
+) -> mlua::Result<RespFrame> {
+    let lua = new_lua()?;
+    let globals = lua.globals();
+    globals.set("KEYS", lua_strings(&lua, keys)?)?;
+    globals.set("ARGV", lua_strings(&lua, args)?)?;
+
+    lua.scope(|scope| {
+        register_calls(&lua, scope, backend, user)?;
+        let ret: Value = lua.load(body).set_name("@user_script").eval()?;
+        lua_to_frame(ret)
+    })
+}
+
+// Lua strings are binary safe, so KEYS and ARGV are passed as is.
+fn lua_strings(lua: &Lua, values: &[Bytes]) -> mlua::Result<Table> {
+    let values = values
+        .iter()
+        .map(|v| lua.create_string(v))
+        .collect::<mlua::Result<Vec<_>>>()?;
+    lua.create_sequence_from(values)
+}
+
+// Creates a sandboxed Lua state with the `redis` library, minus the functions touching data.
+fn new_lua() -> mlua::Result<Lua> {
+    let lua = Lua::new_with(
+        StdLib::TABLE | StdLib::STRING | StdLib::MATH,
+        LuaOptions::default(),
+    )?;
+    let redis = lua.create_table()?;
+    redis.set(
+        "error_reply",
+        lua.create_function(|lua, msg: String| single_field_table(lua, "err", &msg))?,
+    )?;
+    redis.set(
+        "status_reply",
+        lua.create_function(|lua, msg: String| single_field_table(lua, "ok", &msg))?,
+    )?;
+    lua.globals().set("redis", redis)?;
+    Ok(lua)
+}
+
+// Adds redis.call and redis.pcall, which live only as long as the scope.
+fn register_calls<'scope, 'env: 'scope>(
+    lua: &Lua,
+    scope: &'scope Scope<'scope, 'env>,
+    backend: &'env Backend,
+    user: Option<&'env str>,
+) -> mlua::Result<()> {
+    let redis: Table = lua.globals().get("redis")?;
+    redis.set(
+        "call",
+        scope.create_function(move |lua, args: MultiValue| {
+            match call(lua, backend, user, args)? {
+                RespFrame::Error(e) => Err(mlua::Error::runtime(e.0)),
+                frame => frame_to_lua(lua, frame),
+            }
+        })?,
+    )?;
+    redis.set(
+        "pcall",
+        scope.create_function(move |lua, args: MultiValue| {
+            let frame = call(lua, backend, user, args)?;
+            frame_to_lua(lua, frame)
+        })?,
+    )?;
+    Ok(())
+}
+
+// redis.call / redis.pcall: builds a command from the Lua arguments and executes it, if the user
+// may run it on its keys.
+fn call(
+    lua: &Lua,
+    backend: &Backend,
+    user: Option<&str>,
+    args: MultiValue,
+) -> mlua::Result<RespFrame> {
+    let mut frames = Vec::with_capacity(args.len());
+    for arg in args {
+        match lua.coerce_string(arg)? {
+            Some(s) => frames.push(BulkString::new(s.as_bytes().to_vec()).into()),
+            None => {
+                return Ok(SimpleError::new(
+                    "ERR Lua redis lib command arguments must be strings or integers",
+                )
+                .into())
+            }
+        }
+    }
+    if frames.is_empty() {
+        return Ok(SimpleError::new(
+            "ERR Please specify at least one argument for this redis lib call",
+        )
+        .into());
+    }
+
+    let name = request_name(&frames);
+    if let Some(user) = user {
+        let custom = backend.commands.contains(&name);
+        if let Err(e) = backend
+            .acl
+            .check(user, &name, custom, || request_keys(&frames))
+        {
+            return Ok(SimpleError::new(e).into());
+        }
+    }
+    if is_noscript(&name) {
+        return Ok(SimpleError::new(NOSCRIPT).into());
+    }
+    let write = is_write(&name);
+    let cmd = match Command::try_from(RespArray::new(frames)) {
+        Ok(cmd) => backend.commands.resolve(cmd),
+        Err(e) => return Ok(SimpleError::new(format!("ERR {}", e)).into()),
+    };
+    match cmd {
+        // MIGRATE talks to another server, which needs the connection a script does not have
+        cmd if cmd.is_async() => Ok(SimpleError::new(NOSCRIPT).into()),
+        _ if write && backend.replication.read_only(&backend.config) => {
+            Ok(SimpleError::new(replication::READONLY).into())
+        }
+        cmd => Ok(cmd
+            .execute(&mut ExecContext::new(backend))
+            .unwrap_or_else(|e| SimpleError::new(format!("ERR {}", e)).into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha1_hex() {
+        assert_eq!(
+            sha1_hex("return 1"),
+            "e0e1f9fabfc9d4800c877a703b823ac0578ff8db"
+        );
+    }
+
+    #[test]
+    fn test_eval_keys_and_argv() {
+        let backend = Backend::new();
+        let ret = eval(
+            &backend,
+            None,
+            "redis.call('SET', KEYS[1], ARGV[1]); return redis.call('GET', KEYS[1])",
+            &["foo".into()],
+            &["bar".into()],
+        );
+        assert_eq!(ret, RespFrame::BulkString(b"bar".into()));
+        assert_eq!(
+            backend.get(b"foo"),
+            Some(RespFrame::BulkString(b"bar".into()))
+        );
+    }
+
+    #[test]
+    fn test_eval_return_values() {
+        let backend = Backend::new();
+        let ret = eval(
+            &backend,
+            None,
+            "return {1, 'two', {ok='fine'}, false}",
+            &[],
+            &[],
+        );
+        assert_eq!(
+            ret,
+            RespArray::new([
+                RespFrame::Integer(1),
+                BulkString::from("two").into(),
+                crate::SimpleString::new("fine").into(),
+                RespFrame::null(),
+            ])
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let backend = Backend::new();
+        let ret = eval(&backend, None, "return redis.call('GET')", &[], &[]);
+        assert!(matches!(ret, RespFrame::Error(_)));
+
+        let ret = eval(&backend, None, "return redis.pcall('MULTI')", &[], &[]);
+        assert_eq!(
+            ret,
+            SimpleError::new("ERR This Redis command is not allowed from script").into()
+        );
+
+        let ret = eval(&backend, None, "return os.exit()", &[], &[]);
+        assert!(matches!(ret, RespFrame::Error(_)));
+
+        for admin in [
+            "ACL SETUSER x",
+            "CONFIG SET maxmemory 1",
+            "REPLICAOF NO ONE",
+            "SAVE",
+            "PSYNC ? -1",
+            "MIGRATE 127.0.0.1 6380 k 0 1000",
+        ] {
+            let script = format!("return redis.pcall('{}')", admin.replace(' ', "','"));
+            let ret = eval(&backend, None, &script, &[], &[]);
+            assert_eq!(ret, SimpleError::new(NOSCRIPT).into());
+        }
+    }
+
+    #[test]
+    fn test_eval_checks_the_acl() -> Result<(), crate::acl::AclError> {
+        let backend = Backend::new();
+        let rules = ["on", "nopass", "+eval", "+set", "+get", "~app:*"].map(String::from);
+        backend.acl.set_user("app", &rules)?;
+
+        let set = "return redis.pcall('SET', KEYS[1], 'v')";
+        let ret = eval(&backend, Some("app"), set, &["app:1".into()], &[]);
+        assert_eq!(ret, crate::SimpleString::new("OK").into());
+        let ret = eval(&backend, Some("app"), set, &["admin:x".into()], &[]);
+        assert_eq!(
+            ret,
+            SimpleError::new("NOPERM No permissions to access a key").into()
+        );
+        assert!(!backend.exists(b"admin:x"));
+
+        let ret = eval(
+            &backend,
+            Some("app"),
+            "return redis.pcall('DEL', 'app:1')",
+            &[],
+            &[],
+        );
+        assert!(matches!(ret, RespFrame::Error(e) if e.0.starts_with("NOPERM")));
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{apply_save_rules, network, Backend, Listeners};
+use anyhow::{anyhow, Context, Result};
+use std::net::SocketAddr;
+use tokio::task::{self, JoinHandle};
+
+// A server running in the tasks of the current runtime, for the applications and the test suites
+// embedding it:
+//
+//   let server = RedisServer::builder().bind("127.0.0.1:0").spawn().await?;
+//   let client = RedisClient::connect(server.addr()).await?;
+//   server.shutdown(Some(false)).await?;
+pub struct RedisServer;
+
+impl RedisServer {
+    pub fn builder() -> RedisServerBuilder {
+        RedisServerBuilder::default()
+    }
+}
+
+// The options of an embedded server, see `RedisServer::builder`.
+#[derive(Debug, Default)]
+pub struct RedisServerBuilder {
+    bind: Option<String>,
+    backend: Option<Backend>,
+}
+
+// What `spawn` returns, the server keeps running when it's dropped.
+#[derive(Debug)]
+pub struct ServerHandle {
+    addr: SocketAddr,
+    backend: Backend,
+    task: JoinHandle<Result<()>>,
+}
+
+impl RedisServerBuilder {
+    // The host and port to listen on, like 127.0.0.1:0 for a port picked by the OS. bind and port
+    // of the backend configuration by default.
+    pub fn bind(mut self, addr: impl Into<String>) -> Self {
+        self.bind = Some(addr.into());
+        self
+    }
+
+    // The backend serving the clients, with its configuration and its dataset, an empty one with
+    // the default configuration by default. Nothing is loaded from the disk.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    // Binds the sockets and starts serving in the background, the clients can connect as soon as
+    // it returns.
+    pub async fn spawn(self) -> Result<ServerHandle> {
+        let backend = self.backend.unwrap_or_default();
+        if let Some(addr) = self.bind {
+            let (host, port) = addr
+                .rsplit_once(':')
+                .ok_or_else(|| anyhow!("'{}' is not a host:port address", addr))?;
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            backend
+                .config
+                .apply(&[
+                    ("bind".to_string(), host.to_string()),
+                    ("port".to_string(), port.to_string()),
+                ])
+                .with_context(|| format!("can't bind {}", addr))?;
+        }
+        let listeners = Listeners::bind(&backend.config.snapshot()).await?;
+        let addr = *listeners
+            .local_addrs()
+            .first()
+            .ok_or_else(|| anyhow!("the server doesn't listen on any TCP address"))?;
+
+        let cloned_backend = backend.clone();
+        tokio::spawn(async move { cloned_backend.active_expire().await });
+        tokio::spawn(apply_save_rules(backend.clone()));
+        let task = tokio::spawn(network::serve(listeners, backend.clone()));
+        Ok(ServerHandle {
+            addr,
+            backend,
+            task,
+        })
+    }
+}
+
+impl ServerHandle {
+    // The first address the clients can connect to, with the port picked by the OS for port 0.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    // Stops the server like SHUTDOWN, see `ShutdownHandle::shutdown` for `save`, and waits until
+    // the connections are closed.
+    pub async fn shutdown(self, save: Option<bool>) -> Result<()> {
+        let handle = self.backend.shutdown_handle();
+        task::spawn_blocking(move || handle.shutdown(save)).await??;
+        self.wait().await
+    }
+
+    // Waits until the server stops, by a SHUTDOWN command or a signal.
+    pub async fn wait(self) -> Result<()> {
+        self.task.await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RedisClient};
+    use std::time::Duration;
+    use tokio::time;
+
+    #[tokio::test]
+    async fn test_embedded_server() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("foo".into(), BulkString::from("1").into());
+        let server = RedisServer::builder()
+            .bind("127.0.0.1:0")
+            .backend(backend)
+            .spawn()
+            .await?;
+        assert_ne!(server.addr().port(), 0);
+
+        let mut client = RedisClient::connect(server.addr()).await?;
+        assert_eq!(
+            client.command(&["GET", "foo"]).await?,
+            BulkString::from("1").into()
+        );
+        client.command(&["SET", "bar", "baz"]).await?;
+        let (addr, backend) = (server.addr(), server.backend().clone());
+        time::timeout(Duration::from_secs(5), server.shutdown(Some(false))).await??;
+        assert!(backend.exists(b"bar"));
+        assert!(RedisClient::connect(addr).await.is_err());
+
+        let err = RedisServer::builder()
+            .bind("nope")
+            .spawn()
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "'nope' is not a host:port address");
+        Ok(())
+    }
+}
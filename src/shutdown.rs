@@ -0,0 +1,95 @@
+use crate::{persistence, Backend};
+use std::io;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    task,
+};
+use tracing::{info, warn};
+
+// Stops the server from outside of a connection, for the signals and the applications embedding
+// the server. See `network::serve` for what happens then.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    backend: Backend,
+}
+
+impl ShutdownHandle {
+    // Like SHUTDOWN, the dataset is saved first with `save`, or if None when save points are
+    // configured. The server keeps running if the save fails. Blocks until the commands running
+    // finish, must not be called from a command.
+    pub fn shutdown(&self, save: Option<bool>) -> io::Result<()> {
+        let backend = &self.backend;
+        let _guard = backend.exec_lock.write().unwrap_or_else(|e| e.into_inner());
+        persistence::shutdown(backend, save)
+    }
+
+    // Resolves once the server stopped, the connections are closed and the AOF is flushed.
+    pub async fn stopped(&self) {
+        self.backend.stopped.cancelled().await
+    }
+}
+
+impl Backend {
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            backend: self.clone(),
+        }
+    }
+}
+
+// Shuts the server down on SIGTERM and SIGINT, saving as shutdown-on-sigterm and
+// shutdown-on-sigint say.
+pub async fn shutdown_on_signals(handle: ShutdownHandle) -> io::Result<()> {
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
+    loop {
+        let (name, on_signal) = tokio::select! {
+            _ = sigterm.recv() => ("SIGTERM", handle.backend.config.snapshot().shutdown_on_sigterm),
+            _ = sigint.recv() => ("SIGINT", handle.backend.config.snapshot().shutdown_on_sigint),
+        };
+        info!("Received {}, scheduling shutdown...", name);
+        let save = match on_signal.as_str() {
+            "save" => Some(true),
+            "nosave" => Some(false),
+            _ => None,
+        };
+        let cloned_handle = handle.clone();
+        match task::spawn_blocking(move || cloned_handle.shutdown(save)).await? {
+            Ok(()) => return Ok(()),
+            Err(e) => warn!("Error trying to save the DB, can't exit: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, ServerConfig};
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_shutdown_handle() -> io::Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("simple-redis-shutdown-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let backend = Backend::with_config(ServerConfig::new(Config {
+            dir: dir.display().to_string(),
+            ..Default::default()
+        }));
+        let handle = backend.shutdown_handle();
+        handle.shutdown(None)?;
+        backend.shutdown_requested().await;
+        // save points are configured by default
+        assert!(dir.join("dump.rdb").exists());
+        fs::remove_dir_all(&dir)?;
+
+        let backend = Backend::with_config(ServerConfig::new(Config {
+            dir: "/nonexistent".to_string(),
+            ..Default::default()
+        }));
+        assert!(backend.shutdown_handle().shutdown(Some(true)).is_err());
+        backend.shutdown_handle().shutdown(Some(false))?;
+        backend.shutdown_requested().await;
+        Ok(())
+    }
+}
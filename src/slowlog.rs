@@ -0,0 +1,166 @@
+use crate::{BulkString, RespArray, RespFrame};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// like redis, long argument lists and long arguments are truncated
+const MAX_ARGS: usize = 32;
+const MAX_ARG_LEN: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowLogEntry {
+    pub id: u64,
+    // unix timestamp in seconds
+    pub timestamp: u64,
+    pub duration: Duration,
+    pub args: Vec<String>,
+    pub addr: String,
+    pub name: String,
+}
+
+// Commands slower than `slowlog-log-slower-than`, newest first.
+#[derive(Debug, Default)]
+pub struct SlowLog {
+    entries: Mutex<VecDeque<SlowLogEntry>>,
+    next_id: AtomicU64,
+}
+
+impl SlowLog {
+    pub fn record(
+        &self,
+        args: &RespFrame,
+        duration: Duration,
+        addr: String,
+        name: String,
+        max_len: usize,
+    ) {
+        let entry = SlowLogEntry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            duration,
+            args: format_args(args),
+            addr,
+            name,
+        };
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.push_front(entry);
+        entries.truncate(max_len);
+    }
+
+    // The `count` most recent entries, all of them if None.
+    pub fn get(&self, count: Option<usize>) -> Vec<SlowLogEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let count = count.unwrap_or(entries.len());
+        entries.iter().take(count).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn reset(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+impl SlowLogEntry {
+    // [id, timestamp, microseconds, [args...], client addr, client name]
+    pub fn to_frame(&self) -> RespFrame {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| BulkString::from(arg.as_str()).into())
+            .collect::<Vec<RespFrame>>();
+        RespArray::new([
+            RespFrame::Integer(self.id as i64),
+            RespFrame::Integer(self.timestamp as i64),
+            RespFrame::Integer(self.duration.as_micros() as i64),
+            RespArray::new(args).into(),
+            BulkString::from(self.addr.as_str()).into(),
+            BulkString::from(self.name.as_str()).into(),
+        ])
+        .into()
+    }
+}
+
+fn format_args(frame: &RespFrame) -> Vec<String> {
+    let RespFrame::Array(args) = frame else {
+        return vec![];
+    };
+    let mut formatted = args
+        .iter()
+        .take(if args.len() > MAX_ARGS {
+            MAX_ARGS - 1
+        } else {
+            MAX_ARGS
+        })
+        .map(|arg| match arg {
+            RespFrame::BulkString(arg) if arg.len() > MAX_ARG_LEN => format!(
+                "{}... ({} more bytes)",
+                String::from_utf8_lossy(&arg[..MAX_ARG_LEN]),
+                arg.len() - MAX_ARG_LEN
+            ),
+            RespFrame::BulkString(arg) => String::from_utf8_lossy(arg).into_owned(),
+            _ => String::new(),
+        })
+        .collect::<Vec<_>>();
+    if args.len() > MAX_ARGS {
+        formatted.push(format!(
+            "... ({} more arguments)",
+            args.len() - MAX_ARGS + 1
+        ));
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slowlog_is_bounded() {
+        let slowlog = SlowLog::default();
+        let args = RespArray::new([b"get".into(), b"key".into()]).into();
+        for _ in 0..3 {
+            slowlog.record(
+                &args,
+                Duration::from_millis(20),
+                "127.0.0.1:5000".to_string(),
+                String::new(),
+                2,
+            );
+        }
+        let entries = slowlog.get(None);
+        assert_eq!(entries.iter().map(|e| e.id).collect::<Vec<_>>(), [2, 1]);
+        assert_eq!(entries[0].args, ["get", "key"]);
+        assert_eq!(slowlog.get(Some(1)).len(), 1);
+
+        slowlog.reset();
+        assert!(slowlog.is_empty());
+    }
+
+    #[test]
+    fn test_slowlog_truncates_arguments() {
+        let long = vec![b'x'; 130];
+        let mut args = vec![BulkString::new(long).into()];
+        args.extend((0..40).map(|_| b"a".into()));
+        let args = format_args(&RespArray::new(args).into());
+        assert_eq!(args.len(), 32);
+        assert!(args[0].ends_with("... (2 more bytes)"));
+        assert_eq!(args[31], "... (10 more arguments)");
+    }
+}
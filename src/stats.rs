@@ -0,0 +1,293 @@
+use crate::cmd::command_names;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// The latency histograms have 8 buckets per power of two, so a percentile is within 12.5% of the
+// latency it reports. The calls of more than 2^35us, about 9 hours, all go to the last bucket.
+const SUB_BUCKETS: usize = 8;
+const LATENCY_BUCKETS: usize = 34 * SUB_BUCKETS;
+
+// Server wide counters, updated by the network and command layers and reported by INFO.
+#[derive(Debug)]
+pub struct ServerStats {
+    started_at: Instant,
+    connections_received: AtomicU64,
+    connected_clients: AtomicU64,
+    // refused because of maxclients
+    rejected_connections: AtomicU64,
+    commands_processed: AtomicU64,
+    keyspace_hits: AtomicU64,
+    keyspace_misses: AtomicU64,
+    expired_keys: AtomicU64,
+    evicted_keys: AtomicU64,
+    // by command name, with the subcommand for containers, e.g. `config|get`. The map is built
+    // once, the counters are updated without locking.
+    commands: HashMap<String, CommandStats>,
+}
+
+impl Default for ServerStats {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            connections_received: AtomicU64::new(0),
+            connected_clients: AtomicU64::new(0),
+            rejected_connections: AtomicU64::new(0),
+            commands_processed: AtomicU64::new(0),
+            keyspace_hits: AtomicU64::new(0),
+            keyspace_misses: AtomicU64::new(0),
+            expired_keys: AtomicU64::new(0),
+            evicted_keys: AtomicU64::new(0),
+            commands: command_names()
+                .into_iter()
+                .map(|name| (name, CommandStats::default()))
+                .collect(),
+        }
+    }
+}
+
+impl ServerStats {
+    pub fn client_connected(&self) {
+        self.connections_received.fetch_add(1, Ordering::Relaxed);
+        self.connected_clients.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn client_disconnected(&self) {
+        self.connected_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_rejected(&self) {
+        self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn command_processed(&self) {
+        self.commands_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Records the result of a key lookup by a read command.
+    pub fn keyspace_lookup(&self, hit: bool) {
+        match hit {
+            true => self.keyspace_hits.fetch_add(1, Ordering::Relaxed),
+            false => self.keyspace_misses.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    // Records a call of a command, named like command_name does. The unknown commands are not
+    // counted, the unknown subcommands of a container are counted as calls of the container.
+    pub fn command_called(&self, name: &str, elapsed: Duration, failed: bool) {
+        if let Some(stats) = self.command(name) {
+            stats.record(elapsed, failed);
+        }
+    }
+
+    // Records a command refused before it ran, like for a wrong number of arguments or by ACL.
+    pub fn command_rejected(&self, name: &str) {
+        if let Some(stats) = self.command(name) {
+            stats.rejected_calls.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn command(&self, name: &str) -> Option<&CommandStats> {
+        self.commands.get(name).or_else(|| {
+            let (container, _) = name.split_once('|')?;
+            self.commands.get(container)
+        })
+    }
+
+    // the commands called or refused at least once, sorted by name
+    pub fn command_stats(&self) -> Vec<(&str, &CommandStats)> {
+        let mut commands: Vec<_> = self
+            .commands
+            .iter()
+            .filter(|(_, stats)| stats.calls() + stats.rejected_calls() > 0)
+            .map(|(name, stats)| (name.as_str(), stats))
+            .collect();
+        commands.sort_unstable_by_key(|(name, _)| *name);
+        commands
+    }
+
+    pub fn key_expired(&self) {
+        self.expired_keys.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn key_evicted(&self) {
+        self.evicted_keys.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn uptime_secs(&self) -> u64 {
+        self.started_at.elapsed().as_secs()
+    }
+
+    pub fn connections_received(&self) -> u64 {
+        self.connections_received.load(Ordering::Relaxed)
+    }
+
+    pub fn connected_clients(&self) -> u64 {
+        self.connected_clients.load(Ordering::Relaxed)
+    }
+
+    pub fn rejected_connections(&self) -> u64 {
+        self.rejected_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn commands_processed(&self) -> u64 {
+        self.commands_processed.load(Ordering::Relaxed)
+    }
+
+    pub fn keyspace_hits(&self) -> u64 {
+        self.keyspace_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn keyspace_misses(&self) -> u64 {
+        self.keyspace_misses.load(Ordering::Relaxed)
+    }
+
+    pub fn expired_keys(&self) -> u64 {
+        self.expired_keys.load(Ordering::Relaxed)
+    }
+
+    pub fn evicted_keys(&self) -> u64 {
+        self.evicted_keys.load(Ordering::Relaxed)
+    }
+}
+
+// The calls of a command, reported by INFO commandstats and latencystats.
+#[derive(Debug)]
+pub struct CommandStats {
+    calls: AtomicU64,
+    usec: AtomicU64,
+    rejected_calls: AtomicU64,
+    // the calls replied with an error
+    failed_calls: AtomicU64,
+    latencies: [AtomicU64; LATENCY_BUCKETS],
+}
+
+impl Default for CommandStats {
+    fn default() -> Self {
+        Self {
+            calls: AtomicU64::new(0),
+            usec: AtomicU64::new(0),
+            rejected_calls: AtomicU64::new(0),
+            failed_calls: AtomicU64::new(0),
+            latencies: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl CommandStats {
+    fn record(&self, elapsed: Duration, failed: bool) {
+        let usec = elapsed.as_micros() as u64;
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.usec.fetch_add(usec, Ordering::Relaxed);
+        if failed {
+            self.failed_calls.fetch_add(1, Ordering::Relaxed);
+        }
+        self.latencies[bucket(usec)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    pub fn usec(&self) -> u64 {
+        self.usec.load(Ordering::Relaxed)
+    }
+
+    pub fn rejected_calls(&self) -> u64 {
+        self.rejected_calls.load(Ordering::Relaxed)
+    }
+
+    pub fn failed_calls(&self) -> u64 {
+        self.failed_calls.load(Ordering::Relaxed)
+    }
+
+    // The latency in microseconds that `percentile`% of the calls didn't exceed, the upper bound
+    // of the bucket it falls in.
+    pub fn latency_percentile(&self, percentile: f64) -> u64 {
+        let counts: Vec<u64> = self
+            .latencies
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect();
+        let total: u64 = counts.iter().sum();
+        let rank = ((percentile / 100.0 * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, count) in counts.into_iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return bucket_max(i);
+            }
+        }
+        0
+    }
+}
+
+// The latencies under 16us have a bucket each, then each power of two is split in 8.
+fn bucket(usec: u64) -> usize {
+    if usec < 2 * SUB_BUCKETS as u64 {
+        return usec as usize;
+    }
+    let power = 63 - usec.leading_zeros() as usize;
+    let sub = (usec >> (power - 3)) as usize & (SUB_BUCKETS - 1);
+    ((power - 2) * SUB_BUCKETS + sub).min(LATENCY_BUCKETS - 1)
+}
+
+fn bucket_max(index: usize) -> u64 {
+    if index < 2 * SUB_BUCKETS {
+        return index as u64;
+    }
+    let power = index / SUB_BUCKETS + 2;
+    let sub = (index % SUB_BUCKETS) as u64;
+    ((SUB_BUCKETS as u64 + sub + 1) << (power - 3)) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_counters() {
+        let stats = ServerStats::default();
+        stats.client_connected();
+        stats.client_connected();
+        stats.client_disconnected();
+        assert_eq!(stats.connections_received(), 2);
+        assert_eq!(stats.connected_clients(), 1);
+    }
+
+    #[test]
+    fn test_command_stats() {
+        let stats = ServerStats::default();
+        for usec in 1..=100 {
+            stats.command_called("get", Duration::from_micros(usec), false);
+        }
+        stats.command_called("config|get", Duration::from_micros(5), true);
+        stats.command_rejected("config|nope");
+        stats.command_called("nosuchcommand", Duration::from_micros(5), false);
+
+        let commands = stats.command_stats();
+        let names: Vec<&str> = commands.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["config", "config|get", "get"]);
+        let (_, get) = commands[2];
+        assert_eq!(
+            (get.calls(), get.usec(), get.failed_calls()),
+            (100, 5050, 0)
+        );
+        // within a bucket of the exact percentiles
+        assert_eq!(get.latency_percentile(50.0), 51);
+        assert_eq!(get.latency_percentile(99.0), 103);
+        assert_eq!(get.latency_percentile(100.0), 103);
+        let (_, config_get) = commands[1];
+        assert_eq!(config_get.failed_calls(), 1);
+        assert_eq!(commands[0].1.rejected_calls(), 1);
+    }
+
+    #[test]
+    fn test_latency_buckets() {
+        for usec in [0, 1, 15, 16, 17, 31, 32, 1000, 1 << 34, u64::MAX] {
+            let index = bucket(usec);
+            assert!(usec <= bucket_max(index) || index == LATENCY_BUCKETS - 1);
+            assert!(index == 0 || usec > bucket_max(index - 1));
+        }
+    }
+}
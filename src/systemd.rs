@@ -0,0 +1,81 @@
+use std::{
+    env, io,
+    os::{
+        fd::{FromRawFd, OwnedFd, RawFd},
+        unix::net::UnixDatagram,
+    },
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// the first descriptor passed by socket activation, after the standard streams
+const LISTEN_FDS_START: RawFd = 3;
+
+static LISTEN_FDS_TAKEN: AtomicBool = AtomicBool::new(false);
+
+// Tells the service manager about the state of the server, like sd_notify: READY=1 once the
+// connections are accepted, STOPPING=1 on shutdown. NOTIFY_SOCKET is a path, or the name of an
+// abstract socket prefixed with @, nothing is sent without it.
+pub(crate) fn notify(state: &str) -> io::Result<()> {
+    let Ok(path) = env::var("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+    let socket = UnixDatagram::unbound()?;
+    match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+            socket.send_to_addr(state.as_bytes(), &SocketAddr::from_abstract_name(name)?)?;
+        }
+        _ => {
+            socket.send_to(state.as_bytes(), &path)?;
+        }
+    }
+    Ok(())
+}
+
+// The sockets passed by socket activation, like sd_listen_fds: LISTEN_FDS descriptors from 3 when
+// LISTEN_PID is this process. They are taken once, the next calls get none.
+pub(crate) fn listen_fds() -> Vec<OwnedFd> {
+    let pid = env::var("LISTEN_PID").ok().and_then(|pid| pid.parse().ok());
+    let count: RawFd = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(0);
+    if pid != Some(std::process::id()) || LISTEN_FDS_TAKEN.swap(true, Ordering::SeqCst) {
+        return vec![];
+    }
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| {
+            // SAFETY: the descriptors were passed to this process for it to own, and are only
+            // taken once
+            unsafe {
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                OwnedFd::from_raw_fd(fd)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_the_service_manager() -> io::Result<()> {
+        let path = env::temp_dir().join(format!("simple-redis-notify-{}", std::process::id()));
+        let manager = UnixDatagram::bind(&path)?;
+        // no other test reads these
+        env::set_var("NOTIFY_SOCKET", &path);
+        let notified = notify("READY=1");
+        env::remove_var("NOTIFY_SOCKET");
+        notified?;
+
+        let mut buf = [0; 64];
+        let len = manager.recv(&mut buf)?;
+        assert_eq!(&buf[..len], b"READY=1");
+        std::fs::remove_file(&path)?;
+        // the sockets passed to another process are not taken
+        assert!(listen_fds().is_empty());
+        Ok(())
+    }
+}